@@ -1,21 +1,32 @@
 import { useState, useEffect } from 'react'
-import { Button, Form, InputNumber, ConfigProvider, theme, message, Tooltip } from 'antd'
+import { Button, Form, Input, InputNumber, Select, ConfigProvider, theme, message, Tooltip } from 'antd'
 import { ReloadOutlined } from '@ant-design/icons'
 import './App.css'
 
+const DEFAULTS = {
+  speed: 1.0,
+  repeat: 1,
+  interval: 0.0,
+  countdown: 0.0,
+  humanizeMs: 0,
+  moveIntervalMs: 0,
+  maxGap: 0.0,
+  recordingsDir: ''
+};
+
+// Screenshot taken as the loaded recording started, if it has one
+const THUMBNAIL = window.initialConfig?.thumbnail;
+
+// Named speed and repeat settings from the config file
+const PRESETS = window.initialConfig?.presets ?? {};
+
 function App() {
   const [form] = Form.useForm();
   const [loading, setLoading] = useState(true);
 
   useEffect(() => {
     try {
-      const config = window.initialConfig || { speed: 1.0, repeat: 1, interval: 0.0 };
-
-      form.setFieldsValue({
-        speed: config.speed,
-        repeat: config.repeat,
-        interval: config.interval
-      });
+      form.setFieldsValue({ ...DEFAULTS, ...window.initialConfig });
       setLoading(false);
     } catch (e) {
       console.error("Error loading config:", e);
@@ -28,7 +39,12 @@ function App() {
       speed: values.speed,
       repeat: values.repeat,
       interval: values.interval,
-      should_play: shouldPlay
+      should_play: shouldPlay,
+      countdown: values.countdown ?? 0,
+      humanize_ms: values.humanizeMs ?? 0,
+      move_interval_ms: values.moveIntervalMs ?? 0,
+      max_gap: values.maxGap ?? 0,
+      recordings_dir: values.recordingsDir ?? ''
     };
 
     if (window.ipc) {
@@ -57,12 +73,18 @@ function App() {
     }
   };
 
-  const handleReset = () => {
+  // Fills in what the preset sets, leaving the rest at their defaults
+  const handlePreset = (name) => {
+    const preset = PRESETS[name];
     form.setFieldsValue({
-      speed: 1.0,
-      repeat: 1,
-      interval: 0.0
+      speed: preset.speed ?? DEFAULTS.speed,
+      repeat: preset.repeat_count ?? DEFAULTS.repeat,
+      interval: preset.repeat_interval ?? DEFAULTS.interval
     });
+  };
+
+  const handleReset = () => {
+    form.setFieldsValue(DEFAULTS);
     message.info("Settings reset to defaults");
   };
 
@@ -110,12 +132,30 @@ function App() {
           />
         </Tooltip>
 
+        {THUMBNAIL && (
+          <img
+            src={THUMBNAIL}
+            alt="Loaded recording"
+            style={{ width: 'calc(100% - 40px)', borderRadius: 8, marginBottom: 8 }}
+          />
+        )}
+
         <Form
           form={form}
           layout="vertical"
-          initialValues={{ speed: 1.0, repeat: 1, interval: 0 }}
+          initialValues={DEFAULTS}
           style={{ height: '100%', display: 'flex', flexDirection: 'column', marginTop: 10 }}
         >
+          {Object.keys(PRESETS).length > 0 && (
+            <Form.Item label="Preset">
+              <Select
+                placeholder="Choose a preset"
+                options={Object.keys(PRESETS).map((name) => ({ value: name, label: name }))}
+                onChange={handlePreset}
+              />
+            </Form.Item>
+          )}
+
           <Form.Item
             label="Speed"
             name="speed"
@@ -151,6 +191,59 @@ function App() {
             />
           </Form.Item>
 
+          <Form.Item
+            label="Countdown (s)"
+            name="countdown"
+          >
+            <InputNumber
+              step={1}
+              min={0.0}
+              style={{ width: '100%' }}
+            />
+          </Form.Item>
+
+          <Form.Item
+            label="Humanize (± ms)"
+            name="humanizeMs"
+          >
+            <InputNumber
+              step={10}
+              min={0}
+              precision={0}
+              style={{ width: '100%' }}
+            />
+          </Form.Item>
+
+          <Form.Item
+            label="Mouse Move Interval (ms, 0 = all)"
+            name="moveIntervalMs"
+          >
+            <InputNumber
+              step={10}
+              min={0}
+              precision={0}
+              style={{ width: '100%' }}
+            />
+          </Form.Item>
+
+          <Form.Item
+            label="Max Gap (s, 0 = no limit)"
+            name="maxGap"
+          >
+            <InputNumber
+              step={0.5}
+              min={0.0}
+              style={{ width: '100%' }}
+            />
+          </Form.Item>
+
+          <Form.Item
+            label="Recordings Folder"
+            name="recordingsDir"
+          >
+            <Input placeholder="Default" />
+          </Form.Item>
+
           <div style={{ marginTop: 'auto', display: 'flex', gap: 8 }}>
             <Button
               onClick={handleApply}
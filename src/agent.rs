@@ -0,0 +1,259 @@
+use crate::config;
+use crate::logging;
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+/// Start of the labels of the LaunchAgents this app installs.
+const LABEL_PREFIX: &str = "com.keval8solanki.macro.";
+
+/// When a scheduled macro runs, as the five fields of a cron line: minute,
+/// hour, day of month, month and day of week (0 or 7 for Sunday). Fields
+/// take `*`, numbers, ranges like `1-5`, steps like `*/15` and lists of
+/// those separated by commas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: String,
+    /// Allowed values of each field, `None` for any.
+    fields: [Option<Vec<u32>>; 5],
+}
+
+/// Names launchd gives the fields of a calendar interval, in cron order.
+const FIELD_KEYS: [&str; 5] = ["Minute", "Hour", "Day", "Month", "Weekday"];
+const FIELD_NAMES: [&str; 5] = ["minute", "hour", "day", "month", "weekday"];
+const FIELD_RANGES: [(u32, u32); 5] = [(0, 59), (0, 23), (1, 31), (1, 12), (0, 7)];
+
+impl CronSchedule {
+    /// The times the schedule matches as launchd calendar intervals, each a
+    /// list of keys and values. Fields left as `*` are left out.
+    pub fn calendar_intervals(&self) -> Vec<Vec<(&'static str, u32)>> {
+        let mut intervals = vec![Vec::new()];
+        for (key, values) in FIELD_KEYS.iter().zip(&self.fields) {
+            let Some(values) = values else { continue };
+            intervals = intervals
+                .into_iter()
+                .flat_map(|interval| {
+                    values.iter().map(move |&value| {
+                        let mut interval = interval.clone();
+                        interval.push((*key, value));
+                        interval
+                    })
+                })
+                .collect();
+        }
+        intervals
+    }
+}
+
+fn parse_field(field: &str, index: usize) -> Result<Option<Vec<u32>>, String> {
+    if field == "*" {
+        return Ok(None);
+    }
+    let (min, max) = FIELD_RANGES[index];
+    let invalid = || format!("expected a {} from {} to {}, got '{}'", FIELD_NAMES[index], min, max, field);
+    let number = |s: &str| s.parse::<u32>().ok().filter(|n| (min..=max).contains(n)).ok_or_else(invalid);
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|&step| step > 0).ok_or_else(invalid)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if start > end {
+            return Err(invalid());
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    if index == 4 {
+        // launchd counts Sunday as 0 only
+        values = values.into_iter().map(|day| day % 7).collect();
+    }
+    values.sort_unstable();
+    values.dedup();
+    Ok(Some(values))
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        if parts.len() != 5 {
+            return Err(format!("expected 5 fields like '30 9 * * 1-5', got '{}'", s));
+        }
+        let mut fields: [Option<Vec<u32>>; 5] = Default::default();
+        for (index, part) in parts.iter().enumerate() {
+            fields[index] = parse_field(part, index)?;
+        }
+        Ok(CronSchedule { expression: parts.join(" "), fields })
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+/// Label of the agent that plays `recording`, e.g.
+/// `com.keval8solanki.macro.login` for `login.json`.
+pub fn label_for(recording: &Path) -> String {
+    let stem = recording.file_stem().unwrap_or_default().to_string_lossy();
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    format!("{}{}", LABEL_PREFIX, name)
+}
+
+/// Folder launchd loads the user's agents from.
+pub fn agents_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or(PathBuf::from(".")).join("Library").join("LaunchAgents")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// LaunchAgent plist running the command line `program` as `label` on
+/// `schedule`. The schedule is kept in a comment, for listing.
+pub fn plist(label: &str, program: &[String], schedule: &CronSchedule) -> String {
+    let mut plist = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+    ));
+    plist.push_str(&format!("<!-- schedule: {} -->\n", schedule));
+    plist.push_str("<plist version=\"1.0\">\n<dict>\n");
+    plist.push_str(&format!("  <key>Label</key>\n  <string>{}</string>\n", escape(label)));
+    plist.push_str("  <key>ProgramArguments</key>\n  <array>\n");
+    for argument in program {
+        plist.push_str(&format!("    <string>{}</string>\n", escape(argument)));
+    }
+    plist.push_str("  </array>\n  <key>StartCalendarInterval</key>\n  <array>\n");
+    for interval in schedule.calendar_intervals() {
+        plist.push_str("    <dict>\n");
+        for (key, value) in interval {
+            plist.push_str(&format!("      <key>{}</key>\n      <integer>{}</integer>\n", key, value));
+        }
+        plist.push_str("    </dict>\n");
+    }
+    plist.push_str("  </array>\n</dict>\n</plist>\n");
+    plist
+}
+
+/// Schedule kept in a plist written by [`plist`].
+pub fn plist_schedule(plist: &str) -> Option<&str> {
+    let start = plist.find("<!-- schedule: ")? + "<!-- schedule: ".len();
+    let end = start + plist[start..].find(" -->")?;
+    Some(&plist[start..end])
+}
+
+/// Recording played by the agent in `plist`.
+fn plist_recording(plist: &str) -> Option<String> {
+    let rest = &plist[plist.find("<string>play</string>")? + "<string>play</string>".len()..];
+    let start = rest.find("<string>")? + "<string>".len();
+    let end = start + rest[start..].find("</string>")?;
+    Some(rest[start..end].replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&"))
+}
+
+fn launchctl(args: &[&str]) -> Result<()> {
+    let status = Command::new("launchctl").args(args).status().context("Could not run launchctl")?;
+    if !status.success() {
+        anyhow::bail!("launchctl {} failed with {}", args.join(" "), status);
+    }
+    Ok(())
+}
+
+fn ensure_macos() -> Result<()> {
+    if !cfg!(target_os = "macos") {
+        anyhow::bail!("Scheduled macros use launchd, which is only available on macOS");
+    }
+    Ok(())
+}
+
+/// Installs and loads a LaunchAgent that plays `recording` on `schedule`
+/// with `play_args` passed on to `macro play`. Replaces an agent already
+/// installed for a recording of the same name.
+pub fn install_agent(recording: &Path, schedule: &CronSchedule, play_args: &[String]) -> Result<()> {
+    ensure_macos()?;
+    let recording = std::path::absolute(recording)?;
+    if !recording.is_file() {
+        anyhow::bail!("Recording {:?} does not exist", recording);
+    }
+    let label = label_for(&recording);
+    let exe = std::env::current_exe()?;
+    let mut program = vec![
+        exe.to_string_lossy().into_owned(),
+        "play".to_string(),
+        recording.to_string_lossy().into_owned(),
+        "--immediate".to_string(),
+        "--log-file".to_string(),
+        logging::default_path(&label).to_string_lossy().into_owned(),
+    ];
+    // Scheduled runs use the config file this one was told to
+    if let Some(config) = std::env::var_os(config::PATH_VAR) {
+        program.extend(["--config".to_string(), config.to_string_lossy().into_owned()]);
+    }
+    program.extend(play_args.iter().cloned());
+
+    let path = agents_dir().join(format!("{}.plist", label));
+    if path.exists() {
+        // Loading again would keep the old schedule
+        let _ = launchctl(&["unload", &path.to_string_lossy()]);
+    }
+    fs::create_dir_all(agents_dir())?;
+    fs::write(&path, plist(&label, &program, schedule))?;
+    launchctl(&["load", "-w", &path.to_string_lossy()])?;
+    println!("Installed {} to play {} at '{}'.", label, recording.display(), schedule);
+    Ok(())
+}
+
+/// Plist of the agent called `name`, either its full label or the name of
+/// the recording it plays.
+fn agent_path(name: &str) -> PathBuf {
+    let label = if name.starts_with(LABEL_PREFIX) { name.to_string() } else { label_for(Path::new(name)) };
+    agents_dir().join(format!("{}.plist", label))
+}
+
+/// Unloads and removes the agent called `name`.
+pub fn uninstall_agent(name: &str) -> Result<()> {
+    ensure_macos()?;
+    let path = agent_path(name);
+    if !path.exists() {
+        anyhow::bail!("No scheduled macro called '{}', see `macro list-agents`", name);
+    }
+    let _ = launchctl(&["unload", "-w", &path.to_string_lossy()]);
+    fs::remove_file(&path)?;
+    println!("Removed {}.", path.file_stem().unwrap_or_default().to_string_lossy());
+    Ok(())
+}
+
+/// Prints the agents this app installed, with their schedules.
+pub fn list_agents() -> Result<()> {
+    ensure_macos()?;
+    let mut paths: Vec<PathBuf> = fs::read_dir(agents_dir())
+        .map(|entries| entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    paths.retain(|path| path.file_name().unwrap_or_default().to_string_lossy().starts_with(LABEL_PREFIX));
+    paths.sort();
+    if paths.is_empty() {
+        println!("No scheduled macros.");
+        return Ok(());
+    }
+    for path in paths {
+        let contents = fs::read_to_string(&path)?;
+        let label = path.file_stem().unwrap_or_default().to_string_lossy();
+        let recording = plist_recording(&contents).unwrap_or_default();
+        println!("{:<16} {}  {}", plist_schedule(&contents).unwrap_or("?"), label, recording);
+    }
+    Ok(())
+}
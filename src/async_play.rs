@@ -0,0 +1,69 @@
+use crate::play::{PlaybackControl, PlaybackProgress, Player};
+use crate::recording::Recording;
+use anyhow::Result;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Handle to playback running on tokio's blocking thread pool.
+///
+/// Dropping the handle does not stop playback; call [`stop`](Self::stop) first.
+pub struct PlaybackHandle {
+    control: PlaybackControl,
+    progress: mpsc::UnboundedReceiver<PlaybackProgress>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl PlaybackHandle {
+    pub fn pause(&self) {
+        self.control.pause();
+    }
+
+    pub fn resume(&self) {
+        self.control.resume();
+    }
+
+    pub fn stop(&self) {
+        self.control.stop();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.control.is_paused()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Next progress update, or `None` once playback has ended.
+    pub async fn progress(&mut self) -> Option<PlaybackProgress> {
+        self.progress.recv().await
+    }
+
+    /// Waits for playback to complete or be stopped. Fails if an assertion
+    /// in the recording does not hold.
+    pub async fn wait(self) -> Result<()> {
+        self.task
+            .await
+            .map_err(|e| anyhow::anyhow!("Playback task failed: {:?}", e))?
+    }
+}
+
+impl Player {
+    /// Starts playback without blocking the async runtime. Must be called
+    /// from within a tokio runtime.
+    pub fn play_async(self, recording: Recording) -> PlaybackHandle {
+        let control = self.control();
+        let (progress_tx, progress) = mpsc::unbounded_channel();
+        let task = tokio::task::spawn_blocking(move || {
+            self.play_with_progress(&recording, &mut |update| {
+                // The receiver may have been dropped; playback carries on regardless
+                let _ = progress_tx.send(update);
+            })
+        });
+        PlaybackHandle {
+            control,
+            progress,
+            task,
+        }
+    }
+}
@@ -0,0 +1,723 @@
+use crate::clock::Clock;
+use crate::display::{self, DisplayInfo};
+use crate::event::{ScrollDetails, ScrollPhase};
+use anyhow::Result;
+use rdev::{Event, EventType};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub type EventCallback = Box<dyn FnMut(Event) + Send + 'static>;
+
+/// Source of captured input events and sink for simulated ones.
+///
+/// Recording and playback only talk to the system through this trait, so a
+/// different implementation (native event taps, enigo, a test double) can be
+/// swapped in without touching their logic.
+pub trait InputBackend: Send + Sync {
+    /// Blocks the calling thread, delivering every captured event to `callback`.
+    fn listen(&self, callback: EventCallback) -> Result<()>;
+
+    /// Injects a single event into the system.
+    fn simulate(&self, event_type: &EventType) -> Result<()>;
+
+    /// Injects a pixel-precision scroll. Backends that cannot post continuous
+    /// scroll events fall back to a rounded wheel event.
+    fn simulate_smooth_scroll(&self, delta_x: f64, delta_y: f64, _phase: ScrollPhase) -> Result<()> {
+        self.simulate(&EventType::Wheel {
+            delta_x: delta_x.round() as i64,
+            delta_y: delta_y.round() as i64,
+        })
+    }
+
+    /// What the backend saw of the wheel event it is handing to the
+    /// `listen` callback right now, beyond its deltas. Only answers from
+    /// inside that callback, and only where the backend can tell.
+    fn scroll_details(&self) -> Option<ScrollDetails> {
+        None
+    }
+
+    /// Types `text` directly, independent of the keyboard layout. An empty
+    /// string types nothing.
+    fn simulate_text(&self, text: &str) -> Result<()> {
+        let _ = text;
+        anyhow::bail!("Text input is not supported by this backend")
+    }
+
+    /// Current text on the system clipboard, or `None` if it holds no text.
+    fn clipboard_text(&self) -> Result<Option<String>> {
+        anyhow::bail!("Clipboard is not supported by this backend")
+    }
+
+    /// Replaces the system clipboard contents with `text`.
+    fn set_clipboard_text(&self, text: &str) -> Result<()> {
+        let _ = text;
+        anyhow::bail!("Clipboard is not supported by this backend")
+    }
+
+    /// RGB color of the screen pixel at `x`, `y`, in the coordinate space used
+    /// for events.
+    fn pixel_color(&self, x: f64, y: f64) -> Result<[u8; 3]> {
+        let _ = (x, y);
+        anyhow::bail!("Reading screen pixels is not supported by this backend")
+    }
+
+    /// Name of the application that currently has keyboard focus.
+    fn frontmost_app(&self) -> Result<String> {
+        anyhow::bail!("Querying the frontmost app is not supported by this backend")
+    }
+
+    /// Bundle identifier of the application that currently has keyboard
+    /// focus, e.g. "com.apple.Safari".
+    fn frontmost_app_id(&self) -> Result<String> {
+        anyhow::bail!("Querying the frontmost app is not supported by this backend")
+    }
+
+    /// Opens the application named `app`, or with that bundle identifier,
+    /// bringing it to the front if it is already running.
+    fn launch_app(&self, app: &str) -> Result<()> {
+        let _ = app;
+        anyhow::bail!("Launching apps is not supported by this backend")
+    }
+
+    /// Saves a PNG image of all displays to `path`.
+    fn capture_screen(&self, path: &Path) -> Result<()> {
+        let _ = path;
+        anyhow::bail!("Screenshots are not supported by this backend")
+    }
+
+    /// Displays currently connected, in the coordinate space used for events.
+    fn displays(&self) -> Vec<DisplayInfo> {
+        display::active_displays()
+    }
+}
+
+/// Backend built on rdev: CGEventTap on macOS, XRecord/XTest on Linux and
+/// low-level hooks on Windows.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RdevBackend;
+
+// Not exposed by core-graphics: kCGScrollWheelEventScrollPhase / MomentumPhase
+#[cfg(target_os = "macos")]
+const SCROLL_PHASE: u32 = 99;
+#[cfg(target_os = "macos")]
+const MOMENTUM_PHASE: u32 = 123;
+
+#[cfg(target_os = "macos")]
+thread_local! {
+    // Set while the listening thread hands a scroll event to its callback
+    static SCROLL: std::cell::Cell<Option<ScrollDetails>> = const { std::cell::Cell::new(None) };
+}
+
+impl InputBackend for RdevBackend {
+    #[cfg(not(target_os = "macos"))]
+    fn listen(&self, callback: EventCallback) -> Result<()> {
+        rdev::listen(callback).map_err(|e| anyhow::anyhow!("Listen error: {:?}", e))
+    }
+
+    /// rdev reports scrolling as whole points only, so a second event tap
+    /// on the same run loop takes over scroll events and hands them on with
+    /// their device type and gesture phase.
+    #[cfg(target_os = "macos")]
+    fn listen(&self, callback: EventCallback) -> Result<()> {
+        use core_foundation::runloop::{kCFRunLoopCommonModes, CFRunLoop};
+        use core_graphics::event::{CGEventTap, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement, CGEventType, EventField};
+        use std::rc::Rc;
+        use std::time::SystemTime;
+
+        // Both taps call back on this thread, one event at a time
+        let callback = Rc::new(RefCell::new(callback));
+        let scroll_callback = callback.clone();
+        let tap = CGEventTap::new(
+            CGEventTapLocation::HID,
+            CGEventTapPlacement::HeadInsertEventTap,
+            CGEventTapOptions::ListenOnly,
+            vec![CGEventType::ScrollWheel],
+            move |_, _, event| {
+                let delta_x = event.get_integer_value_field(EventField::SCROLL_WHEEL_EVENT_POINT_DELTA_AXIS_2);
+                let delta_y = event.get_integer_value_field(EventField::SCROLL_WHEEL_EVENT_POINT_DELTA_AXIS_1);
+                SCROLL.set(Some(ScrollDetails {
+                    continuous: event.get_integer_value_field(EventField::SCROLL_WHEEL_EVENT_IS_CONTINUOUS) != 0,
+                    delta_x: delta_x as f64,
+                    delta_y: delta_y as f64,
+                    phase: scroll_phase(event.get_integer_value_field(SCROLL_PHASE), event.get_integer_value_field(MOMENTUM_PHASE)),
+                }));
+                (scroll_callback.borrow_mut())(Event {
+                    time: SystemTime::now(),
+                    name: None,
+                    event_type: EventType::Wheel { delta_x, delta_y },
+                });
+                SCROLL.set(None);
+                None
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("Listen error: could not create the scroll event tap"))?;
+        let source = tap
+            .mach_port
+            .create_runloop_source(0)
+            .map_err(|_| anyhow::anyhow!("Listen error: could not add the scroll event tap to the run loop"))?;
+        unsafe { CFRunLoop::get_current().add_source(&source, kCFRunLoopCommonModes) };
+        tap.enable();
+
+        // rdev runs this thread's run loop, which serves the scroll tap too
+        rdev::listen(move |event| {
+            if !matches!(event.event_type, EventType::Wheel { .. }) {
+                (callback.borrow_mut())(event);
+            }
+        })
+        .map_err(|e| anyhow::anyhow!("Listen error: {:?}", e))
+    }
+
+    #[cfg(target_os = "macos")]
+    fn scroll_details(&self) -> Option<ScrollDetails> {
+        SCROLL.get()
+    }
+
+    fn simulate(&self, event_type: &EventType) -> Result<()> {
+        rdev::simulate(event_type).map_err(|e| anyhow::anyhow!("Simulate error: {:?}", e))
+    }
+
+    fn clipboard_text(&self) -> Result<Option<String>> {
+        with_clipboard(|clipboard| match clipboard.get_text() {
+            Ok(text) => Ok(Some(text)),
+            Err(arboard::Error::ContentNotAvailable) => Ok(None),
+            Err(e) => Err(e),
+        })
+    }
+
+    fn set_clipboard_text(&self, text: &str) -> Result<()> {
+        with_clipboard(|clipboard| clipboard.set_text(text))
+    }
+
+    /// Posts a continuous pixel scroll, so apps apply trackpad-style smooth
+    /// scrolling rather than treating each event as a wheel notch.
+    #[cfg(target_os = "macos")]
+    fn simulate_smooth_scroll(&self, delta_x: f64, delta_y: f64, phase: ScrollPhase) -> Result<()> {
+        use core_graphics::event::{CGEvent, CGEventTapLocation, EventField, ScrollEventUnit};
+        use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+
+        let source = CGEventSource::new(CGEventSourceStateID::HIDSystemState)
+            .map_err(|_| anyhow::anyhow!("Simulate error: could not create event source"))?;
+        let event = CGEvent::new_scroll_event(source, ScrollEventUnit::PIXEL, 2, delta_y.round() as i32, delta_x.round() as i32, 0)
+            .map_err(|_| anyhow::anyhow!("Simulate error: could not create scroll event"))?;
+        event.set_integer_value_field(EventField::SCROLL_WHEEL_EVENT_IS_CONTINUOUS, 1);
+        event.set_double_value_field(EventField::SCROLL_WHEEL_EVENT_FIXED_POINT_DELTA_AXIS_1, delta_y);
+        event.set_double_value_field(EventField::SCROLL_WHEEL_EVENT_FIXED_POINT_DELTA_AXIS_2, delta_x);
+        // NSEventPhase values for the gesture, and kCGMomentumScrollPhaseContinue for momentum
+        match phase {
+            ScrollPhase::None => {}
+            ScrollPhase::Began => event.set_integer_value_field(SCROLL_PHASE, 1),
+            ScrollPhase::Changed => event.set_integer_value_field(SCROLL_PHASE, 4),
+            ScrollPhase::Ended => event.set_integer_value_field(SCROLL_PHASE, 8),
+            ScrollPhase::Momentum => event.set_integer_value_field(MOMENTUM_PHASE, 2),
+        }
+        event.post(CGEventTapLocation::HID);
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    fn simulate_text(&self, text: &str) -> Result<()> {
+        use core_graphics::event::{CGEvent, CGEventTapLocation};
+        use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+
+        if text.is_empty() {
+            return Ok(());
+        }
+        for key_down in [true, false] {
+            let source = CGEventSource::new(CGEventSourceStateID::HIDSystemState)
+                .map_err(|_| anyhow::anyhow!("Simulate error: could not create event source"))?;
+            let event = CGEvent::new_keyboard_event(source, 0, key_down)
+                .map_err(|_| anyhow::anyhow!("Simulate error: could not create keyboard event"))?;
+            // The attached string replaces whatever the key code would have typed
+            event.set_string(text);
+            event.post(CGEventTapLocation::HID);
+        }
+        Ok(())
+    }
+
+    /// Reads the pixel from a one-point screen capture, which needs the
+    /// Screen Recording permission to see anything but the desktop.
+    #[cfg(target_os = "macos")]
+    fn pixel_color(&self, x: f64, y: f64) -> Result<[u8; 3]> {
+        use core_graphics::display::{
+            kCGNullWindowID, kCGWindowImageDefault, kCGWindowListOptionOnScreenOnly, CGDisplay, CGPoint, CGRect, CGSize,
+        };
+
+        let rect = CGRect::new(&CGPoint::new(x, y), &CGSize::new(1.0, 1.0));
+        let image = CGDisplay::screenshot(rect, kCGWindowListOptionOnScreenOnly, kCGNullWindowID, kCGWindowImageDefault)
+            .ok_or_else(|| anyhow::anyhow!("Could not capture the screen at ({}, {})", x, y))?;
+        if image.bits_per_pixel() != 32 {
+            anyhow::bail!("Unsupported screen pixel format: {} bits per pixel", image.bits_per_pixel());
+        }
+        // Screen captures are 32-bit little-endian ARGB, i.e. BGRA in memory
+        match image.data().bytes() {
+            [b, g, r, _, ..] => Ok([*r, *g, *b]),
+            _ => anyhow::bail!("Screen capture at ({}, {}) was empty", x, y),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn capture_screen(&self, path: &Path) -> Result<()> {
+        // -x: no shutter sound
+        let status = std::process::Command::new("screencapture")
+            .arg("-x")
+            .arg(path)
+            .status()?;
+        if !status.success() {
+            anyhow::bail!("screencapture exited with {}", status);
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    fn frontmost_app(&self) -> Result<String> {
+        use objc2_app_kit::NSWorkspace;
+
+        NSWorkspace::sharedWorkspace()
+            .frontmostApplication()
+            .and_then(|app| app.localizedName())
+            .map(|name| name.to_string())
+            .ok_or_else(|| anyhow::anyhow!("No frontmost application"))
+    }
+
+    #[cfg(target_os = "macos")]
+    fn frontmost_app_id(&self) -> Result<String> {
+        use objc2_app_kit::NSWorkspace;
+
+        NSWorkspace::sharedWorkspace()
+            .frontmostApplication()
+            .and_then(|app| app.bundleIdentifier())
+            .map(|id| id.to_string())
+            .ok_or_else(|| anyhow::anyhow!("No frontmost application"))
+    }
+
+    #[cfg(target_os = "macos")]
+    fn launch_app(&self, app: &str) -> Result<()> {
+        // -b opens by bundle identifier, -a by name
+        let flag = if is_bundle_id(app) { "-b" } else { "-a" };
+        let status = std::process::Command::new("open").arg(flag).arg(app).status()?;
+        if !status.success() {
+            anyhow::bail!("Could not open {:?}: open exited with {}", app, status);
+        }
+        Ok(())
+    }
+}
+
+/// Gesture phase of a scroll from its NSEventPhase and momentum phase fields.
+#[cfg(target_os = "macos")]
+fn scroll_phase(phase: i64, momentum: i64) -> ScrollPhase {
+    match (phase, momentum) {
+        (_, 1..) => ScrollPhase::Momentum,
+        (1, _) => ScrollPhase::Began,
+        (4, _) => ScrollPhase::Changed,
+        // Ended or cancelled
+        (8 | 16, _) => ScrollPhase::Ended,
+        _ => ScrollPhase::None,
+    }
+}
+
+/// Whether `app` looks like a bundle identifier such as "com.apple.Safari"
+/// rather than an application name.
+#[cfg(target_os = "macos")]
+fn is_bundle_id(app: &str) -> bool {
+    app.contains('.') && !app.contains(char::is_whitespace) && !app.ends_with(".app")
+}
+
+thread_local! {
+    // Kept open for the life of the thread: on X11 the clipboard contents are
+    // served by their owner, so they would vanish as soon as it was dropped.
+    static CLIPBOARD: RefCell<Option<arboard::Clipboard>> = const { RefCell::new(None) };
+}
+
+fn with_clipboard<T>(f: impl FnOnce(&mut arboard::Clipboard) -> Result<T, arboard::Error>) -> Result<T> {
+    CLIPBOARD.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let clipboard = match cell.as_mut() {
+            Some(clipboard) => clipboard,
+            None => cell.insert(arboard::Clipboard::new().map_err(|e| anyhow::anyhow!("Clipboard error: {:?}", e))?),
+        };
+        f(clipboard).map_err(|e| anyhow::anyhow!("Clipboard error: {:?}", e))
+    })
+}
+
+pub fn default_backend() -> Arc<dyn InputBackend> {
+    Arc::new(RdevBackend)
+}
+
+/// How long a simulated event may take to come back through capture before
+/// it is no longer expected.
+const ECHO_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Captured event a simulated one is expected to come back as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Echo {
+    /// This event, with mouse positions within a point of it.
+    Event(EventType),
+    /// Any wheel event, from a smooth scroll.
+    Wheel,
+    /// Any key press or release, from typed text.
+    KeyPress,
+    KeyRelease,
+}
+
+impl Echo {
+    fn matches(&self, captured: &EventType) -> bool {
+        match (self, captured) {
+            (Echo::Event(EventType::MouseMove { x, y }), EventType::MouseMove { x: cx, y: cy }) => (x - cx).abs() <= 1.0 && (y - cy).abs() <= 1.0,
+            (Echo::Event(expected), captured) => expected == captured,
+            (Echo::Wheel, EventType::Wheel { .. }) => true,
+            (Echo::KeyPress, EventType::KeyPress(_)) => true,
+            (Echo::KeyRelease, EventType::KeyRelease(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Simulated events on their way back through capture, so they can be told
+/// apart from real input. Each expected echo swallows the first matching
+/// captured event, or is dropped once `timeout` has passed.
+pub struct EchoFilter {
+    timeout: Duration,
+    pending: Mutex<VecDeque<(Instant, Echo)>>,
+}
+
+impl EchoFilter {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout, pending: Mutex::new(VecDeque::new()) }
+    }
+
+    /// Expects `echoes` back, in order, from an event about to be simulated.
+    pub fn expect(&self, echoes: &[Echo]) {
+        let now = Instant::now();
+        self.pending.lock().unwrap().extend(echoes.iter().map(|&echo| (now, echo)));
+    }
+
+    /// Takes back the last `count` echoes expected, for an event that
+    /// could not be simulated after all.
+    pub fn withdraw(&self, count: usize) {
+        let mut pending = self.pending.lock().unwrap();
+        let keep = pending.len().saturating_sub(count);
+        pending.truncate(keep);
+    }
+
+    /// Whether `captured` is the echo of a simulated event, which it is then
+    /// no longer expected as.
+    pub fn is_echo(&self, captured: &EventType) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|(at, _)| at.elapsed() < self.timeout);
+        match pending.iter().position(|(_, echo)| echo.matches(captured)) {
+            Some(index) => {
+                pending.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Wraps a backend so that one process can record and listen for hotkeys
+/// again and again. Most backends can only start listening once per process
+/// and never stop, so the wrapped backend listens once, on its own thread,
+/// and hands events to whoever called `listen` last. An earlier caller's
+/// `listen` returns as soon as a later one takes over.
+///
+/// Events simulated through it are not handed on when they come back
+/// through capture, so recording while playing never records the playback
+/// and played hotkeys do not fire. Events simulated by other processes are
+/// still captured.
+pub struct SharedBackend {
+    inner: Arc<dyn InputBackend>,
+    listener: Arc<Mutex<SharedListener>>,
+    echoes: Arc<EchoFilter>,
+}
+
+#[derive(Default)]
+struct SharedListener {
+    started: bool,
+    subscriber: Option<Subscriber>,
+    /// Callbacks handed every event next to the subscriber, never replaced.
+    watchers: Vec<EventCallback>,
+    /// How the wrapped backend stopped listening, once it has.
+    ended: Option<Result<(), String>>,
+}
+
+struct Subscriber {
+    callback: EventCallback,
+    /// Dropped when the subscriber is replaced, which ends its `listen`.
+    done: mpsc::Sender<Result<()>>,
+}
+
+impl SharedBackend {
+    pub fn new(inner: Arc<dyn InputBackend>) -> Self {
+        Self {
+            inner,
+            listener: Arc::default(),
+            echoes: Arc::new(EchoFilter::new(ECHO_TIMEOUT)),
+        }
+    }
+
+    /// Runs `simulate`, expecting `echoes` back from it unless it fails.
+    fn simulating(&self, echoes: &[Echo], simulate: impl FnOnce() -> Result<()>) -> Result<()> {
+        // Expected beforehand, as capture can see the event before simulate returns
+        self.echoes.expect(echoes);
+        let result = simulate();
+        if result.is_err() {
+            self.echoes.withdraw(echoes.len());
+        }
+        result
+    }
+
+    /// Hands every event to `callback` too, whoever is listening, for as
+    /// long as the process runs, e.g. for a stop hotkey that should work
+    /// while recording.
+    pub fn watch(&self, callback: EventCallback) {
+        let mut listener = self.listener.lock().unwrap();
+        listener.watchers.push(callback);
+        if !listener.started {
+            listener.started = true;
+            self.start_listening();
+        }
+    }
+
+    fn start_listening(&self) {
+        let inner = self.inner.clone();
+        let listener = self.listener.clone();
+        let echoes = self.echoes.clone();
+        thread::spawn(move || {
+            let deliver = listener.clone();
+            let result = inner.listen(Box::new(move |event| {
+                if echoes.is_echo(&event.event_type) {
+                    return;
+                }
+                let mut deliver = deliver.lock().unwrap();
+                for watcher in &mut deliver.watchers {
+                    watcher(event.clone());
+                }
+                if let Some(subscriber) = &mut deliver.subscriber {
+                    (subscriber.callback)(event);
+                }
+            }));
+            let mut listener = listener.lock().unwrap();
+            listener.ended = Some(result.as_ref().map(|_| ()).map_err(|e| format!("{:#}", e)));
+            if let Some(subscriber) = listener.subscriber.take() {
+                let _ = subscriber.done.send(result);
+            }
+        });
+    }
+}
+
+impl InputBackend for SharedBackend {
+    fn listen(&self, callback: EventCallback) -> Result<()> {
+        let (done_tx, done_rx) = mpsc::channel();
+        {
+            let mut listener = self.listener.lock().unwrap();
+            if let Some(ended) = &listener.ended {
+                return ended.clone().map_err(anyhow::Error::msg);
+            }
+            listener.subscriber = Some(Subscriber { callback, done: done_tx });
+            if !listener.started {
+                listener.started = true;
+                self.start_listening();
+            }
+        }
+        // Disconnected once another caller takes over
+        done_rx.recv().unwrap_or(Ok(()))
+    }
+
+    fn simulate(&self, event_type: &EventType) -> Result<()> {
+        self.simulating(&[Echo::Event(*event_type)], || self.inner.simulate(event_type))
+    }
+
+    fn simulate_smooth_scroll(&self, delta_x: f64, delta_y: f64, phase: ScrollPhase) -> Result<()> {
+        self.simulating(&[Echo::Wheel], || self.inner.simulate_smooth_scroll(delta_x, delta_y, phase))
+    }
+
+    fn scroll_details(&self) -> Option<ScrollDetails> {
+        self.inner.scroll_details()
+    }
+
+    fn simulate_text(&self, text: &str) -> Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.simulating(&[Echo::KeyPress, Echo::KeyRelease], || self.inner.simulate_text(text))
+    }
+
+    fn clipboard_text(&self) -> Result<Option<String>> {
+        self.inner.clipboard_text()
+    }
+
+    fn set_clipboard_text(&self, text: &str) -> Result<()> {
+        self.inner.set_clipboard_text(text)
+    }
+
+    fn pixel_color(&self, x: f64, y: f64) -> Result<[u8; 3]> {
+        self.inner.pixel_color(x, y)
+    }
+
+    fn frontmost_app(&self) -> Result<String> {
+        self.inner.frontmost_app()
+    }
+
+    fn frontmost_app_id(&self) -> Result<String> {
+        self.inner.frontmost_app_id()
+    }
+
+    fn launch_app(&self, app: &str) -> Result<()> {
+        self.inner.launch_app(app)
+    }
+
+    fn capture_screen(&self, path: &Path) -> Result<()> {
+        self.inner.capture_screen(path)
+    }
+
+    fn displays(&self) -> Vec<DisplayInfo> {
+        self.inner.displays()
+    }
+}
+
+/// Backend that never touches the system: `listen` replays a scripted list of
+/// input events and `simulate` appends to an in-memory buffer, timestamped by
+/// the given clock.
+pub struct MockBackend {
+    clock: Arc<dyn Clock>,
+    input: Mutex<Vec<Event>>,
+    simulated: Mutex<Vec<(Duration, EventType)>>,
+    clipboard: Mutex<Option<String>>,
+    screenshots: Mutex<Vec<PathBuf>>,
+    frontmost_app: Mutex<Option<String>>,
+    displays: Vec<DisplayInfo>,
+    /// Details of the wheel events in `input` still to come, and of the one
+    /// being delivered.
+    scrolls: Mutex<VecDeque<ScrollDetails>>,
+    scroll: Mutex<Option<ScrollDetails>>,
+}
+
+impl MockBackend {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            input: Mutex::new(Vec::new()),
+            simulated: Mutex::new(Vec::new()),
+            clipboard: Mutex::new(None),
+            screenshots: Mutex::new(Vec::new()),
+            frontmost_app: Mutex::new(None),
+            displays: vec![DisplayInfo {
+                id: 1,
+                x: 0.0,
+                y: 0.0,
+                width: 1920.0,
+                height: 1080.0,
+                is_primary: true,
+                scale_factor: 1.0,
+            }],
+            scrolls: Mutex::new(VecDeque::new()),
+            scroll: Mutex::new(None),
+        }
+    }
+
+    /// Events delivered to the callback by the next `listen` call.
+    pub fn with_input(self, events: Vec<Event>) -> Self {
+        *self.input.lock().unwrap() = events;
+        self
+    }
+
+    /// Details reported for the wheel events in the input, in order. Wheel
+    /// events beyond them report none.
+    pub fn with_scroll_details(self, details: Vec<ScrollDetails>) -> Self {
+        *self.scrolls.lock().unwrap() = details.into();
+        self
+    }
+
+    pub fn with_displays(mut self, displays: Vec<DisplayInfo>) -> Self {
+        self.displays = displays;
+        self
+    }
+
+    /// Initial clipboard text.
+    pub fn with_clipboard(self, text: &str) -> Self {
+        *self.clipboard.lock().unwrap() = Some(text.to_string());
+        self
+    }
+
+    /// Name of the initially frontmost app. `launch_app` brings the app it
+    /// opens to the front.
+    pub fn with_frontmost_app(self, app: &str) -> Self {
+        *self.frontmost_app.lock().unwrap() = Some(app.to_string());
+        self
+    }
+
+    /// Simulated events with the clock time at which each was injected.
+    pub fn simulated(&self) -> Vec<(Duration, EventType)> {
+        self.simulated.lock().unwrap().clone()
+    }
+
+    pub fn clipboard(&self) -> Option<String> {
+        self.clipboard.lock().unwrap().clone()
+    }
+
+    /// Paths screenshots were requested at. No image is written.
+    pub fn screenshots(&self) -> Vec<PathBuf> {
+        self.screenshots.lock().unwrap().clone()
+    }
+}
+
+impl InputBackend for MockBackend {
+    fn listen(&self, mut callback: EventCallback) -> Result<()> {
+        let events = std::mem::take(&mut *self.input.lock().unwrap());
+        for event in events {
+            if matches!(event.event_type, EventType::Wheel { .. }) {
+                *self.scroll.lock().unwrap() = self.scrolls.lock().unwrap().pop_front();
+            }
+            callback(event);
+            *self.scroll.lock().unwrap() = None;
+        }
+        Ok(())
+    }
+
+    fn scroll_details(&self) -> Option<ScrollDetails> {
+        *self.scroll.lock().unwrap()
+    }
+
+    fn simulate(&self, event_type: &EventType) -> Result<()> {
+        self.simulated
+            .lock()
+            .unwrap()
+            .push((self.clock.elapsed(), *event_type));
+        Ok(())
+    }
+
+    fn clipboard_text(&self) -> Result<Option<String>> {
+        Ok(self.clipboard())
+    }
+
+    fn set_clipboard_text(&self, text: &str) -> Result<()> {
+        *self.clipboard.lock().unwrap() = Some(text.to_string());
+        Ok(())
+    }
+
+    fn frontmost_app(&self) -> Result<String> {
+        self.frontmost_app
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("No frontmost application"))
+    }
+
+    fn launch_app(&self, app: &str) -> Result<()> {
+        *self.frontmost_app.lock().unwrap() = Some(app.to_string());
+        Ok(())
+    }
+
+    fn capture_screen(&self, path: &Path) -> Result<()> {
+        self.screenshots.lock().unwrap().push(path.to_path_buf());
+        Ok(())
+    }
+
+    fn displays(&self) -> Vec<DisplayInfo> {
+        self.displays.clone()
+    }
+}
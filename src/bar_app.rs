@@ -1,42 +1,129 @@
 use anyhow::Result;
+use base64::prelude::{BASE64_STANDARD, Engine};
 use chrono::Local;
-use dirs::document_dir;
-use global_hotkey::GlobalHotKeyEvent;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager};
 use global_hotkey::hotkey::{Code, HotKey, Modifiers};
 
 use std::fs;
-use std::path::PathBuf;
-use std::process::{Child, Command};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 use tao::event_loop::{ControlFlow, EventLoopProxy};
 use tao::window::{Window, WindowBuilder};
-use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tray_icon::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
 use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
 use wry::{WebView, WebViewBuilder};
 
-use self_update::cargo_crate_version;
+use crate::overlay::{OverlayFeed, OverlayMessage, PlaybackOverlay, RecordingIndicator};
+
+use macro_lib::config::{Config, ConfigWatcher, KeyCombo, KeyMaps, Modifier, UpdateChannel};
+use macro_lib::environment::Environment;
+use macro_lib::history::{self, HistoryEntry, RunKind, RunTimer};
+use macro_lib::backend::{self, InputBackend, SharedBackend};
+use macro_lib::platform;
+use macro_lib::play::{self, PlaybackControl, PlaybackOptions, PlaybackOutcome, Player};
+use macro_lib::record::{self, RecordLimit, RecordOptions, Recorder, RecordingHandle, UndoScope};
+use macro_lib::recording::{self, JsonlWriter, Recording, RecordingMetadata};
+use macro_lib::own_ui::{Area, OwnUi};
+use macro_lib::recovery;
+use macro_lib::screenshot;
+use macro_lib::sound::{self, Cue};
+use macro_lib::update;
+use macro_lib::worker::WorkerReport;
+use rdev::Key;
+
+/// How many times in a row recording is restarted after failing before it
+/// is stopped for good.
+const MAX_RECORDER_RESTARTS: u32 = 3;
+
+/// Most recent runs listed in the History submenu.
+const HISTORY_MENU_LENGTH: usize = 10;
 
 #[derive(Debug, Clone)]
 pub enum AppEvent {
     GlobalHotkeyEvent(GlobalHotKeyEvent),
     MenuEvent(MenuEvent),
     SettingsApplied(SettingsMessage),
+    PlaybackReport(WorkerReport),
+    Overlay(OverlayMessage),
+    /// The check on launch found this version to update to.
+    UpdateAvailable(String),
 }
 
 pub struct AppState {
     pub is_recording: bool,
-    pub recording_process: Option<Child>,
-    pub playback_process: Option<Child>,
+    pub is_paused: bool,
+    pub recording_run: Option<RecordingRun>,
+    pub playback_run: Option<PlaybackRun>,
     pub playback_speed: f64,
     pub repeat_count: u32,
     pub repeat_interval: f64,
+    /// Seconds to wait before playing, from the loaded recording's or a
+    /// preset's settings. The config file's countdown when `None`.
+    pub countdown: Option<f64>,
     pub pending_playback: Option<PathBuf>,
     pub current_recording_path: Option<PathBuf>,
+    /// Temp files of the earlier parts of the current recording, from before
+    /// it failed and was restarted. Oldest first.
+    pub recording_segments: Vec<PathBuf>,
+    /// Times the current recording from its first part, for the history.
+    pub recording_started: Option<RunTimer>,
     pub last_record_hotkey_pressed: bool,
     pub last_playback_hotkey_pressed: bool,
     pub last_load_hotkey_pressed: bool,
+    pub last_pause_hotkey_pressed: bool,
+    pub last_panic_hotkey_pressed: bool,
+    /// Last error playback reported, shown if the run fails.
+    pub playback_error: Option<String>,
+}
+
+/// Recording captured on a thread of the app.
+pub struct RecordingRun {
+    handle: RecordingHandle,
+    thread: JoinHandle<Result<Option<Recording>>>,
+    /// The limit that stopped capture, if one did.
+    limit: Arc<Mutex<Option<RecordLimit>>>,
+}
+
+impl RecordingRun {
+    fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stops capture and waits for the events still in flight to be written.
+    fn stop(self) -> Result<Option<Recording>> {
+        self.handle.stop();
+        self.join()
+    }
+
+    fn join(self) -> Result<Option<Recording>> {
+        self.thread.join().map_err(|_| anyhow::anyhow!("Recording thread panicked"))?
+    }
+}
+
+/// Playback running on a thread of the app.
+pub struct PlaybackRun {
+    control: PlaybackControl,
+    thread: JoinHandle<Result<()>>,
+}
+
+impl PlaybackRun {
+    fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stops playback and waits for it to release what it is holding.
+    fn stop(self) -> Result<()> {
+        self.control.stop();
+        self.join()
+    }
+
+    fn join(self) -> Result<()> {
+        self.thread.join().map_err(|_| anyhow::anyhow!("Playback thread panicked"))?
+    }
 }
 
 pub struct BarApp {
@@ -45,19 +132,95 @@ pub struct BarApp {
     pub tray_icon: Option<TrayIcon>,
     pub recording_menu_item: MenuItem,
     pub playback_menu_item: MenuItem,
+    pub pause_menu_item: MenuItem,
     pub load_menu_item: MenuItem,
     pub settings_menu_item: MenuItem, // Changed from Submenu
+    /// Applies a preset from the config to the playback settings.
+    pub presets_menu: Submenu,
+    /// Items of `presets_menu`, with the name of the preset each applies.
+    pub preset_items: Vec<(MenuItem, String)>,
+    pub reload_last_item: CheckMenuItem,
+    pub overlay_item: CheckMenuItem,
+    pub indicator_item: CheckMenuItem,
+    pub sounds_item: CheckMenuItem,
+    /// Lists the most recent runs from the history file.
+    pub history_menu: Submenu,
     pub quit_i: MenuItem,
     pub icon_idle: Icon,
     pub icon_recording: Icon,
+    /// Alternates with `icon_recording` each second so live capture stands out.
+    pub icon_recording_dim: Icon,
     pub icon_playing: Icon,
     pub icon_armed: Icon,
+    pub icon_paused: Icon,
     pub record_hotkey: HotKey,
     pub playback_hotkey: HotKey,
     pub load_hotkey: HotKey,
+    pub pause_hotkey: HotKey,
+    pub panic_hotkey: HotKey,
+    pub hotkey_manager: GlobalHotKeyManager,
+    pub config: Config,
+    pub config_watcher: ConfigWatcher,
     pub check_updates_item: MenuItem,
+    pub open_logs_item: MenuItem,
+    /// Folder holding the app's log file.
+    pub logs_dir: PathBuf,
     pub settings_window: Option<Window>,
     pub settings_webview: Option<WebView>,
+    /// Input for recording and playback. Shared, as capture can only be
+    /// started once per process.
+    backend: Arc<dyn InputBackend>,
+    /// Running while recording, for the time shown in the tray.
+    recording_timer: Option<RecordingTimer>,
+    /// Times the current recording has failed and been restarted.
+    recorder_restarts: u32,
+    /// Shown while playing with the overlay turned on, from the first event
+    /// it has something to show for.
+    overlay: Option<PlaybackOverlay>,
+    /// Shown while recording with the indicator turned on.
+    recording_indicator: Option<RecordingIndicator>,
+    /// The indicator could not be opened for the current recording, so it
+    /// is not tried again until the next one.
+    recording_indicator_failed: bool,
+    /// Where the tray icon and settings window are, so recordings leave out
+    /// clicks on them.
+    own_ui: OwnUi,
+}
+
+/// Time spent recording, leaving out time spent paused.
+struct RecordingTimer {
+    started: Instant,
+    paused_at: Option<Instant>,
+    paused_for: Duration,
+    /// Whole seconds last shown in the tray, to update it once a second.
+    shown_secs: Option<u64>,
+}
+
+impl RecordingTimer {
+    fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            paused_at: None,
+            paused_for: Duration::ZERO,
+            shown_secs: None,
+        }
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        match (paused, self.paused_at) {
+            (true, None) => self.paused_at = Some(Instant::now()),
+            (false, Some(at)) => {
+                self.paused_for += at.elapsed();
+                self.paused_at = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        let end = self.paused_at.unwrap_or_else(Instant::now);
+        end.duration_since(self.started).saturating_sub(self.paused_for)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -67,15 +230,32 @@ pub struct SettingsMessage {
     pub interval: f64,
     #[serde(default)]
     pub should_play: bool,
+    /// Seconds to wait before playback starts.
+    #[serde(default)]
+    pub countdown: f64,
+    /// Largest random shift of each played event, in milliseconds.
+    #[serde(default)]
+    pub humanize_ms: u64,
+    /// Shortest time between recorded mouse moves in milliseconds, 0 for all.
+    #[serde(default)]
+    pub move_interval_ms: u64,
+    /// Longest recorded pause in seconds, 0 for no limit.
+    #[serde(default)]
+    pub max_gap: f64,
+    /// Where recordings are saved and loaded from, empty for the default.
+    #[serde(default)]
+    pub recordings_dir: String,
 }
 
 impl BarApp {
-    pub fn new(proxy: EventLoopProxy<AppEvent>) -> Result<Self> {
+    pub fn new(proxy: EventLoopProxy<AppEvent>, log_file: &Path) -> Result<Self> {
         // Icons
         let icon_idle = create_icon(255, 255, 255, 255); // White
         let icon_recording = create_icon(255, 86, 86, 255); // #FF5656
+        let icon_recording_dim = create_icon(255, 86, 86, 128);
         let icon_playing = create_icon(115, 175, 111, 255); // #73AF6F
         let icon_armed = create_icon(255, 162, 57, 255); // #FFA239
+        let icon_paused = create_icon(142, 142, 147, 255); // #8E8E93
 
         // Menu
         let tray_menu = Menu::new();
@@ -83,22 +263,40 @@ impl BarApp {
             MenuItem::new(concat!("Macro v", env!("CARGO_PKG_VERSION")), false, None);
         let recording_menu_item = MenuItem::new("Record", true, None);
         let playback_menu_item = MenuItem::new("Play", false, None); // Disabled by default
+        let pause_menu_item = MenuItem::new("Pause", false, None); // Enabled while recording or playing
         let load_menu_item = MenuItem::new("Load", true, None);
 
         // Settings Menu
         let settings_menu_item = MenuItem::new("Settings...", false, None); // Disabled by default
 
+        let config = Config::load();
+        let reload_last_item = CheckMenuItem::new("Reload Last on Launch", true, config.reload_last_on_launch, None);
+        let overlay_item = CheckMenuItem::new("Show Input While Playing", true, config.playback_overlay, None);
+        let indicator_item = CheckMenuItem::new("Show Recording Indicator", true, config.recording_indicator, None);
+        let sounds_item = CheckMenuItem::new("Play Sounds", true, config.sound_cues, None);
+        let history_menu = Submenu::new("History", true);
+        let presets_menu = Submenu::new("Presets", true);
+
         let quit_i = MenuItem::new("Quit", true, None);
-        let check_updates_item = MenuItem::new("Check for Updates...", true, None);
+        let check_updates_item = MenuItem::new("Check for Updates...", !config.disable_updates, None);
+        let open_logs_item = MenuItem::new("Open Logs", true, None);
 
         tray_menu.append(&app_title_item)?;
         tray_menu.append(&PredefinedMenuItem::separator())?;
         tray_menu.append(&recording_menu_item)?;
         tray_menu.append(&playback_menu_item)?;
+        tray_menu.append(&pause_menu_item)?;
         tray_menu.append(&PredefinedMenuItem::separator())?;
         tray_menu.append(&load_menu_item)?;
         tray_menu.append(&settings_menu_item)?;
+        tray_menu.append(&presets_menu)?;
+        tray_menu.append(&reload_last_item)?;
+        tray_menu.append(&overlay_item)?;
+        tray_menu.append(&indicator_item)?;
+        tray_menu.append(&sounds_item)?;
+        tray_menu.append(&history_menu)?;
         tray_menu.append(&PredefinedMenuItem::separator())?;
+        tray_menu.append(&open_logs_item)?;
         tray_menu.append(&check_updates_item)?;
         tray_menu.append(&quit_i)?;
 
@@ -110,19 +308,35 @@ impl BarApp {
                 .build()?,
         );
 
+        // Start armed with the last recording, if it is still there
+        let pending_playback = config
+            .last_recording
+            .clone()
+            .filter(|path| config.reload_last_on_launch && path.exists());
+        if let Some(path) = &pending_playback {
+            tracing::info!("Reloading last recording: {:?}", path);
+        }
+
         // Shared state
         let state = Arc::new(Mutex::new(AppState {
             is_recording: false,
-            recording_process: None,
-            playback_process: None,
-            playback_speed: 1.0,
+            is_paused: false,
+            recording_run: None,
+            playback_run: None,
+            playback_speed: config.playback_speed,
             repeat_count: 1,
             repeat_interval: 0.0,
-            pending_playback: None,
+            countdown: None,
+            pending_playback,
             current_recording_path: None,
+            recording_segments: Vec::new(),
+            recording_started: None,
             last_record_hotkey_pressed: false,
             last_playback_hotkey_pressed: false,
             last_load_hotkey_pressed: false,
+            last_pause_hotkey_pressed: false,
+            last_panic_hotkey_pressed: false,
+            playback_error: None,
         }));
 
         // Listen for menu and hotkey events in a separate thread (or just setup handlers)
@@ -140,28 +354,112 @@ impl BarApp {
             }));
         });
 
-        let (record_hotkey, playback_hotkey, load_hotkey) = create_hotkeys();
+        // Global Hotkey Manager
+        let hotkey_manager = GlobalHotKeyManager::new()?;
+        let (record_hotkey, playback_hotkey, load_hotkey, pause_hotkey, panic_hotkey) = create_hotkeys(&config.keymaps);
+        hotkey_manager.register_all(&[record_hotkey, playback_hotkey, load_hotkey, pause_hotkey])?;
+        // Registered on its own so it works even if the others clash with another app
+        hotkey_manager.register(panic_hotkey)?;
 
-        Ok(Self {
+        let mut app = Self {
             state,
             proxy,
             tray_icon,
             recording_menu_item,
             playback_menu_item,
+            pause_menu_item,
             load_menu_item,
             settings_menu_item,
+            presets_menu,
+            preset_items: Vec::new(),
+            reload_last_item,
+            overlay_item,
+            indicator_item,
+            sounds_item,
+            history_menu,
             quit_i,
             icon_idle,
             icon_recording,
+            icon_recording_dim,
             icon_playing,
             icon_armed,
+            icon_paused,
             record_hotkey,
             playback_hotkey,
             load_hotkey,
+            pause_hotkey,
+            panic_hotkey,
+            hotkey_manager,
+            config,
+            config_watcher: ConfigWatcher::new(),
             check_updates_item,
+            open_logs_item,
+            logs_dir: log_file.parent().map_or_else(platform::logs_dir, Path::to_path_buf),
             settings_window: None,
             settings_webview: None,
-        })
+            backend: Arc::new(SharedBackend::new(backend::default_backend())),
+            recording_timer: None,
+            recorder_restarts: 0,
+            overlay: None,
+            recording_indicator: None,
+            recording_indicator_failed: false,
+            own_ui: OwnUi::default(),
+        };
+        if app.config.check_updates_on_launch && !app.config.disable_updates {
+            app.check_for_update_in_background();
+        }
+        // Reflect a reloaded recording in the menu and icon
+        app.update_menu_state();
+        app.update_history_menu();
+        app.update_presets_menu();
+        let pending_playback = app.state.lock().unwrap().pending_playback.clone();
+        if let Some(path) = pending_playback {
+            app.apply_saved_playback(&path);
+        }
+        app.offer_recovery();
+        Ok(app)
+    }
+
+    /// Offers to save recordings a crash left behind in the temp folder
+    /// into the recordings folder, or else discards them.
+    fn offer_recovery(&self) {
+        let mut orphans = Vec::new();
+        for path in recovery::orphaned_recordings(&std::env::temp_dir()) {
+            match Recording::load(&path) {
+                Ok(recording) if recording.events.is_empty() => {
+                    recovery::discard(&path);
+                }
+                Ok(recording) => orphans.push((path, recording.events.len())),
+                Err(e) => tracing::warn!("Skipping unreadable temp recording {:?}: {:?}", path, e),
+            }
+        }
+        if orphans.is_empty() {
+            return;
+        }
+
+        let recordings_dir = self.config.recordings_dir();
+        let events: usize = orphans.iter().map(|(_, events)| events).sum();
+        let answer = rfd::MessageDialog::new()
+            .set_title("Recover Recordings")
+            .set_description(format!(
+                "Macro quit during {} recording(s) that were never saved ({} events in all).\nRecover them into {}?",
+                orphans.len(),
+                events,
+                recordings_dir.display()
+            ))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+        for (path, _) in orphans {
+            if answer != rfd::MessageDialogResult::Yes {
+                tracing::info!("Discarding temp recording {:?}", path);
+                recovery::discard(&path);
+                continue;
+            }
+            match recovery::recover(&path, &recordings_dir) {
+                Ok(saved) => tracing::info!("Recovered {:?} to {:?}", path, saved),
+                Err(e) => tracing::error!("Failed to recover {:?}: {:?}", path, e),
+            }
+        }
     }
 
     pub fn handle_hotkey(
@@ -172,7 +470,27 @@ impl BarApp {
         let mut state = self.state.lock().unwrap();
 
         // Check if this is a press event (state change from not pressed to pressed)
-        if event.id == self.record_hotkey.id() {
+        if event.id == self.panic_hotkey.id() {
+            let is_pressed = event.state == global_hotkey::HotKeyState::Pressed;
+
+            if is_pressed && !state.last_panic_hotkey_pressed {
+                state.last_panic_hotkey_pressed = true;
+                drop(state);
+                self.handle_panic();
+            } else if !is_pressed {
+                state.last_panic_hotkey_pressed = false;
+            }
+        } else if event.id == self.pause_hotkey.id() {
+            let is_pressed = event.state == global_hotkey::HotKeyState::Pressed;
+
+            if is_pressed && !state.last_pause_hotkey_pressed {
+                state.last_pause_hotkey_pressed = true;
+                drop(state);
+                self.handle_toggle_pause();
+            } else if !is_pressed {
+                state.last_pause_hotkey_pressed = false;
+            }
+        } else if event.id == self.record_hotkey.id() {
             // Event state: HotKeyState::Pressed or HotKeyState::Released
             let is_pressed = event.state == global_hotkey::HotKeyState::Pressed;
 
@@ -182,7 +500,7 @@ impl BarApp {
                 
                 // CONSTRAINT: Do not allow recording if a recording is loaded
                 if state.pending_playback.is_some() {
-                    log::warn!("HotKey: Cannot start recording while a recording is loaded.");
+                    tracing::warn!("HotKey: Cannot start recording while a recording is loaded.");
                     // We don't drop state here because we continue to update last_record_hotkey_pressed
                 } else {
                     drop(state); // Release lock before calling handler
@@ -212,7 +530,7 @@ impl BarApp {
                 
                 // CONSTRAINT: Do not allow loading if we are recording
                 if state.is_recording {
-                     log::warn!("HotKey: Cannot load recording while recording is active.");
+                     tracing::warn!("HotKey: Cannot load recording while recording is active.");
                 } else {
                     // Logic for load/unload
                     // Check if we have a recording loaded
@@ -222,17 +540,17 @@ impl BarApp {
                     if has_recording {
                         // Unload
                         let mut state = self.state.lock().unwrap();
-                        log::info!("HotKey: Unloading recording...");
+                        tracing::info!("HotKey: Unloading recording...");
                         state.pending_playback = None;
                         drop(state);
                         self.update_menu_state();
                     } else {
                         // Load
-                        log::info!("HotKey: opening file picker to load recording...");
-                        let recording_dir = get_recordings_dir();
+                        tracing::info!("HotKey: opening file picker to load recording...");
+                        let recording_dir = self.config.recordings_dir();
                         let file_handle = rfd::FileDialog::new()
                             .set_directory(&recording_dir)
-                            .add_filter("JSON", &["json"])
+                            .add_filter("Recordings", &["json", "jsonl", "macro"])
                             .pick_file();
 
                         if let Some(path) = file_handle {
@@ -253,8 +571,14 @@ impl BarApp {
         event_loop: &tao::event_loop::EventLoopWindowTarget<AppEvent>,
     ) {
         let mut state = self.state.lock().unwrap();
-        state.pending_playback = Some(path);
+        state.pending_playback = Some(path.clone());
         drop(state);
+        self.apply_saved_playback(&path);
+
+        self.config.last_recording = Some(path);
+        if let Err(e) = self.config.save() {
+            tracing::error!("Failed to remember last recording: {}", e);
+        }
 
         self.update_menu_state();
         self.open_settings(event_loop);
@@ -264,53 +588,100 @@ impl BarApp {
         let mut state = self.state.lock().unwrap();
 
         // If playback is running, stop it
-        if let Some(mut child) = state.playback_process.take() {
-            log::info!("Stopping playback...");
-            let _ = child.kill();
-            let _ = child.wait();
+        if let Some(run) = state.playback_run.take() {
+            tracing::info!("Stopping playback...");
+            match run.stop() {
+                Ok(()) => tracing::info!("Playback stopped."),
+                Err(e) => tracing::error!("Playback ended with an error: {:?}", e),
+            }
+            state.is_paused = false;
 
             // Reset icon and menu text
             drop(state);
             self.update_menu_state();
+            self.update_history_menu();
             return;
         }
 
         // If no playback running, check if we have a pending playback to start
         if let Some(path) = &state.pending_playback {
-            log::info!("Starting playback of: {:?}", path);
+            tracing::info!("Starting playback of: {:?}", path);
 
-            // Spawn `macro play` (self)
-            let macro_bin = std::env::current_exe().unwrap();
-
-            let (speed, repeat, interval) = (
-                state.playback_speed,
-                state.repeat_count,
-                state.repeat_interval,
-            );
-
-            let child = Command::new(macro_bin)
-                .arg("play")
-                .arg(path)
-                .arg("--speed")
-                .arg(speed.to_string())
-                .arg("--repeat-count")
-                .arg(repeat.to_string())
-                .arg("--repeat-interval")
-                .arg(interval.to_string())
-                .arg("--immediate")
-                .spawn();
-
-            log::info!("Spawned playback process: {:?}", child);
-
-            if let Ok(child) = child {
-                state.playback_process = Some(child);
-                drop(state);
-                self.update_menu_state();
+            let recording = match Recording::load(path) {
+                Ok(recording) => recording,
+                Err(e) => {
+                    tracing::error!("Failed to load {:?}: {:?}", path, e);
+                    drop(state);
+                    rfd::MessageDialog::new()
+                        .set_title("Playback Failed")
+                        .set_description(format!("Could not load the recording: {:#}", e))
+                        .set_level(rfd::MessageLevel::Error)
+                        .show();
+                    return;
+                }
+            };
+
+            let options = PlaybackOptions {
+                speed: state.playback_speed,
+                repeat_count: state.repeat_count,
+                repeat_interval: state.repeat_interval,
+                countdown: Duration::from_secs_f64(state.countdown.unwrap_or(self.config.countdown).max(0.0)),
+                humanize: Duration::from_millis(self.config.humanize_ms),
+                ..Default::default()
+            };
+            let timer = RunTimer::start(RunKind::Play, Some(path), options.to_args());
+
+            let error_proxy = self.proxy.clone();
+            let finish_proxy = self.proxy.clone();
+            let player = Player::new()
+                .backend(self.backend.clone())
+                .options(options)
+                .on_error(move |e| {
+                    let _ = error_proxy.send_event(AppEvent::PlaybackReport(WorkerReport::Error { message: format!("{:#}", e) }));
+                })
+                .on_finish(move |outcome| {
+                    let _ = finish_proxy.send_event(AppEvent::PlaybackReport(WorkerReport::Finished { outcome }));
+                });
+            let player = if self.config.playback_overlay {
+                let overlay_proxy = self.proxy.clone();
+                let feed = Mutex::new(OverlayFeed::default());
+                player.on_event(move |event| {
+                    if let Some(message) = feed.lock().unwrap().message(event) {
+                        let _ = overlay_proxy.send_event(AppEvent::Overlay(message));
+                    }
+                })
             } else {
-                drop(state);
+                player
+            };
+            let control = player.control();
+
+            // The playback hotkey stops playback too, so only a different stop combo needs listening for
+            let keymaps = &self.config.keymaps;
+            if keymaps.stop_playback != keymaps.start_playback {
+                play::spawn_stop_listener(self.backend.clone(), keymaps.clone(), control.clone());
             }
+
+            let proxy = self.proxy.clone();
+            let history_control = control.clone();
+            let thread = thread::spawn(move || {
+                let result = player.play_with_progress(&recording, &mut |progress| {
+                    let _ = proxy.send_event(AppEvent::PlaybackReport(WorkerReport::Progress {
+                        repeat: progress.repeat,
+                        events_played: progress.events_played,
+                        total_events: progress.total_events,
+                    }));
+                });
+                history::log(&timer.finish(&result, history_control.is_stopped()));
+                result
+            });
+
+            state.playback_error = None;
+            state.playback_run = Some(PlaybackRun { control, thread });
+            drop(state);
+            self.update_menu_state();
+            self.play_cue(Cue::PlaybackStarted);
         } else {
-            log::warn!("No recording selected for playback.");
+            tracing::warn!("No recording selected for playback.");
         }
     }
 
@@ -318,69 +689,29 @@ impl BarApp {
         let mut state = self.state.lock().unwrap();
 
         // If playback is running, we cannot record
-        if state.playback_process.is_some() {
-            log::warn!("Cannot start recording while playback is active.");
+        if state.playback_run.is_some() {
+            tracing::warn!("Cannot start recording while playback is active.");
             return;
         }
 
         // If we are recording, stop it
         if state.is_recording {
-            log::info!("Stopping recording...");
+            tracing::info!("Stopping recording...");
             state.is_recording = false;
+            state.is_paused = false;
 
-            // Kill the child process gracefully
-            if let Some(mut child) = state.recording_process.take() {
-                let pid = child.id();
-
-                // Check if it has already exited (it should have if it caught the hotkey)
-                match child.try_wait() {
-                    Ok(Some(status)) => {
-                        log::info!("Child process already exited with: {:?}", status);
-                    }
-                    Ok(None) => {
-                        log::info!("Child process still running. Waiting for it to exit...");
-                        // Wait a bit for it to exit on its own
-                        let start = std::time::Instant::now();
-                        let mut exited = false;
-                        while start.elapsed() < std::time::Duration::from_millis(1000) {
-                            if let Ok(Some(status)) = child.try_wait() {
-                                log::info!("Child process exited gracefully with: {:?}", status);
-                                exited = true;
-                                break;
-                            }
-                            std::thread::sleep(std::time::Duration::from_millis(50));
-                        }
-
-                        if !exited {
-                            log::info!("Child process did not exit. Sending SIGTERM...");
-                            // Send SIGTERM (15) to allow graceful shutdown and saving
-                            let kill_output = Command::new("kill")
-                                .arg("-15")
-                                .arg(pid.to_string())
-                                .output();
-
-                            match kill_output {
-                                Ok(output) => log::info!("Kill command output: {:?}", output),
-                                Err(e) => log::error!("Failed to execute kill command: {}", e),
-                            }
-
-                            // Wait for it to finish
-                            let exit_status = child.wait();
-                            log::info!("Child process exited with: {:?}", exit_status);
-                        }
-                    }
-                    Err(e) => {
-                        log::error!("Error waiting for child process: {}", e);
-                        let _ = child.kill();
-                    }
+            // Events still in flight are written before it returns
+            if let Some(run) = state.recording_run.take() {
+                match run.stop() {
+                    Ok(recording) => tracing::info!("Recording stopped with {} events.", recording.map_or(0, |r| r.events.len())),
+                    Err(e) => tracing::error!("Recording ended with an error: {:?}", e),
                 }
-
-                // Give the process a moment to flush and close the file
-                std::thread::sleep(std::time::Duration::from_millis(500));
             }
+            self.play_cue(Cue::RecordingStopped);
 
-            // Handle file saving - extract path before releasing the lock
-            let temp_path = state.current_recording_path.take();
+            // Handle file saving - extract paths before releasing the lock
+            let segments = take_recording_segments(&mut state);
+            let timer = state.recording_started.take();
 
             // Release the lock before opening the file picker
             drop(state);
@@ -388,91 +719,34 @@ impl BarApp {
             // Update UI state
             self.update_menu_state();
 
-            // Handle file saving after releasing the lock
-            if let Some(temp_path) = temp_path {
-                // Verify the temp file exists
-                if !temp_path.exists() {
-                    log::error!("Temp recording file not found at: {:?}", temp_path);
-                    return;
-                }
-
-                // Run file picker on the main thread
-                let recording_dir = get_recordings_dir();
-                let default_name =
-                    format!("recording_{}.json", Local::now().format("%Y%m%d_%H%M%S"));
-
-                log::info!("Opening file picker to save recording...");
-
-                let file_handle = rfd::FileDialog::new()
-                    .set_directory(&recording_dir)
-                    .set_file_name(&default_name)
-                    .add_filter("JSON", &["json"])
-                    .save_file();
-
-                if let Some(target_path) = file_handle {
-                    log::info!("Saving recording to: {:?}", target_path);
-                    if let Err(e) = fs::rename(&temp_path, &target_path) {
-                        log::error!("Failed to save recording (rename failed): {}", e);
-                        // Try copying if rename fails (cross-device link error)
-                        if let Err(e) = fs::copy(&temp_path, &target_path) {
-                            log::error!("Failed to save recording (copy failed): {}", e);
-                        } else {
-                            let _ = fs::remove_file(&temp_path);
-                            log::info!("Recording saved successfully (copied)");
-
-                            // Do not auto-load. Just update UI.
-                            self.update_menu_state();
-                        }
-                    } else {
-                        log::info!("Recording saved successfully");
-
-                        // Do not auto-load. Just update UI.
-                        self.update_menu_state();
-                    }
-                } else {
-                    log::info!("Save canceled. Discarding recording.");
-                    let _ = fs::remove_file(&temp_path);
-                }
-            }
+            self.save_recording(segments, timer);
         } else {
             // Start Recording
-            log::info!("Starting recording...");
+            tracing::info!("Starting recording...");
             state.is_recording = true;
             // Clear any pending playback so we don't return to "loaded" state after this recording
             state.pending_playback = None;
 
             // Use a temporary file for recording
-            let temp_dir = std::env::temp_dir();
-            let filename = format!(
-                "macro_recording_{}.json",
-                Local::now().format("%Y%m%d_%H%M%S")
-            );
-            let path = temp_dir.join(filename);
-
-            log::info!("Recording to temp file: {:?}", path);
-            state.current_recording_path = Some(path.clone());
-
-            // Spawn `macro record` (self)
-            let macro_bin = std::env::current_exe().unwrap();
+            let path = recovery::temp_recording_path(&std::env::temp_dir());
 
-            let child = Command::new(macro_bin)
-                .arg("record")
-                .arg(path)
-                .arg("--immediate")
-                .spawn();
-
-            log::info!("Spawned recording process: {:?}", child);
+            tracing::info!("Recording to temp file: {:?}", path);
+            state.current_recording_path = Some(path.clone());
+            state.recording_started = Some(RunTimer::start(RunKind::Record, None, self.recording_options().to_args()));
+            self.recorder_restarts = 0;
 
-            match child {
-                Ok(child) => {
-                    state.recording_process = Some(child);
+            match self.start_recorder(&path) {
+                Ok(run) => {
+                    state.recording_run = Some(run);
                     drop(state);
                     self.update_menu_state();
+                    self.play_cue(Cue::RecordingStarted);
                 }
                 Err(e) => {
-                    log::error!("Failed to spawn macro record: {}", e);
+                    tracing::error!("Failed to start recording: {:?}", e);
                     state.is_recording = false;
                     state.current_recording_path = None;
+                    state.recording_started = None;
                     drop(state);
                     self.update_menu_state();
                 }
@@ -480,14 +754,177 @@ impl BarApp {
         }
     }
 
+    /// Starts capturing into the temp file at `path` on a background thread.
+    fn start_recorder(&self, path: &Path) -> Result<RecordingRun> {
+        let writer = Arc::new(Mutex::new(JsonlWriter::create(path, &RecordingMetadata::capture(&self.backend.displays()).with_environment(Environment::capture(self.backend.as_ref())))?));
+        let mut options = self.recording_options();
+        if self.config.thumbnails {
+            options.thumbnail = true;
+            options.screenshot_dir = Some(screenshot::dir_for(path));
+        }
+        let undo_writer = writer.clone();
+        let undo_path = path.to_path_buf();
+        let limit = Arc::new(Mutex::new(None));
+        let limit_reached = limit.clone();
+        let session = Recorder::new()
+            .backend(self.backend.clone())
+            .hotkeys(self.config.keymaps.clone())
+            .options(options)
+            .own_ui(self.own_ui.clone())
+            .on_event(move |event| {
+                if let Err(e) = writer.lock().unwrap().write_event(event) {
+                    tracing::error!("Failed to append event to the recording: {:?}", e);
+                }
+            })
+            .on_undo(move |recording| {
+                if let Err(e) = undo_writer.lock().unwrap().rewrite(&undo_path, recording) {
+                    tracing::error!("Failed to rewrite the recording after undo: {:?}", e);
+                }
+            })
+            .on_limit(move |reached| *limit_reached.lock().unwrap() = Some(reached))
+            .start();
+        let handle = session.handle();
+        let thread = thread::spawn(move || session.wait());
+        Ok(RecordingRun { handle, thread, limit })
+    }
+
+    /// Recording options from the config.
+    fn recording_options(&self) -> RecordOptions {
+        RecordOptions {
+            max_gap: (self.config.max_gap > 0.0).then(|| Duration::from_secs_f64(self.config.max_gap)),
+            move_interval: (self.config.move_interval_ms > 0).then(|| Duration::from_millis(self.config.move_interval_ms)),
+            undo: Some(if self.config.undo_seconds > 0.0 {
+                UndoScope::Last(Duration::from_secs_f64(self.config.undo_seconds))
+            } else {
+                UndoScope::Checkpoint
+            }),
+            duration: (self.config.max_recording_minutes > 0.0).then(|| Duration::from_secs_f64(self.config.max_recording_minutes * 60.0)),
+            max_events: (self.config.max_recording_events > 0).then_some(self.config.max_recording_events),
+            max_size: (self.config.max_recording_mb > 0.0).then_some((self.config.max_recording_mb * (1 << 20) as f64) as u64),
+            ..Default::default()
+        }
+    }
+
+    /// Asks where to save a finished recording and saves it there, joining
+    /// up `segments` (its temp files, oldest first) if recording had to be
+    /// restarted part way.
+    fn save_recording(&mut self, segments: Vec<PathBuf>, timer: Option<RunTimer>) {
+        let segments: Vec<PathBuf> = segments.into_iter().filter(|path| path.exists()).collect();
+        if segments.is_empty() {
+            tracing::error!("Temp recording file not found.");
+            return;
+        }
+
+        // Run file picker on the main thread
+        let recording_dir = self.config.recordings_dir();
+        let default_name = format!("recording_{}.json", Local::now().format("%Y%m%d_%H%M%S"));
+
+        tracing::info!("Opening file picker to save recording...");
+
+        let file_handle = rfd::FileDialog::new()
+            .set_directory(&recording_dir)
+            .set_file_name(&default_name)
+            .add_filter("JSON", &["json"])
+            .save_file();
+
+        if let Some(target_path) = file_handle {
+            tracing::info!("Saving recording to: {:?}", target_path);
+            // The temp files are JSON lines, so convert rather than move them
+            let result = join_segments(&segments).and_then(|recording| recording.save(&target_path));
+            match &result {
+                Ok(()) => {
+                    // Taken as the first part started
+                    if let Err(e) = screenshot::copy_thumbnail(&segments[0], &target_path) {
+                        tracing::warn!("Could not save the thumbnail: {:?}", e);
+                    }
+                    for path in &segments {
+                        recovery::discard(path);
+                    }
+                    tracing::info!("Recording saved successfully");
+
+                    // Do not auto-load. Just update UI.
+                    self.update_menu_state();
+                }
+                // The temp files are kept, to be offered for recovery on the next launch
+                Err(e) => tracing::error!("Failed to save recording: {:?}", e),
+            }
+            if let Some(timer) = timer {
+                history::log(&HistoryEntry {
+                    path: Some(target_path),
+                    ..timer.finish(&result, false)
+                });
+            }
+        } else {
+            tracing::info!("Save canceled. Discarding recording.");
+            for path in &segments {
+                recovery::discard(path);
+            }
+            if let Some(timer) = timer {
+                history::log(&timer.finish(&Ok(()), true));
+            }
+        }
+        self.update_history_menu();
+    }
+
+    /// Pauses or resumes whichever of recording and playback is running.
+    pub fn handle_toggle_pause(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        let paused = !state.is_paused;
+        // The pause hotkey also reaches the recorder and the stop listener,
+        // which toggle by themselves, so set the state outright to agree with them
+        if let Some(run) = &state.recording_run {
+            if paused { run.handle.pause() } else { run.handle.resume() }
+        } else if let Some(run) = &state.playback_run {
+            if paused { run.control.pause() } else { run.control.resume() }
+        } else {
+            tracing::warn!("Nothing to pause.");
+            return;
+        }
+        tracing::info!("{}.", if paused { "Paused" } else { "Resumed" });
+        state.is_paused = paused;
+        drop(state);
+        self.update_menu_state();
+    }
+
+    /// Stops recording and playback without waiting for them to finish,
+    /// releases any input they left held down and returns to the idle state.
+    pub fn handle_panic(&mut self) {
+        tracing::warn!("Panic hotkey pressed. Stopping everything...");
+        let mut state = self.state.lock().unwrap();
+        // Their threads wind down by themselves
+        if let Some(run) = state.recording_run.take() {
+            run.handle.stop();
+        }
+        if let Some(run) = state.playback_run.take() {
+            run.control.stop();
+        }
+        for temp_path in take_recording_segments(&mut state) {
+            recovery::discard(&temp_path);
+        }
+        if let Some(timer) = state.recording_started.take() {
+            history::log(&timer.finish(&Ok(()), true));
+        }
+        state.is_recording = false;
+        state.is_paused = false;
+        state.pending_playback = None;
+        drop(state);
+
+        // Playback may still be holding keys or buttons it never got to release
+        play::release_all(self.backend.as_ref());
+
+        self.settings_window = None;
+        self.settings_webview = None;
+        self.update_menu_state();
+    }
+
     pub fn handle_settings_applied(&mut self, settings: SettingsMessage) {
         let mut state = self.state.lock().unwrap();
         state.playback_speed = settings.speed;
         state.repeat_count = settings.repeat;
         state.repeat_interval = settings.interval;
+        state.countdown = None;
 
-        // Save settings to persistent storage if needed (future improvement)
-        log::info!(
+        tracing::info!(
             "Settings applied: Speed={}, Repeat={}, Interval={}, ShouldPlay={}",
             settings.speed,
             settings.repeat,
@@ -495,6 +932,17 @@ impl BarApp {
             settings.should_play
         );
 
+        // Speed, repeat and interval apply to this session, the rest is kept in the config file
+        self.config.countdown = settings.countdown;
+        self.config.humanize_ms = settings.humanize_ms;
+        self.config.move_interval_ms = settings.move_interval_ms;
+        self.config.max_gap = settings.max_gap;
+        let recordings_dir = settings.recordings_dir.trim();
+        self.config.recordings_dir = (!recordings_dir.is_empty()).then(|| PathBuf::from(recordings_dir));
+        if let Err(e) = self.config.save() {
+            tracing::error!("Failed to save settings to {:?}: {}", Config::path(), e);
+        }
+
         // Close window
         self.settings_window = None;
         self.settings_webview = None;
@@ -518,29 +966,42 @@ impl BarApp {
             return;
         }
 
-        let window = WindowBuilder::new()
-            .with_title("Macro Settings")
-            .with_inner_size(tao::dpi::LogicalSize::new(232.0, 320.0))
-            .with_resizable(false)
-            .build(event_loop)
-            .unwrap();
-
         let state_clone = self.state.clone();
 
         // Prepare init script with current values
-        let (speed, repeat, interval) = {
+        let (speed, repeat, interval, loaded) = {
             let state = state_clone.lock().unwrap();
             (
                 state.playback_speed,
                 state.repeat_count,
                 state.repeat_interval,
+                state.pending_playback.clone(),
             )
         };
+        // Shows which recording is loaded above the settings
+        let thumbnail = loaded.as_deref().and_then(thumbnail_data_url);
 
-        let init_script = format!(
-            "window.initialConfig = {{ speed: {}, repeat: {}, interval: {} }};",
-            speed, repeat, interval
-        );
+        let height = if thumbnail.is_some() { 840.0 } else { 700.0 };
+        let window = WindowBuilder::new()
+            .with_title("Macro Settings")
+            .with_inner_size(tao::dpi::LogicalSize::new(232.0, height))
+            .with_resizable(false)
+            .build(event_loop)
+            .unwrap();
+
+        let initial_config = serde_json::json!({
+            "speed": speed,
+            "repeat": repeat,
+            "interval": interval,
+            "countdown": self.config.countdown,
+            "humanizeMs": self.config.humanize_ms,
+            "moveIntervalMs": self.config.move_interval_ms,
+            "maxGap": self.config.max_gap,
+            "recordingsDir": self.config.recordings_dir.as_ref().map(|dir| dir.display().to_string()).unwrap_or_default(),
+            "presets": self.config.presets,
+            "thumbnail": thumbnail,
+        });
+        let init_script = format!("window.initialConfig = {};", initial_config);
 
         let proxy = self.proxy.clone();
         let webview = WebViewBuilder::new()
@@ -551,7 +1012,7 @@ impl BarApp {
                 if let Ok(settings) = serde_json::from_str::<SettingsMessage>(msg) {
                     let _ = proxy.send_event(AppEvent::SettingsApplied(settings));
                 } else {
-                    log::error!("Failed to parse settings IPC message: {}", msg);
+                    tracing::error!("Failed to parse settings IPC message: {}", msg);
                 }
             })
             .build(&window)
@@ -570,23 +1031,25 @@ impl BarApp {
         if event.id == self.quit_i.id() {
             // Cleanup
             let mut state = self.state.lock().unwrap();
-            if let Some(mut child) = state.recording_process.take() {
-                let _ = child.kill();
+            if let Some(run) = state.recording_run.take() {
+                run.handle.stop();
             }
-            if let Some(mut child) = state.playback_process.take() {
-                let _ = child.kill();
+            if let Some(run) = state.playback_run.take() {
+                let _ = run.stop();
             }
             *control_flow = ControlFlow::Exit;
         } else if event.id == self.recording_menu_item.id() {
             self.handle_toggle_recording();
         } else if event.id == self.playback_menu_item.id() {
             self.handle_toggle_playback();
+        } else if event.id == self.pause_menu_item.id() {
+            self.handle_toggle_pause();
         } else if event.id == self.load_menu_item.id() {
             // Check if we are loading or unloading
             let mut state = self.state.lock().unwrap();
             if state.pending_playback.is_some() {
                 // Unload Recording
-                log::info!("Unloading recording...");
+                tracing::info!("Unloading recording...");
                 state.pending_playback = None;
                 drop(state);
                 self.update_menu_state();
@@ -594,61 +1057,512 @@ impl BarApp {
                 // Load Recording
                 drop(state);
                 // Open File Picker - run on main thread
-                let recording_dir = get_recordings_dir();
+                let recording_dir = self.config.recordings_dir();
 
-                log::info!("Opening file picker to load recording...");
+                tracing::info!("Opening file picker to load recording...");
 
                 let file_handle = rfd::FileDialog::new()
                     .set_directory(&recording_dir)
-                    .add_filter("JSON", &["json"])
+                    .add_filter("Recordings", &["json", "jsonl", "macro"])
                     .pick_file();
 
                 if let Some(path) = file_handle {
-                    log::info!("Selected recording: {:?}", path);
+                    tracing::info!("Selected recording: {:?}", path);
                     self.handle_file_selected(path, event_loop);
                 }
             }
         } else if event.id == self.settings_menu_item.id() {
             self.open_settings(event_loop);
-        } else if event.id == self.check_updates_item.id() {
-            std::thread::spawn(|| {
-                check_and_update();
+        } else if event.id == self.reload_last_item.id() {
+            self.config.reload_last_on_launch = self.reload_last_item.is_checked();
+            if let Err(e) = self.config.save() {
+                tracing::error!("Failed to save settings to {:?}: {}", Config::path(), e);
+            }
+        } else if event.id == self.overlay_item.id() {
+            self.config.playback_overlay = self.overlay_item.is_checked();
+            if let Err(e) = self.config.save() {
+                tracing::error!("Failed to save settings to {:?}: {}", Config::path(), e);
+            }
+        } else if event.id == self.indicator_item.id() {
+            self.config.recording_indicator = self.indicator_item.is_checked();
+            if let Err(e) = self.config.save() {
+                tracing::error!("Failed to save settings to {:?}: {}", Config::path(), e);
+            }
+        } else if event.id == self.sounds_item.id() {
+            self.config.sound_cues = self.sounds_item.is_checked();
+            if let Err(e) = self.config.save() {
+                tracing::error!("Failed to save settings to {:?}: {}", Config::path(), e);
+            }
+        } else if let Some((_, name)) = self.preset_items.iter().find(|(item, _)| event.id == item.id()) {
+            self.apply_preset(&name.clone());
+        } else if event.id == self.open_logs_item.id() {
+            tracing::info!("Opening logs folder {:?}", self.logs_dir);
+            if let Err(e) = platform::open_path(&self.logs_dir) {
+                tracing::error!("Failed to open logs folder {:?}: {}", self.logs_dir, e);
+            }
+        } else if event.id == self.check_updates_item.id() && !self.config.disable_updates {
+            let channel = self.config.update_channel;
+            std::thread::spawn(move || {
+                check_and_update(channel);
             });
         }
     }
 
+    /// Applies changes made to the config file while the app is running.
+    pub fn check_config_changes(&mut self) {
+        let Some(config) = self.config_watcher.poll() else {
+            return;
+        };
+
+        if config.keymaps != self.config.keymaps {
+            let (record_hotkey, playback_hotkey, load_hotkey, pause_hotkey, panic_hotkey) = create_hotkeys(&config.keymaps);
+            let _ = self.hotkey_manager.unregister_all(&[self.record_hotkey, self.playback_hotkey, self.load_hotkey, self.pause_hotkey]);
+            if let Err(e) = self.hotkey_manager.register_all(&[record_hotkey, playback_hotkey, load_hotkey, pause_hotkey]) {
+                tracing::error!("Failed to register hotkeys from config: {}", e);
+            }
+            if panic_hotkey != self.panic_hotkey {
+                let _ = self.hotkey_manager.unregister(self.panic_hotkey);
+                if let Err(e) = self.hotkey_manager.register(panic_hotkey) {
+                    // Keep the old one rather than be left without a panic hotkey
+                    tracing::error!("Failed to register panic hotkey from config: {}", e);
+                    let _ = self.hotkey_manager.register(self.panic_hotkey);
+                } else {
+                    self.panic_hotkey = panic_hotkey;
+                }
+            }
+            self.record_hotkey = record_hotkey;
+            self.playback_hotkey = playback_hotkey;
+            self.load_hotkey = load_hotkey;
+            self.pause_hotkey = pause_hotkey;
+            tracing::info!("Hotkeys updated from config.");
+        }
+
+        if config.playback_speed != self.config.playback_speed {
+            self.state.lock().unwrap().playback_speed = config.playback_speed;
+            tracing::info!("Default playback speed updated to {}", config.playback_speed);
+        }
+
+        self.reload_last_item.set_checked(config.reload_last_on_launch);
+        self.overlay_item.set_checked(config.playback_overlay);
+        self.indicator_item.set_checked(config.recording_indicator);
+        self.sounds_item.set_checked(config.sound_cues);
+        self.check_updates_item.set_enabled(!config.disable_updates);
+        let presets_changed = config.presets != self.config.presets;
+        self.config = config;
+        if presets_changed {
+            self.update_presets_menu();
+        }
+    }
+
+    /// Sets the playback speed and repeats from the preset called `name`,
+    /// for the rest of the session like the settings window does.
+    fn apply_preset(&mut self, name: &str) {
+        let preset = match self.config.preset(name) {
+            Ok(preset) => preset.clone(),
+            Err(e) => {
+                tracing::error!("{}", e);
+                return;
+            }
+        };
+        let mut state = self.state.lock().unwrap();
+        state.playback_speed = preset.speed.unwrap_or(self.config.playback_speed);
+        state.repeat_count = preset.repeat_count.unwrap_or(1);
+        state.repeat_interval = preset.repeat_interval.unwrap_or(0.0);
+        state.countdown = preset.countdown;
+        tracing::info!(
+            "Preset '{}' applied: Speed={}, Repeat={}, Interval={}",
+            name,
+            state.playback_speed,
+            state.repeat_count,
+            state.repeat_interval
+        );
+    }
+
+    /// Applies the playback settings saved in the recording at `path`, if it
+    /// has any, until they are changed in the settings window or by a preset.
+    fn apply_saved_playback(&self, path: &Path) {
+        let settings = match recording::saved_playback(path) {
+            Ok(Some(settings)) => settings,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::warn!("Could not read the playback settings of {:?}: {:?}", path, e);
+                return;
+            }
+        };
+        let mut state = self.state.lock().unwrap();
+        state.playback_speed = settings.speed.unwrap_or(state.playback_speed);
+        state.repeat_count = settings.repeat_count.unwrap_or(state.repeat_count);
+        state.repeat_interval = settings.repeat_interval.unwrap_or(state.repeat_interval);
+        state.countdown = settings.countdown.or(state.countdown);
+        tracing::info!(
+            "Playback settings of {:?} applied: Speed={}, Repeat={}, Interval={}",
+            path,
+            state.playback_speed,
+            state.repeat_count,
+            state.repeat_interval
+        );
+    }
+
+    /// Lists the presets from the config in the Presets menu.
+    pub fn update_presets_menu(&mut self) {
+        while self.presets_menu.remove_at(0).is_some() {}
+        self.preset_items = self
+            .config
+            .presets
+            .keys()
+            .map(|name| (MenuItem::new(name, true, None), name.clone()))
+            .collect();
+        if self.preset_items.is_empty() {
+            let empty = MenuItem::new("No presets in the config file", false, None);
+            if let Err(e) = self.presets_menu.append(&empty) {
+                tracing::error!("Failed to add to the Presets menu: {}", e);
+            }
+        }
+        for (item, _) in &self.preset_items {
+            if let Err(e) = self.presets_menu.append(item) {
+                tracing::error!("Failed to add to the Presets menu: {}", e);
+            }
+        }
+    }
+
+    /// Looks for an update without getting in the way: nothing is shown
+    /// unless one is found, and then only in the menu.
+    fn check_for_update_in_background(&self) {
+        let channel = self.config.update_channel;
+        let proxy = self.proxy.clone();
+        thread::spawn(move || match update::available_update(channel) {
+            Ok(Some(release)) => {
+                tracing::info!("Update available: {}", release.version);
+                let _ = proxy.send_event(AppEvent::UpdateAvailable(release.version));
+            }
+            Ok(None) => tracing::info!("No update available."),
+            Err(e) => tracing::warn!("Failed to check for updates: {:?}", e),
+        });
+    }
+
+    /// Points "Check for Updates..." at the update found on launch.
+    pub fn show_update_available(&self, version: &str) {
+        self.check_updates_item.set_text(format!("Update to {}...", version));
+    }
+
+    /// Follows playback's reports: progress goes in the tray tooltip, and a
+    /// failed run is explained in a dialog.
+    pub fn handle_playback_report(&mut self, report: WorkerReport) {
+        match report {
+            WorkerReport::Progress { repeat, events_played, total_events } => {
+                let mut tooltip = format!("Macro - Playing event {} of {}", events_played, total_events);
+                if repeat > 0 {
+                    tooltip.push_str(&format!(" (repeat {})", repeat + 1));
+                }
+                if let Some(tray) = &self.tray_icon {
+                    let _ = tray.set_tooltip(Some(tooltip));
+                }
+            }
+            // Only sent by playback running in a worker process
+            WorkerReport::Heartbeat | WorkerReport::Status { .. } => {}
+            WorkerReport::Error { message } => {
+                tracing::warn!("Playback error: {}", message);
+                self.state.lock().unwrap().playback_error = Some(message);
+            }
+            WorkerReport::Finished { outcome } => {
+                tracing::info!("Playback finished: {:?}", outcome);
+                let error = self.state.lock().unwrap().playback_error.take();
+                self.play_cue(if outcome == PlaybackOutcome::Failed { Cue::PlaybackFailed } else { Cue::PlaybackFinished });
+                if outcome == PlaybackOutcome::Failed {
+                    rfd::MessageDialog::new()
+                        .set_title("Playback Failed")
+                        .set_description(error.as_deref().unwrap_or("Playback stopped with an error."))
+                        .set_level(rfd::MessageLevel::Error)
+                        .show();
+                }
+            }
+        }
+    }
+
     pub fn check_playback_status(&mut self) {
         let mut state = self.state.lock().unwrap();
+        let Some(run) = state.playback_run.take_if(|run| run.is_finished()) else {
+            return;
+        };
+        // Playback finished, reset UI
+        state.is_paused = false;
+        drop(state);
+        match run.join() {
+            Ok(()) => tracing::info!("Playback thread finished."),
+            Err(e) => tracing::error!("Playback thread failed: {:?}", e),
+        }
+        self.update_menu_state();
+        self.update_history_menu();
+    }
+
+    /// Notices a recorder that ended without being told to. One that ended
+    /// cleanly was stopped by its own stop hotkey, so the recording is saved
+    /// as usual. One that failed is restarted if the config asks for it, and
+    /// otherwise the recording stops with an error.
+    pub fn check_recording_status(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        let Some(run) = state.recording_run.take_if(|run| run.is_finished()) else {
+            return;
+        };
 
-        if let Some(mut child) = state.playback_process.take() {
-            match child.try_wait() {
-                Ok(Some(status)) => {
-                    log::info!("Playback finished with status: {:?}", status);
-                    // Playback finished, reset UI
+        let limit = run.limit.lock().unwrap().take();
+        let error = match run.join() {
+            // The tray got the same hotkey and finishes the recording itself
+            Ok(_) if limit.is_none() && self.config.keymaps.stop_recording == self.config.keymaps.start_recording => return,
+            Ok(_) => {
+                match limit {
+                    Some(limit) => tracing::info!("Recording stopped at its {}.", limit),
+                    None => tracing::info!("Recording stopped by its own hotkey."),
+                }
+                state.is_recording = false;
+                state.is_paused = false;
+                let segments = take_recording_segments(&mut state);
+                let timer = state.recording_started.take();
+                drop(state);
+                self.update_menu_state();
+                self.play_cue(Cue::RecordingStopped);
+                if let Some(limit) = limit {
+                    rfd::MessageDialog::new()
+                        .set_title("Recording Limit Reached")
+                        .set_description(format!("The recording reached its {} and was stopped. Choose where to save it.", limit))
+                        .set_level(rfd::MessageLevel::Warning)
+                        .show();
+                }
+                self.save_recording(segments, timer);
+                return;
+            }
+            Err(e) => e,
+        };
+
+        let reason = format!("The recorder stopped unexpectedly: {:#}", error);
+        tracing::error!("{}", reason);
+
+        // Keep what was recorded so far, to be joined with what comes next
+        if let Some(path) = state.current_recording_path.take() {
+            state.recording_segments.push(path);
+        }
+
+        if self.config.restart_recorder && self.recorder_restarts < MAX_RECORDER_RESTARTS {
+            self.recorder_restarts += 1;
+            let path = recovery::temp_recording_path(&std::env::temp_dir());
+            let path = if path.exists() { record::next_available_path(&path) } else { path };
+            match self.start_recorder(&path) {
+                Ok(run) => {
+                    tracing::warn!("Restarted recorder ({} of {}), recording to {:?}", self.recorder_restarts, MAX_RECORDER_RESTARTS, path);
+                    state.current_recording_path = Some(path);
+                    state.recording_run = Some(run);
+                    // The new recorder starts out unpaused
+                    state.is_paused = false;
                     drop(state);
                     self.update_menu_state();
+                    return;
                 }
-                Ok(None) => {
-                    // Still running, put it back
-                    state.playback_process = Some(child);
+                Err(e) => tracing::error!("Failed to restart recording: {:?}", e),
+            }
+        }
+
+        state.is_recording = false;
+        state.is_paused = false;
+        let segments = take_recording_segments(&mut state);
+        let timer = state.recording_started.take();
+        drop(state);
+        self.update_menu_state();
+        if let Some(timer) = timer {
+            history::log(&timer.finish(&Err::<(), _>(error), false));
+            self.update_history_menu();
+        }
+
+        if segments.iter().any(|path| path.exists()) {
+            let answer = rfd::MessageDialog::new()
+                .set_title("Recording Stopped")
+                .set_description(format!("{}\nSave what was recorded before it stopped?", reason))
+                .set_level(rfd::MessageLevel::Error)
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show();
+            if answer == rfd::MessageDialogResult::Yes {
+                self.save_recording(segments, None);
+            } else {
+                for path in &segments {
+                    recovery::discard(path);
+                }
+            }
+        } else {
+            rfd::MessageDialog::new()
+                .set_title("Recording Stopped")
+                .set_description(&reason)
+                .set_level(rfd::MessageLevel::Error)
+                .show();
+        }
+    }
+
+    /// Shows how long the current recording has been running in the tray
+    /// tooltip (and the menu bar on macOS), pulsing the icon once a second
+    /// while capture is live.
+    pub fn update_recording_timer(&mut self) {
+        let Some(timer) = &mut self.recording_timer else {
+            return;
+        };
+        let secs = timer.elapsed().as_secs();
+        if timer.shown_secs == Some(secs) {
+            return;
+        }
+        timer.shown_secs = Some(secs);
+        let paused = timer.paused_at.is_some();
+        let time = if secs >= 3600 {
+            format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+        } else {
+            format!("{:02}:{:02}", secs / 60, secs % 60)
+        };
+        let Some(tray) = &self.tray_icon else {
+            return;
+        };
+        let tooltip = format!("Macro - Recording {}{}", time, if paused { " (paused)" } else { "" });
+        let _ = tray.set_tooltip(Some(tooltip));
+        #[cfg(target_os = "macos")]
+        tray.set_title(Some(&time));
+        if !paused {
+            let icon = if secs % 2 == 0 { &self.icon_recording } else { &self.icon_recording_dim };
+            let _ = tray.set_icon(Some(icon.clone()));
+        }
+    }
+
+    /// Shows `message` in the playback overlay, opening it if need be.
+    pub fn show_overlay(&mut self, message: OverlayMessage, event_loop: &tao::event_loop::EventLoopWindowTarget<AppEvent>) {
+        // Messages can still arrive after playback was stopped
+        if self.state.lock().unwrap().playback_run.is_none() {
+            return;
+        }
+        if self.overlay.is_none() {
+            match PlaybackOverlay::new(event_loop) {
+                Ok(overlay) => self.overlay = Some(overlay),
+                Err(e) => {
+                    tracing::error!("Failed to open the overlay: {:?}", e);
+                    return;
                 }
+            }
+        }
+        if let Some(overlay) = &self.overlay {
+            overlay.show(&message);
+        }
+    }
+
+    /// Shows the recording indicator while recording with it turned on, and
+    /// hides it otherwise.
+    pub fn update_recording_indicator(&mut self, event_loop: &tao::event_loop::EventLoopWindowTarget<AppEvent>) {
+        let state = self.state.lock().unwrap();
+        let (is_recording, is_paused) = (state.is_recording, state.is_paused);
+        drop(state);
+        if !is_recording || !self.config.recording_indicator {
+            self.recording_indicator = None;
+            if !is_recording {
+                self.recording_indicator_failed = false;
+            }
+            return;
+        }
+        if self.recording_indicator.is_none() && !self.recording_indicator_failed {
+            match RecordingIndicator::new(event_loop) {
+                Ok(indicator) => self.recording_indicator = Some(indicator),
                 Err(e) => {
-                    log::error!("Error waiting for playback process: {}", e);
-                    // Assume it's gone or broken, reset UI
-                    drop(state);
-                    self.update_menu_state();
+                    tracing::error!("Failed to open the recording indicator: {:?}", e);
+                    self.recording_indicator_failed = true;
                 }
             }
         }
+        if let Some(indicator) = &mut self.recording_indicator {
+            indicator.set_paused(is_paused);
+        }
+    }
+
+    /// Lists the most recent runs in the History submenu, newest first.
+    pub fn update_history_menu(&self) {
+        while self.history_menu.remove_at(0).is_some() {}
+        let entries = history::load(&history::default_path()).unwrap_or_else(|e| {
+            tracing::warn!("Could not read the history: {:?}", e);
+            Vec::new()
+        });
+        let items: Vec<MenuItem> = if entries.is_empty() {
+            vec![MenuItem::new("No runs yet", false, None)]
+        } else {
+            entries
+                .iter()
+                .rev()
+                .take(HISTORY_MENU_LENGTH)
+                .map(|entry| MenuItem::new(history_menu_text(entry), false, None))
+                .collect()
+        };
+        for item in &items {
+            if let Err(e) = self.history_menu.append(item) {
+                tracing::error!("Failed to add to the History menu: {}", e);
+            }
+        }
+    }
+
+    /// Tells recordings where the tray icon and settings window are now and
+    /// whether the settings window has the keyboard, so that using the app
+    /// while recording leaves no trace in the recording. The recording
+    /// indicator is left out, as it lets clicks through to the apps under it.
+    pub fn update_own_ui(&mut self) {
+        if !self.state.lock().unwrap().is_recording {
+            return;
+        }
+        let settings = self.settings_window.as_ref().and_then(|window| {
+            let scale = window.scale_factor();
+            let position = window.outer_position().ok()?.to_logical::<f64>(scale);
+            let size = window.outer_size().to_logical::<f64>(scale);
+            Some(Area { x: position.x, y: position.y, width: size.width, height: size.height })
+        });
+        self.own_ui.set_window("settings", settings);
+        self.own_ui.set_focused(self.settings_window.as_ref().is_some_and(Window::is_focused));
+        // The tray icon reports pixels, and sits on the primary display
+        let scale = self.backend.displays().iter().find(|display| display.is_primary).map_or(1.0, |display| display.scale_factor);
+        let icon = self.tray_icon.as_ref().and_then(TrayIcon::rect).map(|rect| Area {
+            x: rect.position.x / scale,
+            y: rect.position.y / scale,
+            width: f64::from(rect.size.width) / scale,
+            height: f64::from(rect.size.height) / scale,
+        });
+        self.own_ui.set_menu_icon(icon);
+    }
+
+    /// Plays the sound for `cue` if sounds are turned on.
+    fn play_cue(&self, cue: Cue) {
+        if self.config.sound_cues {
+            sound::play(cue, self.config.sounds.get(&cue).map(PathBuf::as_path));
+        }
     }
 
     pub fn update_menu_state(&mut self) {
         let state = self.state.lock().unwrap();
         let is_recording = state.is_recording;
-        let is_playing = state.playback_process.is_some();
+        let is_playing = state.playback_run.is_some();
+        let is_paused = state.is_paused;
         let has_recording = state.pending_playback.is_some();
         drop(state);
 
+        if !is_playing {
+            self.overlay = None;
+        }
+
+        if !is_playing && !is_recording && let Some(tray) = &self.tray_icon {
+            let _ = tray.set_tooltip(Some("Macro"));
+            #[cfg(target_os = "macos")]
+            tray.set_title(None::<&str>);
+        }
+        if is_recording {
+            let timer = self.recording_timer.get_or_insert_with(RecordingTimer::start);
+            timer.set_paused(is_paused);
+            timer.shown_secs = None;
+        } else {
+            self.recording_timer = None;
+        }
+        self.pause_menu_item.set_enabled(is_recording || is_playing);
+        self.pause_menu_item.set_text(match (is_paused, is_recording) {
+            (false, true) => "Pause Recording",
+            (true, true) => "Resume Recording",
+            (false, false) => "Pause Playback",
+            (true, false) => "Resume Playback",
+        });
+
         if is_recording {
             // Recording Started
             let _ = self.recording_menu_item.set_text("Stop");
@@ -663,7 +1577,8 @@ impl BarApp {
             let _ = self.settings_menu_item.set_enabled(false);
 
             if let Some(tray) = &mut self.tray_icon {
-                let _ = tray.set_icon(Some(self.icon_recording.clone()));
+                let icon = if is_paused { &self.icon_paused } else { &self.icon_recording };
+                let _ = tray.set_icon(Some(icon.clone()));
             }
         } else if is_playing {
             // Playback Started
@@ -679,7 +1594,8 @@ impl BarApp {
             let _ = self.settings_menu_item.set_enabled(false);
 
             if let Some(tray) = &mut self.tray_icon {
-                let _ = tray.set_icon(Some(self.icon_playing.clone()));
+                let icon = if is_paused { &self.icon_paused } else { &self.icon_playing };
+                let _ = tray.set_icon(Some(icon.clone()));
             }
         } else if has_recording {
             // Recording Loaded
@@ -714,26 +1630,140 @@ impl BarApp {
                 let _ = tray.set_icon(Some(self.icon_idle.clone()));
             }
         }
+        self.update_recording_timer();
     }
 }
 
-pub fn create_hotkeys() -> (HotKey, HotKey, HotKey) {
-    let record_hotkey = HotKey::new(Some(Modifiers::META | Modifiers::SHIFT), Code::Digit1);
-    // We need to set the ID manually if possible, but HotKey::new generates a random ID or hashes it.
-    // Actually GlobalHotKeyManager uses the ID from the HotKey struct.
-    // We can't easily force an ID on `HotKey` struct from `global_hotkey` crate as fields are private or it's constructed via new.
-    // Wait, `HotKey` struct in `global_hotkey` 0.5.0 might not allow setting ID directly if it's not exposed.
-    // Let's check how we can identify them.
-    // Ah, `HotKey` implements `PartialEq` and `Hash`. We can store the created hotkeys in `BarApp` and compare `event.id` with `hotkey.id()`.
+/// Menu text for a run in the History submenu, e.g.
+/// "Oct 16 02:13  Play demo.json: completed in 1m 3s".
+fn history_menu_text(entry: &HistoryEntry) -> String {
+    let kind = match entry.kind {
+        RunKind::Record => "Record",
+        RunKind::Play => "Play",
+    };
+    let name = entry
+        .path
+        .as_ref()
+        .and_then(|path| path.file_name())
+        .map_or_else(|| "unsaved".to_string(), |name| name.to_string_lossy().into_owned());
+    let duration = humantime::format_duration(Duration::from_secs(entry.duration.round() as u64));
+    format!("{}  {} {}: {} in {}", entry.started.format("%b %d %H:%M"), kind, name, entry.outcome, duration)
+}
 
-    let playback_hotkey = HotKey::new(Some(Modifiers::META | Modifiers::SHIFT), Code::Digit2);
-    let load_hotkey = HotKey::new(Some(Modifiers::META | Modifiers::SHIFT), Code::Digit0);
+/// The thumbnail of the recording at `path` as a `data:` URL the settings
+/// page can show, if it has one.
+fn thumbnail_data_url(path: &Path) -> Option<String> {
+    let png = fs::read(screenshot::thumbnail_path(path)).ok()?;
+    Some(format!("data:image/png;base64,{}", BASE64_STANDARD.encode(png)))
+}
 
-    (record_hotkey, playback_hotkey, load_hotkey)
+/// Takes the temp files of the current recording out of `state`, oldest
+/// first.
+fn take_recording_segments(state: &mut AppState) -> Vec<PathBuf> {
+    let mut segments = std::mem::take(&mut state.recording_segments);
+    segments.extend(state.current_recording_path.take());
+    segments
 }
 
-fn get_recordings_dir() -> PathBuf {
-    document_dir().unwrap_or(PathBuf::from(".")).join("Macros")
+/// Loads the parts of a recording whose recorder was restarted as one
+/// recording, each part following on from the one before.
+fn join_segments(segments: &[PathBuf]) -> Result<Recording> {
+    let mut recording: Option<Recording> = None;
+    for path in segments {
+        let segment = Recording::load(path)?;
+        match &mut recording {
+            Some(recording) => recording.events.extend(segment.events),
+            None => recording = Some(segment),
+        }
+    }
+    recording.ok_or_else(|| anyhow::anyhow!("No recording to save"))
+}
+
+pub fn create_hotkeys(keymaps: &KeyMaps) -> (HotKey, HotKey, HotKey, HotKey, HotKey) {
+    let defaults = KeyMaps::default();
+    let hotkey = |combo: &KeyCombo, default: &KeyCombo| {
+        to_hotkey(combo).unwrap_or_else(|| {
+            tracing::warn!("Unsupported hotkey {:?} + {:?}, using the default.", combo.modifiers, combo.trigger);
+            to_hotkey(default).expect("default hotkeys are supported")
+        })
+    };
+
+    // The tray toggles with the start combos, the stop combos are watched by the recorder and
+    // the playback stop listener. The pause combo reaches those directly as well; the tray only
+    // pauses or resumes explicitly, so both agree on the state.
+    let record_hotkey = hotkey(&keymaps.start_recording, &defaults.start_recording);
+    let playback_hotkey = hotkey(&keymaps.start_playback, &defaults.start_playback);
+    let load_hotkey = hotkey(&keymaps.load_recording, &defaults.load_recording);
+    let pause_hotkey = hotkey(&keymaps.pause, &defaults.pause);
+    let panic_hotkey = hotkey(&keymaps.panic, &defaults.panic);
+
+    (record_hotkey, playback_hotkey, load_hotkey, pause_hotkey, panic_hotkey)
+}
+
+fn to_hotkey(combo: &KeyCombo) -> Option<HotKey> {
+    let modifiers = combo.modifiers.iter().fold(Modifiers::empty(), |acc, m| {
+        acc | match m {
+            Modifier::Cmd => Modifiers::META,
+            Modifier::Alt => Modifiers::ALT,
+            Modifier::Ctrl => Modifiers::CONTROL,
+            Modifier::Shift => Modifiers::SHIFT,
+        }
+    });
+    let code = match combo.trigger {
+        Key::Num0 => Code::Digit0,
+        Key::Num1 => Code::Digit1,
+        Key::Num2 => Code::Digit2,
+        Key::Num3 => Code::Digit3,
+        Key::Num4 => Code::Digit4,
+        Key::Num5 => Code::Digit5,
+        Key::Num6 => Code::Digit6,
+        Key::Num7 => Code::Digit7,
+        Key::Num8 => Code::Digit8,
+        Key::Num9 => Code::Digit9,
+        Key::KeyA => Code::KeyA,
+        Key::KeyB => Code::KeyB,
+        Key::KeyC => Code::KeyC,
+        Key::KeyD => Code::KeyD,
+        Key::KeyE => Code::KeyE,
+        Key::KeyF => Code::KeyF,
+        Key::KeyG => Code::KeyG,
+        Key::KeyH => Code::KeyH,
+        Key::KeyI => Code::KeyI,
+        Key::KeyJ => Code::KeyJ,
+        Key::KeyK => Code::KeyK,
+        Key::KeyL => Code::KeyL,
+        Key::KeyM => Code::KeyM,
+        Key::KeyN => Code::KeyN,
+        Key::KeyO => Code::KeyO,
+        Key::KeyP => Code::KeyP,
+        Key::KeyQ => Code::KeyQ,
+        Key::KeyR => Code::KeyR,
+        Key::KeyS => Code::KeyS,
+        Key::KeyT => Code::KeyT,
+        Key::KeyU => Code::KeyU,
+        Key::KeyV => Code::KeyV,
+        Key::KeyW => Code::KeyW,
+        Key::KeyX => Code::KeyX,
+        Key::KeyY => Code::KeyY,
+        Key::KeyZ => Code::KeyZ,
+        Key::F1 => Code::F1,
+        Key::F2 => Code::F2,
+        Key::F3 => Code::F3,
+        Key::F4 => Code::F4,
+        Key::F5 => Code::F5,
+        Key::F6 => Code::F6,
+        Key::F7 => Code::F7,
+        Key::F8 => Code::F8,
+        Key::F9 => Code::F9,
+        Key::F10 => Code::F10,
+        Key::F11 => Code::F11,
+        Key::F12 => Code::F12,
+        Key::Space => Code::Space,
+        Key::Escape => Code::Escape,
+        _ => return None,
+    };
+    let modifiers = (!modifiers.is_empty()).then_some(modifiers);
+    Some(HotKey::new(modifiers, code))
 }
 
 fn create_icon(r: u8, g: u8, b: u8, a: u8) -> Icon {
@@ -768,73 +1798,58 @@ fn create_icon(r: u8, g: u8, b: u8, a: u8) -> Icon {
     Icon::from_rgba(rgba, width, height).expect("Failed to create icon")
 }
 
-fn check_and_update() {
-    log::info!("Checking for updates...");
-
-    let status = self_update::backends::github::Update::configure()
-        .repo_owner("keval8solanki")
-        .repo_name("macro")
-        .bin_name("macro")
-        .target("macos")
-        .show_download_progress(true)
-        .current_version(cargo_crate_version!())
-        .build();
+/// Explains why a second copy of the app quits straight away.
+pub fn show_already_running() {
+    rfd::MessageDialog::new()
+        .set_title("Macro Is Already Running")
+        .set_description("Another copy of Macro is already running. Look for its icon in the menu bar or system tray.\nRunning two copies would make every hotkey fire twice.")
+        .set_level(rfd::MessageLevel::Warning)
+        .show();
+}
 
-    let updater = match status {
-        Ok(s) => s,
-        Err(e) => {
-            log::error!("Failed to configure update: {}", e);
-            rfd::MessageDialog::new()
-                .set_title("Update Error")
-                .set_description(&format!("Failed to configure update: {}", e))
+/// Offers to install the newest release on `channel`, if it is newer than
+/// the running version.
+fn check_and_update(channel: UpdateChannel) {
+    tracing::info!("Checking for updates on the {:?} channel...", channel);
+
+    match update::available_update(channel) {
+        Ok(Some(release)) => {
+            let confirm = rfd::MessageDialog::new()
+                .set_title("Update Available")
+                .set_description(&format!(
+                    "New version {} is available (current: {}).\nUpdate now?",
+                    release.version,
+                    env!("CARGO_PKG_VERSION")
+                ))
+                .set_buttons(rfd::MessageButtons::YesNo)
                 .show();
-            return;
-        }
-    };
 
-    match updater.get_latest_release() {
-        Ok(release) => {
-            let latest_version = release.version;
-            let current_version = cargo_crate_version!();
-
-            if self_update::version::bump_is_greater(current_version, &latest_version)
-                .unwrap_or(false)
-            {
-                let confirm = rfd::MessageDialog::new()
-                    .set_title("Update Available")
-                    .set_description(&format!(
-                        "New version {} is available (current: {}).\nUpdate now?",
-                        latest_version, current_version
-                    ))
-                    .set_buttons(rfd::MessageButtons::YesNo)
-                    .show();
-
-                if confirm == rfd::MessageDialogResult::Yes {
-                    // Perform update
-                    match updater.update() {
-                        Ok(_) => {
-                            rfd::MessageDialog::new()
-                                .set_title("Update Successful")
-                                .set_description("Application updated successfully. Please restart the application.")
-                                .show();
-                        }
-                        Err(e) => {
-                            rfd::MessageDialog::new()
-                                .set_title("Update Failed")
-                                .set_description(&format!("Failed to update: {}", e))
-                                .show();
-                        }
+            if confirm == rfd::MessageDialogResult::Yes {
+                match update::install(&release) {
+                    Ok(()) => {
+                        rfd::MessageDialog::new()
+                            .set_title("Update Successful")
+                            .set_description("Application updated successfully. Please restart the application.")
+                            .show();
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to update: {:?}", e);
+                        rfd::MessageDialog::new()
+                            .set_title("Update Failed")
+                            .set_description(&format!("Failed to update: {}", e))
+                            .show();
                     }
                 }
-            } else {
-                rfd::MessageDialog::new()
-                    .set_title("No Update")
-                    .set_description("You are on the latest version.")
-                    .show();
             }
         }
+        Ok(None) => {
+            rfd::MessageDialog::new()
+                .set_title("No Update")
+                .set_description("You are on the latest version.")
+                .show();
+        }
         Err(e) => {
-            log::error!("Failed to check for updates: {}", e);
+            tracing::error!("Failed to check for updates: {:?}", e);
             rfd::MessageDialog::new()
                 .set_title("Update Check Failed")
                 .set_description(&format!("Failed to check for updates: {}", e))
@@ -0,0 +1,201 @@
+use crate::backend::InputBackend;
+use crate::clock::Clock;
+use crate::platform;
+use anyhow::Result;
+use rdev::EventType;
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Sleep lengths measured, from a fast key repeat to one frame at 60Hz.
+const SLEEP_LENGTHS_MS: [u64; 3] = [1, 5, 16];
+
+/// How long to wait for a simulated event to come back through capture
+/// before counting it as lost.
+const ROUND_TRIP_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Spread of a set of timings, in microseconds.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timings {
+    pub samples: usize,
+    pub min_us: u64,
+    pub median_us: u64,
+    pub p95_us: u64,
+    pub max_us: u64,
+}
+
+impl Timings {
+    /// Spread of `samples`, or `None` if there are none.
+    pub fn from_samples(samples: &[Duration]) -> Option<Self> {
+        let mut micros: Vec<u64> = samples.iter().map(|sample| sample.as_micros() as u64).collect();
+        micros.sort_unstable();
+        let at = |fraction: f64| micros[((micros.len() - 1) as f64 * fraction).round() as usize];
+        (!micros.is_empty()).then(|| Timings {
+            samples: micros.len(),
+            min_us: micros[0],
+            median_us: at(0.5),
+            p95_us: at(0.95),
+            max_us: micros[micros.len() - 1],
+        })
+    }
+}
+
+/// How much longer than asked sleeps of one length took.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SleepOvershoot {
+    pub requested_ms: u64,
+    pub overshoot: Timings,
+}
+
+/// Input and timing latencies measured on this machine.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BenchReport {
+    /// How long simulating an event blocks for.
+    pub simulate: Option<Timings>,
+    /// From simulating an event to capture receiving it.
+    pub round_trip: Option<Timings>,
+    /// From the time the system stamped a captured event with to the
+    /// capture callback running.
+    pub capture: Option<Timings>,
+    /// Simulated events capture never received.
+    pub lost: usize,
+    /// Sleeps as playback does them, which spin for the last stretch.
+    pub sleep_overshoot: Vec<SleepOvershoot>,
+}
+
+/// Measures `samples` of each latency. Moves the mouse back and forth by a
+/// pixel at the center of the primary display to time simulated events
+/// coming back through capture, so it needs both to work.
+pub fn bench(backend: Arc<dyn InputBackend>, clock: &dyn Clock, samples: usize) -> Result<BenchReport> {
+    let mut sleep_overshoot = Vec::new();
+    for requested_ms in SLEEP_LENGTHS_MS {
+        let requested = Duration::from_millis(requested_ms);
+        let overshoots: Vec<Duration> = (0..samples)
+            .map(|_| {
+                let before = clock.elapsed();
+                clock.sleep(requested);
+                (clock.elapsed() - before).saturating_sub(requested)
+            })
+            .collect();
+        if let Some(overshoot) = Timings::from_samples(&overshoots) {
+            sleep_overshoot.push(SleepOvershoot { requested_ms, overshoot });
+        }
+    }
+
+    let (captured_tx, captured_rx) = mpsc::channel();
+    let listener = backend.clone();
+    thread::spawn(move || {
+        let result = listener.listen(Box::new(move |event| {
+            if let EventType::MouseMove { .. } = event.event_type {
+                let capture = SystemTime::now().duration_since(event.time).unwrap_or_default();
+                let _ = captured_tx.send((Instant::now(), capture));
+            }
+        }));
+        if let Err(e) = result {
+            tracing::error!("Listen error: {:?}", e);
+        }
+    });
+    // Give capture a moment to start
+    thread::sleep(Duration::from_millis(200));
+
+    let displays = backend.displays();
+    let primary = displays.iter().find(|display| display.is_primary).or(displays.first());
+    let (center_x, center_y) = primary.map_or((100.0, 100.0), |display| {
+        ((display.x + display.width / 2.0).round(), (display.y + display.height / 2.0).round())
+    });
+    let (mut simulate, mut round_trip, mut capture) = (Vec::new(), Vec::new(), Vec::new());
+    let mut lost = 0;
+    for sample in 0..samples {
+        while captured_rx.try_recv().is_ok() {}
+        let x = center_x + (sample % 2) as f64;
+        let sent = Instant::now();
+        backend.simulate(&EventType::MouseMove { x, y: center_y })?;
+        simulate.push(sent.elapsed());
+
+        // The mouse is left alone meanwhile, so the next move is this one.
+        // Fails straight away once capture has stopped.
+        match captured_rx.recv_timeout(ROUND_TRIP_TIMEOUT) {
+            Ok((at, latency)) => {
+                round_trip.push(at - sent);
+                capture.push(latency);
+            }
+            Err(_) => lost += 1,
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    Ok(BenchReport {
+        simulate: Timings::from_samples(&simulate),
+        round_trip: Timings::from_samples(&round_trip),
+        capture: Timings::from_samples(&capture),
+        lost,
+        sleep_overshoot,
+    })
+}
+
+fn describe(timings: Option<&Timings>) -> String {
+    let ms = |us: u64| us as f64 / 1000.0;
+    match timings {
+        Some(t) => format!(
+            "median {:.3}ms  p95 {:.3}ms  min {:.3}ms  max {:.3}ms  ({} samples)",
+            ms(t.median_us),
+            ms(t.p95_us),
+            ms(t.min_us),
+            ms(t.max_us),
+            t.samples
+        ),
+        None => "no samples".to_string(),
+    }
+}
+
+/// Prints `report` along with what its numbers mean for recording and
+/// playback.
+pub fn print_report(report: &BenchReport) {
+    println!("Simulate call:    {}", describe(report.simulate.as_ref()));
+    println!("Round trip:       {}", describe(report.round_trip.as_ref()));
+    println!("Capture callback: {}", describe(report.capture.as_ref()));
+    println!("Lost events:      {} of {}", report.lost, report.simulate.map_or(0, |t| t.samples));
+    for sleep in &report.sleep_overshoot {
+        println!("Sleep {:>2}ms:       overshoot {}", sleep.requested_ms, describe(Some(&sleep.overshoot)));
+    }
+
+    println!();
+    if report.lost > 0 {
+        println!("- Some simulated events never reached capture. Recording and playing at the same time may miss input.");
+    }
+    if let Some(round_trip) = report.round_trip
+        && round_trip.p95_us > 10_000
+    {
+        println!("- Input takes over 10ms to come back through capture, so recorded delays can be off by that much.");
+    }
+    if let Some(capture) = report.capture
+        && capture.p95_us > 5_000
+    {
+        println!("- Captured events arrive late. Fast sequences may be recorded closer together than they happened.");
+    }
+    let worst = report.sleep_overshoot.iter().map(|sleep| sleep.overshoot.p95_us).max().unwrap_or(0);
+    if worst > 2_000 {
+        println!(
+            "- Waits overshoot by up to {:.1}ms, so events closer together than that play late. A lower --speed or a larger --move-interval when recording helps.",
+            worst as f64 / 1000.0
+        );
+    } else {
+        println!("- Timing looks tight enough for playback to keep the recorded pace.");
+    }
+}
+
+/// Runs the benchmark with `samples` of each measurement and prints the
+/// report, as JSON if `json` is set.
+pub fn run_bench(backend: Arc<dyn InputBackend>, clock: &dyn Clock, samples: usize, json: bool) -> Result<()> {
+    platform::check_input_backend()?;
+    eprintln!("Measuring input latency. This moves the mouse to the middle of the screen, so leave it alone for a few seconds...");
+    let report = bench(backend, clock, samples)?;
+    if json {
+        println!("{}", serde_json::to_string(&report)?);
+    } else {
+        print_report(&report);
+    }
+    Ok(())
+}
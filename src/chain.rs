@@ -0,0 +1,157 @@
+use crate::backend::InputBackend;
+use crate::config::KeyMaps;
+use crate::history::{self, RunKind, RunTimer};
+use crate::play::{self, PlaybackOptions, PlaybackOutcome, Player};
+use crate::platform;
+use crate::recording::Recording;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What a chain does when one of its steps fails to load or play.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum OnFailure {
+    /// Stop the chain there.
+    #[default]
+    Abort,
+    /// Carry on with the next step.
+    Continue,
+    /// Play this recording, e.g. one that puts the target app back the way
+    /// it was, then stop the chain.
+    Run(PathBuf),
+}
+
+impl FromStr for OnFailure {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "abort" => Ok(OnFailure::Abort),
+            "continue" => Ok(OnFailure::Continue),
+            other => match other.strip_prefix("run ") {
+                Some(path) if !path.trim().is_empty() => Ok(OnFailure::Run(PathBuf::from(path.trim()))),
+                _ => Err(format!("expected 'abort', 'continue' or 'run <recording>', got '{}'", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for OnFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OnFailure::Abort => write!(f, "abort"),
+            OnFailure::Continue => write!(f, "continue"),
+            OnFailure::Run(path) => write!(f, "run {}", path.display()),
+        }
+    }
+}
+
+impl TryFrom<String> for OnFailure {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<OnFailure> for String {
+    fn from(on_failure: OnFailure) -> Self {
+        on_failure.to_string()
+    }
+}
+
+/// One recording in a chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainStep {
+    pub recording: PathBuf,
+    #[serde(default)]
+    pub on_failure: OnFailure,
+}
+
+/// Recordings played one after the other, e.g.
+/// `{"steps": [{"recording": "login.json"}, {"recording": "export.json", "on_failure": "run logout.json"}]}`.
+/// Paths are relative to the chain file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chain {
+    pub steps: Vec<ChainStep>,
+}
+
+impl Chain {
+    /// Loads the chain at `path`, with its recordings' paths made relative
+    /// to where the chain is.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).with_context(|| format!("Could not read chain {:?}", path))?;
+        let mut chain: Chain = serde_json::from_str(&contents).with_context(|| format!("Could not parse chain {:?}", path))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for step in &mut chain.steps {
+            step.recording = dir.join(&step.recording);
+            if let OnFailure::Run(cleanup) = &mut step.on_failure {
+                *cleanup = dir.join(&*cleanup);
+            }
+        }
+        Ok(chain)
+    }
+}
+
+fn play_step(player: &Player, path: &Path) -> Result<()> {
+    let recording = Recording::load(path)?;
+    player.play(&recording)
+}
+
+/// Plays the steps of `chain` in order with `player`, following each
+/// step's `on_failure` when it fails. Fails with the error of the step that
+/// stopped the chain, if any.
+pub fn play_chain(player: &Player, chain: &Chain) -> Result<PlaybackOutcome> {
+    let control = player.control();
+    for (index, step) in chain.steps.iter().enumerate() {
+        let number = index + 1;
+        tracing::info!("Step {} of {}: {:?}", number, chain.steps.len(), step.recording);
+        let result = play_step(player, &step.recording);
+        if control.is_stopped() {
+            tracing::info!("Chain stopped by user.");
+            return Ok(PlaybackOutcome::Stopped);
+        }
+        let Err(e) = result else { continue };
+        let e = e.context(format!("Step {} ({}) failed", number, step.recording.display()));
+        match &step.on_failure {
+            OnFailure::Continue => tracing::warn!("{:#}, continuing.", e),
+            OnFailure::Abort => return Err(e),
+            OnFailure::Run(cleanup) => {
+                tracing::warn!("{:#}, playing {:?} to clean up.", e, cleanup);
+                if let Err(cleanup_error) = play_step(player, cleanup) {
+                    tracing::error!("Cleanup {:?} failed too: {:#}", cleanup, cleanup_error);
+                }
+                return Err(e);
+            }
+        }
+    }
+    tracing::info!("Chain complete.");
+    Ok(PlaybackOutcome::Completed)
+}
+
+/// Plays the chain at `input` after `countdown`, with the stop and pause
+/// hotkeys working throughout. `options` apply to every step.
+pub fn run_chain(backend: Arc<dyn InputBackend>, input: &Path, options: PlaybackOptions, countdown: Duration, keymaps: KeyMaps) -> Result<PlaybackOutcome> {
+    platform::check_input_backend()?;
+    let chain = Chain::load(input)?;
+    tracing::info!("Loaded chain of {} steps.", chain.steps.len());
+    tracing::info!("Stop Playback: {:?} + {:?}", keymaps.stop_playback.modifiers, keymaps.stop_playback.trigger);
+
+    let timer = RunTimer::start(RunKind::Play, Some(input), options.to_args());
+    let player = Player::new().backend(backend.clone()).options(options);
+    play::spawn_stop_listener(backend, keymaps, player.control());
+    let result = if player.count_down(countdown) {
+        play_chain(&player, &chain)
+    } else {
+        tracing::info!("Chain stopped by user during countdown.");
+        Ok(PlaybackOutcome::Stopped)
+    };
+    history::log(&timer.finish(&result, player.control().is_stopped()));
+    result
+}
@@ -0,0 +1,76 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time source used by playback, so timing logic can run against a virtual
+/// clock in tests instead of really sleeping.
+pub trait Clock: Send + Sync {
+    /// Time elapsed since the clock was created.
+    fn elapsed(&self) -> Duration;
+
+    fn sleep(&self, duration: Duration);
+}
+
+/// Wall-clock time backed by `Instant` and `thread::sleep`.
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Sleeps most of the way and spins for the last stretch, as OS sleeps
+    /// can overshoot by a millisecond or more.
+    fn sleep(&self, duration: Duration) {
+        const SPIN: Duration = Duration::from_millis(2);
+        let deadline = Instant::now() + duration;
+        if duration > SPIN {
+            thread::sleep(duration - SPIN);
+        }
+        while Instant::now() < deadline {
+            thread::yield_now();
+        }
+    }
+}
+
+/// Clock that only advances when slept on. Sleeping returns immediately.
+#[derive(Default)]
+pub struct VirtualClock {
+    now: Mutex<Duration>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for VirtualClock {
+    fn elapsed(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
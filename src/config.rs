@@ -1,15 +1,406 @@
+use crate::platform;
+use crate::sound::Cue;
+use anyhow::Result;
 use rdev::Key;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Settings read from `config.json` in [`platform::config_dir`]. Missing
+/// fields fall back to their defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub keymaps: KeyMaps,
+    /// Overrides the platform default from [`platform::recordings_dir`].
+    pub recordings_dir: Option<PathBuf>,
+    /// Playback speed used when none is given explicitly.
+    pub playback_speed: f64,
+    /// Seconds the menu bar app waits before starting playback.
+    pub countdown: f64,
+    /// Milliseconds by which the menu bar app randomly shifts each played event.
+    pub humanize_ms: u64,
+    /// Shortest time in milliseconds between mouse moves kept by the menu bar
+    /// app's recordings, 0 to keep all of them.
+    pub move_interval_ms: u64,
+    /// Longest pause in seconds kept by the menu bar app's recordings, 0 for no limit.
+    pub max_gap: f64,
+    /// Seconds the undo hotkey takes back while the menu bar app records, 0
+    /// for everything since the last checkpoint.
+    pub undo_seconds: f64,
+    /// Minutes after which the menu bar app stops and saves a recording, 0 for no limit.
+    pub max_recording_minutes: f64,
+    /// Events after which the menu bar app stops and saves a recording, 0 for no limit.
+    pub max_recording_events: usize,
+    /// Size in megabytes at which the menu bar app stops and saves a
+    /// recording, 0 for no limit.
+    pub max_recording_mb: f64,
+    /// Recording most recently loaded in the menu bar app.
+    pub last_recording: Option<PathBuf>,
+    /// Load `last_recording` when the menu bar app starts, ready to play.
+    pub reload_last_on_launch: bool,
+    /// Start recording again when recording in the menu bar app fails, rather
+    /// than stopping it.
+    pub restart_recorder: bool,
+    /// Show the keys pressed and clicks made on screen while the menu bar app
+    /// plays a recording.
+    pub playback_overlay: bool,
+    /// Show a floating "REC" badge while the menu bar app records.
+    pub recording_indicator: bool,
+    /// Play a short sound as the menu bar app starts and stops recording
+    /// and playback, and when playback fails.
+    pub sound_cues: bool,
+    /// Sound files played instead of the system sounds, by cue, e.g.
+    /// `"playback_failed": "/path/to/alarm.wav"`.
+    pub sounds: BTreeMap<Cue, PathBuf>,
+    /// Save a screenshot as the menu bar app starts recording, shown when the
+    /// recording is loaded.
+    pub thumbnails: bool,
+    /// Releases offered by "Check for Updates...".
+    pub update_channel: UpdateChannel,
+    /// Look for an update in the background when the menu bar app starts.
+    pub check_updates_on_launch: bool,
+    /// Turn off update checks altogether, e.g. where installs are managed.
+    pub disable_updates: bool,
+    /// Named playback settings for `macro play --preset` and the Presets menu.
+    pub presets: BTreeMap<String, Preset>,
+    /// Recordings `macro midi` plays when MIDI notes are pressed.
+    pub midi_triggers: Vec<MidiTrigger>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            keymaps: KeyMaps::default(),
+            recordings_dir: None,
+            playback_speed: 1.0,
+            countdown: 0.0,
+            humanize_ms: 0,
+            move_interval_ms: 0,
+            max_gap: 0.0,
+            undo_seconds: 5.0,
+            max_recording_minutes: 0.0,
+            max_recording_events: 0,
+            max_recording_mb: 0.0,
+            last_recording: None,
+            reload_last_on_launch: false,
+            restart_recorder: false,
+            playback_overlay: false,
+            recording_indicator: false,
+            sound_cues: false,
+            sounds: BTreeMap::new(),
+            thumbnails: false,
+            update_channel: UpdateChannel::default(),
+            check_updates_on_launch: false,
+            disable_updates: false,
+            presets: BTreeMap::from([
+                ("fast-debug".to_string(), Preset { speed: Some(5.0), repeat_count: Some(1), ..Default::default() }),
+                ("overnight".to_string(), Preset { speed: Some(1.0), repeat_count: Some(0), repeat_interval: Some(60.0), ..Default::default() }),
+            ]),
+            midi_triggers: Vec::new(),
+        }
+    }
+}
+
+/// Environment variable naming a config file to use instead of the one in
+/// [`platform::config_dir`]. `--config` sets it, so processes started by
+/// this one use the same file.
+pub const PATH_VAR: &str = "MACRO_CONFIG";
+
+impl Config {
+    pub fn path() -> PathBuf {
+        std::env::var_os(PATH_VAR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| platform::config_dir().join("config.json"))
+    }
+
+    /// Loads the config file, falling back to defaults if it is missing or invalid.
+    pub fn load() -> Self {
+        let path = Self::path();
+        match fs::read_to_string(&path) {
+            Ok(contents) => match serde_json::from_str(&contents) {
+                Ok(config) => config,
+                Err(e) => {
+                    tracing::warn!("Ignoring invalid config file {:?}: {}", path, e);
+                    Self::default()
+                }
+            },
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Writes the config file, creating the config directory if needed.
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn recordings_dir(&self) -> PathBuf {
+        self.recordings_dir.clone().unwrap_or_else(platform::recordings_dir)
+    }
+
+    /// The preset called `name`.
+    pub fn preset(&self, name: &str) -> Result<&Preset> {
+        self.presets.get(name).ok_or_else(|| {
+            let names: Vec<&str> = self.presets.keys().map(String::as_str).collect();
+            anyhow::anyhow!("No preset named '{}', expected one of: {}", name, names.join(", "))
+        })
+    }
+}
+
+/// Problems with the config file `text`: where it fails to parse, with the
+/// line it fails on, or else settings it does not know and values out of
+/// range. Empty when the file is fine.
+pub fn check(text: &str) -> Vec<String> {
+    let config: Config = match serde_json::from_str(text) {
+        Ok(config) => config,
+        Err(e) => return vec![with_line_context(text, &e)],
+    };
+    let value: serde_json::Value = serde_json::from_str(text).unwrap_or_default();
+    let mut problems = Vec::new();
+    let known = serde_json::to_value(Config::default()).unwrap_or_default();
+    if let (Some(fields), Some(known)) = (value.as_object(), known.as_object()) {
+        for name in fields.keys().filter(|name| !known.contains_key(*name)) {
+            problems.push(format!("Unknown setting '{}', it is ignored", name));
+        }
+    }
+    if config.playback_speed <= 0.0 {
+        problems.push(format!("playback_speed must be above 0, got {}", config.playback_speed));
+    }
+    for (name, value) in [("max_recording_minutes", config.max_recording_minutes), ("max_recording_mb", config.max_recording_mb)] {
+        if value < 0.0 {
+            problems.push(format!("{} must be 0 or more, got {}", name, value));
+        }
+    }
+    for (cue, path) in config.sounds.iter().filter(|(_, path)| !path.exists()) {
+        problems.push(format!("Sound for {}: {} does not exist", cue, path.display()));
+    }
+    for (name, preset) in &config.presets {
+        if let Some(speed) = preset.speed.filter(|speed| *speed <= 0.0) {
+            problems.push(format!("Preset '{}': speed must be above 0, got {}", name, speed));
+        }
+    }
+    for trigger in &config.midi_triggers {
+        if trigger.note > 127 {
+            problems.push(format!("MIDI trigger for {:?}: note must be 0 to 127, got {}", trigger.recording, trigger.note));
+        }
+        if let Some(channel) = trigger.channel.filter(|channel| !(1..=16).contains(channel)) {
+            problems.push(format!("MIDI trigger for {:?}: channel must be 1 to 16, got {}", trigger.recording, channel));
+        }
+    }
+    problems
+}
+
+/// `error` with the line of `text` it happened on and a caret under its column.
+fn with_line_context(text: &str, error: &serde_json::Error) -> String {
+    let Some(line) = text.lines().nth(error.line().saturating_sub(1)) else {
+        return error.to_string();
+    };
+    let number = error.line().to_string();
+    format!(
+        "{}\n{} | {}\n{} | {}^",
+        error,
+        number,
+        line,
+        " ".repeat(number.len()),
+        " ".repeat(error.column().saturating_sub(1))
+    )
+}
+
+/// Checks the config file, printing what is wrong with it. Fails if it
+/// cannot be used as it is.
+pub fn run_validate() -> Result<()> {
+    let path = Config::path();
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("{} does not exist, the defaults are used.", path.display());
+            return Ok(());
+        }
+        Err(e) => return Err(anyhow::Error::new(e).context(format!("Could not read {:?}", path))),
+    };
+    let problems = check(&text);
+    if problems.is_empty() {
+        println!("{} is valid.", path.display());
+        return Ok(());
+    }
+    for problem in &problems {
+        eprintln!("{}", problem);
+    }
+    anyhow::bail!("{} has {} problem{}", path.display(), problems.len(), if problems.len() == 1 { "" } else { "s" })
+}
+
+/// Prints the settings in effect: the config file's, with defaults for
+/// those it leaves out.
+pub fn run_show() -> Result<()> {
+    let path = Config::path();
+    if let Ok(text) = fs::read_to_string(&path)
+        && serde_json::from_str::<Config>(&text).is_err()
+    {
+        eprintln!("Warning: {} is invalid and ignored, see `macro config validate`.", path.display());
+    }
+    eprintln!("Settings from {}, with defaults for those it leaves out:", path.display());
+    println!("{}", serde_json::to_string_pretty(&Config::load())?);
+    Ok(())
+}
+
+/// Detects changes to the config file by polling its modification time.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl Default for ConfigWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigWatcher {
+    const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new() -> Self {
+        let path = Config::path();
+        Self {
+            modified: modified_time(&path),
+            path,
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Returns the reloaded config if the file changed since the last call.
+    /// Cheap to call often; the file is checked at most once per second.
+    /// A file that cannot be read or parsed, e.g. halfway through an edit,
+    /// returns `None`, so the config in use is kept rather than replaced
+    /// by the defaults, and saved over the file.
+    pub fn poll(&mut self) -> Option<Config> {
+        if self.last_check.elapsed() < Self::CHECK_INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        tracing::info!("Config file changed, reloading {:?}", self.path);
+        let config = fs::read_to_string(&self.path)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(serde_json::from_str(&contents)?));
+        match config {
+            Ok(config) => Some(config),
+            Err(e) => {
+                tracing::warn!("Keeping the current config, could not reload {:?}: {}", self.path, e);
+                None
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
 pub struct KeyMaps {
     pub start_recording: KeyCombo,
     pub stop_recording: KeyCombo,
     pub start_playback: KeyCombo,
     pub stop_playback: KeyCombo,
+    /// Loads or unloads a recording in the tray app.
+    pub load_recording: KeyCombo,
+    /// Saves a screenshot while recording with hotkey screenshots enabled.
+    pub take_screenshot: KeyCombo,
+    /// Saves the last few seconds of input when recording with `--buffer`.
+    pub save_buffer: KeyCombo,
+    /// Pauses or resumes the running recording or playback.
+    pub pause: KeyCombo,
+    /// Stops everything the tray app is running and releases held input.
+    pub panic: KeyCombo,
+    /// Marks a named checkpoint while recording.
+    pub checkpoint: KeyCombo,
+    /// Takes back the last few seconds, or everything since the last
+    /// checkpoint, while recording.
+    pub undo: KeyCombo,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Playback settings saved under a name. Settings left out keep their
+/// usual values.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Preset {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+    /// Times to play, 0 for forever.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_count: Option<u32>,
+    /// Seconds between repeats.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_interval: Option<f64>,
+    /// Seconds to wait before playing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub countdown: Option<f64>,
+}
+
+impl Preset {
+    /// These settings, with those left out taken from `fallback`.
+    pub fn or(self, fallback: Preset) -> Preset {
+        Preset {
+            speed: self.speed.or(fallback.speed),
+            repeat_count: self.repeat_count.or(fallback.repeat_count),
+            repeat_interval: self.repeat_interval.or(fallback.repeat_interval),
+            countdown: self.countdown.or(fallback.countdown),
+        }
+    }
+}
+
+/// Recording played when a MIDI note is pressed, e.g. a pad on a controller
+/// or a Stream Deck key set up to send MIDI.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MidiTrigger {
+    /// Note number from 0 to 127, e.g. 36 for the first pad of many controllers.
+    pub note: u8,
+    /// Channel from 1 to 16. Notes on any channel match when left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<u8>,
+    /// Recording to play, relative to the recordings folder.
+    pub recording: PathBuf,
+}
+
+impl MidiTrigger {
+    /// Whether the raw MIDI `message` presses this trigger's note.
+    pub fn matches(&self, message: &[u8]) -> bool {
+        match *message {
+            // A note on with velocity 0 is a note off
+            [status, note, velocity, ..] if status & 0xF0 == 0x90 && velocity > 0 => {
+                note == self.note && self.channel.is_none_or(|channel| channel == (status & 0x0F) + 1)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Which releases updates are taken from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    /// Only full releases.
+    #[default]
+    Stable,
+    /// Pre-releases such as `1.4.0-beta.1` as well.
+    Beta,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct KeyCombo {
     pub modifiers: Vec<Modifier>,
     pub trigger: Key,
@@ -25,23 +416,52 @@ pub enum Modifier {
 
 impl Default for KeyMaps {
     fn default() -> Self {
+        let primary = platform::primary_modifier();
         Self {
             start_recording: KeyCombo {
-                modifiers: vec![Modifier::Cmd, Modifier::Shift],
+                modifiers: vec![primary.clone(), Modifier::Shift],
                 trigger: Key::Num1,
             },
             stop_recording: KeyCombo {
-                modifiers: vec![Modifier::Cmd, Modifier::Shift],
+                modifiers: vec![primary.clone(), Modifier::Shift],
                 trigger: Key::Num1,
             },
             start_playback: KeyCombo {
-                modifiers: vec![Modifier::Cmd, Modifier::Shift],
+                modifiers: vec![primary.clone(), Modifier::Shift],
                 trigger: Key::Num2,
             },
             stop_playback: KeyCombo {
-                modifiers: vec![Modifier::Cmd, Modifier::Shift],
+                modifiers: vec![primary.clone(), Modifier::Shift],
                 trigger: Key::Num2,
             },
+            load_recording: KeyCombo {
+                modifiers: vec![primary.clone(), Modifier::Shift],
+                trigger: Key::Num0,
+            },
+            take_screenshot: KeyCombo {
+                modifiers: vec![primary.clone(), Modifier::Shift],
+                trigger: Key::Num8,
+            },
+            save_buffer: KeyCombo {
+                modifiers: vec![primary.clone(), Modifier::Shift],
+                trigger: Key::Num7,
+            },
+            pause: KeyCombo {
+                modifiers: vec![primary.clone(), Modifier::Shift],
+                trigger: Key::Num3,
+            },
+            panic: KeyCombo {
+                modifiers: vec![primary.clone(), Modifier::Shift],
+                trigger: Key::Escape,
+            },
+            checkpoint: KeyCombo {
+                modifiers: vec![primary.clone(), Modifier::Shift],
+                trigger: Key::Num6,
+            },
+            undo: KeyCombo {
+                modifiers: vec![primary.clone(), Modifier::Shift],
+                trigger: Key::Num9,
+            },
         }
     }
 }
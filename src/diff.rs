@@ -0,0 +1,181 @@
+use crate::event::{SerializableEvent, SerializableEventType};
+use crate::recording::{CoordinateSpace, Recording};
+use anyhow::Result;
+use std::path::Path;
+use std::time::Duration;
+
+/// Mouse moves closer than this many points count as the same move.
+const MOVE_TOLERANCE: f64 = 5.0;
+
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Compare mouse moves too. They are skipped by default, as two takes of
+    /// the same macro rarely move the mouse along the same path.
+    pub include_moves: bool,
+    /// Matching events whose timestamps differ by more than this are reported.
+    pub timing_tolerance: Duration,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            include_moves: false,
+            timing_tolerance: Duration::from_millis(100),
+        }
+    }
+}
+
+/// One step of the alignment between two recordings, holding indices into
+/// their event lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffEntry {
+    /// The same event in both. `offset_ms` is how much later it happens on
+    /// the right, relative to the start of the recording.
+    Same { left: usize, right: usize, offset_ms: i64 },
+    /// Only in the left recording.
+    Removed { left: usize },
+    /// Only in the right recording.
+    Added { right: usize },
+}
+
+/// An event with its time from the start of its recording.
+struct TimedEvent<'a> {
+    index: usize,
+    at_ms: u64,
+    event: &'a SerializableEvent,
+}
+
+/// Aligns the events of two recordings along their longest common
+/// subsequence.
+pub fn diff(left: &Recording, right: &Recording, options: &DiffOptions) -> Vec<DiffEntry> {
+    let a = timed_events(left, options);
+    let b = timed_events(right, options);
+    let same = |i: usize, j: usize| {
+        same_action(&a[i].event.event_type, left, &b[j].event.event_type, right)
+    };
+
+    // lengths[i][j] is the LCS length of a[i..] and b[j..]
+    let width = b.len() + 1;
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if same(i, j) {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut entries = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if same(i, j) {
+            entries.push(DiffEntry::Same {
+                left: a[i].index,
+                right: b[j].index,
+                offset_ms: b[j].at_ms as i64 - a[i].at_ms as i64,
+            });
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            entries.push(DiffEntry::Removed { left: a[i].index });
+            i += 1;
+        } else {
+            entries.push(DiffEntry::Added { right: b[j].index });
+            j += 1;
+        }
+    }
+    entries.extend(a[i..].iter().map(|e| DiffEntry::Removed { left: e.index }));
+    entries.extend(b[j..].iter().map(|e| DiffEntry::Added { right: e.index }));
+    entries
+}
+
+fn timed_events<'a>(recording: &'a Recording, options: &DiffOptions) -> Vec<TimedEvent<'a>> {
+    let mut at_ms = 0;
+    let mut events = Vec::new();
+    for (index, event) in recording.events.iter().enumerate() {
+        at_ms += event.delay_ms;
+        let is_move = matches!(event.event_type, SerializableEventType::MouseMove { .. });
+        if options.include_moves || !is_move {
+            events.push(TimedEvent { index, at_ms, event });
+        }
+    }
+    events
+}
+
+/// Whether two events do the same thing. Moves only need to land close
+/// together and scrolls to go the same way, since neither is ever repeated
+/// exactly.
+fn same_action(a: &SerializableEventType, left: &Recording, b: &SerializableEventType, right: &Recording) -> bool {
+    match (a, b) {
+        (SerializableEventType::MouseMove { x: x1, y: y1, .. }, SerializableEventType::MouseMove { x: x2, y: y2, .. }) => {
+            let (s1, s2) = (points_per_unit(left), points_per_unit(right));
+            (x1 * s1 - x2 * s2).abs() <= MOVE_TOLERANCE && (y1 * s1 - y2 * s2).abs() <= MOVE_TOLERANCE
+        }
+        (
+            SerializableEventType::SmoothWheel { delta_x: x1, delta_y: y1, .. },
+            SerializableEventType::SmoothWheel { delta_x: x2, delta_y: y2, .. },
+        ) => x1.signum() == x2.signum() && y1.signum() == y2.signum(),
+        (SerializableEventType::Wheel { delta_x: x1, delta_y: y1 }, SerializableEventType::Wheel { delta_x: x2, delta_y: y2 }) => {
+            x1.signum() == x2.signum() && y1.signum() == y2.signum()
+        }
+        _ => a == b,
+    }
+}
+
+pub(crate) fn points_per_unit(recording: &Recording) -> f64 {
+    match recording.metadata.coordinate_space {
+        CoordinateSpace::Pixels if recording.metadata.scale_factor > 0.0 => 1.0 / recording.metadata.scale_factor,
+        _ => 1.0,
+    }
+}
+
+/// Prints the differences between two recording files. Returns whether
+/// they matched.
+pub fn run_diff(left_path: &Path, right_path: &Path, options: &DiffOptions) -> Result<bool> {
+    let left = Recording::load(left_path)?;
+    let right = Recording::load(right_path)?;
+    let entries = diff(&left, &right, options);
+    let tolerance = options.timing_tolerance.as_millis() as i64;
+    let at = |recording: &Recording, index: usize| -> f64 {
+        recording.events[..=index].iter().map(|e| e.delay_ms).sum::<u64>() as f64 / 1000.0
+    };
+
+    println!(
+        "--- {} ({} events, {:.3}s)",
+        left_path.display(),
+        left.events.len(),
+        left.events.iter().map(|e| e.delay_ms).sum::<u64>() as f64 / 1000.0
+    );
+    println!(
+        "+++ {} ({} events, {:.3}s)",
+        right_path.display(),
+        right.events.len(),
+        right.events.iter().map(|e| e.delay_ms).sum::<u64>() as f64 / 1000.0
+    );
+
+    let (mut matching, mut retimed, mut removed, mut added) = (0, 0, 0, 0);
+    for entry in &entries {
+        match *entry {
+            DiffEntry::Same { left: i, offset_ms, .. } if offset_ms.abs() > tolerance => {
+                retimed += 1;
+                println!("~ {:>9.3}s  {:?}  ({:+.3}s)", at(&left, i), left.events[i].event_type, offset_ms as f64 / 1000.0);
+            }
+            DiffEntry::Same { .. } => matching += 1,
+            DiffEntry::Removed { left: i } => {
+                removed += 1;
+                println!("- {:>9.3}s  {:?}", at(&left, i), left.events[i].event_type);
+            }
+            DiffEntry::Added { right: j } => {
+                added += 1;
+                println!("+ {:>9.3}s  {:?}", at(&right, j), right.events[j].event_type);
+            }
+        }
+    }
+    println!(
+        "{} matching, {} retimed by more than {}ms, {} removed, {} added",
+        matching, retimed, tolerance, removed, added
+    );
+    Ok(retimed == 0 && removed == 0 && added == 0)
+}
@@ -0,0 +1,314 @@
+use crate::recording::{CoordinateSpace, RecordingMetadata};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A connected display and its bounds in global screen coordinates.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DisplayInfo {
+    pub id: u32,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub is_primary: bool,
+    /// Backing scale factor (pixels per point), e.g. 2.0 on Retina displays.
+    #[serde(default = "default_scale_factor")]
+    pub scale_factor: f64,
+}
+
+fn default_scale_factor() -> f64 {
+    1.0
+}
+
+impl DisplayInfo {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// Position of a mouse event relative to the display it occurred on.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DisplayPoint {
+    pub display_id: u32,
+    pub x: f64,
+    pub y: f64,
+}
+
+/// How recorded display positions are mapped onto the current display arrangement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayMapping {
+    /// Replay on the display with the same id, wherever it is now positioned.
+    #[default]
+    Same,
+    /// Replay everything on the primary display.
+    Primary,
+    /// Replay everything on the display with the given id.
+    Target(u32),
+}
+
+impl FromStr for DisplayMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "same" => Ok(DisplayMapping::Same),
+            "primary" => Ok(DisplayMapping::Primary),
+            other => other
+                .parse::<u32>()
+                .map(DisplayMapping::Target)
+                .map_err(|_| format!("expected 'same', 'primary' or a display id, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for DisplayMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisplayMapping::Same => write!(f, "same"),
+            DisplayMapping::Primary => write!(f, "primary"),
+            DisplayMapping::Target(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+/// Mirroring or rotation applied to replayed positions, about the center of
+/// the display they land on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transform {
+    #[default]
+    None,
+    /// Flip left and right.
+    MirrorX,
+    /// Flip top and bottom.
+    MirrorY,
+    /// Rotate clockwise a quarter turn.
+    Rotate90,
+    Rotate180,
+    /// Rotate counterclockwise a quarter turn.
+    Rotate270,
+}
+
+impl Transform {
+    /// `x`, `y` transformed about `center`.
+    fn apply(self, x: f64, y: f64, center: (f64, f64)) -> (f64, f64) {
+        let (dx, dy) = (x - center.0, y - center.1);
+        let (dx, dy) = match self {
+            Transform::None => (dx, dy),
+            Transform::MirrorX => (-dx, dy),
+            Transform::MirrorY => (dx, -dy),
+            // Screen y grows downwards, so this turns clockwise
+            Transform::Rotate90 => (-dy, dx),
+            Transform::Rotate180 => (-dx, -dy),
+            Transform::Rotate270 => (dy, -dx),
+        };
+        (center.0 + dx, center.1 + dy)
+    }
+}
+
+impl FromStr for Transform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Transform::None),
+            "mirror-x" => Ok(Transform::MirrorX),
+            "mirror-y" => Ok(Transform::MirrorY),
+            "rotate-90" => Ok(Transform::Rotate90),
+            "rotate-180" => Ok(Transform::Rotate180),
+            "rotate-270" => Ok(Transform::Rotate270),
+            other => Err(format!(
+                "expected 'none', 'mirror-x', 'mirror-y', 'rotate-90', 'rotate-180' or 'rotate-270', got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transform::None => write!(f, "none"),
+            Transform::MirrorX => write!(f, "mirror-x"),
+            Transform::MirrorY => write!(f, "mirror-y"),
+            Transform::Rotate90 => write!(f, "rotate-90"),
+            Transform::Rotate180 => write!(f, "rotate-180"),
+            Transform::Rotate270 => write!(f, "rotate-270"),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn active_displays() -> Vec<DisplayInfo> {
+    use core_graphics::display::CGDisplay;
+
+    let ids = match CGDisplay::active_displays() {
+        Ok(ids) => ids,
+        Err(e) => {
+            tracing::error!("Failed to query active displays: {:?}", e);
+            return Vec::new();
+        }
+    };
+
+    ids.into_iter()
+        .map(|id| {
+            let display = CGDisplay::new(id);
+            let bounds = display.bounds();
+            let scale_factor = display
+                .display_mode()
+                .filter(|mode| mode.width() > 0)
+                .map(|mode| mode.pixel_width() as f64 / mode.width() as f64)
+                .unwrap_or(1.0);
+            DisplayInfo {
+                id,
+                x: bounds.origin.x,
+                y: bounds.origin.y,
+                width: bounds.size.width,
+                height: bounds.size.height,
+                is_primary: display.is_main(),
+                scale_factor,
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn active_displays() -> Vec<DisplayInfo> {
+    // Without a native display API we only know about the primary display.
+    match rdev::display_size() {
+        Ok((width, height)) => vec![DisplayInfo {
+            id: 0,
+            x: 0.0,
+            y: 0.0,
+            width: width as f64,
+            height: height as f64,
+            is_primary: true,
+            scale_factor: 1.0,
+        }],
+        Err(e) => {
+            tracing::error!("Failed to query display size: {:?}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Backing scale factor of the primary display.
+pub fn primary_scale_factor(displays: &[DisplayInfo]) -> f64 {
+    displays
+        .iter()
+        .find(|d| d.is_primary)
+        .map(|d| d.scale_factor)
+        .unwrap_or(1.0)
+}
+
+/// Number of native input-event units per point on this platform.
+///
+/// CoreGraphics reports and accepts event locations in points, so macOS needs
+/// no conversion. Other platforms deliver raw pixel coordinates.
+pub fn native_units_per_point(displays: &[DisplayInfo]) -> f64 {
+    if cfg!(target_os = "macos") {
+        1.0
+    } else {
+        primary_scale_factor(displays)
+    }
+}
+
+/// Finds the display containing the given global point.
+pub fn display_at(displays: &[DisplayInfo], x: f64, y: f64) -> Option<&DisplayInfo> {
+    displays.iter().find(|d| d.contains(x, y))
+}
+
+/// Converts a global point into display-local coordinates.
+pub fn to_display_point(displays: &[DisplayInfo], x: f64, y: f64) -> Option<DisplayPoint> {
+    display_at(displays, x, y).map(|d| DisplayPoint {
+        display_id: d.id,
+        x: x - d.x,
+        y: y - d.y,
+    })
+}
+
+/// Maps recorded positions onto the displays that are connected at playback time.
+pub struct DisplayMapper {
+    mapping: DisplayMapping,
+    displays: Vec<DisplayInfo>,
+    /// Multiplier converting recorded coordinates into points.
+    source_scale: f64,
+    transform: Transform,
+    /// Added to every position after mapping and `transform`, in points.
+    offset: (f64, f64),
+}
+
+impl DisplayMapper {
+    pub fn new(mapping: DisplayMapping, metadata: &RecordingMetadata, displays: Vec<DisplayInfo>) -> Self {
+        tracing::info!("Display mapping: {} ({} displays connected)", mapping, displays.len());
+        let source_scale = match metadata.coordinate_space {
+            CoordinateSpace::Points => 1.0,
+            CoordinateSpace::Pixels if metadata.scale_factor > 0.0 => 1.0 / metadata.scale_factor,
+            CoordinateSpace::Pixels => 1.0,
+        };
+        Self {
+            mapping,
+            displays,
+            source_scale,
+            transform: Transform::None,
+            offset: (0.0, 0.0),
+        }
+    }
+
+    /// Mirrors or rotates positions with `transform`, then moves them by
+    /// `offset` points, e.g. to follow a window that has moved since recording.
+    pub fn with_transform(mut self, transform: Transform, offset: (f64, f64)) -> Self {
+        self.transform = transform;
+        self.offset = offset;
+        self
+    }
+
+    fn primary(&self) -> Option<&DisplayInfo> {
+        self.displays.iter().find(|d| d.is_primary).or(self.displays.first())
+    }
+
+    fn find(&self, id: u32) -> Option<&DisplayInfo> {
+        self.displays.iter().find(|d| d.id == id)
+    }
+
+    /// Returns the native global point to replay, given the recorded global
+    /// point and the optional display-local position captured with it.
+    pub fn map_point(&self, x: f64, y: f64, point: Option<&DisplayPoint>) -> (f64, f64) {
+        let (x, y) = self.map_point_in_points(x * self.source_scale, y * self.source_scale, point);
+        let (x, y) = self.transform_in_points(x, y);
+        let native = native_units_per_point(&self.displays);
+        (x * native, y * native)
+    }
+
+    fn transform_in_points(&self, x: f64, y: f64) -> (f64, f64) {
+        let (x, y) = match display_at(&self.displays, x, y).or(self.primary()) {
+            Some(display) if self.transform != Transform::None => {
+                self.transform.apply(x, y, (display.x + display.width / 2.0, display.y + display.height / 2.0))
+            }
+            _ => (x, y),
+        };
+        (x + self.offset.0, y + self.offset.1)
+    }
+
+    fn map_point_in_points(&self, x: f64, y: f64, point: Option<&DisplayPoint>) -> (f64, f64) {
+        // Recordings without display information replay at their absolute position.
+        let Some(point) = point else {
+            return (x, y);
+        };
+        let (local_x, local_y) = (point.x * self.source_scale, point.y * self.source_scale);
+
+        let target = match self.mapping {
+            DisplayMapping::Same => self.find(point.display_id).or(self.primary()),
+            DisplayMapping::Primary => self.primary(),
+            DisplayMapping::Target(id) => self.find(id).or(self.primary()),
+        };
+
+        match target {
+            Some(display) => (
+                display.x + local_x.clamp(0.0, (display.width - 1.0).max(0.0)),
+                display.y + local_y.clamp(0.0, (display.height - 1.0).max(0.0)),
+            ),
+            None => (x, y),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use crate::backend::InputBackend;
+use crate::display::DisplayInfo;
+use crate::platform;
+use crate::recording::Recording;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// The machine a recording was captured on, beyond its displays, kept in
+/// its metadata so playback can point out what changed since.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Environment {
+    /// Operating system, e.g. "macos".
+    pub os: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyboard_layout: Option<String>,
+    /// Application in front when capture started. Kept for reference only,
+    /// as playback is usually started from another app.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frontmost_app: Option<String>,
+}
+
+impl Environment {
+    /// Describes the current machine, asking `backend` for the frontmost app.
+    pub fn capture(backend: &dyn InputBackend) -> Self {
+        Self {
+            os: std::env::consts::OS.to_string(),
+            os_version: platform::os_version(),
+            keyboard_layout: platform::keyboard_layout(),
+            frontmost_app: backend.frontmost_app().ok(),
+        }
+    }
+
+    /// Ways `current` differs from this environment that are likely to
+    /// make a recording made in it play back differently.
+    pub fn differences(&self, current: &Environment) -> Vec<String> {
+        let mut differences = Vec::new();
+        let major = |version: &Option<String>| version.as_deref().and_then(|v| v.split('.').next()).map(str::to_string);
+        if self.os != current.os {
+            differences.push(format!("recorded on {}, playing on {}", self.os, current.os));
+        } else if let (Some(recorded), Some(now)) = (major(&self.os_version), major(&current.os_version))
+            && recorded != now
+        {
+            differences.push(format!(
+                "recorded on {} {}, playing on {} {}",
+                self.os,
+                self.os_version.as_deref().unwrap_or_default(),
+                current.os,
+                current.os_version.as_deref().unwrap_or_default()
+            ));
+        }
+        if let (Some(recorded), Some(now)) = (&self.keyboard_layout, &current.keyboard_layout)
+            && recorded != now
+        {
+            differences.push(format!("keyboard layout was {}, is now {}", recorded, now));
+        }
+        differences
+    }
+}
+
+/// Describes a display arrangement for comparing and showing, ignoring
+/// display ids, which can change between restarts.
+fn arrangement(displays: &[DisplayInfo]) -> Vec<String> {
+    let mut arrangement: Vec<String> = displays
+        .iter()
+        .map(|d| format!("{}x{} at {},{} @{}x", d.width, d.height, d.x, d.y, d.scale_factor))
+        .collect();
+    arrangement.sort();
+    arrangement
+}
+
+/// Ways the current machine differs from the one `recording` was made on
+/// that are likely to break it. Recordings from before environments were
+/// kept are only compared by their displays.
+pub fn differences(recording: &Recording, current: &Environment, displays: &[DisplayInfo]) -> Vec<String> {
+    let mut differences = match &recording.metadata.environment {
+        Some(recorded) => recorded.differences(current),
+        None => Vec::new(),
+    };
+    let (recorded, now) = (arrangement(&recording.metadata.displays), arrangement(displays));
+    if !recorded.is_empty() && recorded != now {
+        differences.push(format!("displays were {}, are now {}", recorded.join(", "), now.join(", ")));
+    }
+    differences
+}
+
+/// Warns about what changed since `recording` was made that is likely to
+/// break it, or with `strict` fails instead.
+pub fn check(recording: &Recording, backend: &dyn InputBackend, strict: bool) -> Result<()> {
+    let differences = differences(recording, &Environment::capture(backend), &backend.displays());
+    if differences.is_empty() {
+        return Ok(());
+    }
+    if strict {
+        anyhow::bail!("This machine differs from the one the recording was made on: {}", differences.join("; "));
+    }
+    for difference in &differences {
+        tracing::warn!("Environment changed: {}", difference);
+        eprintln!("Warning: {}, the recording may not play back as it was made.", difference);
+    }
+    Ok(())
+}
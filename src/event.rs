@@ -1,47 +1,262 @@
+use crate::display::{self, DisplayInfo, DisplayMapper, DisplayPoint};
+use crate::remap::Remap;
 use rdev::{Button, Event, EventType, Key};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
 
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SerializableEvent {
     pub event_type: SerializableEventType,
+    /// Whole milliseconds since the previous event.
     pub delay_ms: u64,
+    /// Microseconds on top of `delay_ms`, always below a millisecond. Left
+    /// out of recordings made before delays were this precise.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub delay_us: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum SerializableEventType {
     KeyPress(Key),
     KeyRelease(Key),
     ButtonPress(Button),
     ButtonRelease(Button),
-    MouseMove { x: f64, y: f64 },
+    MouseMove {
+        x: f64,
+        y: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        display: Option<DisplayPoint>,
+    },
     Wheel { delta_x: i64, delta_y: i64 },
+    /// Key press with the text it produced. Always kept for non-ASCII
+    /// characters (accents, Option-combinations) and dead keys, which produce
+    /// nothing; kept for every printable key when recording with text capture.
+    KeyPressText { key: Key, text: String },
+    /// Clipboard text captured just before a paste, put back on the clipboard
+    /// during playback so the paste inserts the same text.
+    Clipboard { text: String },
+    /// Fails playback unless the screen pixel at `x`, `y` (in the same space as
+    /// mouse moves) is within `tolerance` of `color` on every RGB channel.
+    AssertPixelColor {
+        x: f64,
+        y: f64,
+        color: [u8; 3],
+        #[serde(default)]
+        tolerance: u8,
+    },
+    /// Fails playback unless the clipboard text contains `text`.
+    AssertClipboardContains { text: String },
+    /// Fails playback unless the frontmost application is named `name`,
+    /// ignoring case.
+    AssertFrontmostApp { name: String },
+    /// Holds playback until the application named `app`, or with that bundle
+    /// identifier, is frontmost, failing after `timeout_ms`. With `launch`,
+    /// the application is opened first.
+    WaitForApp {
+        app: String,
+        #[serde(default = "default_wait_timeout_ms")]
+        timeout_ms: u64,
+        #[serde(default)]
+        launch: bool,
+    },
+    /// Key held down for `duration_ms`, in place of the auto-repeat presses
+    /// the system sent while it was held. Played as a press and a release,
+    /// with repeats at the playback machine's key repeat rate in between.
+    Hold {
+        key: Key,
+        duration_ms: u64,
+        /// Text the key typed, for each press and repeat.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    /// Named place in the recording that playback can start from. Does
+    /// nothing when played.
+    Checkpoint { name: String },
+    /// Start of a named part of the recording, added each time more is
+    /// recorded onto the end of it. Does nothing when played.
+    Segment { name: String },
+    /// Pixel-precision scroll from a continuous device such as a trackpad.
+    SmoothWheel {
+        delta_x: f64,
+        delta_y: f64,
+        #[serde(default)]
+        phase: ScrollPhase,
+    },
+}
+
+/// Whether recorded key presses are replayed as the physical keys or as the
+/// characters they typed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyReplay {
+    /// Physical keys, except text the key alone may not reproduce: non-ASCII
+    /// characters and dead keys.
+    #[default]
+    Auto,
+    /// Always the physical keys, as on the recording machine's layout.
+    Physical,
+    /// The recorded characters wherever known, so text comes out the same on
+    /// any keyboard layout.
+    Characters,
+}
+
+impl FromStr for KeyReplay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(KeyReplay::Auto),
+            "physical" => Ok(KeyReplay::Physical),
+            "characters" => Ok(KeyReplay::Characters),
+            other => Err(format!("expected 'auto', 'physical' or 'characters', got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for KeyReplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyReplay::Auto => write!(f, "auto"),
+            KeyReplay::Physical => write!(f, "physical"),
+            KeyReplay::Characters => write!(f, "characters"),
+        }
+    }
+}
+
+/// Gesture phase of a continuous scroll, where the backend reports it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScrollPhase {
+    #[default]
+    None,
+    Began,
+    Changed,
+    Ended,
+    Momentum,
+}
+
+/// What a backend saw of a wheel event beyond its rounded deltas.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ScrollDetails {
+    /// Scrolled by a trackpad or other continuous device rather than in a
+    /// mouse wheel's notches.
+    pub continuous: bool,
+    pub delta_x: f64,
+    pub delta_y: f64,
+    pub phase: ScrollPhase,
 }
 
 impl SerializableEvent {
-    pub fn from_rdev(event: Event, delay_ms: u64) -> Option<Self> {
+    /// Time since the previous event.
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms) + Duration::from_micros(self.delay_us.into())
+    }
+
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay_ms = delay.as_millis() as u64;
+        self.delay_us = delay.subsec_micros() % 1000;
+    }
+
+    /// Converts a captured event. With `capture_text`, every key press that
+    /// types printable text keeps that text, not only the non-ASCII ones.
+    /// Wheel events with continuous `scroll` details become smooth scrolls.
+    pub fn from_rdev(event: Event, delay: Duration, displays: &[DisplayInfo], capture_text: bool, scroll: Option<ScrollDetails>) -> Option<Self> {
         let event_type = match event.event_type {
-            EventType::KeyPress(key) => SerializableEventType::KeyPress(key),
+            EventType::KeyPress(key) => match event.name {
+                Some(text) if is_layout_text(&text) || (capture_text && !text.chars().any(char::is_control)) => {
+                    SerializableEventType::KeyPressText { key, text }
+                }
+                _ => SerializableEventType::KeyPress(key),
+            },
             EventType::KeyRelease(key) => SerializableEventType::KeyRelease(key),
             EventType::ButtonPress(btn) => SerializableEventType::ButtonPress(btn),
             EventType::ButtonRelease(btn) => SerializableEventType::ButtonRelease(btn),
-            EventType::MouseMove { x, y } => SerializableEventType::MouseMove { x, y },
-            EventType::Wheel { delta_x, delta_y } => SerializableEventType::Wheel { delta_x, delta_y },
+            EventType::MouseMove { x, y } => {
+                // Always store points so recordings replay correctly when scaling changes
+                let scale = display::native_units_per_point(displays);
+                let (x, y) = (x / scale, y / scale);
+                SerializableEventType::MouseMove {
+                    x,
+                    y,
+                    display: display::to_display_point(displays, x, y),
+                }
+            }
+            EventType::Wheel { delta_x, delta_y } => match scroll {
+                Some(ScrollDetails { continuous: true, delta_x, delta_y, phase }) => SerializableEventType::SmoothWheel { delta_x, delta_y, phase },
+                _ => SerializableEventType::Wheel { delta_x, delta_y },
+            },
         };
-        Some(Self {
+        let mut event = Self {
             event_type,
-            delay_ms,
-        })
+            delay_ms: 0,
+            delay_us: 0,
+        };
+        event.set_delay(delay);
+        Some(event)
     }
 
-    pub fn to_rdev(&self) -> EventType {
-        match self.event_type {
-            SerializableEventType::KeyPress(key) => EventType::KeyPress(key),
-            SerializableEventType::KeyRelease(key) => EventType::KeyRelease(key),
-            SerializableEventType::ButtonPress(btn) => EventType::ButtonPress(btn),
-            SerializableEventType::ButtonRelease(btn) => EventType::ButtonRelease(btn),
-            SerializableEventType::MouseMove { x, y } => EventType::MouseMove { x, y },
-            SerializableEventType::Wheel { delta_x, delta_y } => EventType::Wheel { delta_x, delta_y },
+    /// Text this event should type in place of its key, given how keys are
+    /// being replayed.
+    pub fn replay_text(&self, mode: KeyReplay) -> Option<&str> {
+        match &self.event_type {
+            SerializableEventType::KeyPressText { text, .. } => match mode {
+                KeyReplay::Auto if is_layout_text(text) => Some(text),
+                KeyReplay::Characters => Some(text),
+                _ => None,
+            },
+            _ => None,
         }
     }
+
+    /// The input event to simulate, with keys, buttons and scrolling changed
+    /// as `remap` asks, or `None` for events that restore state rather than
+    /// inject input.
+    pub fn to_rdev(&self, mapper: &DisplayMapper, remap: &Remap) -> Option<EventType> {
+        let event_type = match self.event_type {
+            SerializableEventType::KeyPress(key) => EventType::KeyPress(remap.key(key)),
+            SerializableEventType::KeyPressText { key, .. } => EventType::KeyPress(remap.key(key)),
+            SerializableEventType::KeyRelease(key) => EventType::KeyRelease(remap.key(key)),
+            SerializableEventType::ButtonPress(btn) => EventType::ButtonPress(remap.button(btn)),
+            SerializableEventType::ButtonRelease(btn) => EventType::ButtonRelease(remap.button(btn)),
+            SerializableEventType::MouseMove { x, y, display } => {
+                let (x, y) = mapper.map_point(x, y, display.as_ref());
+                EventType::MouseMove { x, y }
+            }
+            SerializableEventType::Wheel { delta_x, delta_y } => {
+                let (delta_x, delta_y) = remap.wheel(delta_x, delta_y);
+                EventType::Wheel { delta_x, delta_y }
+            }
+            SerializableEventType::SmoothWheel { delta_x, delta_y, .. } => {
+                let (delta_x, delta_y) = remap.wheel(delta_x, delta_y);
+                EventType::Wheel {
+                    delta_x: delta_x.round() as i64,
+                    delta_y: delta_y.round() as i64,
+                }
+            }
+            SerializableEventType::Clipboard { .. }
+            | SerializableEventType::AssertPixelColor { .. }
+            | SerializableEventType::AssertClipboardContains { .. }
+            | SerializableEventType::AssertFrontmostApp { .. }
+            | SerializableEventType::WaitForApp { .. }
+            | SerializableEventType::Hold { .. }
+            | SerializableEventType::Checkpoint { .. }
+            | SerializableEventType::Segment { .. } => return None,
+        };
+        Some(event_type)
+    }
+}
+
+/// Text that typing its key may not reproduce on another layout or input
+/// state: non-ASCII characters, or nothing at all from a dead key.
+fn is_layout_text(text: &str) -> bool {
+    text.is_empty() || !text.is_ascii()
+}
+
+fn default_wait_timeout_ms() -> u64 {
+    10_000
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
 }
@@ -0,0 +1,74 @@
+use crate::platform::MissingPermissions;
+use crate::play::PlaybackOutcome;
+use anyhow::Result;
+use serde::Serialize;
+use std::time::Duration;
+
+/// How a `macro play`, `record` or `chain` run ended, each with its own
+/// exit code so wrapper scripts can tell them apart. 2 is left to usage
+/// errors, which the argument parser exits with.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitStatus {
+    /// Exit code 0.
+    Success,
+    /// Exit code 1, for anything not covered below.
+    Error,
+    /// Exit code 3: the stop hotkey ended playback early.
+    Stopped,
+    /// Exit code 4: input capture or simulation is not allowed.
+    PermissionDenied,
+    /// Exit code 5: a recording could not be read or written.
+    FileError,
+    /// Exit code 6: playback failed, e.g. an assertion did not hold.
+    PlaybackError,
+}
+
+impl ExitStatus {
+    pub fn code(self) -> i32 {
+        match self {
+            ExitStatus::Success => 0,
+            ExitStatus::Error => 1,
+            ExitStatus::Stopped => 3,
+            ExitStatus::PermissionDenied => 4,
+            ExitStatus::FileError => 5,
+            ExitStatus::PlaybackError => 6,
+        }
+    }
+
+    /// Status of a run that ended with `result`. Errors that are not about
+    /// permissions or files get `failure`.
+    pub fn of(result: &Result<PlaybackOutcome>, failure: ExitStatus) -> Self {
+        match result {
+            Ok(PlaybackOutcome::Stopped) => ExitStatus::Stopped,
+            Ok(_) => ExitStatus::Success,
+            Err(e) if e.chain().any(|cause| cause.is::<MissingPermissions>()) => ExitStatus::PermissionDenied,
+            Err(e) if e.chain().any(|cause| cause.is::<std::io::Error>() || cause.is::<serde_json::Error>()) => ExitStatus::FileError,
+            Err(_) => failure,
+        }
+    }
+}
+
+/// Result of a run, printed as one JSON object on stdout at the end of it
+/// when asked for with `--json-result`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RunResult {
+    pub status: ExitStatus,
+    pub exit_code: i32,
+    /// Seconds from start to finish.
+    pub duration: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RunResult {
+    pub fn new(result: &Result<PlaybackOutcome>, failure: ExitStatus, duration: Duration) -> Self {
+        let status = ExitStatus::of(result, failure);
+        RunResult {
+            status,
+            exit_code: status.code(),
+            duration: duration.as_secs_f64(),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        }
+    }
+}
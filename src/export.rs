@@ -0,0 +1,191 @@
+use crate::event::SerializableEventType;
+use crate::recording::Recording;
+use anyhow::Result;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// One row per event: timestamp, event type, key/button and coordinates.
+    #[default]
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            other => Err(format!("expected 'csv', got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+const CSV_HEADER: &str = "timestamp_ms,event,key_button,x,y,delta_x,delta_y,text";
+
+/// Writes `recording` as CSV, with timestamps in milliseconds from the start
+/// of the recording.
+pub fn write_csv(recording: &Recording, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "{}", CSV_HEADER)?;
+    let mut timestamp = Duration::ZERO;
+    for event in &recording.events {
+        timestamp += event.delay();
+        let row = CsvRow::from(&event.event_type);
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            timestamp.as_micros() as f64 / 1000.0,
+            row.event,
+            row.key_button,
+            row.x,
+            row.y,
+            row.delta_x,
+            row.delta_y,
+            csv_field(&row.text)
+        )?;
+    }
+    Ok(())
+}
+
+/// Columns of one event, left empty where they do not apply.
+#[derive(Default)]
+struct CsvRow {
+    event: &'static str,
+    key_button: String,
+    x: String,
+    y: String,
+    delta_x: String,
+    delta_y: String,
+    text: String,
+}
+
+impl From<&SerializableEventType> for CsvRow {
+    fn from(event_type: &SerializableEventType) -> Self {
+        match event_type {
+            SerializableEventType::KeyPress(key) => Self {
+                event: "KeyPress",
+                key_button: format!("{:?}", key),
+                ..Default::default()
+            },
+            SerializableEventType::KeyRelease(key) => Self {
+                event: "KeyRelease",
+                key_button: format!("{:?}", key),
+                ..Default::default()
+            },
+            SerializableEventType::KeyPressText { key, text } => Self {
+                event: "KeyPress",
+                key_button: format!("{:?}", key),
+                text: text.clone(),
+                ..Default::default()
+            },
+            SerializableEventType::ButtonPress(button) => Self {
+                event: "ButtonPress",
+                key_button: format!("{:?}", button),
+                ..Default::default()
+            },
+            SerializableEventType::ButtonRelease(button) => Self {
+                event: "ButtonRelease",
+                key_button: format!("{:?}", button),
+                ..Default::default()
+            },
+            SerializableEventType::MouseMove { x, y, .. } => Self {
+                event: "MouseMove",
+                x: x.to_string(),
+                y: y.to_string(),
+                ..Default::default()
+            },
+            SerializableEventType::Wheel { delta_x, delta_y } => Self {
+                event: "Wheel",
+                delta_x: delta_x.to_string(),
+                delta_y: delta_y.to_string(),
+                ..Default::default()
+            },
+            SerializableEventType::SmoothWheel { delta_x, delta_y, .. } => Self {
+                event: "SmoothWheel",
+                delta_x: delta_x.to_string(),
+                delta_y: delta_y.to_string(),
+                ..Default::default()
+            },
+            SerializableEventType::Clipboard { text } => Self {
+                event: "Clipboard",
+                text: text.clone(),
+                ..Default::default()
+            },
+            SerializableEventType::AssertPixelColor { x, y, color, .. } => Self {
+                event: "AssertPixelColor",
+                x: x.to_string(),
+                y: y.to_string(),
+                text: format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2]),
+                ..Default::default()
+            },
+            SerializableEventType::AssertClipboardContains { text } => Self {
+                event: "AssertClipboardContains",
+                text: text.clone(),
+                ..Default::default()
+            },
+            SerializableEventType::AssertFrontmostApp { name } => Self {
+                event: "AssertFrontmostApp",
+                text: name.clone(),
+                ..Default::default()
+            },
+            SerializableEventType::WaitForApp { app, .. } => Self {
+                event: "WaitForApp",
+                text: app.clone(),
+                ..Default::default()
+            },
+            SerializableEventType::Hold { key, duration_ms, .. } => Self {
+                event: "Hold",
+                key_button: format!("{:?}", key),
+                text: format!("{}ms", duration_ms),
+                ..Default::default()
+            },
+            SerializableEventType::Checkpoint { name } => Self {
+                event: "Checkpoint",
+                text: name.clone(),
+                ..Default::default()
+            },
+            SerializableEventType::Segment { name } => Self {
+                event: "Segment",
+                text: name.clone(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Quotes a field when it contains characters that are special in CSV.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Exports the recording at `input` to `output`, or to stdout for `-`.
+pub fn run_export(input: &Path, output: &Path, format: ExportFormat) -> Result<()> {
+    let recording = Recording::load(input)?;
+    let mut out: Box<dyn Write> = if output == Path::new("-") {
+        Box::new(io::stdout().lock())
+    } else {
+        Box::new(BufWriter::new(File::create(output)?))
+    };
+    match format {
+        ExportFormat::Csv => write_csv(&recording, &mut out)?,
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use crate::platform;
+use crate::play::PlaybackOutcome;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Whether a run made a recording or played one.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RunKind {
+    Record,
+    Play,
+}
+
+impl fmt::Display for RunKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunKind::Record => write!(f, "record"),
+            RunKind::Play => write!(f, "play"),
+        }
+    }
+}
+
+/// One recording or playback run, as kept in the history file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub kind: RunKind,
+    pub started: DateTime<Local>,
+    /// Seconds from start to finish.
+    pub duration: f64,
+    /// Recording that was made or played, if it is a file.
+    pub path: Option<PathBuf>,
+    /// Recordings are `completed` once saved, `stopped` if discarded, or
+    /// `failed`.
+    pub outcome: PlaybackOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Options the run used, as command line flags.
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+/// Times a run from when it starts, for its history entry.
+pub struct RunTimer {
+    kind: RunKind,
+    path: Option<PathBuf>,
+    options: Vec<String>,
+    started: DateTime<Local>,
+    start: Instant,
+}
+
+impl RunTimer {
+    pub fn start(kind: RunKind, path: Option<&Path>, options: Vec<String>) -> Self {
+        Self {
+            kind,
+            path: path.map(|path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())),
+            options,
+            started: Local::now(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Entry for the run ending now with `result`. A successful run was
+    /// `stopped` early or else completed.
+    pub fn finish<T>(self, result: &Result<T>, stopped: bool) -> HistoryEntry {
+        let (outcome, error) = match result {
+            Err(e) => (PlaybackOutcome::Failed, Some(format!("{:#}", e))),
+            Ok(_) if stopped => (PlaybackOutcome::Stopped, None),
+            Ok(_) => (PlaybackOutcome::Completed, None),
+        };
+        HistoryEntry {
+            kind: self.kind,
+            started: self.started,
+            duration: self.start.elapsed().as_secs_f64(),
+            path: self.path,
+            outcome,
+            error,
+            options: self.options,
+        }
+    }
+}
+
+/// File the command line and the menu bar app keep their history in.
+pub fn default_path() -> PathBuf {
+    platform::config_dir().join("history.jsonl")
+}
+
+/// Adds `entry` as a line at the end of the history file at `path`.
+pub fn append(path: &Path, entry: &HistoryEntry) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(&line)?;
+    Ok(())
+}
+
+/// Adds `entry` to the default history file. A run is never failed for
+/// want of a history, so errors are only logged.
+pub fn log(entry: &HistoryEntry) {
+    let path = default_path();
+    if let Err(e) = append(&path, entry) {
+        tracing::warn!("Could not add the run to the history in {:?}: {:?}", path, e);
+    }
+}
+
+/// Entries in the history file at `path`, oldest first. A missing file is
+/// an empty history, and unreadable lines are skipped.
+pub fn load(path: &Path) -> Result<Vec<HistoryEntry>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut entries = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => tracing::warn!("Skipping line {} of {:?}: {}", number + 1, path, e),
+        }
+    }
+    Ok(entries)
+}
+
+/// One line describing `entry`, for listings.
+pub fn describe(entry: &HistoryEntry) -> String {
+    let path = entry.path.as_ref().map_or_else(|| "-".to_string(), |path| path.display().to_string());
+    let duration = humantime::format_duration(Duration::from_secs(entry.duration.round() as u64));
+    format!(
+        "{}  {:<6} {:<9} {:>8}  {}",
+        entry.started.format("%Y-%m-%d %H:%M:%S"),
+        entry.kind,
+        entry.outcome,
+        duration.to_string(),
+        path
+    )
+}
+
+/// Prints the last `limit` runs in the history file at `path` (all of them
+/// for 0), leaving out those that started more than `since` ago.
+pub fn run_history(path: &Path, limit: usize, since: Option<Duration>, json: bool) -> Result<()> {
+    let mut entries = load(path)?;
+    if let Some(since) = since {
+        let cutoff = Local::now() - chrono::Duration::from_std(since)?;
+        entries.retain(|entry| entry.started >= cutoff);
+    }
+    if limit > 0 && entries.len() > limit {
+        entries.drain(..entries.len() - limit);
+    }
+
+    if json {
+        for entry in &entries {
+            println!("{}", serde_json::to_string(entry)?);
+        }
+        return Ok(());
+    }
+    if entries.is_empty() {
+        println!("No runs in {}.", path.display());
+        return Ok(());
+    }
+    for entry in &entries {
+        println!("{}", describe(entry));
+        if !entry.options.is_empty() {
+            println!("    options: {}", entry.options.join(" "));
+        }
+        if let Some(error) = &entry.error {
+            println!("    error:   {}", error);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use crate::event::SerializableEventType;
+use crate::recording::Recording;
+use anyhow::Result;
+use std::path::Path;
+use std::time::Duration;
+
+/// A checkpoint event and where it falls in its recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub name: String,
+    /// Index of the checkpoint event.
+    pub index: usize,
+    /// Time from the start of the recording.
+    pub at: Duration,
+}
+
+/// Checkpoints in the order they appear in `recording`.
+pub fn checkpoints(recording: &Recording) -> Vec<Checkpoint> {
+    let mut at = Duration::ZERO;
+    let mut checkpoints = Vec::new();
+    for (index, event) in recording.events.iter().enumerate() {
+        at += event.delay();
+        if let SerializableEventType::Checkpoint { name } = &event.event_type {
+            checkpoints.push(Checkpoint { name: name.clone(), index, at });
+        }
+    }
+    checkpoints
+}
+
+/// Prints a summary of the recording at `input` and its checkpoints.
+pub fn run_inspect(input: &Path) -> Result<()> {
+    let recording = Recording::load(input)?;
+    let duration: Duration = recording.events.iter().map(|e| e.delay()).sum();
+    println!("{}", input.display());
+    println!("  version:      {}", recording.metadata.version);
+    println!("  events:       {}", recording.events.len());
+    println!("  duration:     {:.3}s", duration.as_secs_f64());
+    println!("  scale factor: {}", recording.metadata.scale_factor);
+    println!("  coordinates:  {:?}", recording.metadata.coordinate_space);
+    println!("  displays:     {}", recording.metadata.displays.len());
+    if let Some(environment) = &recording.metadata.environment {
+        println!("  os:           {} {}", environment.os, environment.os_version.as_deref().unwrap_or_default());
+        if let Some(layout) = &environment.keyboard_layout {
+            println!("  keyboard:     {}", layout);
+        }
+        if let Some(app) = &environment.frontmost_app {
+            println!("  frontmost:    {}", app);
+        }
+    }
+    let segments = recording.segments();
+    if !segments.is_empty() {
+        println!("  segments:     {}", segments.join(", "));
+    }
+
+    let checkpoints = checkpoints(&recording);
+    if checkpoints.is_empty() {
+        println!("No checkpoints.");
+    } else {
+        println!("Checkpoints:");
+        for checkpoint in &checkpoints {
+            println!("  {:>9.3}s  #{:<6} {}", checkpoint.at.as_secs_f64(), checkpoint.index, checkpoint.name);
+        }
+    }
+    Ok(())
+}
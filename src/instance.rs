@@ -0,0 +1,58 @@
+use anyhow::Result;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+/// Local port the running menu bar app holds, so a second copy can tell it
+/// is not alone. Binding fails for everyone but the first.
+const INSTANCE_PORT: u16 = 47_862;
+
+/// What the holder of the port answers with, to tell it apart from some
+/// other program that happens to use the same port.
+const GREETING: &str = "macro";
+
+/// Proof that this is the running instance of the menu bar app. The port
+/// stays claimed until the process exits.
+pub struct InstanceLock {
+    _listener: TcpListener,
+}
+
+/// Claims the single instance, or returns `None` if another copy of the
+/// menu bar app already has it. Fails if some other program holds the port.
+pub fn acquire() -> Result<Option<InstanceLock>> {
+    acquire_on(INSTANCE_PORT)
+}
+
+/// [`acquire`] with the lock on `port`.
+pub fn acquire_on(port: u16) -> Result<Option<InstanceLock>> {
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+        Ok(listener) => listener,
+        Err(_) if is_running_on(port) => return Ok(None),
+        Err(e) => anyhow::bail!("Could not claim port {} to check for other instances: {}", port, e),
+    };
+    let greeter = listener.try_clone()?;
+    thread::spawn(move || {
+        for mut stream in greeter.incoming().flatten() {
+            let _ = writeln!(stream, "{} {}", GREETING, env!("CARGO_PKG_VERSION"));
+        }
+    });
+    Ok(Some(InstanceLock { _listener: listener }))
+}
+
+/// Whether a menu bar app is running, whose hotkeys and input capture would
+/// clash with recording or playing from the command line.
+pub fn is_running() -> bool {
+    is_running_on(INSTANCE_PORT)
+}
+
+/// [`is_running`] with the lock on `port`.
+pub fn is_running_on(port: u16) -> bool {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let Ok(stream) = TcpStream::connect_timeout(&addr, Duration::from_millis(200)) else {
+        return false;
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_millis(200)));
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).is_ok() && line.starts_with(GREETING)
+}
@@ -0,0 +1,161 @@
+use crate::event::{SerializableEvent, SerializableEventType};
+use crate::platform;
+use crate::recording::Recording;
+use rdev::Key;
+use std::time::Duration;
+
+/// How a held key repeats: the wait before the first repeat and the time
+/// between the ones after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyRepeat {
+    pub delay: Duration,
+    pub interval: Duration,
+}
+
+impl Default for KeyRepeat {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_millis(500),
+            interval: Duration::from_millis(33),
+        }
+    }
+}
+
+impl KeyRepeat {
+    /// This machine's key repeat settings, or the defaults where they
+    /// cannot be found out.
+    pub fn current() -> Self {
+        platform::key_repeat().map_or_else(Self::default, |(delay, interval)| Self { delay, interval })
+    }
+}
+
+fn pressed_key(event_type: &SerializableEventType) -> Option<(Key, Option<&str>)> {
+    match event_type {
+        SerializableEventType::KeyPress(key) => Some((*key, None)),
+        SerializableEventType::KeyPressText { key, text } => Some((*key, Some(text))),
+        _ => None,
+    }
+}
+
+/// Replaces each key that auto-repeated while held, seen as presses of it
+/// with no release in between, by a single [`SerializableEventType::Hold`]
+/// lasting until its release. Keys pressed once, and keys never released,
+/// are left as they are. The delays of dropped events are handed on to
+/// the next kept one, so everything else keeps its timing.
+pub fn collapse(events: &[SerializableEvent]) -> Vec<SerializableEvent> {
+    let mut at = Duration::ZERO;
+    let times: Vec<Duration> = events
+        .iter()
+        .map(|event| {
+            at += event.delay();
+            at
+        })
+        .collect();
+    let mut dropped = vec![false; events.len()];
+    let mut holds = vec![None; events.len()];
+    for index in 0..events.len() {
+        if dropped[index] {
+            continue;
+        }
+        let Some((key, text)) = pressed_key(&events[index].event_type) else {
+            continue;
+        };
+        let mut repeats = Vec::new();
+        let mut release = None;
+        for (later, event) in events.iter().enumerate().skip(index + 1) {
+            match &event.event_type {
+                SerializableEventType::KeyRelease(released) if *released == key => {
+                    release = Some(later);
+                    break;
+                }
+                event_type if pressed_key(event_type).is_some_and(|(pressed, _)| pressed == key) => repeats.push(later),
+                _ => {}
+            }
+        }
+        let Some(release) = release.filter(|_| !repeats.is_empty()) else {
+            continue;
+        };
+        for later in repeats.into_iter().chain([release]) {
+            dropped[later] = true;
+        }
+        holds[index] = Some(SerializableEventType::Hold {
+            key,
+            duration_ms: (times[release] - times[index]).as_millis() as u64,
+            text: text.map(str::to_string),
+        });
+    }
+
+    let mut collapsed = Vec::with_capacity(events.len());
+    let mut previous = Duration::ZERO;
+    for (index, event) in events.iter().enumerate() {
+        if dropped[index] {
+            continue;
+        }
+        let mut event = event.clone();
+        if let Some(hold) = holds[index].take() {
+            event.event_type = hold;
+        }
+        event.set_delay(times[index] - previous);
+        previous = times[index];
+        collapsed.push(event);
+    }
+    collapsed
+}
+
+/// The press, repeats and release a hold of `key` for `duration` stands
+/// for, each with its time from the start of the hold.
+pub fn hold_events(key: Key, text: Option<&str>, duration: Duration, repeat: KeyRepeat) -> Vec<(Duration, SerializableEventType)> {
+    let press = || match text {
+        Some(text) => SerializableEventType::KeyPressText { key, text: text.to_string() },
+        None => SerializableEventType::KeyPress(key),
+    };
+    let mut events = vec![(Duration::ZERO, press())];
+    let mut at = repeat.delay;
+    while at < duration && !repeat.interval.is_zero() {
+        events.push((at, press()));
+        at += repeat.interval;
+    }
+    events.push((duration, SerializableEventType::KeyRelease(key)));
+    events
+}
+
+pub fn has_holds(recording: &Recording) -> bool {
+    recording.events.iter().any(|event| matches!(event.event_type, SerializableEventType::Hold { .. }))
+}
+
+/// Turns every hold in `recording` back into a press, repeats at `repeat`
+/// and a release, in among the events that came while it was held.
+pub fn expand(recording: &Recording, repeat: KeyRepeat) -> Recording {
+    let mut timeline = Vec::with_capacity(recording.events.len());
+    let mut at = Duration::ZERO;
+    for event in &recording.events {
+        at += event.delay();
+        match &event.event_type {
+            SerializableEventType::Hold { key, duration_ms, text } => {
+                let duration = Duration::from_millis(*duration_ms);
+                timeline.extend(hold_events(*key, text.as_deref(), duration, repeat).into_iter().map(|(offset, event_type)| (at + offset, event_type)));
+            }
+            event_type => timeline.push((at, event_type.clone())),
+        }
+    }
+    // Stable, so events at the same time keep their order
+    timeline.sort_by_key(|(at, _)| *at);
+    let mut previous = Duration::ZERO;
+    let events = timeline
+        .into_iter()
+        .map(|(at, event_type)| {
+            let mut event = SerializableEvent {
+                event_type,
+                delay_ms: 0,
+                delay_us: 0,
+            };
+            event.set_delay(at - previous);
+            previous = at;
+            event
+        })
+        .collect();
+    Recording {
+        metadata: recording.metadata.clone(),
+        events,
+    }
+}
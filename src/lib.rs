@@ -1,4 +1,45 @@
+pub mod agent;
+#[cfg(feature = "async")]
+pub mod async_play;
+pub mod backend;
+pub mod bench;
+pub mod chain;
+pub mod clock;
 pub mod config;
+pub mod diff;
+pub mod display;
+pub mod environment;
 pub mod event;
+pub mod exit;
+pub mod export;
+pub mod history;
+pub mod inspect;
+pub mod instance;
+pub mod key_repeat;
+pub mod logging;
+pub mod midi;
+pub mod modifiers;
+pub mod optimize;
+pub mod own_ui;
+pub mod pack;
+pub mod platform;
 pub mod play;
 pub mod record;
+pub mod recording;
+pub mod recovery;
+pub mod remap;
+pub mod remote;
+pub mod repl;
+pub mod replace;
+pub mod retime;
+pub mod screenshot;
+pub mod serve;
+pub mod sound;
+pub mod update;
+pub mod worker;
+
+pub use play::{PlaybackControl, PlaybackOptions, PlaybackOutcome, PlaybackProgress, Player};
+#[cfg(feature = "async")]
+pub use async_play::PlaybackHandle;
+pub use record::{RecordOptions, Recorder, RecordingHandle, RecordingSession};
+pub use recording::Recording;
@@ -0,0 +1,157 @@
+use crate::platform;
+use anyhow::Result;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Size at which a log file is rotated.
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// Rotated log files kept next to the current one, as `<name>.1` (newest)
+/// up to `<name>.3`.
+const KEPT_LOGS: usize = 3;
+
+/// Default log file for the process named `process`, e.g. "app" for the
+/// menu bar app or "record" for `macro record`.
+pub fn default_path(process: &str) -> PathBuf {
+    platform::logs_dir().join(format!("{}.log", process))
+}
+
+/// Log file that starts over once it grows past a size, moving the old
+/// contents aside as `<name>.1`, `<name>.2` and so on.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    /// Appends to the file at `path`, rotating it once it passes `max_bytes`
+    /// and keeping `keep` rotated files.
+    pub fn open(path: &Path, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+        Ok(Self { path: path.to_path_buf(), max_bytes, keep, file, written })
+    }
+
+    /// Where the `n`th most recent rotated file is kept.
+    pub fn rotated_path(path: &Path, n: usize) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.keep == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            let _ = fs::remove_file(Self::rotated_path(&self.path, self.keep));
+            for n in (1..self.keep).rev() {
+                let _ = fs::rename(Self::rotated_path(&self.path, n), Self::rotated_path(&self.path, n + 1));
+            }
+            fs::rename(&self.path, Self::rotated_path(&self.path, 1))?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// How log lines are written: plain text, or one JSON object per line with
+/// the spans it happened in, for analyzing runs with other tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("expected 'text' or 'json', got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Layer writing to `writer` in `format`. Spans are logged as they close,
+/// with how long they were open.
+fn layer<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer).with_ansi(ansi).with_span_events(FmtSpan::CLOSE);
+    match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer.json().with_current_span(true).with_span_list(true).boxed(),
+    }
+}
+
+/// `RUST_LOG` if it is set, or else `default`.
+fn env_filter(default: &str) -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default))
+}
+
+/// Sets up logging in `format` to stderr as set by `RUST_LOG`, and to the
+/// log file at `path` at info level or whatever `RUST_LOG` asks for. The
+/// file is only a convenience, so if it cannot be opened logging carries
+/// on without it. Records from the `log` crate are picked up too.
+pub fn init(path: &Path, format: LogFormat) -> Result<()> {
+    let file = match RotatingFile::open(path, MAX_LOG_BYTES, KEPT_LOGS) {
+        Ok(file) => Some(layer(format, Mutex::new(file), false).with_filter(env_filter("info"))),
+        Err(e) => {
+            eprintln!("Could not open the log file {:?}: {}", path, e);
+            None
+        }
+    };
+    tracing_subscriber::registry()
+        .with(layer(format, io::stderr, true).with_filter(env_filter("error")))
+        .with(file)
+        .try_init()?;
+    Ok(())
+}
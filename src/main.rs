@@ -1,13 +1,31 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::NaiveTime;
 use clap::{Parser, Subcommand};
-use global_hotkey::GlobalHotKeyManager;
-use macro_lib::config;
-use macro_lib::{play, record};
-use std::path::PathBuf;
+use macro_lib::agent::CronSchedule;
+use macro_lib::backend;
+use macro_lib::clock::SystemClock;
+use macro_lib::config::{self, Preset};
+use macro_lib::display::{DisplayMapping, Transform};
+use macro_lib::event::KeyReplay;
+use macro_lib::exit::{ExitStatus, RunResult};
+use macro_lib::screenshot::{self, ScreenshotTrigger};
+use macro_lib::export::ExportFormat;
+use macro_lib::logging::LogFormat;
+use macro_lib::remap::{ButtonMapping, KeyMapping, Remap, WheelRemap};
+use macro_lib::replace::PointMapping;
+use macro_lib::retime::MoveTiming;
+use macro_lib::play::PlaybackOutcome;
+use macro_lib::{agent, bench, chain, diff, export, history, inspect, instance, logging, midi, optimize, pack, play, record, recording, remote, repl, replace, retime, serve};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tao::event_loop::{ControlFlow, EventLoopBuilder};
+#[cfg(target_os = "macos")]
 use tao::platform::macos::{ActivationPolicy, EventLoopExtMacOS};
 
 mod bar_app;
+mod overlay;
 use bar_app::{AppEvent, BarApp};
 
 #[derive(Parser)]
@@ -15,95 +33,537 @@ use bar_app::{AppEvent, BarApp};
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+    /// Write the log to this file instead of the default one for the command
+    /// in the logs folder, e.g. `record.log`
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+    /// Format of log lines: "text", or "json" for one object per line with
+    /// the record session, playback run and repeat it belongs to
+    #[arg(long, global = true, default_value = "text")]
+    log_format: LogFormat,
+    /// Use this config file instead of config.json in the config folder
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
+enum ConfigAction {
+    /// Report what is wrong with the config file, with the line a syntax error is on
+    Validate,
+    /// Print the settings in effect, with defaults filled in for those the file leaves out
+    Show,
+}
+
+#[derive(Subcommand)]
+// Parsed once per run, so Play's many flags are not worth boxing
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     /// Record mouse and keyboard events
     Record {
-        /// Output file path
+        /// Output file path, or "-" to stream events to stdout as JSON lines
         #[arg(default_value = "events.json")]
         output: PathBuf,
+        /// Stop and save automatically after this long, e.g. "30s" or "5m"
+        #[arg(long, value_parser = humantime::parse_duration)]
+        duration: Option<Duration>,
+        /// Cap any single pause between events at this length, e.g. "2s"
+        #[arg(long, value_parser = humantime::parse_duration)]
+        max_gap: Option<Duration>,
+        /// Keep at most one mouse move per interval, e.g. "50ms", for smaller recordings
+        #[arg(long, value_parser = humantime::parse_duration)]
+        move_interval: Option<Duration>,
+        /// Overwrite the output file if it already exists
+        #[arg(long, default_value_t = false)]
+        force: bool,
+        /// Save to the next free numbered name (e.g. events-2.json) if the output file exists
+        #[arg(long, default_value_t = false, conflicts_with = "force")]
+        auto_number: bool,
+        /// Add to the end of the output file as a new segment instead of replacing it
+        #[arg(long, default_value_t = false, conflicts_with_all = ["force", "auto_number", "buffer"])]
+        append: bool,
+        /// Name of the segment added with --append. Defaults to "segment-<n>"
+        #[arg(long, requires = "append")]
+        segment: Option<String>,
+        /// Also record the character each key typed, for `play --key-replay characters`
+        #[arg(long, default_value_t = false)]
+        capture_text: bool,
+        /// Save the clipboard text at each paste and restore it on playback
+        #[arg(long, default_value_t = false)]
+        capture_clipboard: bool,
+        /// Save keys held until they auto-repeat as single holds, repeated on playback at that machine's key repeat rate
+        #[arg(long, default_value_t = false)]
+        collapse_key_repeat: bool,
+        /// Stop and save automatically once this many events have been recorded
+        #[arg(long, conflicts_with = "buffer")]
+        max_events: Option<usize>,
+        /// Stop and save automatically once the recording reaches this size, e.g. "500KB" or "10MB"
+        #[arg(long, value_parser = record::parse_size, conflicts_with = "buffer")]
+        max_size: Option<u64>,
+        /// Save screenshots next to the recording at these moments: "click", "hotkey"
+        #[arg(long, value_delimiter = ',')]
+        screenshots: Vec<ScreenshotTrigger>,
+        /// Save a screenshot as recording starts, to tell recordings apart later
+        #[arg(long, default_value_t = false)]
+        thumbnail: bool,
+        /// Keep only the last stretch of input this long, e.g. "30s", saving it whenever the save buffer hotkey is pressed
+        #[arg(long, value_parser = humantime::parse_duration)]
+        buffer: Option<Duration>,
+        /// Save a JSON recording while it is being made, every this many events (e.g. "500") or this long (e.g. "10s")
+        #[arg(long)]
+        autosave_interval: Option<record::AutosaveInterval>,
+        /// What the undo hotkey takes back: this much time, e.g. "10s", or "checkpoint" for everything since the last checkpoint
+        #[arg(long, default_value = "5s")]
+        undo: record::UndoScope,
+        /// Print how the run ended as a JSON object on stdout, e.g. {"status":"stopped","exit_code":3,"duration":4.2}
+        #[arg(long, default_value_t = false)]
+        json_result: bool,
         /// Internal flag to start recording immediately without waiting for hotkey
         #[arg(long, default_value_t = false, hide = true)]
         immediate: bool,
     },
     /// Play back recorded events
     Play {
-        /// Input file path
+        /// Input file path, or "-" to play JSON lines from stdin
         #[arg(default_value = "events.json")]
         input: PathBuf,
-        /// Playback speed factor (e.g., 2.0 for 2x speed, 0.5 for half speed)
+        /// Playback speed factor (e.g., 2.0 for 2x speed, 0.5 for half speed).
+        /// Defaults to the speed in the config file
+        #[arg(long)]
+        speed: Option<f64>,
+        /// Extra speed factor for mouse moves, clicks and scrolls, on top of --speed
         #[arg(long, default_value_t = 1.0)]
-        speed: f64,
-        /// Number of times to repeat playback (0 for infinite)
-        #[arg(long, default_value_t = 1)]
-        repeat_count: u32,
-        /// Interval between repeats in seconds
-        #[arg(long, default_value_t = 0.0)]
-        repeat_interval: f64,
+        mouse_speed: f64,
+        /// Extra speed factor for key presses, on top of --speed. Lower it to keep typing realistic
+        #[arg(long, default_value_t = 1.0)]
+        keyboard_speed: f64,
+        /// Number of times to repeat playback (0 for infinite). Defaults to 1,
+        /// or infinite when --repeat-for or --repeat-until is given
+        #[arg(long)]
+        repeat_count: Option<u32>,
+        /// Interval between repeats in seconds. Defaults to 0
+        #[arg(long)]
+        repeat_interval: Option<f64>,
+        /// Use the speed and repeat settings of this preset from the config file, e.g. "overnight". Flags given too win over it
+        #[arg(long)]
+        preset: Option<String>,
+        /// Keep repeating for this long, e.g. "2h" or "30m"
+        #[arg(long, value_parser = humantime::parse_duration)]
+        repeat_for: Option<Duration>,
+        /// Keep repeating until this local time of day, e.g. "17:00"
+        #[arg(long, value_parser = play::parse_time_of_day)]
+        repeat_until: Option<NaiveTime>,
+        /// Safety limit: fail playback once it has run this long, e.g. "8h"
+        #[arg(long, value_parser = humantime::parse_duration)]
+        max_total_duration: Option<Duration>,
+        /// Safety limit: fail playback rather than start more repeats than this
+        #[arg(long)]
+        max_total_repeats: Option<u32>,
+        /// Health check: fail playback once this many events in a row could not be sent
+        #[arg(long)]
+        max_consecutive_failures: Option<u32>,
+        /// Refuse to play a recording made with another OS version, keyboard layout or display arrangement, instead of only warning
+        #[arg(long, default_value_t = false)]
+        strict: bool,
+        /// Display to replay mouse events on: "same", "primary", or a display id
+        #[arg(long, default_value_t = DisplayMapping::Same)]
+        display_mapping: DisplayMapping,
+        /// Mirror or rotate mouse positions about the center of their display:
+        /// "mirror-x", "mirror-y", "rotate-90", "rotate-180" or "rotate-270"
+        #[arg(long, default_value_t = Transform::None)]
+        transform: Transform,
+        /// Move mouse positions right by this many points (negative for left)
+        #[arg(long, allow_hyphen_values = true, default_value_t = 0.0)]
+        offset_x: f64,
+        /// Move mouse positions down by this many points (negative for up)
+        #[arg(long, allow_hyphen_values = true, default_value_t = 0.0)]
+        offset_y: f64,
+        /// Replay keys as "physical" keys, as recorded "characters" (layout independent), or "auto"
+        #[arg(long, default_value_t = KeyReplay::Auto)]
+        key_replay: KeyReplay,
+        /// Save a screenshot when an assertion fails, next to the recording unless DIR is given
+        #[arg(long, num_args = 0..=1, value_name = "DIR")]
+        screenshot_on_failure: Option<Option<PathBuf>>,
+        /// Start at this event index, or at this time into the recording, e.g. "120" or "1m30s"
+        #[arg(long)]
+        from: Option<play::PlaybackPosition>,
+        /// Stop after this event index, or at this time into the recording
+        #[arg(long)]
+        to: Option<play::PlaybackPosition>,
+        /// Start at the checkpoint with this name, e.g. "login"
+        #[arg(long, conflicts_with = "from")]
+        from_checkpoint: Option<String>,
+        /// Stop after the checkpoint with this name
+        #[arg(long, conflicts_with = "to")]
+        to_checkpoint: Option<String>,
+        /// Play only the segment with this name, e.g. "setup"
+        #[arg(long, conflicts_with_all = ["from", "to", "from_checkpoint", "to_checkpoint"])]
+        segment: Option<String>,
+        /// Wait this long before playing, e.g. "3s", to switch to the target window. Defaults to 0
+        #[arg(long, value_parser = humantime::parse_duration)]
+        countdown: Option<Duration>,
+        /// Move each event up to this much earlier or later at random, e.g. "30ms"
+        #[arg(long, value_parser = humantime::parse_duration, default_value = "0s")]
+        humanize: Duration,
+        /// How long keys and buttons stay down: "scaled" by --speed, as "recorded" at any speed, or at least this long, e.g. "30ms"
+        #[arg(long, default_value_t = play::HoldTiming::Scaled)]
+        hold: play::HoldTiming,
+        /// Play one key as another, e.g. "A=B" or "ShiftLeft=ControlLeft". Can be given more than once
+        #[arg(long, value_name = "FROM=TO")]
+        remap_key: Vec<KeyMapping>,
+        /// Play one mouse button as another, e.g. "Right=Left" for flipped buttons. Can be given more than once
+        #[arg(long, value_name = "FROM=TO")]
+        remap_button: Vec<ButtonMapping>,
+        /// Scroll as recorded ("same"), the other way ("invert") or on the other axis ("swap")
+        #[arg(long, default_value_t = WheelRemap::Same)]
+        remap_wheel: WheelRemap,
+        /// Print progress, a heartbeat every second, errors and the outcome as JSON lines on stdout
+        #[arg(long, default_value_t = false)]
+        json_progress: bool,
+        /// Print how the run ended as a JSON object on stdout, e.g. {"status":"stopped","exit_code":3,"duration":4.2}
+        #[arg(long, default_value_t = false)]
+        json_result: bool,
+        /// Play this recording at the same time, e.g. a keyboard-only one next to a mouse-only one. Can be given more than once
+        #[arg(long, value_name = "RECORDING")]
+        parallel: Vec<PathBuf>,
         /// Internal flag to start playback immediately without waiting for hotkey
         #[arg(long, default_value_t = false, hide = true)]
         immediate: bool,
     },
+    /// Compare two recordings, exiting with status 1 if they differ
+    Diff {
+        /// Original recording
+        left: PathBuf,
+        /// Recording to compare against it
+        right: PathBuf,
+        /// Compare mouse moves too, not just clicks, keys and scrolls
+        #[arg(long, default_value_t = false)]
+        moves: bool,
+        /// Report matching events whose timing differs by more than this, e.g. "250ms"
+        #[arg(long, value_parser = humantime::parse_duration, default_value = "100ms")]
+        timing_tolerance: Duration,
+    },
+    /// Convert a recording for use in other tools
+    Export {
+        /// Recording to export
+        input: PathBuf,
+        /// Output format
+        #[arg(long, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// Output file path, or "-" for stdout
+        #[arg(long, short, default_value = "-")]
+        output: PathBuf,
+    },
+    /// Shrink a recording: simplify mouse paths, drop redundant events and round delays
+    Optimize {
+        /// Recording to optimize
+        input: PathBuf,
+        /// Where to save the optimized recording. Defaults to overwriting the input
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Drop mouse moves within this many points of the simplified path
+        #[arg(long, default_value_t = 1.0)]
+        tolerance: f64,
+        /// Round event times to a multiple of this, e.g. "10ms", or "0ms" to keep them
+        #[arg(long, value_parser = humantime::parse_duration, default_value = "1ms")]
+        round_delays: Duration,
+    },
+    /// Swap keys or move clicks across a recording, e.g. after a button of the target app moved
+    Replace {
+        /// Recording to change
+        input: PathBuf,
+        /// Where to save the changed recording. Defaults to overwriting the input
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Change every press and release of one key to another, e.g. "F13=F5". Can be given more than once
+        #[arg(long, value_name = "FROM=TO")]
+        key: Vec<KeyMapping>,
+        /// Move clicks made at one point to another, e.g. "1200,800=900,600". Can be given more than once
+        #[arg(long, value_name = "FROM=TO")]
+        point: Vec<PointMapping>,
+        /// How far from a --point a click can be and still be moved
+        #[arg(long, default_value_t = 2.0)]
+        tolerance: f64,
+    },
+    /// Even out a recording's delays by kind of event, e.g. steady typing and click spacing
+    Retime {
+        /// Recording to retime
+        input: PathBuf,
+        /// Where to save the retimed recording. Defaults to overwriting the input
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Time between key presses and releases while typing, e.g. "60ms"
+        #[arg(long, value_parser = humantime::parse_duration)]
+        typing: Option<Duration>,
+        /// Time before each mouse button press and release, e.g. "150ms"
+        #[arg(long, value_parser = humantime::parse_duration)]
+        clicks: Option<Duration>,
+        /// Timing of mouse moves: "keep", "natural" to take longer the further the mouse goes, or a time between moves like "10ms"
+        #[arg(long, default_value_t = MoveTiming::Keep)]
+        moves: MoveTiming,
+        /// Leave delays longer than this as recorded, as waits for an app to respond. "0s" retimes them all
+        #[arg(long, value_parser = humantime::parse_duration, default_value = "1s")]
+        keep_pauses: Duration,
+    },
+    /// Bundle a recording, its screenshots and playback settings into one
+    /// `.macro` file to share, which `play` and the other commands open directly
+    Pack {
+        /// Recording to pack
+        input: PathBuf,
+        /// Where to save the package. Defaults to next to the recording, with the `.macro` extension
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Play the package with the speed and repeat settings of this preset from the config file unless others are given
+        #[arg(long)]
+        preset: Option<String>,
+    },
+    /// Save the playback settings a recording is played with unless others
+    /// are given, or show them when no settings are given
+    Defaults {
+        /// Recording to change
+        input: PathBuf,
+        /// Playback speed factor
+        #[arg(long)]
+        speed: Option<f64>,
+        /// Number of times to play (0 for infinite)
+        #[arg(long)]
+        repeat_count: Option<u32>,
+        /// Interval between repeats in seconds
+        #[arg(long)]
+        repeat_interval: Option<f64>,
+        /// Wait this long before playing, e.g. "3s"
+        #[arg(long, value_parser = humantime::parse_duration)]
+        countdown: Option<Duration>,
+        /// Start from the settings of this preset from the config file, e.g. "overnight"
+        #[arg(long)]
+        preset: Option<String>,
+        /// Remove the saved settings
+        #[arg(long, conflicts_with_all = ["speed", "repeat_count", "repeat_interval", "countdown", "preset"])]
+        clear: bool,
+    },
+    /// Serve an HTTP API for listing recordings and controlling playback and recording
+    Serve {
+        /// Port to listen on
+        #[arg(long, default_value_t = 8787)]
+        port: u16,
+        /// Address to listen on. Use 0.0.0.0 to accept requests from other machines
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: IpAddr,
+    },
+    /// Capture input and stream it live to a machine running `macro receive`
+    Send {
+        /// Address of the receiver, e.g. "office-mac.local:8788"
+        addr: String,
+        /// Keep at most one mouse move per interval, e.g. "20ms", to send less over slow links
+        #[arg(long, value_parser = humantime::parse_duration)]
+        move_interval: Option<Duration>,
+        /// Also send the character each key typed, for `receive --key-replay characters`
+        #[arg(long, default_value_t = false)]
+        capture_text: bool,
+    },
+    /// Simulate input streamed live from `macro send` on another machine
+    Receive {
+        /// Port to listen on
+        #[arg(long, default_value_t = 8788)]
+        port: u16,
+        /// Address to listen on. Use 0.0.0.0 to accept senders from other machines
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: IpAddr,
+        /// Display to replay mouse events on: "same", "primary", or a display id
+        #[arg(long, default_value_t = DisplayMapping::Same)]
+        display_mapping: DisplayMapping,
+        /// Replay keys as "physical" keys, as sent "characters" (layout independent), or "auto"
+        #[arg(long, default_value_t = KeyReplay::Auto)]
+        key_replay: KeyReplay,
+    },
+    /// Summarize a recording and list its checkpoints
+    Inspect {
+        /// Recording to inspect
+        input: PathBuf,
+    },
+    /// Type commands such as `click 100 200`, `type "hi"` or `play events.json`
+    /// at a prompt and have them run right away, to try out a macro step by step
+    Repl,
+    /// Play recordings when notes are pressed on a MIDI controller, as set up
+    /// in midi_triggers in the config file
+    Midi {
+        /// Part of the name of the MIDI input to listen to. Defaults to the first one
+        #[arg(long)]
+        port: Option<String>,
+    },
+    /// Check the config file or show the settings in effect
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Play the recordings of a chain file one after the other, following
+    /// each step's `on_failure` when it fails
+    Chain {
+        /// Chain file, e.g. {"steps": [{"recording": "a.json", "on_failure": "continue"}]}
+        input: PathBuf,
+        /// Playback speed factor for every step. Defaults to the speed in the config file
+        #[arg(long)]
+        speed: Option<f64>,
+        /// Wait this long before the first step, e.g. "3s", to switch to the target window
+        #[arg(long, value_parser = humantime::parse_duration, default_value = "0s")]
+        countdown: Duration,
+        /// Print how the run ended as a JSON object on stdout, e.g. {"status":"stopped","exit_code":3,"duration":4.2}
+        #[arg(long, default_value_t = false)]
+        json_result: bool,
+    },
+    /// Play a recording on a schedule with launchd (macOS), even after a
+    /// restart and without the menu bar app running
+    InstallAgent {
+        /// Recording to play
+        input: PathBuf,
+        /// When to play it, as a cron line, e.g. "30 9 * * 1-5" for 9:30 on weekdays
+        #[arg(long)]
+        cron: CronSchedule,
+        /// Options for `macro play`, after `--`, e.g. `-- --speed 2`
+        #[arg(last = true)]
+        play_args: Vec<String>,
+    },
+    /// Remove a scheduled recording installed with `install-agent`
+    UninstallAgent {
+        /// Name of the recording, or the agent's label from `list-agents`
+        name: String,
+    },
+    /// List the recordings scheduled with `install-agent`
+    ListAgents,
+    /// Measure input and timing latency on this machine, to tune playback
+    /// settings or look into recordings that replay too fast or too slow
+    Bench {
+        /// Measurements to take of each latency
+        #[arg(long, default_value_t = 50)]
+        samples: usize,
+        /// Print the report as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// List past recording and playback runs, most recent last
+    History {
+        /// Show at most this many runs, or 0 for all of them
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Only show runs started within this long, e.g. "12h"
+        #[arg(long, value_parser = humantime::parse_duration)]
+        since: Option<Duration>,
+        /// Print each run as a JSON line
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
 }
 
-fn main() -> Result<()> {
-    env_logger::init();
-    let args: Vec<String> = std::env::args().collect();
-    log::info!("Launched with args: {:?}", args);
-    let cli = Cli::parse();
+impl Commands {
+    /// Name the command's log file is given by default.
+    fn log_name(&self) -> &'static str {
+        match self {
+            Commands::Record { .. } => "record",
+            Commands::Play { .. } => "play",
+            Commands::Diff { .. } => "diff",
+            Commands::Export { .. } => "export",
+            Commands::Optimize { .. } => "optimize",
+            Commands::Replace { .. } => "replace",
+            Commands::Retime { .. } => "retime",
+            Commands::Pack { .. } => "pack",
+            Commands::Defaults { .. } => "defaults",
+            Commands::Serve { .. } => "serve",
+            Commands::Send { .. } => "send",
+            Commands::Receive { .. } => "receive",
+            Commands::Inspect { .. } => "inspect",
+            Commands::Repl => "repl",
+            Commands::Midi { .. } => "midi",
+            Commands::Config { .. } => "config",
+            Commands::Bench { .. } => "bench",
+            Commands::Chain { .. } => "chain",
+            Commands::InstallAgent { .. } | Commands::UninstallAgent { .. } | Commands::ListAgents => "agent",
+            Commands::History { .. } => "history",
+        }
+    }
 
-    if let Some(command) = cli.command {
-        // CLI / Worker Mode
-        let keymaps = config::KeyMaps::default();
+    /// For runs that end with a documented exit code, the status errors
+    /// not about permissions or files get, and whether to print the result.
+    fn reported_run(&self) -> Option<(ExitStatus, bool)> {
+        match self {
+            Commands::Play { json_result, .. } | Commands::Chain { json_result, .. } => Some((ExitStatus::PlaybackError, *json_result)),
+            Commands::Record { json_result, .. } => Some((ExitStatus::Error, *json_result)),
+            _ => None,
+        }
+    }
+}
 
-        match command {
-            Commands::Record { output, immediate } => {
-                let final_path = if output.is_absolute() {
-                    output
-                } else {
-                    std::env::current_dir()?.join(output)
-                };
+/// Exits with the code for `result`, printing its error on stderr and, if
+/// `json_result` is set, a [`RunResult`] on stdout.
+fn exit_with(result: &Result<PlaybackOutcome>, failure: ExitStatus, json_result: bool, started: Instant) -> ! {
+    let run = RunResult::new(result, failure, started.elapsed());
+    if let Err(e) = result {
+        eprintln!("Error: {:?}", e);
+    }
+    if json_result {
+        match serde_json::to_string(&run) {
+            Ok(line) => println!("{}", line),
+            Err(e) => tracing::error!("Could not write the result: {}", e),
+        }
+    }
+    std::process::exit(run.exit_code)
+}
 
-                // Ensure parent directory exists
-                if let Some(parent) = final_path.parent() {
-                    std::fs::create_dir_all(parent)?;
-                }
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    if let Some(path) = &cli.config {
+        // Through the environment, so workers and other runs started from here use it too
+        let path = std::path::absolute(path)?;
+        // SAFETY: no other threads have been started yet
+        unsafe { std::env::set_var(config::PATH_VAR, path) };
+    }
+    let log_file = cli
+        .log_file
+        .clone()
+        .unwrap_or_else(|| logging::default_path(cli.command.as_ref().map_or("app", Commands::log_name)));
+    logging::init(&log_file, cli.log_format)?;
+    let args: Vec<String> = std::env::args().collect();
+    tracing::info!("Launched with args: {:?}", args);
 
-                record::run_record(final_path, keymaps, immediate)?;
-            }
-            Commands::Play {
-                input,
-                speed,
-                repeat_count,
-                repeat_interval,
-                immediate,
-            } => {
-                play::run_play(input, speed, repeat_count, repeat_interval, keymaps, immediate)?;
-            }
+    if let Some(command) = cli.command {
+        // Runs of play, record and chain end with a documented exit code
+        let reported = command.reported_run();
+        let started = Instant::now();
+        let result = run_command(command);
+        match reported {
+            Some((failure, json_result)) => exit_with(&result, failure, json_result, started),
+            None => result.map(|_| ()),
         }
     } else {
         // GUI Mode
-        log::info!("Starting Macro...");
+        tracing::info!("Starting Macro...");
 
+        #[cfg_attr(not(target_os = "macos"), allow(unused_mut))]
         let mut event_loop = EventLoopBuilder::<AppEvent>::with_user_event().build();
+        #[cfg(target_os = "macos")]
         event_loop.set_activation_policy(ActivationPolicy::Accessory);
 
-        let proxy = event_loop.create_proxy();
+        // A second copy would fire every hotkey twice and capture the other's playback
+        let instance = match instance::acquire() {
+            Ok(Some(lock)) => Some(lock),
+            Ok(None) => {
+                tracing::warn!("Macro is already running, exiting.");
+                bar_app::show_already_running();
+                return Ok(());
+            }
+            Err(e) => {
+                tracing::warn!("{:#}", e);
+                None
+            }
+        };
 
-        // Global Hotkey Manager
-        let hotkey_manager = GlobalHotKeyManager::new().unwrap();
-        let (record_hotkey, playback_hotkey, load_hotkey) = bar_app::create_hotkeys();
-        hotkey_manager.register(record_hotkey).unwrap();
-        hotkey_manager.register(playback_hotkey).unwrap();
-        hotkey_manager.register(load_hotkey).unwrap();
+        let proxy = event_loop.create_proxy();
 
         // Initialize App
-        let mut app = BarApp::new(proxy)?;
+        let mut app = BarApp::new(proxy, &log_file)?;
 
         event_loop.run(move |event, event_loop, control_flow| {
-            // Poll every 100ms to check child process status
+            // Held for as long as the app runs
+            let _instance = &instance;
+            // Poll every 100ms to check on recording and playback
             *control_flow = ControlFlow::WaitUntil(std::time::Instant::now() + std::time::Duration::from_millis(100));
 
             match event {
@@ -117,18 +577,359 @@ fn main() -> Result<()> {
                     AppEvent::SettingsApplied(settings) => {
                         app.handle_settings_applied(settings);
                     }
+                    AppEvent::PlaybackReport(report) => {
+                        app.handle_playback_report(report);
+                    }
+                    AppEvent::Overlay(message) => {
+                        app.show_overlay(message, event_loop);
+                    }
+                    AppEvent::UpdateAvailable(version) => {
+                        app.show_update_available(&version);
+                    }
                 },
                 tao::event::Event::WindowEvent { event: tao::event::WindowEvent::CloseRequested, .. } => {
                     app.handle_window_close();
                 }
                 tao::event::Event::MainEventsCleared => {
-                    // Check if playback process has finished
+                    // Check whether the workers have finished
                     app.check_playback_status();
+                    app.check_recording_status();
+                    app.check_config_changes();
+                    app.update_recording_timer();
+                    app.update_recording_indicator(event_loop);
+                    app.update_own_ui();
                 }
                 _ => {}
             }
         });
     }
+}
+
+/// Runs a CLI or worker command. Returns how playback ended, or
+/// `Completed` for commands that play nothing.
+fn run_command(command: Commands) -> Result<PlaybackOutcome> {
+    // CLI / Worker Mode
+    let config = config::Config::load();
+    let keymaps = config.keymaps.clone();
+
+    if let Commands::Record { screenshots, .. } = &command
+        && screenshots.contains(&ScreenshotTrigger::Failure)
+    {
+        anyhow::bail!("'failure' screenshots only apply to playback, use `macro play --screenshot-on-failure`");
+    }
+    if let Commands::Record { output, buffer: Some(_), .. } = &command
+        && output == Path::new("-")
+    {
+        anyhow::bail!("--buffer needs an output file, it cannot stream to stdout");
+    }
+    if let Commands::Record { output, append: true, .. } = &command
+        && output == Path::new("-")
+    {
+        anyhow::bail!("--append needs an output file, it cannot stream to stdout");
+    }
+    if let Commands::Record { output, json_result: true, .. } = &command
+        && output == Path::new("-")
+    {
+        anyhow::bail!("--json-result needs an output file, stdout is taken by the recorded events");
+    }
+    // Runs with --immediate are workers driven by another program, e.g. `macro serve`, so only runs started by hand clash with the app
+    let clashes_with_app = matches!(
+        &command,
+        Commands::Record { immediate: false, .. } | Commands::Play { immediate: false, .. } | Commands::Send { .. } | Commands::Receive { .. } | Commands::Bench { .. } | Commands::Chain { .. }
+    );
+    if clashes_with_app && instance::is_running() {
+        tracing::warn!("The Macro menu bar app is running too. Its hotkeys will also fire, and it may capture input played back here.");
+    }
+
+    match command {
+        Commands::Record { output, duration, max_gap, move_interval, capture_text, capture_clipboard, max_events, max_size, screenshots, thumbnail, immediate, .. } if output == Path::new("-") => {
+            let options = record::RecordOptions {
+                duration,
+                max_gap,
+                capture_text,
+                capture_clipboard,
+                screenshots,
+                screenshot_dir: Some(screenshot::dir_for(&output)),
+                thumbnail,
+                rolling_window: None,
+                move_interval,
+                autosave: None,
+                // Streamed events cannot be taken back, nor told apart from
+                // repeats before they go out
+                undo: None,
+                collapse_key_repeat: false,
+                max_events,
+                max_size,
+            };
+            record::run_record_stream(backend::default_backend(), options, keymaps, immediate)?;
+        }
+        Commands::Record {
+            output,
+            duration,
+            max_gap,
+            move_interval,
+            force,
+            auto_number,
+            append,
+            segment,
+            capture_text,
+            capture_clipboard,
+            collapse_key_repeat,
+            max_events,
+            max_size,
+            screenshots,
+            thumbnail,
+            buffer,
+            autosave_interval,
+            undo,
+            immediate,
+            ..
+        } => {
+            let mut final_path = if output.is_absolute() {
+                output
+            } else {
+                std::env::current_dir()?.join(output)
+            };
+
+            // Buffer saves pick a free name themselves when they happen
+            if final_path.exists() && !force && !append && buffer.is_none() {
+                if auto_number {
+                    final_path = record::next_available_path(&final_path);
+                    tracing::info!("Output file exists, recording to {:?} instead.", final_path);
+                } else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} already exists. Use --force to overwrite it or --auto-number to pick a new name.", final_path.display()),
+                    )
+                    .into());
+                }
+            }
+
+            // Ensure parent directory exists
+            if let Some(parent) = final_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let options = record::RecordOptions {
+                duration,
+                max_gap,
+                capture_text,
+                capture_clipboard,
+                screenshots,
+                screenshot_dir: Some(screenshot::dir_for(&final_path)),
+                thumbnail,
+                rolling_window: None,
+                move_interval,
+                autosave: autosave_interval,
+                undo: Some(undo),
+                collapse_key_repeat,
+                max_events,
+                max_size,
+            };
+            match buffer {
+                Some(window) => record::run_record_buffer(backend::default_backend(), final_path, options, keymaps, window, force)?,
+                None if append => record::run_record_segment(backend::default_backend(), final_path, segment, options, keymaps, immediate)?,
+                None => record::run_record(backend::default_backend(), final_path, options, keymaps, immediate)?,
+            }
+        }
+        command @ Commands::Play { immediate, json_progress, json_result, .. } => {
+            let (inputs, options) = play_plan(command, &config)?;
+            return play::run_play(backend::default_backend(), inputs, options, keymaps, immediate, json_progress, json_result);
+        }
+        Commands::Diff { left, right, moves, timing_tolerance } => {
+            let options = diff::DiffOptions {
+                include_moves: moves,
+                timing_tolerance,
+            };
+            if !diff::run_diff(&left, &right, &options)? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Export { input, format, output } => {
+            export::run_export(&input, &output, format)?;
+        }
+        Commands::Optimize { input, output, tolerance, round_delays } => {
+            let options = optimize::OptimizeOptions {
+                path_tolerance: tolerance,
+                round_delays,
+            };
+            optimize::run_optimize(&input, output.as_deref().unwrap_or(&input), &options)?;
+        }
+        Commands::Replace { input, output, key, point, tolerance } => {
+            let options = replace::ReplaceOptions { keys: key, points: point, tolerance };
+            replace::run_replace(&input, output.as_deref().unwrap_or(&input), &options)?;
+        }
+        Commands::Retime { input, output, typing, clicks, moves, keep_pauses } => {
+            let options = retime::RetimeOptions { typing, clicks, moves, keep_pauses };
+            retime::run_retime(&input, output.as_deref().unwrap_or(&input), &options)?;
+        }
+        Commands::Pack { input, output, preset } => {
+            let preset = preset.map(|name| config.preset(&name).cloned()).transpose()?;
+            pack::pack(&input, &output.unwrap_or_else(|| pack::default_output(&input)), preset.as_ref())?;
+        }
+        Commands::Defaults { input, speed, repeat_count, repeat_interval, countdown, preset, clear } => {
+            let changes = Preset {
+                speed,
+                repeat_count,
+                repeat_interval,
+                countdown: countdown.map(|countdown| countdown.as_secs_f64()),
+            };
+            let changes = match preset {
+                Some(name) => changes.or(config.preset(&name)?.clone()),
+                None => changes,
+            };
+            recording::run_defaults(&input, changes, clear)?;
+        }
+        Commands::Serve { port, bind } => {
+            let defaults = play::PlaybackOptions {
+                speed: config.playback_speed,
+                countdown: Duration::from_secs_f64(config.countdown.max(0.0)),
+                humanize: Duration::from_millis(config.humanize_ms),
+                ..Default::default()
+            };
+            let server = serve::Server::new(backend::default_backend(), config.recordings_dir(), defaults).history(history::default_path());
+            serve::run_serve(server, SocketAddr::new(bind, port))?;
+        }
+        Commands::Send { addr, move_interval, capture_text } => {
+            let options = record::RecordOptions {
+                move_interval,
+                capture_text,
+                ..Default::default()
+            };
+            remote::run_send(backend::default_backend(), &addr, options, keymaps)?;
+        }
+        Commands::Receive { port, bind, display_mapping, key_replay } => {
+            let options = play::PlaybackOptions {
+                display_mapping,
+                key_replay,
+                ..Default::default()
+            };
+            remote::run_receive(backend::default_backend(), SocketAddr::new(bind, port), options, keymaps)?;
+        }
+        Commands::Inspect { input } => {
+            inspect::run_inspect(&input)?;
+        }
+        Commands::Repl => {
+            let repl = repl::Repl::new(backend::default_backend(), keymaps).play_options(move |args| {
+                let words = ["macro", "play"].into_iter().map(String::from).chain(args.iter().cloned());
+                let command = Cli::try_parse_from(words)?.command.context("Expected a play command")?;
+                play_plan(command, &config)
+            });
+            repl::run_repl(repl, io::stdin().lock())?;
+        }
+        Commands::Config { action } => match action {
+            ConfigAction::Validate => config::run_validate()?,
+            ConfigAction::Show => config::run_show()?,
+        },
+        Commands::Midi { port } => {
+            let options = play::PlaybackOptions {
+                speed: config.playback_speed,
+                humanize: Duration::from_millis(config.humanize_ms),
+                ..Default::default()
+            };
+            midi::run_midi(backend::default_backend(), &config.midi_triggers, &config.recordings_dir(), port.as_deref(), options, keymaps)?;
+        }
+        Commands::Chain { input, speed, countdown, .. } => {
+            let options = play::PlaybackOptions { speed: speed.unwrap_or(config.playback_speed), ..Default::default() };
+            return chain::run_chain(backend::default_backend(), &input, options, countdown, keymaps);
+        }
+        Commands::InstallAgent { input, cron, play_args } => {
+            agent::install_agent(&input, &cron, &play_args)?;
+        }
+        Commands::UninstallAgent { name } => {
+            agent::uninstall_agent(&name)?;
+        }
+        Commands::ListAgents => {
+            agent::list_agents()?;
+        }
+        Commands::Bench { samples, json } => {
+            bench::run_bench(backend::default_backend(), &SystemClock::new(), samples, json)?;
+        }
+        Commands::History { limit, since, json } => {
+            history::run_history(&history::default_path(), limit, since, json)?;
+        }
+    }
+    Ok(PlaybackOutcome::Completed)
+}
 
-    Ok(())
+/// The recordings a `play` command plays and the options it plays them
+/// with, filled in from `config`, the preset and the recording's own
+/// settings.
+fn play_plan(command: Commands, config: &config::Config) -> Result<(Vec<PathBuf>, play::PlaybackOptions)> {
+    let Commands::Play {
+        input,
+        speed,
+        mouse_speed,
+        keyboard_speed,
+        repeat_count,
+        repeat_interval,
+        preset,
+        repeat_for,
+        repeat_until,
+        max_total_duration,
+        max_total_repeats,
+        max_consecutive_failures,
+        strict,
+        display_mapping,
+        transform,
+        offset_x,
+        offset_y,
+        key_replay,
+        screenshot_on_failure,
+        from,
+        to,
+        from_checkpoint,
+        to_checkpoint,
+        segment,
+        countdown,
+        humanize,
+        hold,
+        remap_key,
+        remap_button,
+        remap_wheel,
+        parallel,
+        ..
+    } = command
+    else {
+        anyhow::bail!("Expected a play command");
+    };
+    let has_deadline = repeat_for.is_some() || repeat_until.is_some();
+    let preset = match &preset {
+        Some(name) => config.preset(name)?.clone(),
+        // The recording's own settings, if it has them
+        None if input != Path::new("-") => recording::saved_playback(&input)?.unwrap_or_default(),
+        None => Preset::default(),
+    };
+    let options = play::PlaybackOptions {
+        speed: speed.or(preset.speed).unwrap_or(config.playback_speed),
+        mouse_speed,
+        keyboard_speed,
+        repeat_count: repeat_count.or(preset.repeat_count).unwrap_or(if has_deadline { 0 } else { 1 }),
+        repeat_interval: repeat_interval.or(preset.repeat_interval).unwrap_or(0.0),
+        display_mapping,
+        transform,
+        offset: (offset_x, offset_y),
+        repeat_for,
+        repeat_until,
+        key_replay,
+        screenshot_on_failure: screenshot_on_failure.map(|dir| dir.unwrap_or_else(|| screenshot::dir_for(&input))),
+        countdown: countdown.or(preset.countdown.map(Duration::from_secs_f64)).unwrap_or_default(),
+        humanize,
+        from: segment.clone().map(play::PlaybackPosition::Segment).or(from_checkpoint.map(play::PlaybackPosition::Checkpoint)).or(from),
+        to: segment.map(play::PlaybackPosition::Segment).or(to_checkpoint.map(play::PlaybackPosition::Checkpoint)).or(to),
+        hold,
+        remap: Remap {
+            keys: remap_key.into_iter().map(|mapping| (mapping.from, mapping.to)).collect(),
+            buttons: remap_button.into_iter().map(|mapping| (mapping.from, mapping.to)).collect(),
+            wheel: remap_wheel,
+        },
+        watchdog: play::Watchdog {
+            max_total_duration,
+            max_total_repeats,
+            max_consecutive_failures,
+        },
+        strict,
+    };
+    let inputs = std::iter::once(input).chain(parallel).collect();
+    Ok((inputs, options))
 }
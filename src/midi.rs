@@ -0,0 +1,72 @@
+use crate::backend::InputBackend;
+use crate::config::{KeyMaps, MidiTrigger};
+use crate::play::PlaybackOptions;
+use anyhow::Result;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Plays the recording of the first of `triggers` whose note is pressed on
+/// the MIDI input whose name contains `port`, or the first input, until the
+/// process is killed. Recordings are relative to `recordings_dir`. Notes
+/// pressed while a recording plays are ignored, and the stop hotkey stops
+/// only the recording playing.
+#[cfg(feature = "midi")]
+pub fn run_midi(backend: Arc<dyn InputBackend>, triggers: &[MidiTrigger], recordings_dir: &Path, port: Option<&str>, options: PlaybackOptions, keymaps: KeyMaps) -> Result<()> {
+    use crate::play::{self, Player};
+    use crate::platform;
+    use crate::recording::Recording;
+    use anyhow::Context;
+    use midir::MidiInput;
+    use std::sync::mpsc;
+
+    if triggers.is_empty() {
+        anyhow::bail!("No MIDI triggers are set up, add some to midi_triggers in {:?}", crate::config::Config::path());
+    }
+    platform::check_input_backend()?;
+    let input = MidiInput::new("macro")?;
+    let ports = input.ports();
+    let mut names = Vec::new();
+    for candidate in &ports {
+        names.push(input.port_name(candidate)?);
+    }
+    let index = match port {
+        Some(port) => names.iter().position(|name| name.contains(port)),
+        None => (!names.is_empty()).then_some(0),
+    };
+    let Some(index) = index else {
+        anyhow::bail!("No MIDI input found{}, connected inputs: {}", port.map(|port| format!(" matching '{}'", port)).unwrap_or_default(), names.join(", "));
+    };
+
+    let (sender, receiver) = mpsc::channel::<Vec<u8>>();
+    let _connection = input
+        .connect(&ports[index], "macro", move |_, message, _| drop(sender.send(message.to_vec())), ())
+        .map_err(|e| anyhow::anyhow!("Could not connect to MIDI input '{}': {}", names[index], e))?;
+    println!("Listening to MIDI input '{}' for {} triggers. Press Ctrl+C to quit.", names[index], triggers.len());
+
+    let player = Player::new().backend(backend.clone()).options(options);
+    let control = player.control();
+    play::spawn_stop_listener(backend, keymaps, control.clone());
+    for message in &receiver {
+        let Some(trigger) = triggers.iter().find(|trigger| trigger.matches(&message)) else {
+            continue;
+        };
+        let path = recordings_dir.join(&trigger.recording);
+        tracing::info!("MIDI note {} pressed, playing {:?}.", trigger.note, path);
+        control.reset();
+        let result = Recording::load(&path)
+            .with_context(|| format!("Could not load {:?}", path))
+            .and_then(|recording| player.play(&recording));
+        if let Err(e) = result {
+            tracing::error!("Triggered playback failed: {:?}", e);
+            eprintln!("Error: {:#}", e);
+        }
+        // Drop notes pressed while playing rather than queueing them up
+        while receiver.try_recv().is_ok() {}
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "midi"))]
+pub fn run_midi(_backend: Arc<dyn InputBackend>, _triggers: &[MidiTrigger], _recordings_dir: &Path, _port: Option<&str>, _options: PlaybackOptions, _keymaps: KeyMaps) -> Result<()> {
+    anyhow::bail!("This build has no MIDI support, rebuild with `--features midi`")
+}
@@ -0,0 +1,122 @@
+use crate::config::{KeyCombo, Modifier};
+use rdev::{EventType, Key};
+
+/// Which modifier keys are currently held down.
+///
+/// Tracked from key press/release events, seeded from the OS so a modifier
+/// that was already held when listening started is not missed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ModifierState {
+    pub cmd: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+    pub shift: bool,
+}
+
+impl ModifierState {
+    /// Current modifier state as reported by macOS.
+    #[cfg(target_os = "macos")]
+    pub fn from_os() -> Self {
+        use core_graphics::event::CGEventFlags;
+
+        // kCGEventSourceStateCombinedSessionState
+        const COMBINED_SESSION_STATE: i32 = 0;
+
+        #[link(name = "CoreGraphics", kind = "framework")]
+        unsafe extern "C" {
+            fn CGEventSourceFlagsState(state_id: i32) -> u64;
+        }
+
+        // SAFETY: only reads the current modifier flags of the session.
+        let flags = CGEventFlags::from_bits_truncate(unsafe { CGEventSourceFlagsState(COMBINED_SESSION_STATE) });
+        Self {
+            cmd: flags.contains(CGEventFlags::CGEventFlagCommand),
+            alt: flags.contains(CGEventFlags::CGEventFlagAlternate),
+            ctrl: flags.contains(CGEventFlags::CGEventFlagControl),
+            shift: flags.contains(CGEventFlags::CGEventFlagShift),
+        }
+    }
+
+    /// Current modifier state as reported by the X server, or nothing held
+    /// if there is no display to ask.
+    #[cfg(target_os = "linux")]
+    pub fn from_os() -> Self {
+        use std::os::raw::c_char;
+        use x11::keysym::*;
+        use x11::xlib;
+
+        // SAFETY: the display is only used here and closed before returning,
+        // and XQueryKeymap fills exactly the 32 bytes it is given.
+        unsafe {
+            let display = xlib::XOpenDisplay(std::ptr::null());
+            if display.is_null() {
+                return Self::default();
+            }
+            // One bit per key code, set while that key is down
+            let mut keys = [0 as c_char; 32];
+            xlib::XQueryKeymap(display, keys.as_mut_ptr());
+            let held = |keysyms: &[u32]| {
+                keysyms.iter().any(|&keysym| {
+                    let code = xlib::XKeysymToKeycode(display, keysym.into()) as usize;
+                    code != 0 && keys[code / 8] as u8 & (1 << (code % 8)) != 0
+                })
+            };
+            let state = Self {
+                cmd: held(&[XK_Super_L, XK_Super_R]),
+                alt: held(&[XK_Alt_L, XK_Alt_R, XK_ISO_Level3_Shift]),
+                ctrl: held(&[XK_Control_L, XK_Control_R]),
+                shift: held(&[XK_Shift_L, XK_Shift_R]),
+            };
+            xlib::XCloseDisplay(display);
+            state
+        }
+    }
+
+    /// Current modifier state as reported by Windows.
+    #[cfg(target_os = "windows")]
+    pub fn from_os() -> Self {
+        use winapi::um::winuser::{GetAsyncKeyState, VK_CONTROL, VK_LWIN, VK_MENU, VK_RWIN, VK_SHIFT};
+
+        // SAFETY: only reads whether each key is down, which sets the high bit
+        let held = |keys: &[i32]| keys.iter().any(|&key| unsafe { GetAsyncKeyState(key) } < 0);
+        Self {
+            cmd: held(&[VK_LWIN, VK_RWIN]),
+            alt: held(&[VK_MENU]),
+            ctrl: held(&[VK_CONTROL]),
+            shift: held(&[VK_SHIFT]),
+        }
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+    pub fn from_os() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, event_type: &EventType) {
+        match *event_type {
+            EventType::KeyPress(Key::MetaLeft) | EventType::KeyPress(Key::MetaRight) => self.cmd = true,
+            EventType::KeyRelease(Key::MetaLeft) | EventType::KeyRelease(Key::MetaRight) => self.cmd = false,
+            EventType::KeyPress(Key::Alt) | EventType::KeyPress(Key::AltGr) => self.alt = true,
+            EventType::KeyRelease(Key::Alt) | EventType::KeyRelease(Key::AltGr) => self.alt = false,
+            EventType::KeyPress(Key::ControlLeft) | EventType::KeyPress(Key::ControlRight) => self.ctrl = true,
+            EventType::KeyRelease(Key::ControlLeft) | EventType::KeyRelease(Key::ControlRight) => self.ctrl = false,
+            EventType::KeyPress(Key::ShiftLeft) | EventType::KeyPress(Key::ShiftRight) => self.shift = true,
+            EventType::KeyRelease(Key::ShiftLeft) | EventType::KeyRelease(Key::ShiftRight) => self.shift = false,
+            _ => {}
+        }
+    }
+
+    pub fn is_held(&self, modifier: &Modifier) -> bool {
+        match modifier {
+            Modifier::Cmd => self.cmd,
+            Modifier::Alt => self.alt,
+            Modifier::Ctrl => self.ctrl,
+            Modifier::Shift => self.shift,
+        }
+    }
+
+    /// Whether pressing `key` right now triggers `combo`.
+    pub fn matches(&self, combo: &KeyCombo, key: Key) -> bool {
+        key == combo.trigger && combo.modifiers.iter().all(|m| self.is_held(m))
+    }
+}
@@ -0,0 +1,212 @@
+use crate::diff::points_per_unit;
+use crate::event::{SerializableEvent, SerializableEventType};
+use crate::recording::Recording;
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct OptimizeOptions {
+    /// Mouse moves within this many points of the simplified path are dropped.
+    pub path_tolerance: f64,
+    /// Event times are rounded to a multiple of this. Zero keeps them as recorded.
+    pub round_delays: Duration,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            path_tolerance: 1.0,
+            round_delays: Duration::from_millis(1),
+        }
+    }
+}
+
+/// An event with its time from the start of the recording. Dropping one
+/// hands its delay on to the next, so everything after it keeps its timing.
+struct TimedEvent {
+    at: Duration,
+    event: SerializableEvent,
+}
+
+/// Simplifies `recording` for size without changing what it does: removes
+/// presses released at the same instant, merges moves that are redundant,
+/// simplifies mouse paths with Douglas–Peucker and rounds event times.
+pub fn optimize(recording: &Recording, options: &OptimizeOptions) -> Recording {
+    let mut at = Duration::ZERO;
+    let mut events: Vec<TimedEvent> = recording
+        .events
+        .iter()
+        .map(|event| {
+            at += event.delay();
+            TimedEvent { at, event: event.clone() }
+        })
+        .collect();
+
+    events = remove_zero_length_presses(events);
+    events = merge_redundant_moves(events);
+    events = simplify_paths(events, options.path_tolerance / points_per_unit(recording));
+
+    let mut previous = Duration::ZERO;
+    let events = events
+        .into_iter()
+        .map(|TimedEvent { at, mut event }| {
+            let at = round(at, options.round_delays);
+            event.set_delay(at - previous);
+            previous = at;
+            event
+        })
+        .collect();
+    Recording {
+        metadata: recording.metadata.clone(),
+        events,
+    }
+}
+
+/// Drops a press directly followed by its release at the same instant,
+/// which has no visible effect.
+fn remove_zero_length_presses(events: Vec<TimedEvent>) -> Vec<TimedEvent> {
+    let mut kept: Vec<TimedEvent> = Vec::with_capacity(events.len());
+    for timed in events {
+        if let Some(last) = kept.last()
+            && last.at == timed.at
+            && is_press_and_release(&last.event.event_type, &timed.event.event_type)
+        {
+            kept.pop();
+            continue;
+        }
+        kept.push(timed);
+    }
+    kept
+}
+
+fn is_press_and_release(press: &SerializableEventType, release: &SerializableEventType) -> bool {
+    match (press, release) {
+        (SerializableEventType::KeyPress(a), SerializableEventType::KeyRelease(b)) => a == b,
+        (SerializableEventType::KeyPressText { key: a, .. }, SerializableEventType::KeyRelease(b)) => a == b,
+        (SerializableEventType::ButtonPress(a), SerializableEventType::ButtonRelease(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Drops moves to where the mouse already is, and moves replaced by another
+/// at the same instant.
+fn merge_redundant_moves(events: Vec<TimedEvent>) -> Vec<TimedEvent> {
+    let mut kept: Vec<TimedEvent> = Vec::with_capacity(events.len());
+    // Position after the last kept move, which outlives other events
+    let mut position: Option<(f64, f64)> = None;
+    for timed in events {
+        let SerializableEventType::MouseMove { x, y, .. } = timed.event.event_type else {
+            kept.push(timed);
+            continue;
+        };
+        if position == Some((x, y)) {
+            continue;
+        }
+        if let Some(last) = kept.last()
+            && last.at == timed.at
+            && matches!(last.event.event_type, SerializableEventType::MouseMove { .. })
+        {
+            kept.pop();
+        }
+        position = Some((x, y));
+        kept.push(timed);
+    }
+    kept
+}
+
+/// Runs Douglas–Peucker over each stretch of consecutive mouse moves. The
+/// first and last move of a stretch are always kept, so clicks and key
+/// presses still happen at the same place.
+fn simplify_paths(events: Vec<TimedEvent>, tolerance: f64) -> Vec<TimedEvent> {
+    let mut keep = vec![true; events.len()];
+    let mut start = 0;
+    while start < events.len() {
+        let mut end = start;
+        while end < events.len() && move_point(&events[end]).is_some() {
+            end += 1;
+        }
+        if end > start + 2 {
+            let points: Vec<(f64, f64)> = events[start..end].iter().filter_map(move_point).collect();
+            let mut kept_points = vec![false; points.len()];
+            douglas_peucker(&points, tolerance, &mut kept_points);
+            keep[start..end].copy_from_slice(&kept_points);
+        }
+        start = end.max(start + 1);
+    }
+    events.into_iter().zip(keep).filter(|(_, keep)| *keep).map(|(timed, _)| timed).collect()
+}
+
+fn move_point(timed: &TimedEvent) -> Option<(f64, f64)> {
+    match timed.event.event_type {
+        SerializableEventType::MouseMove { x, y, .. } => Some((x, y)),
+        _ => None,
+    }
+}
+
+/// Marks the points of `points` to keep so that none of the dropped ones is
+/// further than `tolerance` from the path through the kept ones.
+fn douglas_peucker(points: &[(f64, f64)], tolerance: f64, keep: &mut [bool]) {
+    let last = points.len() - 1;
+    keep[0] = true;
+    keep[last] = true;
+    if last < 2 {
+        return;
+    }
+    let (index, distance) = (1..last)
+        .map(|i| (i, distance_to_segment(points[i], points[0], points[last])))
+        .fold((0, 0.0), |best, candidate| if candidate.1 > best.1 { candidate } else { best });
+    if distance > tolerance {
+        douglas_peucker(&points[..=index], tolerance, &mut keep[..=index]);
+        douglas_peucker(&points[index..], tolerance, &mut keep[index..]);
+    }
+}
+
+fn distance_to_segment(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    let (x, y) = (start.0 + t * dx, start.1 + t * dy);
+    ((point.0 - x).powi(2) + (point.1 - y).powi(2)).sqrt()
+}
+
+fn round(at: Duration, step: Duration) -> Duration {
+    if step.is_zero() {
+        return at;
+    }
+    let step_nanos = step.as_nanos();
+    let steps = (at.as_nanos() + step_nanos / 2) / step_nanos;
+    Duration::from_nanos((steps * step_nanos) as u64)
+}
+
+/// Optimizes the recording at `input`, saves it to `output` and prints how
+/// much smaller it got.
+pub fn run_optimize(input: &Path, output: &Path, options: &OptimizeOptions) -> Result<()> {
+    let recording = Recording::load(input)?;
+    let bytes_before = fs::metadata(input)?.len();
+    let optimized = optimize(&recording, options);
+    optimized.save(output)?;
+    let bytes_after = fs::metadata(output)?.len();
+
+    println!(
+        "Events: {} -> {} ({})",
+        recording.events.len(),
+        optimized.events.len(),
+        reduction(recording.events.len() as u64, optimized.events.len() as u64)
+    );
+    println!("Size:   {} -> {} bytes ({})", bytes_before, bytes_after, reduction(bytes_before, bytes_after));
+    println!("Saved to {}", output.display());
+    Ok(())
+}
+
+fn reduction(before: u64, after: u64) -> String {
+    if before == 0 {
+        return "unchanged".to_string();
+    }
+    format!("{:+.1}%", (after as f64 - before as f64) / before as f64 * 100.0)
+}
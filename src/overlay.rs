@@ -0,0 +1,274 @@
+use anyhow::Result;
+use macro_lib::event::{SerializableEvent, SerializableEventType};
+use macro_lib::modifiers::ModifierState;
+use rdev::{Button, EventType, Key};
+use serde::Serialize;
+use tao::dpi::{LogicalSize, PhysicalPosition};
+use tao::event_loop::EventLoopWindowTarget;
+use tao::window::{Window, WindowBuilder};
+use wry::{WebView, WebViewBuilder};
+
+use crate::bar_app::AppEvent;
+
+/// Shows the keys being pressed and where clicks land while a macro plays.
+const OVERLAY_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<style>
+  html, body { margin: 0; width: 100%; height: 100%; overflow: hidden; background: transparent; }
+  #keys { position: fixed; left: 50%; bottom: 80px; transform: translateX(-50%); display: flex; gap: 8px; }
+  .key {
+    font: 600 28px -apple-system, "Segoe UI", sans-serif; color: white;
+    background: rgba(0, 0, 0, 0.7); border-radius: 10px; padding: 8px 16px;
+    transition: opacity 0.4s;
+  }
+  .click {
+    position: fixed; width: 36px; height: 36px; margin: -21px 0 0 -21px; border-radius: 50%;
+    border: 3px solid rgba(255, 59, 48, 0.9); animation: ripple 0.6s ease-out forwards;
+  }
+  .click.right { border-color: rgba(0, 122, 255, 0.9); }
+  @keyframes ripple { from { transform: scale(0.4); opacity: 1; } to { transform: scale(1.4); opacity: 0; } }
+</style>
+</head>
+<body>
+<div id="keys"></div>
+<script>
+  const keys = document.getElementById("keys");
+  function show(message) {
+    if (message.type === "key") {
+      const key = document.createElement("div");
+      key.className = "key";
+      key.textContent = message.label;
+      keys.appendChild(key);
+      while (keys.children.length > 6) keys.firstChild.remove();
+      setTimeout(() => { key.style.opacity = 0; }, 1200);
+      setTimeout(() => key.remove(), 1600);
+    } else if (message.type === "click") {
+      const click = document.createElement("div");
+      click.className = "click " + message.button;
+      click.style.left = message.x + "px";
+      click.style.top = message.y + "px";
+      document.body.appendChild(click);
+      setTimeout(() => click.remove(), 600);
+    }
+  }
+</script>
+</body>
+</html>
+"#;
+
+/// Badge shown in a corner of the screen while recording.
+const INDICATOR_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<style>
+  html, body { margin: 0; width: 100%; height: 100%; overflow: hidden; background: transparent; }
+  #badge {
+    box-sizing: border-box; width: 100%; height: 100%; border-radius: 18px;
+    display: flex; align-items: center; justify-content: center; gap: 6px;
+    font: 700 15px -apple-system, "Segoe UI", sans-serif; color: white; background: rgba(0, 0, 0, 0.7);
+  }
+  #dot { color: #ff3b30; animation: blink 1s steps(1) infinite; }
+  @keyframes blink { 50% { opacity: 0.3; } }
+  body.paused #dot { color: #aaa; animation: none; }
+</style>
+</head>
+<body>
+<div id="badge"><span id="label">REC</span><span id="dot">●</span></div>
+<script>
+  function setPaused(paused) {
+    document.body.classList.toggle("paused", paused);
+    document.getElementById("label").textContent = paused ? "PAUSED" : "REC";
+  }
+</script>
+</body>
+</html>
+"#;
+
+/// Size of the recording indicator, in points.
+const INDICATOR_SIZE: LogicalSize<f64> = LogicalSize::new(112.0, 36.0);
+
+/// Something the overlay shows for a played event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum OverlayMessage {
+    /// A key press with the modifiers held for it, e.g. "⌘⇧S".
+    Key { label: String },
+    /// A mouse button pressed at `x`, `y`, in points from the top left of
+    /// the primary display.
+    Click { x: f64, y: f64, button: &'static str },
+}
+
+/// Turns played events into overlay messages, following the modifiers held
+/// and the mouse position along the way.
+#[derive(Default)]
+pub struct OverlayFeed {
+    modifiers: ModifierState,
+    position: (f64, f64),
+}
+
+impl OverlayFeed {
+    /// What to show for `event`, if anything. Modifiers are only shown as
+    /// part of the key they are held for.
+    pub fn message(&mut self, event: &SerializableEvent) -> Option<OverlayMessage> {
+        match &event.event_type {
+            SerializableEventType::KeyPress(key) => {
+                self.modifiers.update(&EventType::KeyPress(*key));
+                (!is_modifier(*key)).then(|| OverlayMessage::Key { label: self.label(&key_name(*key)) })
+            }
+            SerializableEventType::KeyPressText { key, text } => {
+                // Shortcuts show the key, as their text is not what they do
+                let name = if self.modifiers.cmd || self.modifiers.ctrl || text.trim().is_empty() {
+                    key_name(*key)
+                } else {
+                    text.clone()
+                };
+                Some(OverlayMessage::Key { label: self.label(&name) })
+            }
+            SerializableEventType::KeyRelease(key) => {
+                self.modifiers.update(&EventType::KeyRelease(*key));
+                None
+            }
+            &SerializableEventType::MouseMove { x, y, .. } => {
+                self.position = (x, y);
+                None
+            }
+            SerializableEventType::ButtonPress(button) => Some(OverlayMessage::Click {
+                x: self.position.0,
+                y: self.position.1,
+                button: match button {
+                    Button::Right => "right",
+                    _ => "left",
+                },
+            }),
+            _ => None,
+        }
+    }
+
+    fn label(&self, name: &str) -> String {
+        let ModifierState { cmd, alt, ctrl, shift } = self.modifiers;
+        let mut label = String::new();
+        for (held, symbol) in [(ctrl, "⌃"), (alt, "⌥"), (shift, "⇧"), (cmd, "⌘")] {
+            if held {
+                label.push_str(symbol);
+            }
+        }
+        label.push_str(name);
+        label
+    }
+}
+
+fn is_modifier(key: Key) -> bool {
+    matches!(
+        key,
+        Key::MetaLeft | Key::MetaRight | Key::Alt | Key::AltGr | Key::ControlLeft | Key::ControlRight | Key::ShiftLeft | Key::ShiftRight
+    )
+}
+
+/// Short name for `key`, e.g. "A" for `KeyA` and "1" for `Num1`.
+fn key_name(key: Key) -> String {
+    match key {
+        Key::Return => "↩".to_string(),
+        Key::Tab => "⇥".to_string(),
+        Key::Backspace => "⌫".to_string(),
+        Key::Escape => "Esc".to_string(),
+        Key::Space => "Space".to_string(),
+        Key::LeftArrow => "←".to_string(),
+        Key::RightArrow => "→".to_string(),
+        Key::UpArrow => "↑".to_string(),
+        Key::DownArrow => "↓".to_string(),
+        _ => {
+            let name = format!("{:?}", key);
+            name.strip_prefix("Key").or_else(|| name.strip_prefix("Num")).unwrap_or(&name).to_string()
+        }
+    }
+}
+
+/// Click-through window over the primary display that shows what playback
+/// is doing, for anyone watching a macro run.
+pub struct PlaybackOverlay {
+    _window: Window,
+    webview: WebView,
+}
+
+impl PlaybackOverlay {
+    pub fn new(event_loop: &EventLoopWindowTarget<AppEvent>) -> Result<Self> {
+        let mut builder = overlay_window("Macro Overlay");
+        if let Some(monitor) = event_loop.primary_monitor() {
+            builder = builder.with_position(monitor.position()).with_inner_size(monitor.size());
+        }
+        let (window, webview) = build_overlay(builder, event_loop, OVERLAY_HTML)?;
+        Ok(Self { _window: window, webview })
+    }
+
+    pub fn show(&self, message: &OverlayMessage) {
+        let message = match serde_json::to_string(message) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::error!("Failed to encode overlay message: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = self.webview.evaluate_script(&format!("show({});", message)) {
+            tracing::error!("Failed to update the overlay: {}", e);
+        }
+    }
+}
+
+/// Small "REC" badge in the top right corner of the primary display, so a
+/// recording left running is hard to miss.
+pub struct RecordingIndicator {
+    _window: Window,
+    webview: WebView,
+    paused: bool,
+}
+
+impl RecordingIndicator {
+    pub fn new(event_loop: &EventLoopWindowTarget<AppEvent>) -> Result<Self> {
+        let mut builder = overlay_window("Macro Recording").with_inner_size(INDICATOR_SIZE);
+        if let Some(monitor) = event_loop.primary_monitor() {
+            let size = INDICATOR_SIZE.to_physical::<i32>(monitor.scale_factor());
+            // Clear of the menu bar
+            let margin = LogicalSize::new(16.0, 40.0).to_physical::<i32>(monitor.scale_factor());
+            let (origin, screen) = (monitor.position(), monitor.size());
+            builder = builder.with_position(PhysicalPosition::new(
+                origin.x + screen.width as i32 - size.width - margin.width,
+                origin.y + margin.height,
+            ));
+        }
+        let (window, webview) = build_overlay(builder, event_loop, INDICATOR_HTML)?;
+        Ok(Self { _window: window, webview, paused: false })
+    }
+
+    /// Dims the badge and shows "PAUSED" while recording is paused.
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused == self.paused {
+            return;
+        }
+        self.paused = paused;
+        if let Err(e) = self.webview.evaluate_script(&format!("setPaused({});", paused)) {
+            tracing::error!("Failed to update the recording indicator: {}", e);
+        }
+    }
+}
+
+/// Borderless, transparent window that floats above everything else.
+fn overlay_window(title: &str) -> WindowBuilder {
+    WindowBuilder::new()
+        .with_title(title)
+        .with_decorations(false)
+        .with_transparent(true)
+        .with_always_on_top(true)
+        .with_visible_on_all_workspaces(true)
+        .with_focused(false)
+        .with_resizable(false)
+}
+
+/// Builds the window from `builder` showing `html`, letting clicks through
+/// to the apps underneath.
+fn build_overlay(builder: WindowBuilder, event_loop: &EventLoopWindowTarget<AppEvent>, html: &str) -> Result<(Window, WebView)> {
+    let window = builder.build(event_loop)?;
+    window.set_ignore_cursor_events(true)?;
+    let webview = WebViewBuilder::new().with_html(html).with_transparent(true).build(&window)?;
+    Ok((window, webview))
+}
@@ -0,0 +1,138 @@
+use crate::display::{self, DisplayInfo};
+use rdev::{EventType, Key};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Rectangle on screen, in the same points as captured mouse positions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Area {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+#[derive(Debug, Default)]
+struct OwnUiState {
+    windows: BTreeMap<String, Area>,
+    focused: bool,
+    menu_icon: Option<Area>,
+}
+
+/// Where the recording app's own windows and menu icon are, and whether
+/// one of its windows has the keyboard, so that input meant for the app
+/// is left out of what it records. Shared with the app, which keeps it up
+/// to date as its windows open, move and close.
+#[derive(Debug, Clone, Default)]
+pub struct OwnUi {
+    state: Arc<Mutex<OwnUiState>>,
+}
+
+impl OwnUi {
+    /// Sets where the window called `name` is, or `None` once it closes.
+    pub fn set_window(&self, name: &str, area: Option<Area>) {
+        let mut state = self.state.lock().unwrap();
+        match area {
+            Some(area) => state.windows.insert(name.to_string(), area),
+            None => state.windows.remove(name),
+        };
+    }
+
+    /// Sets whether one of the app's windows has the keyboard.
+    pub fn set_focused(&self, focused: bool) {
+        self.state.lock().unwrap().focused = focused;
+    }
+
+    /// Sets where the icon is that opens the app's menu, e.g. in the menu
+    /// bar. The menu takes the click after the one on the icon, which picks
+    /// an item or closes it, so that click is left out too.
+    pub fn set_menu_icon(&self, area: Option<Area>) {
+        self.state.lock().unwrap().menu_icon = area;
+    }
+
+    /// Filter for input captured on `displays`.
+    pub(crate) fn filter(&self, displays: &[DisplayInfo]) -> OwnUiFilter {
+        OwnUiFilter {
+            ui: self.clone(),
+            units_per_point: display::native_units_per_point(displays),
+            pointer: None,
+            menu_open: false,
+            releases: Vec::new(),
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+/// Drops captured input meant for the app's own UI, following the pointer
+/// and the app's menu as events go by.
+pub(crate) struct OwnUiFilter {
+    ui: OwnUi,
+    /// Captured positions are in these units, e.g. pixels, rather than points.
+    units_per_point: f64,
+    /// In points, like the areas of the app's UI.
+    pointer: Option<(f64, f64)>,
+    menu_open: bool,
+    /// Releases of the presses dropped, to be dropped too.
+    releases: Vec<EventType>,
+    /// Time taken up by the events dropped since the last one kept.
+    delay: Duration,
+}
+
+impl OwnUiFilter {
+    /// The delay to store `event_type` with, `delay` after the last event
+    /// captured, or `None` if it was meant for the app. The time taken by
+    /// dropped events is handed on to the next one kept, so the rest of the
+    /// recording keeps its timing.
+    pub(crate) fn keep(&mut self, event_type: &EventType, delay: Duration) -> Option<Duration> {
+        if let EventType::MouseMove { x, y } = *event_type {
+            self.pointer = Some((x / self.units_per_point, y / self.units_per_point));
+        }
+        if self.is_own(event_type) {
+            tracing::debug!("Left out input for the app's own UI: {:?}", event_type);
+            self.delay += delay;
+            return None;
+        }
+        Some(std::mem::take(&mut self.delay) + delay)
+    }
+
+    fn is_own(&mut self, event_type: &EventType) -> bool {
+        let state = self.ui.state.lock().unwrap();
+        let over = |area: &Area| self.pointer.is_some_and(|(x, y)| area.contains(x, y));
+        let on_icon = state.menu_icon.as_ref().is_some_and(over);
+        let over_window = state.windows.values().any(over);
+        let (own, release) = match *event_type {
+            EventType::ButtonPress(button) => {
+                let own = self.menu_open || on_icon || over_window;
+                // A click on the icon opens the menu, and the next one closes it
+                self.menu_open = on_icon && !self.menu_open;
+                (own, EventType::ButtonRelease(button))
+            }
+            EventType::KeyPress(key) => {
+                let own = self.menu_open || state.focused;
+                if matches!(key, Key::Escape | Key::Return) {
+                    self.menu_open = false;
+                }
+                (own, EventType::KeyRelease(key))
+            }
+            EventType::ButtonRelease(_) | EventType::KeyRelease(_) => {
+                let Some(index) = self.releases.iter().position(|release| release == event_type) else {
+                    return false;
+                };
+                self.releases.remove(index);
+                return true;
+            }
+            EventType::MouseMove { .. } | EventType::Wheel { .. } => return self.menu_open || on_icon || over_window,
+        };
+        if own {
+            self.releases.push(release);
+        }
+        own
+    }
+}
@@ -0,0 +1,102 @@
+use crate::config::Preset;
+use crate::recording::Recording;
+use crate::screenshot;
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Extension of a packed recording.
+pub const EXTENSION: &str = "macro";
+
+const RECORDING_ENTRY: &str = "recording.json";
+const PRESET_ENTRY: &str = "preset.json";
+const SCREENSHOTS_DIR: &str = "screenshots";
+
+/// Whether `path` is a packed recording rather than a plain one.
+pub fn is_package(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == EXTENSION)
+}
+
+/// A recording bundled with everything needed to share it: its screenshots
+/// and the playback settings it is meant to be played with. Saved as a
+/// gzipped tar archive.
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub recording: Recording,
+    /// Speed and repeat settings played with unless others are given.
+    pub preset: Option<Preset>,
+    /// Screenshot and thumbnail file names, from the recording's screenshot
+    /// folder when it was packed.
+    pub screenshots: Vec<String>,
+}
+
+impl Package {
+    /// Reads the package at `path`. Screenshots are listed, not extracted.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Could not open {:?}", path))?;
+        let mut archive = tar::Archive::new(GzDecoder::new(BufReader::new(file)));
+        let mut recording = None;
+        let mut preset = None;
+        let mut screenshots = Vec::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            if name == RECORDING_ENTRY {
+                let mut text = String::new();
+                entry.read_to_string(&mut text)?;
+                recording = Some(serde_json::from_str(&text).with_context(|| format!("Could not parse the recording in {:?}", path))?);
+            } else if name == PRESET_ENTRY {
+                let mut text = String::new();
+                entry.read_to_string(&mut text)?;
+                preset = Some(serde_json::from_str(&text).with_context(|| format!("Could not parse the preset in {:?}", path))?);
+            } else if let Some(file_name) = name.strip_prefix(&format!("{}/", SCREENSHOTS_DIR))
+                && entry.header().entry_type().is_file()
+            {
+                screenshots.push(file_name.to_string());
+            }
+        }
+        let recording = recording.ok_or_else(|| anyhow::anyhow!("{:?} has no {}", path, RECORDING_ENTRY))?;
+        Ok(Package { recording, preset, screenshots })
+    }
+}
+
+/// Default path for the package of the recording at `input`, next to it.
+pub fn default_output(input: &Path) -> PathBuf {
+    input.with_extension(EXTENSION)
+}
+
+/// Bundles the recording at `input` and its screenshot folder, if any, into
+/// a package at `output`, to be played with `preset` unless others are given.
+pub fn pack(input: &Path, output: &Path, preset: Option<&Preset>) -> Result<()> {
+    if input == Path::new("-") {
+        anyhow::bail!("Cannot pack a recording streamed to stdout, save it to a file first");
+    }
+    let recording = Recording::load(input).with_context(|| format!("Could not load {:?}", input))?;
+    let file = File::create(output).with_context(|| format!("Could not create {:?}", output))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(BufWriter::new(file), Compression::default()));
+    append_json(&mut archive, RECORDING_ENTRY, &serde_json::to_vec(&recording)?)?;
+    if let Some(preset) = preset {
+        append_json(&mut archive, PRESET_ENTRY, &serde_json::to_vec_pretty(preset)?)?;
+    }
+    let screenshots = screenshot::dir_for(input);
+    if screenshots.is_dir() {
+        archive.append_dir_all(SCREENSHOTS_DIR, &screenshots)?;
+    }
+    archive.into_inner()?.finish()?.flush()?;
+    tracing::info!("Packed {:?} into {:?}.", input, output);
+    Ok(())
+}
+
+fn append_json<W: Write>(archive: &mut tar::Builder<W>, name: &str, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    archive.append_data(&mut header, name, data)?;
+    Ok(())
+}
@@ -0,0 +1,353 @@
+use crate::config::Modifier;
+use crate::sound::Cue;
+use anyhow::Result;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Modifier used for the default hotkeys: Command on macOS, Control elsewhere.
+pub fn primary_modifier() -> Modifier {
+    if cfg!(target_os = "macos") {
+        Modifier::Cmd
+    } else {
+        Modifier::Ctrl
+    }
+}
+
+/// Target name of the release asset for this platform.
+pub fn release_target() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "macos"
+    } else if cfg!(target_os = "windows") {
+        "windows"
+    } else {
+        "linux"
+    }
+}
+
+/// Directory where recordings are saved and loaded from by default.
+///
+/// On Linux this follows the XDG base directory spec (`$XDG_DATA_HOME/macro/recordings`),
+/// elsewhere it is `~/Documents/Macros`.
+pub fn recordings_dir() -> PathBuf {
+    if cfg!(target_os = "linux") {
+        dirs::data_dir()
+            .unwrap_or(PathBuf::from("."))
+            .join("macro")
+            .join("recordings")
+    } else {
+        dirs::document_dir().unwrap_or(PathBuf::from(".")).join("Macros")
+    }
+}
+
+/// Directory holding the configuration file (`$XDG_CONFIG_HOME/macro` on Linux,
+/// `~/Library/Application Support/macro` on macOS, `%APPDATA%\macro` on Windows).
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir().unwrap_or(PathBuf::from(".")).join("macro")
+}
+
+/// Directory log files are written to by default (`$XDG_DATA_HOME/macro/logs`
+/// on Linux, `~/Library/Application Support/macro/logs` on macOS,
+/// `%LOCALAPPDATA%\macro\logs` on Windows).
+pub fn logs_dir() -> PathBuf {
+    dirs::data_local_dir().unwrap_or(PathBuf::from(".")).join("macro").join("logs")
+}
+
+/// Opens `path` in the file manager, or the app that handles it.
+pub fn open_path(path: &Path) -> std::io::Result<()> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    Command::new(opener).arg(path).spawn()?;
+    Ok(())
+}
+
+/// Sound file shipped with the system for `cue`, if it has one.
+pub fn system_sound(cue: Cue) -> Option<PathBuf> {
+    let (dir, name) = if cfg!(target_os = "macos") {
+        let name = match cue {
+            Cue::RecordingStarted => "Tink.aiff",
+            Cue::RecordingStopped => "Pop.aiff",
+            Cue::PlaybackStarted => "Morse.aiff",
+            Cue::PlaybackFinished => "Glass.aiff",
+            Cue::PlaybackFailed => "Basso.aiff",
+        };
+        (PathBuf::from("/System/Library/Sounds"), name)
+    } else if cfg!(target_os = "windows") {
+        let name = match cue {
+            Cue::RecordingStarted => "Speech On.wav",
+            Cue::RecordingStopped => "Speech Off.wav",
+            Cue::PlaybackStarted => "Windows Navigation Start.wav",
+            Cue::PlaybackFinished => "tada.wav",
+            Cue::PlaybackFailed => "Windows Critical Stop.wav",
+        };
+        (PathBuf::from(std::env::var_os("SystemRoot")?).join("Media"), name)
+    } else {
+        // The freedesktop sound theme most desktops install
+        let name = match cue {
+            Cue::RecordingStarted => "device-added.oga",
+            Cue::RecordingStopped => "device-removed.oga",
+            Cue::PlaybackStarted => "message.oga",
+            Cue::PlaybackFinished => "complete.oga",
+            Cue::PlaybackFailed => "dialog-error.oga",
+        };
+        (PathBuf::from("/usr/share/sounds/freedesktop/stereo"), name)
+    };
+    let path = dir.join(name);
+    path.exists().then_some(path)
+}
+
+/// Command that plays the sound file at `path` and exits once it is done.
+pub fn sound_command(path: &Path) -> Command {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("afplay")
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-Command", "(New-Object Media.SoundPlayer $args[0]).PlaySync()"]);
+        command
+    } else {
+        // PulseAudio's player, which PipeWire also provides
+        Command::new("paplay")
+    };
+    command.arg(path).stdout(Stdio::null()).stderr(Stdio::null());
+    command
+}
+
+/// Trimmed standard output of `program`, or `None` if it could not be run
+/// or failed.
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !text.is_empty()).then_some(text)
+}
+
+/// Version of the operating system, e.g. "14.5" on macOS, where it can be
+/// found out.
+pub fn os_version() -> Option<String> {
+    if cfg!(target_os = "macos") {
+        command_output("sw_vers", &["-productVersion"])
+    } else if cfg!(target_os = "windows") {
+        // "Microsoft Windows [Version 10.0.22631.3447]"
+        let text = command_output("cmd", &["/C", "ver"])?;
+        let version = text.split_once("Version ")?.1;
+        Some(version.trim_end_matches(']').to_string())
+    } else {
+        let release = std::fs::read_to_string("/etc/os-release").ok()?;
+        release
+            .lines()
+            .find_map(|line| line.strip_prefix("VERSION_ID="))
+            .map(|version| version.trim_matches('"').to_string())
+    }
+}
+
+/// Current keyboard layout, e.g. "com.apple.keylayout.US" on macOS or "us"
+/// on X11, where it can be found out.
+pub fn keyboard_layout() -> Option<String> {
+    if cfg!(target_os = "macos") {
+        command_output("defaults", &["read", "com.apple.HIToolbox", "AppleCurrentKeyboardLayoutInputSourceID"])
+    } else if cfg!(target_os = "linux") {
+        let query = command_output("setxkbmap", &["-query"])?;
+        query.lines().find_map(|line| line.strip_prefix("layout:")).map(|layout| layout.trim().to_string())
+    } else {
+        None
+    }
+}
+
+/// How long a held key waits before it starts repeating and the time
+/// between repeats, from the system's keyboard settings where they can be
+/// found out.
+pub fn key_repeat() -> Option<(Duration, Duration)> {
+    if cfg!(target_os = "macos") {
+        // Both kept in units of 15ms
+        let setting = |name| {
+            let ticks: u64 = command_output("defaults", &["read", "-g", name])?.parse().ok()?;
+            Some(Duration::from_millis(ticks * 15))
+        };
+        Some((setting("InitialKeyRepeat")?, setting("KeyRepeat")?))
+    } else if cfg!(target_os = "linux") {
+        // "  auto repeat delay:  660    repeat rate:  25"
+        let query = command_output("xset", &["q"])?;
+        let line = query.lines().find(|line| line.contains("auto repeat delay:"))?;
+        let mut numbers = line.split_whitespace().filter_map(|word| word.parse::<u32>().ok());
+        let (delay, rate) = (numbers.next()?, numbers.next()?);
+        Some((Duration::from_millis(delay.into()), Duration::from_secs(1) / rate.max(1)))
+    } else {
+        None
+    }
+}
+
+/// Code the input backend reports for function key F`number` from F13 on,
+/// which have no key name of their own.
+pub fn function_key_code(number: u32) -> Option<u32> {
+    let index = number.checked_sub(13).filter(|index| *index < 12)? as usize;
+    if cfg!(target_os = "macos") {
+        // Virtual key codes, which are not in order and stop at F20
+        [105, 107, 113, 106, 64, 79, 80, 90].get(index).copied()
+    } else if cfg!(target_os = "windows") {
+        // VK_F13 to VK_F24
+        Some(0x7C + index as u32)
+    } else {
+        // X keycodes, the evdev codes of KEY_F13 to KEY_F24 plus 8
+        Some(191 + index as u32)
+    }
+}
+
+/// Permissions the system has not granted yet, by name, which input capture
+/// and simulation need.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingPermissions(pub Vec<&'static str>);
+
+impl fmt::Display for MissingPermissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Missing permissions: {}", self.0.join(", "))
+    }
+}
+
+impl std::error::Error for MissingPermissions {}
+
+/// Checks that global input capture and simulation can work in this session.
+///
+/// Only X11 is supported: the Linux backend talks to the X server (XRecord
+/// for capture, XTest for simulation) and there is no Wayland backend, portal
+/// based or otherwise. Under Wayland that only reaches XWayland clients, and
+/// without XWayland it cannot work at all.
+#[cfg(target_os = "linux")]
+pub fn check_input_backend() -> Result<()> {
+    let has_x11 = std::env::var_os("DISPLAY").is_some();
+    let is_wayland = std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var("XDG_SESSION_TYPE").is_ok_and(|t| t == "wayland");
+
+    if !has_x11 {
+        if is_wayland {
+            anyhow::bail!(
+                "Wayland is not supported and XWayland is not running, so input cannot be captured or simulated. \
+                 Log in to an X11 session or enable XWayland."
+            );
+        }
+        anyhow::bail!("No X11 display found ($DISPLAY is not set).");
+    }
+    if is_wayland {
+        tracing::warn!("Wayland is not supported: only XWayland applications can be recorded and controlled.");
+    }
+    Ok(())
+}
+
+/// Checks that global input capture and simulation can work in this session.
+///
+/// macOS only delivers and accepts global events once the app has been granted
+/// Accessibility and Input Monitoring. Without them capture silently sees
+/// nothing, so explain how to grant them (offering to open System Settings when
+/// run from a terminal) and fail instead.
+#[cfg(target_os = "macos")]
+pub fn check_input_backend() -> Result<()> {
+    use std::io::{self, BufRead, IsTerminal, Write};
+
+    let missing = macos::missing_permissions();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe().unwrap_or_default();
+    eprintln!("Macro needs permissions that have not been granted yet:");
+    for permission in &missing {
+        eprintln!();
+        eprintln!("{}:", permission.name());
+        eprintln!("  1. Open System Settings -> Privacy & Security -> {}.", permission.name());
+        eprintln!("  2. Add {} (or your terminal app) with the + button and enable it.", exe.display());
+        eprintln!("  3. Restart the terminal so the change takes effect.");
+    }
+
+    if io::stdin().is_terminal() {
+        eprint!("\nOpen System Settings now? [y/N] ");
+        let _ = io::stderr().flush();
+        let mut answer = String::new();
+        io::stdin().lock().read_line(&mut answer)?;
+        if answer.trim().eq_ignore_ascii_case("y") {
+            for permission in &missing {
+                if let Err(e) = Command::new("open").arg(permission.settings_url()).status() {
+                    tracing::error!("Failed to open System Settings: {}", e);
+                }
+            }
+        }
+    }
+
+    Err(MissingPermissions(missing.iter().map(|p| p.name()).collect()).into())
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Permission {
+        Accessibility,
+        InputMonitoring,
+    }
+
+    impl Permission {
+        pub fn name(&self) -> &'static str {
+            match self {
+                Permission::Accessibility => "Accessibility",
+                Permission::InputMonitoring => "Input Monitoring",
+            }
+        }
+
+        pub fn settings_url(&self) -> &'static str {
+            match self {
+                Permission::Accessibility => {
+                    "x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility"
+                }
+                Permission::InputMonitoring => {
+                    "x-apple.systempreferences:com.apple.preference.security?Privacy_ListenEvent"
+                }
+            }
+        }
+    }
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    unsafe extern "C" {
+        fn AXIsProcessTrusted() -> bool;
+        fn CGPreflightListenEventAccess() -> bool;
+    }
+
+    pub fn missing_permissions() -> Vec<Permission> {
+        let mut missing = Vec::new();
+        // SAFETY: both functions only query the TCC database and take no arguments.
+        unsafe {
+            if !AXIsProcessTrusted() {
+                missing.push(Permission::Accessibility);
+            }
+            if !CGPreflightListenEventAccess() {
+                missing.push(Permission::InputMonitoring);
+            }
+        }
+        missing
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn check_input_backend() -> Result<()> {
+    Ok(())
+}
+
+/// Replaces the current process with `command`.
+///
+/// On Unix this is `exec`, so it only returns on failure. Windows has no
+/// equivalent, so the command is run as a child and this process exits with
+/// its status once it finishes.
+#[cfg(unix)]
+pub fn replace_process(mut command: Command) -> std::io::Error {
+    use std::os::unix::process::CommandExt;
+    command.exec()
+}
+
+#[cfg(not(unix))]
+pub fn replace_process(mut command: Command) -> std::io::Error {
+    match command.status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(0)),
+        Err(e) => e,
+    }
+}
@@ -1,245 +1,1386 @@
-use crate::event::SerializableEvent;
-use crate::config::{KeyMaps, Modifier};
-use anyhow::Result;
-use rdev::{listen, simulate, EventType, Key};
-use std::fs::File;
-use std::path::PathBuf;
+use crate::backend::{self, EventCallback, InputBackend};
+use crate::clock::{Clock, SystemClock};
+use crate::event::{KeyReplay, SerializableEvent, SerializableEventType};
+use crate::recording::{Recording, RecordingMetadata};
+use crate::remap::Remap;
+use crate::config::KeyMaps;
+use crate::display::{DisplayMapper, DisplayMapping, Transform};
+use crate::environment;
+use crate::history::{self, RunKind, RunTimer};
+use crate::key_repeat::{self, KeyRepeat};
+use crate::modifiers::ModifierState;
+use crate::platform;
+use crate::screenshot::{self, ScreenshotTrigger};
+use crate::worker::{self, WorkerCommand, WorkerReport};
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveTime};
+use rdev::{Button, EventType, Key};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead, BufReader};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::thread;
 use std::time::Duration;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
 
-use std::os::unix::process::CommandExt;
 use std::process::Command;
 use std::env;
 
-pub fn run_play(input_path: PathBuf, speed: f64, repeat_count: u32, repeat_interval: f64, keymaps: KeyMaps, immediate: bool) -> Result<()> {
-    log::info!("Preparing to play back from {:?}...", input_path);
-    
-    // Load events first to ensure file exists and is valid
-    let file = File::open(&input_path)?;
-    let events: Vec<SerializableEvent> = serde_json::from_reader(file)?;
-    log::info!("Loaded {} events.", events.len());
+#[derive(Debug, Clone)]
+pub struct PlaybackOptions {
+    pub speed: f64,
+    /// Multiplies `speed` for the gaps before mouse moves, clicks and scrolls.
+    pub mouse_speed: f64,
+    /// Multiplies `speed` for the gaps before key presses and releases.
+    pub keyboard_speed: f64,
+    pub repeat_count: u32,
+    pub repeat_interval: f64,
+    pub display_mapping: DisplayMapping,
+    /// Mirrors or rotates mouse positions about the center of their display.
+    pub transform: Transform,
+    /// Moves mouse positions by this many points, after `transform`.
+    pub offset: (f64, f64),
+    /// Stops playback once this much time has passed, even mid-repeat.
+    pub repeat_for: Option<Duration>,
+    /// Stops playback at this local time of day, even mid-repeat.
+    pub repeat_until: Option<NaiveTime>,
+    /// Whether key presses replay as physical keys or as the recorded characters.
+    pub key_replay: KeyReplay,
+    /// Saves a screenshot into this directory when an assertion fails.
+    pub screenshot_on_failure: Option<PathBuf>,
+    /// Wait before the first event, to switch to the window the macro targets.
+    pub countdown: Duration,
+    /// Moves each event up to this much earlier or later at random, so
+    /// repeated runs do not have machine-perfect timing. The offsets do not
+    /// add up, so the recording still takes as long as it did.
+    pub humanize: Duration,
+    /// First event to play. Earlier events are skipped.
+    pub from: Option<PlaybackPosition>,
+    /// Last event to play. Later events are skipped.
+    pub to: Option<PlaybackPosition>,
+    /// How long keys and buttons stay down, which `speed` would otherwise
+    /// shrink below what some apps register.
+    pub hold: HoldTiming,
+    /// Keys, buttons and scrolling played as something else.
+    pub remap: Remap,
+    /// Limits that abort a run gone wrong, e.g. an unattended endless loop.
+    pub watchdog: Watchdog,
+    /// Refuses to play a recording made on a machine that differs in ways
+    /// likely to break it, rather than only warning.
+    pub strict: bool,
+}
+
+impl Default for PlaybackOptions {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            mouse_speed: 1.0,
+            keyboard_speed: 1.0,
+            repeat_count: 1,
+            repeat_interval: 0.0,
+            display_mapping: DisplayMapping::Same,
+            transform: Transform::None,
+            offset: (0.0, 0.0),
+            repeat_for: None,
+            repeat_until: None,
+            key_replay: KeyReplay::Auto,
+            screenshot_on_failure: None,
+            countdown: Duration::ZERO,
+            humanize: Duration::ZERO,
+            from: None,
+            to: None,
+            hold: HoldTiming::Scaled,
+            remap: Remap::default(),
+            watchdog: Watchdog::default(),
+            strict: false,
+        }
+    }
+}
+
+impl PlaybackOptions {
+    /// Command line flags for `macro play` that reproduce these options.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--speed".to_string(),
+            self.speed.to_string(),
+            "--repeat-count".to_string(),
+            self.repeat_count.to_string(),
+            "--repeat-interval".to_string(),
+            self.repeat_interval.to_string(),
+            "--display-mapping".to_string(),
+            self.display_mapping.to_string(),
+            "--key-replay".to_string(),
+            self.key_replay.to_string(),
+        ];
+        if self.transform != Transform::None {
+            args.push("--transform".to_string());
+            args.push(self.transform.to_string());
+        }
+        if self.offset.0 != 0.0 {
+            args.push(format!("--offset-x={}", self.offset.0));
+        }
+        if self.offset.1 != 0.0 {
+            args.push(format!("--offset-y={}", self.offset.1));
+        }
+        if self.mouse_speed != 1.0 {
+            args.push("--mouse-speed".to_string());
+            args.push(self.mouse_speed.to_string());
+        }
+        if self.keyboard_speed != 1.0 {
+            args.push("--keyboard-speed".to_string());
+            args.push(self.keyboard_speed.to_string());
+        }
+        if let Some(repeat_for) = self.repeat_for {
+            args.push("--repeat-for".to_string());
+            args.push(humantime::format_duration(repeat_for).to_string());
+        }
+        if let Some(repeat_until) = self.repeat_until {
+            args.push("--repeat-until".to_string());
+            args.push(repeat_until.format("%H:%M:%S").to_string());
+        }
+        if let Some(dir) = &self.screenshot_on_failure {
+            args.push("--screenshot-on-failure".to_string());
+            args.push(dir.display().to_string());
+        }
+        if !self.countdown.is_zero() {
+            args.push("--countdown".to_string());
+            args.push(humantime::format_duration(self.countdown).to_string());
+        }
+        if !self.humanize.is_zero() {
+            args.push("--humanize".to_string());
+            args.push(humantime::format_duration(self.humanize).to_string());
+        }
+        if self.hold != HoldTiming::Scaled {
+            args.push("--hold".to_string());
+            args.push(self.hold.to_string());
+        }
+        for (flag, position) in [("from", &self.from), ("to", &self.to)] {
+            match position {
+                Some(PlaybackPosition::Checkpoint(name)) => {
+                    args.push(format!("--{}-checkpoint", flag));
+                    args.push(name.clone());
+                }
+                // `--segment` sets both ends
+                Some(PlaybackPosition::Segment(name)) if flag == "from" => {
+                    args.push("--segment".to_string());
+                    args.push(name.clone());
+                }
+                Some(PlaybackPosition::Segment(_)) => {}
+                Some(position) => {
+                    args.push(format!("--{}", flag));
+                    args.push(position.to_string());
+                }
+                None => {}
+            }
+        }
+        args.extend(self.remap.to_args());
+        args.extend(self.watchdog.to_args());
+        if self.strict {
+            args.push("--strict".to_string());
+        }
+        args
+    }
 
-    if speed != 1.0 {
-        log::info!("Playback speed: {:.2}x", speed);
+    /// Speed for the gap before `event`, with the mouse or keyboard
+    /// multiplier applied.
+    pub fn speed_for(&self, event: &SerializableEventType) -> f64 {
+        match event {
+            SerializableEventType::MouseMove { .. }
+            | SerializableEventType::ButtonPress(_)
+            | SerializableEventType::ButtonRelease(_)
+            | SerializableEventType::Wheel { .. }
+            | SerializableEventType::SmoothWheel { .. } => self.speed * self.mouse_speed,
+            SerializableEventType::KeyPress(_)
+            | SerializableEventType::KeyRelease(_)
+            | SerializableEventType::KeyPressText { .. }
+            | SerializableEventType::Hold { .. } => self.speed * self.keyboard_speed,
+            _ => self.speed,
+        }
     }
-    if repeat_count == 0 {
-        log::info!("Repeat: Infinite");
-    } else if repeat_count > 1 {
-        log::info!("Repeat: {} times", repeat_count);
+
+    /// How long playback may run, combining `repeat_for` with the time left
+    /// until `repeat_until`.
+    fn time_limit(&self) -> Option<Duration> {
+        let until = self.repeat_until.map(duration_until);
+        match (self.repeat_for, until) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
     }
-    if repeat_interval > 0.0 {
-        log::info!("Repeat Interval: {:.2}s", repeat_interval);
+}
+
+/// Cloneable handle used to stop or pause playback from another thread.
+#[derive(Clone, Default)]
+pub struct PlaybackControl {
+    stop: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+}
+
+impl PlaybackControl {
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
     }
 
-    if immediate {
-        log::info!("Starting playback immediately...");
-        log::info!("Stop Playback: {:?} + {:?}", keymaps.stop_playback.modifiers, keymaps.stop_playback.trigger);
-        
-        // Shared flag to stop playback
-        let stop_flag = Arc::new(std::sync::atomic::AtomicBool::new(false));
-        
-        // Spawn a thread for playback
-        let events_for_thread = events.clone();
-        let stop_flag_play = stop_flag.clone();
-        thread::spawn(move || {
-            do_playback(&events_for_thread, speed, repeat_count, repeat_interval, stop_flag_play);
-            std::process::exit(0);
-        });
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::SeqCst)
+    }
 
-        // Listen for stop hotkey
-        let stop_flag_listen = stop_flag.clone();
-        let keymaps_clone = keymaps.clone();
-        
-        struct StopState {
-            cmd_pressed: bool,
-            alt_pressed: bool,
-            ctrl_pressed: bool,
-            shift_pressed: bool,
+    /// Holds playback before the next event until [`resume`](Self::resume) is called.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Clears a stop or pause, so the next run with this control plays.
+    pub fn reset(&self) {
+        self.stop.store(false, Ordering::SeqCst);
+        self.paused.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Point in a recording to start or end playback at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlaybackPosition {
+    /// Zero-based index of an event.
+    Index(usize),
+    /// Time from the start of the recording.
+    Time(Duration),
+    /// The checkpoint event with this name.
+    Checkpoint(String),
+    /// The segment with this name: its start when playing from it, and its
+    /// end when playing to it.
+    Segment(String),
+}
+
+impl FromStr for PlaybackPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(index) = s.parse() {
+            return Ok(PlaybackPosition::Index(index));
         }
+        humantime::parse_duration(s)
+            .map(PlaybackPosition::Time)
+            .map_err(|_| format!("expected an event index like 120 or a time like 1m30s, got '{}'", s))
+    }
+}
 
-        let state = Arc::new(Mutex::new(StopState {
-            cmd_pressed: false,
-            alt_pressed: false,
-            ctrl_pressed: false,
-            shift_pressed: false,
-        }));
+impl fmt::Display for PlaybackPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlaybackPosition::Index(index) => write!(f, "{}", index),
+            PlaybackPosition::Time(time) => write!(f, "{}", humantime::format_duration(*time)),
+            PlaybackPosition::Checkpoint(name) => write!(f, "checkpoint '{}'", name),
+            PlaybackPosition::Segment(name) => write!(f, "segment '{}'", name),
+        }
+    }
+}
 
-        let state_clone = state.clone();
-        
-        if let Err(error) = listen(move |event| {
-            let mut state = state_clone.lock().unwrap();
-
-            // Update modifiers
-            match event.event_type {
-                EventType::KeyPress(Key::MetaLeft) | EventType::KeyPress(Key::MetaRight) => state.cmd_pressed = true,
-                EventType::KeyRelease(Key::MetaLeft) | EventType::KeyRelease(Key::MetaRight) => state.cmd_pressed = false,
-                EventType::KeyPress(Key::Alt) | EventType::KeyPress(Key::AltGr) => state.alt_pressed = true,
-                EventType::KeyRelease(Key::Alt) | EventType::KeyRelease(Key::AltGr) => state.alt_pressed = false,
-                EventType::KeyPress(Key::ControlLeft) | EventType::KeyPress(Key::ControlRight) => state.ctrl_pressed = true,
-                EventType::KeyRelease(Key::ControlLeft) | EventType::KeyRelease(Key::ControlRight) => state.ctrl_pressed = false,
-                EventType::KeyPress(Key::ShiftLeft) | EventType::KeyPress(Key::ShiftRight) => state.shift_pressed = true,
-                EventType::KeyRelease(Key::ShiftLeft) | EventType::KeyRelease(Key::ShiftRight) => state.shift_pressed = false,
-                _ => {}
-            }
+/// Safety limits for long unattended runs. Unlike `repeat_for`, which ends
+/// a run normally, reaching one of these fails playback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Watchdog {
+    /// Fails playback once it has run this long, counted after the countdown.
+    pub max_total_duration: Option<Duration>,
+    /// Fails playback before starting a repeat beyond this many.
+    pub max_total_repeats: Option<u32>,
+    /// Fails playback once this many events in a row could not be sent,
+    /// e.g. after input access was revoked.
+    pub max_consecutive_failures: Option<u32>,
+}
 
-            // Check stop hotkey
-            let check_modifiers = |modifiers: &[Modifier]| -> bool {
-                for m in modifiers {
-                    match m {
-                        Modifier::Cmd => if !state.cmd_pressed { return false; },
-                        Modifier::Alt => if !state.alt_pressed { return false; },
-                        Modifier::Ctrl => if !state.ctrl_pressed { return false; },
-                        Modifier::Shift => if !state.shift_pressed { return false; },
-                    }
-                }
-                true
-            };
+impl Watchdog {
+    /// Command line flags for `macro play` that set these limits.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(duration) = self.max_total_duration {
+            args.push("--max-total-duration".to_string());
+            args.push(humantime::format_duration(duration).to_string());
+        }
+        if let Some(repeats) = self.max_total_repeats {
+            args.push("--max-total-repeats".to_string());
+            args.push(repeats.to_string());
+        }
+        if let Some(failures) = self.max_consecutive_failures {
+            args.push("--max-consecutive-failures".to_string());
+            args.push(failures.to_string());
+        }
+        args
+    }
+}
 
-            if let EventType::KeyPress(key) = event.event_type {
-                if key == keymaps_clone.stop_playback.trigger && check_modifiers(&keymaps_clone.stop_playback.modifiers) {
-                    log::info!("Stop hotkey detected. Stopping playback...");
-                    stop_flag_listen.store(true, std::sync::atomic::Ordering::SeqCst);
-                    std::process::exit(0);
-                }
-            }
-        }) {
-             log::error!("Error: {:?}", error);
+/// How long playback keeps each key or mouse button down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HoldTiming {
+    /// Scaled by the playback speed like every other delay.
+    #[default]
+    Scaled,
+    /// As long as it was held while recording, whatever the speed.
+    Recorded,
+    /// Scaled, but never shorter than this.
+    AtLeast(Duration),
+}
+
+impl FromStr for HoldTiming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scaled" => Ok(HoldTiming::Scaled),
+            "recorded" => Ok(HoldTiming::Recorded),
+            other => humantime::parse_duration(other)
+                .map(HoldTiming::AtLeast)
+                .map_err(|_| format!("expected 'scaled', 'recorded' or a minimum like 30ms, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for HoldTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HoldTiming::Scaled => write!(f, "scaled"),
+            HoldTiming::Recorded => write!(f, "recorded"),
+            HoldTiming::AtLeast(hold) => write!(f, "{}", humantime::format_duration(*hold)),
+        }
+    }
+}
+
+/// Remembers when each key and button went down, so its release can be put
+/// off until it has been held as long as [`HoldTiming`] asks. Putting one
+/// off pushes back the rest of the timeline, so events never swap places.
+struct HoldTimer {
+    timing: HoldTiming,
+    /// Time into the recording of the current event.
+    recorded_at: Duration,
+    /// Input still down, with when it was pressed on the playback timeline
+    /// and in the recording.
+    pressed: Vec<(Input, Duration, Duration)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Input {
+    Key(Key),
+    Button(Button),
+}
+
+impl HoldTimer {
+    fn new(timing: HoldTiming) -> Self {
+        Self {
+            timing,
+            recorded_at: Duration::ZERO,
+            pressed: Vec::new(),
+        }
+    }
+
+    /// When `event`, recorded `delay` after the one before it and scheduled
+    /// for `due`, should actually play.
+    fn due(&mut self, event: &SerializableEvent, delay: Duration, due: Duration) -> Duration {
+        self.recorded_at += delay;
+        if self.timing == HoldTiming::Scaled {
+            return due;
+        }
+        let (input, is_press) = match event.event_type {
+            SerializableEventType::KeyPress(key) | SerializableEventType::KeyPressText { key, .. } => (Input::Key(key), true),
+            SerializableEventType::KeyRelease(key) => (Input::Key(key), false),
+            SerializableEventType::ButtonPress(button) => (Input::Button(button), true),
+            SerializableEventType::ButtonRelease(button) => (Input::Button(button), false),
+            _ => return due,
+        };
+        if is_press {
+            self.pressed.retain(|(held, ..)| *held != input);
+            self.pressed.push((input, due, self.recorded_at));
+            return due;
+        }
+        let Some(position) = self.pressed.iter().position(|(held, ..)| *held == input) else {
+            return due;
+        };
+        let (_, pressed_due, pressed_at) = self.pressed.remove(position);
+        let hold = match self.timing {
+            HoldTiming::Scaled => Duration::ZERO,
+            HoldTiming::Recorded => self.recorded_at - pressed_at,
+            HoldTiming::AtLeast(hold) => hold,
+        };
+        due.max(pressed_due + hold)
+    }
+}
+
+/// Indices of the events between `from` and `to`, both inclusive. Times
+/// select the events recorded within them.
+pub fn event_range(recording: &Recording, from: Option<&PlaybackPosition>, to: Option<&PlaybackPosition>) -> Result<Range<usize>> {
+    let len = recording.events.len();
+    let times: Vec<Duration> = recording
+        .events
+        .iter()
+        .scan(Duration::ZERO, |at, event| {
+            *at += event.delay();
+            Some(*at)
+        })
+        .collect();
+    let checkpoint = |name: &str| {
+        recording
+            .events
+            .iter()
+            .position(|event| matches!(&event.event_type, SerializableEventType::Checkpoint { name: n } if n == name))
+            .ok_or_else(|| anyhow::anyhow!("No checkpoint named '{}' in the recording", name))
+    };
+    let is_segment = |event: &SerializableEvent| matches!(event.event_type, SerializableEventType::Segment { .. });
+    let segment = |name: &str| {
+        recording
+            .events
+            .iter()
+            .position(|event| matches!(&event.event_type, SerializableEventType::Segment { name: n } if n == name))
+            .ok_or_else(|| anyhow::anyhow!("No segment named '{}' in the recording", name))
+    };
+    let start = match from {
+        None => 0,
+        Some(&PlaybackPosition::Index(index)) if index < len => index,
+        Some(&PlaybackPosition::Index(index)) => anyhow::bail!("--from {} is past the last event ({})", index, len.saturating_sub(1)),
+        Some(&PlaybackPosition::Time(time)) => times.partition_point(|at| *at < time),
+        Some(PlaybackPosition::Checkpoint(name)) => checkpoint(name)?,
+        Some(PlaybackPosition::Segment(name)) => segment(name)?,
+    };
+    let end = match to {
+        None => len,
+        Some(&PlaybackPosition::Index(index)) => (index + 1).min(len),
+        Some(&PlaybackPosition::Time(time)) => times.partition_point(|at| *at <= time),
+        Some(PlaybackPosition::Checkpoint(name)) => checkpoint(name)? + 1,
+        Some(PlaybackPosition::Segment(name)) => {
+            let start = segment(name)? + 1;
+            recording.events[start..].iter().position(is_segment).map_or(len, |next| start + next)
+        }
+    };
+    if start >= end {
+        anyhow::bail!("No events to play between {} and {}", display_position(from), display_position(to));
+    }
+    Ok(start..end)
+}
+
+fn display_position(position: Option<&PlaybackPosition>) -> String {
+    position.map_or_else(|| "the end".to_string(), |position| position.to_string())
+}
+
+/// Position reached during playback, reported after each simulated event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaybackProgress {
+    /// Zero-based index of the current repeat.
+    pub repeat: u32,
+    /// Number of events played so far in the current repeat.
+    pub events_played: usize,
+    pub total_events: usize,
+}
+
+/// How a playback run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaybackOutcome {
+    Completed,
+    Stopped,
+    /// An error, such as an assertion that did not hold, aborted playback.
+    Failed,
+}
+
+impl fmt::Display for PlaybackOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlaybackOutcome::Completed => write!(f, "completed"),
+            PlaybackOutcome::Stopped => write!(f, "stopped"),
+            PlaybackOutcome::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+pub(crate) type Hook<F> = Option<Box<F>>;
+
+/// Callbacks invoked on the playback thread, so they should return quickly.
+#[derive(Default)]
+struct PlaybackHooks {
+    on_start: Hook<dyn Fn() + Send + Sync>,
+    on_event: Hook<dyn Fn(&SerializableEvent) + Send + Sync>,
+    on_repeat_complete: Hook<dyn Fn(u32) + Send + Sync>,
+    on_finish: Hook<dyn Fn(PlaybackOutcome) + Send + Sync>,
+    on_error: Hook<dyn Fn(&anyhow::Error) + Send + Sync>,
+}
+
+/// Replays a [`Recording`] through an input backend.
+pub struct Player {
+    backend: Arc<dyn InputBackend>,
+    clock: Arc<dyn Clock>,
+    options: PlaybackOptions,
+    control: PlaybackControl,
+    hooks: PlaybackHooks,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Player {
+    pub fn new() -> Self {
+        Self {
+            backend: backend::default_backend(),
+            clock: Arc::new(SystemClock::new()),
+            options: PlaybackOptions::default(),
+            control: PlaybackControl::default(),
+            hooks: PlaybackHooks::default(),
+        }
+    }
+
+    pub fn backend(mut self, backend: Arc<dyn InputBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn options(mut self, options: PlaybackOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.options.speed = speed;
+        self
+    }
+
+    /// Multiplies the speed of mouse moves, clicks and scrolls.
+    pub fn mouse_speed(mut self, mouse_speed: f64) -> Self {
+        self.options.mouse_speed = mouse_speed;
+        self
+    }
+
+    /// Multiplies the speed of key presses and releases.
+    pub fn keyboard_speed(mut self, keyboard_speed: f64) -> Self {
+        self.options.keyboard_speed = keyboard_speed;
+        self
+    }
+
+    /// Number of times to play the recording, 0 for infinite.
+    pub fn repeat_count(mut self, repeat_count: u32) -> Self {
+        self.options.repeat_count = repeat_count;
+        self
+    }
+
+    /// Pause between repeats, in seconds.
+    pub fn repeat_interval(mut self, repeat_interval: f64) -> Self {
+        self.options.repeat_interval = repeat_interval;
+        self
+    }
+
+    pub fn display_mapping(mut self, display_mapping: DisplayMapping) -> Self {
+        self.options.display_mapping = display_mapping;
+        self
+    }
+
+    /// Waits this long before the first event.
+    pub fn countdown(mut self, countdown: Duration) -> Self {
+        self.options.countdown = countdown;
+        self
+    }
+
+    /// Moves each event up to this much earlier or later at random.
+    pub fn humanize(mut self, humanize: Duration) -> Self {
+        self.options.humanize = humanize;
+        self
+    }
+
+    /// Called once before the first event is played.
+    pub fn on_start(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        self.hooks.on_start = Some(Box::new(hook));
+        self
+    }
+
+    /// Called after each event is successfully simulated.
+    pub fn on_event(mut self, hook: impl Fn(&SerializableEvent) + Send + Sync + 'static) -> Self {
+        self.hooks.on_event = Some(Box::new(hook));
+        self
+    }
+
+    /// Called with the number of completed repeats each time the recording finishes.
+    pub fn on_repeat_complete(mut self, hook: impl Fn(u32) + Send + Sync + 'static) -> Self {
+        self.hooks.on_repeat_complete = Some(Box::new(hook));
+        self
+    }
+
+    /// Called once when playback ends, however it ends: with `Completed`
+    /// after the last repeat, `Stopped` when stopped through the control or
+    /// a hotkey, or `Failed` when an error aborted it. On failure `on_error`
+    /// has already been called with that error.
+    pub fn on_finish(mut self, hook: impl Fn(PlaybackOutcome) + Send + Sync + 'static) -> Self {
+        self.hooks.on_finish = Some(Box::new(hook));
+        self
+    }
+
+    /// Called when an event cannot be simulated, after which playback carries
+    /// on, or when an error such as a failed assertion aborts playback, just
+    /// before `on_finish`.
+    pub fn on_error(mut self, hook: impl Fn(&anyhow::Error) + Send + Sync + 'static) -> Self {
+        self.hooks.on_error = Some(Box::new(hook));
+        self
+    }
+
+    /// Plays under `control` instead of a control of its own, e.g. one a
+    /// stop hotkey listener already holds.
+    pub fn with_control(mut self, control: PlaybackControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    pub fn control(&self) -> PlaybackControl {
+        self.control.clone()
+    }
+
+    /// Waits out `countdown` on the current thread, held by pauses like
+    /// playback. Returns false if stopped before it ran out.
+    pub fn count_down(&self, countdown: Duration) -> bool {
+        if countdown.is_zero() {
+            return true;
+        }
+        tracing::info!("Starting in {:.1}s...", countdown.as_secs_f64());
+        let mut due = self.clock.elapsed() + countdown;
+        sleep_until_unless_stopped(self.clock.as_ref(), &mut due, None, &self.control)
+    }
+
+    /// Plays the recording on the current thread until it completes or is
+    /// stopped. Fails if an assertion in the recording does not hold.
+    pub fn play(&self, recording: &Recording) -> Result<()> {
+        self.play_with_progress(recording, &mut |_| {})
+    }
+
+    /// Like [`play`](Self::play), calling `on_progress` after every simulated event.
+    pub fn play_with_progress(&self, recording: &Recording, on_progress: &mut dyn FnMut(PlaybackProgress)) -> Result<()> {
+        run_playback(self.backend.as_ref(), self.clock.as_ref(), recording, &self.options, &self.control, &self.hooks, on_progress)
+    }
+
+    /// Plays the recording on a background thread.
+    pub fn spawn(self, recording: Recording) -> thread::JoinHandle<Result<()>> {
+        thread::spawn(move || self.play(&recording))
+    }
+}
+
+/// Plays the recordings at `input_paths`, all at the same time when there
+/// are several, with their events interleaved by [`Recording::interleave`].
+///
+/// With `json_progress`, playback started immediately prints a
+/// [`WorkerReport`] line on stdout for its progress, errors and outcome.
+/// `json_result` is passed on to the process playback is handed to once
+/// the start hotkey is pressed. Returns whether playback completed or was
+/// stopped.
+pub fn run_play(backend: Arc<dyn InputBackend>, input_paths: Vec<PathBuf>, options: PlaybackOptions, keymaps: KeyMaps, immediate: bool, json_progress: bool, json_result: bool) -> Result<PlaybackOutcome> {
+    tracing::info!("Preparing to play back from {:?}...", input_paths);
+    platform::check_input_backend()?;
+    let Some(input_path) = input_paths.first().cloned() else {
+        anyhow::bail!("No recording to play");
+    };
+
+    if input_path == Path::new("-") {
+        if input_paths.len() > 1 {
+            anyhow::bail!("--parallel is not supported when playing from stdin");
         }
-        return Ok(());
+        return run_play_stream(backend, options, keymaps);
+    }
+
+    // Load events first to ensure file exists and is valid
+    let recordings = input_paths
+        .iter()
+        .map(|path| Recording::load(path).with_context(|| format!("Could not load {:?}", path)))
+        .collect::<Result<Vec<_>>>()?;
+    let recording = Recording::interleave(recordings)?;
+    tracing::info!("Loaded {} events.", recording.events.len());
+    environment::check(&recording, backend.as_ref(), options.strict)?;
+    if options.from.is_some() || options.to.is_some() {
+        let range = event_range(&recording, options.from.as_ref(), options.to.as_ref())?;
+        tracing::info!("Playing events {} to {}.", range.start, range.end - 1);
+    }
+
+    if options.speed != 1.0 {
+        tracing::info!("Playback speed: {:.2}x", options.speed);
+    }
+    if options.repeat_count == 0 {
+        tracing::info!("Repeat: Infinite");
+    } else if options.repeat_count > 1 {
+        tracing::info!("Repeat: {} times", options.repeat_count);
+    }
+    if options.repeat_interval > 0.0 {
+        tracing::info!("Repeat Interval: {:.2}s", options.repeat_interval);
+    }
+    if options.mouse_speed != 1.0 {
+        tracing::info!("Mouse speed: {:.2}x", options.mouse_speed);
+    }
+    if options.keyboard_speed != 1.0 {
+        tracing::info!("Keyboard speed: {:.2}x", options.keyboard_speed);
+    }
+    if options.display_mapping != DisplayMapping::Same {
+        tracing::info!("Display Mapping: {}", options.display_mapping);
+    }
+    if options.transform != Transform::None {
+        tracing::info!("Transform: {}", options.transform);
+    }
+    if options.offset != (0.0, 0.0) {
+        tracing::info!("Offset: {:+}, {:+}", options.offset.0, options.offset.1);
     }
+    if options.hold != HoldTiming::Scaled {
+        tracing::info!("Hold: {}", options.hold);
+    }
+
+    if immediate {
+        tracing::info!("Starting playback immediately...");
+        tracing::info!("Stop Playback: {:?} + {:?}", keymaps.stop_playback.modifiers, keymaps.stop_playback.trigger);
+        tracing::info!("Pause Playback: {:?} + {:?}", keymaps.pause.modifiers, keymaps.pause.trigger);
+        
+        let timer = RunTimer::start(RunKind::Play, Some(&input_path), options.to_args());
+
+        // Spawn a thread for playback
+        let mut player = Player::new().backend(backend.clone()).options(options);
+        if json_progress {
+            player = player
+                .on_error(|e| worker::report(&WorkerReport::Error { message: format!("{:#}", e) }))
+                .on_finish(|outcome| worker::report(&WorkerReport::Finished { outcome }));
+            worker::report_heartbeats(Duration::from_secs(1));
+        }
+        let control = player.control();
+        let events_played = Arc::new(AtomicUsize::new(0));
+        let progress_events_played = events_played.clone();
+        let playback = thread::spawn(move || {
+            player.play_with_progress(&recording, &mut |progress| {
+                progress_events_played.store(progress.events_played, Ordering::SeqCst);
+                if json_progress {
+                    worker::report(&WorkerReport::Progress {
+                        repeat: progress.repeat,
+                        events_played: progress.events_played,
+                        total_events: progress.total_events,
+                    });
+                }
+            })
+        });
+
+        // The tray app controls its workers over stdin
+        let command_control = control.clone();
+        worker::listen_for_commands(BufReader::new(io::stdin()), move |command| match command {
+            WorkerCommand::Pause => command_control.pause(),
+            WorkerCommand::Resume => command_control.resume(),
+            WorkerCommand::Stop => command_control.stop(),
+            WorkerCommand::Status => worker::report(&WorkerReport::Status {
+                paused: command_control.is_paused(),
+                events: events_played.load(Ordering::SeqCst),
+            }),
+        });
 
-    log::info!("Waiting for start hotkey: {:?} + {:?}", keymaps.start_playback.modifiers, keymaps.start_playback.trigger);
+        spawn_stop_listener(backend, keymaps, control.clone());
 
-    struct PlayState {
-        cmd_pressed: bool,
-        alt_pressed: bool,
-        ctrl_pressed: bool,
-        shift_pressed: bool,
+        // Playback finishing (or being stopped) ends the worker
+        let result = playback
+            .join()
+            .map_err(|_| anyhow::anyhow!("Playback thread panicked"))
+            .and_then(|result| result);
+        history::log(&timer.finish(&result, control.is_stopped()));
+        return result.map(|()| if control.is_stopped() { PlaybackOutcome::Stopped } else { PlaybackOutcome::Completed });
     }
 
-    let state = Arc::new(Mutex::new(PlayState {
-        cmd_pressed: false,
-        alt_pressed: false,
-        ctrl_pressed: false,
-        shift_pressed: false,
-    }));
+    tracing::info!("Waiting for start hotkey: {:?} + {:?}", keymaps.start_playback.modifiers, keymaps.start_playback.trigger);
 
-    let state_clone = state.clone();
-    let input_path_clone = input_path.clone();
+    let mut modifiers = ModifierState::from_os();
+    let (error_tx, error_rx) = mpsc::channel::<anyhow::Error>();
+    let listen_error_tx = error_tx.clone();
 
     // Spawn the listener in a background thread
     thread::spawn(move || {
-        if let Err(error) = listen(move |event| {
-            let mut state = state_clone.lock().unwrap();
-
-            // Update modifiers
-            match event.event_type {
-                EventType::KeyPress(Key::MetaLeft) | EventType::KeyPress(Key::MetaRight) => state.cmd_pressed = true,
-                EventType::KeyRelease(Key::MetaLeft) | EventType::KeyRelease(Key::MetaRight) => state.cmd_pressed = false,
-                EventType::KeyPress(Key::Alt) | EventType::KeyPress(Key::AltGr) => state.alt_pressed = true,
-                EventType::KeyRelease(Key::Alt) | EventType::KeyRelease(Key::AltGr) => state.alt_pressed = false,
-                EventType::KeyPress(Key::ControlLeft) | EventType::KeyPress(Key::ControlRight) => state.ctrl_pressed = true,
-                EventType::KeyRelease(Key::ControlLeft) | EventType::KeyRelease(Key::ControlRight) => state.ctrl_pressed = false,
-                EventType::KeyPress(Key::ShiftLeft) | EventType::KeyPress(Key::ShiftRight) => state.shift_pressed = true,
-                EventType::KeyRelease(Key::ShiftLeft) | EventType::KeyRelease(Key::ShiftRight) => state.shift_pressed = false,
-                _ => {}
-            }
+        if let Err(error) = backend.listen(Box::new(move |event| {
+            modifiers.update(&event.event_type);
 
             // Check Hotkey
-            let check_modifiers = |modifiers: &[Modifier]| -> bool {
-                for m in modifiers {
-                    match m {
-                        Modifier::Cmd => if !state.cmd_pressed { return false; },
-                        Modifier::Alt => if !state.alt_pressed { return false; },
-                        Modifier::Ctrl => if !state.ctrl_pressed { return false; },
-                        Modifier::Shift => if !state.shift_pressed { return false; },
-                    }
-                }
-                true
-            };
-
             if let EventType::KeyPress(key) = event.event_type {
-                if key == keymaps.start_playback.trigger && check_modifiers(&keymaps.start_playback.modifiers) {
-                    log::info!("Hotkeys detected. Switching to playback process...");
+                if modifiers.matches(&keymaps.start_playback, key) {
+                    tracing::info!("Hotkeys detected. Switching to playback process...");
                     
                     // Replace current process with new one running in immediate mode
                     let exe = env::current_exe().unwrap();
-                    let err = Command::new(exe)
+                    let mut command = Command::new(exe);
+                    command
                         .arg("play")
-                        .arg(input_path_clone.to_str().unwrap())
-                        .arg("--speed")
-                        .arg(speed.to_string())
-                        .arg("--repeat-count")
-                        .arg(repeat_count.to_string())
-                        .arg("--repeat-interval")
-                        .arg(repeat_interval.to_string())
-                        .arg("--immediate")
-                        .exec();
+                        .arg(&input_path)
+                        .args(input_paths[1..].iter().flat_map(|path| [Path::new("--parallel"), path]))
+                        .args(options.to_args())
+                        .args(json_progress.then_some("--json-progress"))
+                        .args(json_result.then_some("--json-result"))
+                        .arg("--immediate");
+                    let err = platform::replace_process(command);
 
                     // If exec returns, it failed
-                    log::error!("Failed to exec: {:?}", err);
-                    std::process::exit(1);
+                    tracing::error!("Failed to exec: {:?}", err);
+                    let _ = error_tx.send(err.into());
                 }
             }
-        }) {
-            log::error!("Listen error: {:?}", error);
+        })) {
+            tracing::error!("Listen error: {:?}", error);
+            let _ = listen_error_tx.send(error);
         }
     });
 
-    // Keep the main thread alive but doing nothing
+    // Wait until the listener replaces this process, or report why it could not
+    match error_rx.recv() {
+        Ok(error) => Err(error),
+        Err(_) => Ok(PlaybackOutcome::Completed),
+    }
+}
+
+/// Plays events piped in on stdin as they arrive. Reading from a stream
+/// cannot wait for the start hotkey or repeat, so playback begins immediately.
+fn run_play_stream(backend: Arc<dyn InputBackend>, options: PlaybackOptions, keymaps: KeyMaps) -> Result<PlaybackOutcome> {
+    if options.repeat_count != 1 {
+        anyhow::bail!("--repeat-count is not supported when playing from stdin");
+    }
+    if options.from.is_some() || options.to.is_some() {
+        anyhow::bail!("--from and --to are not supported when playing from stdin");
+    }
+    tracing::info!("Playing events from stdin...");
+    tracing::info!("Stop Playback: {:?} + {:?}", keymaps.stop_playback.modifiers, keymaps.stop_playback.trigger);
+
+    let control = PlaybackControl::default();
+    spawn_stop_listener(backend.clone(), keymaps, control.clone());
+    play_stream(backend.as_ref(), &SystemClock::new(), BufReader::new(io::stdin()), &options, &control)?;
+    Ok(if control.is_stopped() { PlaybackOutcome::Stopped } else { PlaybackOutcome::Completed })
+}
+
+/// Plays newline-delimited JSON events as they are read.
+///
+/// Each event is scheduled relative to the start of the stream, so events
+/// that already spent their delay in transit (e.g. piped live from
+/// `macro record -`) are not delayed a second time.
+pub fn play_stream(backend: &dyn InputBackend, clock: &dyn Clock, reader: impl BufRead + Send + 'static, options: &PlaybackOptions, control: &PlaybackControl) -> Result<()> {
+    // Read on a separate thread so the stop hotkey still works while waiting for input
+    let (line_tx, line_rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in reader.lines() {
+            if line_tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    // Streams are written by the current recorder, which stores points
+    let mapper = DisplayMapper::new(options.display_mapping, &RecordingMetadata::capture(&[]), backend.displays())
+        .with_transform(options.transform, options.offset);
+    let mut holds = HoldTimer::new(options.hold);
+    let key_repeat = KeyRepeat::current();
+    // Events a hold stands for, played before the next line is read
+    let mut pending = VecDeque::new();
+    let mut due = clock.elapsed();
     loop {
-        thread::park();
+        if control.is_stopped() {
+            tracing::info!("Playback stopped by user.");
+            return Ok(());
+        }
+        let event = match pending.pop_front() {
+            Some(event) => event,
+            None => {
+                let line = match line_rx.recv_timeout(Duration::from_millis(50)) {
+                    Ok(line) => line?,
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                serde_json::from_str::<SerializableEvent>(&line)?
+            }
+        };
+        if matches!(event.event_type, SerializableEventType::Hold { .. }) {
+            let hold = Recording {
+                metadata: RecordingMetadata::capture(&[]),
+                events: vec![event],
+            };
+            pending.extend(key_repeat::expand(&hold, key_repeat).events);
+            continue;
+        }
+
+        due += scaled_delay(event.delay(), options.speed_for(&event.event_type));
+        due = holds.due(&event, event.delay(), due);
+        if !sleep_until_unless_stopped(clock, &mut due, None, control) {
+            tracing::info!("Playback stopped by user.");
+            return Ok(());
+        }
+        if let Err(e) = verify(backend, &event, &mapper) {
+            screenshot_failure(backend, options);
+            return Err(e);
+        }
+        if let SerializableEventType::WaitForApp { app, timeout_ms, launch } = &event.event_type {
+            let before = clock.elapsed();
+            match wait_for_app(backend, clock, app, Duration::from_millis(*timeout_ms), *launch, control) {
+                Ok(true) => due += clock.elapsed() - before,
+                Ok(false) => {
+                    tracing::info!("Playback stopped by user.");
+                    return Ok(());
+                }
+                Err(e) => {
+                    screenshot_failure(backend, options);
+                    return Err(e);
+                }
+            }
+        }
+        let (_, result) = simulate_event(backend, &event, &mapper, options.key_replay, &options.remap);
+        match result {
+            Ok(()) => tracing::debug!("Simulated event: {:?}", event.event_type),
+            Err(e) => tracing::error!("We could not send {:?}: {:?}", event.event_type, e),
+        }
     }
+    tracing::info!("Playback complete.");
+    Ok(())
+}
+
+/// Listens for the stop and pause playback hotkeys on a background thread.
+pub fn spawn_stop_listener(backend: Arc<dyn InputBackend>, keymaps: KeyMaps, control: PlaybackControl) {
+    thread::spawn(move || {
+        if let Err(error) = backend.listen(stop_hotkeys(keymaps, control)) {
+             tracing::error!("Error: {:?}", error);
+        }
+    });
 }
 
-pub fn do_playback(events: &[SerializableEvent], speed: f64, repeat_count: u32, repeat_interval: f64, stop_flag: Arc<std::sync::atomic::AtomicBool>) {
+/// Callback that stops, pauses and resumes `control` as the stop and pause
+/// playback hotkeys are pressed.
+pub fn stop_hotkeys(keymaps: KeyMaps, control: PlaybackControl) -> EventCallback {
+    let mut modifiers = ModifierState::from_os();
+    Box::new(move |event| {
+        modifiers.update(&event.event_type);
+
+        // Check stop and pause hotkeys
+        if let EventType::KeyPress(key) = event.event_type {
+            if modifiers.matches(&keymaps.stop_playback, key) {
+                tracing::info!("Stop hotkey detected. Stopping playback...");
+                control.stop();
+            } else if modifiers.matches(&keymaps.pause, key) {
+                if control.is_paused() {
+                    tracing::info!("Pause hotkey detected. Resuming playback...");
+                    control.resume();
+                } else {
+                    tracing::info!("Pause hotkey detected. Pausing playback...");
+                    control.pause();
+                }
+            }
+        }
+    })
+}
+
+pub fn do_playback(backend: &dyn InputBackend, clock: &dyn Clock, recording: &Recording, options: &PlaybackOptions, stop_flag: Arc<AtomicBool>) -> Result<()> {
+    let control = PlaybackControl {
+        stop: stop_flag,
+        paused: Arc::default(),
+    };
+    run_playback(backend, clock, recording, options, &control, &PlaybackHooks::default(), &mut |_| {})
+}
+
+fn run_playback(backend: &dyn InputBackend, clock: &dyn Clock, recording: &Recording, options: &PlaybackOptions, control: &PlaybackControl, hooks: &PlaybackHooks, on_progress: &mut dyn FnMut(PlaybackProgress)) -> Result<()> {
+    // Holds play as the presses they stand for, repeating like keys held on this machine
+    let expanded;
+    let recording = if key_repeat::has_holds(recording) {
+        expanded = key_repeat::expand(recording, KeyRepeat::current());
+        &expanded
+    } else {
+        recording
+    };
+    let _span = tracing::info_span!("playback", events = recording.events.len(), speed = options.speed, repeat_count = options.repeat_count).entered();
+    if let Some(hook) = &hooks.on_start {
+        hook();
+    }
+    let result = play_events(backend, clock, recording, options, control, hooks, on_progress);
+    if let Err(e) = &result {
+        tracing::error!("Playback aborted: {:#}", e);
+        screenshot_failure(backend, options);
+        if let Some(hook) = &hooks.on_error {
+            hook(e);
+        }
+    }
+    if let Some(hook) = &hooks.on_finish {
+        hook(*result.as_ref().unwrap_or(&PlaybackOutcome::Failed));
+    }
+    result.map(|_| ())
+}
+
+fn play_events(backend: &dyn InputBackend, clock: &dyn Clock, recording: &Recording, options: &PlaybackOptions, control: &PlaybackControl, hooks: &PlaybackHooks, on_progress: &mut dyn FnMut(PlaybackProgress)) -> Result<PlaybackOutcome> {
+    let PlaybackOptions { repeat_count, repeat_interval, display_mapping, transform, offset, key_replay, countdown, humanize, hold, .. } = *options;
+    let range = event_range(recording, options.from.as_ref(), options.to.as_ref())?;
+    let mapper = DisplayMapper::new(display_mapping, &recording.metadata, backend.displays()).with_transform(transform, offset);
+    let mut held = HeldInputs::new(backend);
+    let mut holds = HoldTimer::new(hold);
+    // Every event is due at a fixed offset from the start, so a late wakeup
+    // shortens the next wait instead of delaying the rest of the run
+    let mut due = clock.elapsed();
+    if !countdown.is_zero() {
+        tracing::info!("Starting in {:.1}s...", countdown.as_secs_f64());
+        due += countdown;
+        if !sleep_until_unless_stopped(clock, &mut due, None, control) {
+            tracing::info!("Playback stopped by user during countdown.");
+            return Ok(PlaybackOutcome::Stopped);
+        }
+    }
+    let watchdog = options.watchdog;
+    let watchdog_deadline = watchdog.max_total_duration.map(|limit| clock.elapsed() + limit);
+    let watchdog_check = || match (watchdog.max_total_duration, watchdog_deadline) {
+        (Some(limit), Some(deadline)) if clock.elapsed() >= deadline => {
+            anyhow::bail!("Watchdog: playback ran for its limit of {}", humantime::format_duration(limit))
+        }
+        _ => Ok(()),
+    };
+    let time_limit = options.time_limit().map(|limit| clock.elapsed() + limit);
+    let time_up = || time_limit.is_some_and(|deadline| clock.elapsed() >= deadline);
+    // Wake up for whichever limit comes first
+    let deadline = match (time_limit, watchdog_deadline) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    let mut failures = 0;
     let mut count = 0;
     loop {
         if repeat_count > 0 && count >= repeat_count {
             break;
         }
+        if let Some(max) = watchdog.max_total_repeats
+            && count >= max
+        {
+            anyhow::bail!("Watchdog: playback reached its limit of {} repeats", max);
+        }
         
         // Wait interval if not first run
         if count > 0 && repeat_interval > 0.0 {
-            log::info!("Waiting {:.2}s before next repeat...", repeat_interval);
-             // Check stop flag periodically during long wait
-             let wait_duration = Duration::from_secs_f64(repeat_interval);
-             let start_wait = std::time::Instant::now();
-             while start_wait.elapsed() < wait_duration {
-                 if stop_flag.load(std::sync::atomic::Ordering::SeqCst) {
-                     log::info!("Playback stopped by user during interval.");
-                     return;
-                 }
-                 thread::sleep(Duration::from_millis(50));
+            tracing::info!("Waiting {:.2}s before next repeat...", repeat_interval);
+             due += Duration::from_secs_f64(repeat_interval);
+             if !sleep_until_unless_stopped(clock, &mut due, deadline, control) {
+                 tracing::info!("Playback stopped by user during interval.");
+                 return Ok(PlaybackOutcome::Stopped);
              }
         }
+        if time_up() {
+            tracing::info!("Repeat time limit reached.");
+            tracing::info!("Playback complete.");
+            return Ok(PlaybackOutcome::Completed);
+        }
+        watchdog_check()?;
 
         if count > 0 {
-             log::info!("Repeat #{}", count + 1);
+             tracing::info!("Repeat #{}", count + 1);
         }
+        let _repeat = tracing::info_span!("repeat", number = count + 1).entered();
 
-        for event in events {
+        for (index, event) in recording.events[range.clone()].iter().enumerate() {
             // Check if stop was requested
-            if stop_flag.load(std::sync::atomic::Ordering::SeqCst) {
-                log::info!("Playback stopped by user.");
-                return;
+            if control.is_stopped() {
+                tracing::info!("Playback stopped by user.");
+                return Ok(PlaybackOutcome::Stopped);
             }
             
+            // A slice starts right away rather than after the gap before it
+            let delay = if index == 0 && range.start > 0 { Duration::ZERO } else { event.delay() };
             // Adjust delay based on speed
-            let delay = (event.delay_ms as f64 / speed) as u64;
-            thread::sleep(Duration::from_millis(delay));
-            let rdev_event_type = event.to_rdev();
-            match simulate(&rdev_event_type) {
+            due += scaled_delay(delay, options.speed_for(&event.event_type));
+            due = holds.due(event, delay, due);
+            let planned = humanized(due, humanize);
+            let mut at = planned;
+            if !sleep_until_unless_stopped(clock, &mut at, deadline, control) {
+                tracing::info!("Playback stopped by user.");
+                return Ok(PlaybackOutcome::Stopped);
+            }
+            // Time spent paused pushes back the rest of the timeline
+            due += at - planned;
+            if time_up() {
+                tracing::info!("Repeat time limit reached.");
+                tracing::info!("Playback complete.");
+                return Ok(PlaybackOutcome::Completed);
+            }
+            watchdog_check()?;
+            verify(backend, event, &mapper)?;
+            if let SerializableEventType::WaitForApp { app, timeout_ms, launch } = &event.event_type {
+                let before = clock.elapsed();
+                if !wait_for_app(backend, clock, app, Duration::from_millis(*timeout_ms), *launch, control)? {
+                    tracing::info!("Playback stopped by user.");
+                    return Ok(PlaybackOutcome::Stopped);
+                }
+                // The wait pushes back the rest of the timeline, like a pause
+                due += clock.elapsed() - before;
+            }
+            let (rdev_event_type, result) = simulate_event(backend, event, &mapper, key_replay, &options.remap);
+            match result {
                 Ok(()) => {
-                    log::debug!("Simulated event: {:?}", rdev_event_type);
+                    failures = 0;
+                    // How far behind its planned time the event went out
+                    let late_us = clock.elapsed().saturating_sub(at).as_micros() as u64;
+                    tracing::debug!(index = range.start + index, late_us, "Simulated event: {:?}", event.event_type);
+                    if let Some(rdev_event_type) = &rdev_event_type {
+                        held.track(rdev_event_type);
+                    }
+                    if let Some(hook) = &hooks.on_event {
+                        hook(event);
+                    }
                 },
                 Err(e) => {
-                    log::error!("We could not send {:?}: {:?}", rdev_event_type, e);
+                    tracing::error!("We could not send {:?}: {:?}", event.event_type, e);
+                    if let Some(hook) = &hooks.on_error {
+                        hook(&e);
+                    }
+                    failures += 1;
+                    if watchdog.max_consecutive_failures.is_some_and(|max| failures >= max) {
+                        return Err(e.context(format!("Watchdog: {} events in a row could not be sent", failures)));
+                    }
                 }
             }
+            on_progress(PlaybackProgress {
+                repeat: count,
+                events_played: index + 1,
+                total_events: range.len(),
+            });
         }
         count += 1;
+        if let Some(hook) = &hooks.on_repeat_complete {
+            hook(count);
+        }
+    }
+    // The recording ran to its end, so whatever it left held down is intentional
+    held.forget();
+    tracing::info!("Playback complete.");
+    Ok(PlaybackOutcome::Completed)
+}
+
+fn screenshot_failure(backend: &dyn InputBackend, options: &PlaybackOptions) {
+    if let Some(dir) = &options.screenshot_on_failure {
+        screenshot::capture(backend, &screenshot::playback_path(dir, ScreenshotTrigger::Failure));
+    }
+}
+
+/// Checks an assertion event against the current state of the system. Other
+/// events always pass.
+fn verify(backend: &dyn InputBackend, event: &SerializableEvent, mapper: &DisplayMapper) -> Result<()> {
+    match &event.event_type {
+        &SerializableEventType::AssertPixelColor { x, y, color, tolerance } => {
+            let (x, y) = mapper.map_point(x, y, None);
+            let actual = backend.pixel_color(x, y)?;
+            let matches = actual.iter().zip(color).all(|(a, e)| a.abs_diff(e) <= tolerance);
+            if !matches {
+                anyhow::bail!("Assertion failed: pixel at ({}, {}) is {:?}, expected {:?}", x, y, actual, color);
+            }
+        }
+        SerializableEventType::AssertClipboardContains { text } => {
+            let clipboard = backend.clipboard_text()?.unwrap_or_default();
+            if !clipboard.contains(text.as_str()) {
+                anyhow::bail!("Assertion failed: clipboard {:?} does not contain {:?}", clipboard, text);
+            }
+        }
+        SerializableEventType::AssertFrontmostApp { name } => {
+            let frontmost = backend.frontmost_app()?;
+            if !frontmost.eq_ignore_ascii_case(name) {
+                anyhow::bail!("Assertion failed: frontmost app is {:?}, expected {:?}", frontmost, name);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Waits until `app` is the frontmost application, by name ignoring case or
+/// by bundle identifier, opening it first with `launch`. Fails once `timeout`
+/// has passed. Returns false if playback was stopped while waiting.
+fn wait_for_app(backend: &dyn InputBackend, clock: &dyn Clock, app: &str, timeout: Duration, launch: bool, control: &PlaybackControl) -> Result<bool> {
+    const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+    if launch {
+        tracing::info!("Opening {:?}...", app);
+        backend.launch_app(app).with_context(|| format!("Could not open {:?}", app))?;
+    }
+    let deadline = clock.elapsed() + timeout;
+    loop {
+        if control.is_stopped() {
+            return Ok(false);
+        }
+        let frontmost = backend.frontmost_app()?;
+        // Not every backend knows bundle identifiers, so names alone will do
+        if frontmost.eq_ignore_ascii_case(app) || backend.frontmost_app_id().is_ok_and(|id| id.eq_ignore_ascii_case(app)) {
+            return Ok(true);
+        }
+        let now = clock.elapsed();
+        if now >= deadline {
+            anyhow::bail!("Timed out after {:.1}s waiting for {:?} to be frontmost, {:?} is", timeout.as_secs_f64(), app, frontmost);
+        }
+        clock.sleep((deadline - now).min(CHECK_INTERVAL));
+    }
+}
+
+/// Injects a recorded event, returning the rdev equivalent for tracking
+/// alongside the result. Events that restore or check state have no equivalent.
+fn simulate_event(
+    backend: &dyn InputBackend,
+    event: &SerializableEvent,
+    mapper: &DisplayMapper,
+    key_replay: KeyReplay,
+    remap: &Remap,
+) -> (Option<EventType>, Result<()>) {
+    let Some(rdev_event_type) = event.to_rdev(mapper, remap) else {
+        let result = match &event.event_type {
+            SerializableEventType::Clipboard { text } => backend.set_clipboard_text(text),
+            // Assertions were already checked by `verify`, apps waited for by
+            // the caller, and checkpoints only mark a place
+            _ => Ok(()),
+        };
+        return (None, result);
+    };
+    let result = match event.event_type {
+        SerializableEventType::SmoothWheel { delta_x, delta_y, phase } => {
+            let (delta_x, delta_y) = remap.wheel(delta_x, delta_y);
+            backend.simulate_smooth_scroll(delta_x, delta_y, phase)
+        }
+        // Typing the recorded text reproduces it regardless of keyboard
+        // layout, unless the key is played as another one
+        _ => match event.replay_text(key_replay).filter(|_| !remap.remaps_key(&event.event_type)) {
+            Some(text) => backend.simulate_text(text).or_else(|e| {
+                tracing::debug!("Falling back to key press for {:?}: {:?}", text, e);
+                backend.simulate(&rdev_event_type)
+            }),
+            None => backend.simulate(&rdev_event_type),
+        },
+    };
+    (Some(rdev_event_type), result)
+}
+
+/// Keys and mouse buttons pressed by playback and not yet released. Anything
+/// still held is released when this is dropped, so playback that is cut short
+/// never leaves input stuck down.
+struct HeldInputs<'a> {
+    backend: &'a dyn InputBackend,
+    keys: Vec<Key>,
+    buttons: Vec<Button>,
+}
+
+impl<'a> HeldInputs<'a> {
+    fn new(backend: &'a dyn InputBackend) -> Self {
+        Self {
+            backend,
+            keys: Vec::new(),
+            buttons: Vec::new(),
+        }
+    }
+
+    fn forget(&mut self) {
+        self.keys.clear();
+        self.buttons.clear();
+    }
+
+    fn track(&mut self, event_type: &EventType) {
+        match *event_type {
+            EventType::KeyPress(key) if !self.keys.contains(&key) => self.keys.push(key),
+            EventType::KeyRelease(key) => self.keys.retain(|k| *k != key),
+            EventType::ButtonPress(button) if !self.buttons.contains(&button) => self.buttons.push(button),
+            EventType::ButtonRelease(button) => self.buttons.retain(|b| *b != button),
+            _ => {}
+        }
+    }
+
+}
+
+impl Drop for HeldInputs<'_> {
+    fn drop(&mut self) {
+        let releases = self.keys.drain(..).map(EventType::KeyRelease)
+            .chain(self.buttons.drain(..).map(EventType::ButtonRelease));
+        for release in releases {
+            tracing::info!("Releasing held input: {:?}", release);
+            if let Err(e) = self.backend.simulate(&release) {
+                tracing::error!("We could not send {:?}: {:?}", release, e);
+            }
+        }
+    }
+}
+
+/// Releases every modifier key and mouse button, whether or not it is down.
+/// Used to recover after playback was killed without the chance to release
+/// what it was holding.
+pub fn release_all(backend: &dyn InputBackend) {
+    const KEYS: [Key; 8] = [
+        Key::ShiftLeft,
+        Key::ShiftRight,
+        Key::ControlLeft,
+        Key::ControlRight,
+        Key::Alt,
+        Key::AltGr,
+        Key::MetaLeft,
+        Key::MetaRight,
+    ];
+    const BUTTONS: [Button; 3] = [Button::Left, Button::Right, Button::Middle];
+    let releases = KEYS.into_iter().map(EventType::KeyRelease)
+        .chain(BUTTONS.into_iter().map(EventType::ButtonRelease));
+    for release in releases {
+        if let Err(e) = backend.simulate(&release) {
+            tracing::error!("We could not send {:?}: {:?}", release, e);
+        }
+    }
+}
+
+/// Parses a local time of day such as `17:00` or `17:00:30`.
+pub fn parse_time_of_day(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M:%S"))
+        .map_err(|_| format!("expected a time like 17:00, got '{}'", s))
+}
+
+/// Time from now until the next occurrence of `time` (today, or tomorrow if
+/// it has already passed).
+fn duration_until(time: NaiveTime) -> Duration {
+    let now = Local::now().naive_local();
+    let mut deadline = now.date().and_time(time);
+    if deadline <= now {
+        deadline += chrono::Duration::days(1);
+    }
+    (deadline - now).to_std().unwrap_or_default()
+}
+
+/// Recorded delay adjusted for playback speed. Invalid speeds (zero, negative
+/// or not finite) fall back to real time rather than producing a nonsensical wait.
+pub fn scaled_delay(delay: Duration, speed: f64) -> Duration {
+    let speed = if speed.is_finite() && speed > 0.0 { speed } else { 1.0 };
+    delay.div_f64(speed)
+}
+
+/// `due` moved by a random offset of up to `amount` either way.
+fn humanized(due: Duration, amount: Duration) -> Duration {
+    if amount.is_zero() {
+        return due;
+    }
+    let offset = Duration::from_micros(fastrand::u64(0..=2 * amount.as_micros() as u64));
+    (due + offset).saturating_sub(amount)
+}
+
+/// Sleeps until the clock reaches `due`, or `deadline` if that comes first,
+/// checking the control flags periodically so long delays can be interrupted.
+/// Waiting for an absolute time rather than a delay keeps sleep overshoot
+/// from adding up over a long recording. Time spent paused moves `due` back.
+/// Returns false if playback was stopped.
+fn sleep_until_unless_stopped(clock: &dyn Clock, due: &mut Duration, deadline: Option<Duration>, control: &PlaybackControl) -> bool {
+    const CHECK_INTERVAL: Duration = Duration::from_millis(50);
+    loop {
+        if control.is_stopped() {
+            return false;
+        }
+        if control.is_paused() {
+            let before = clock.elapsed();
+            clock.sleep(CHECK_INTERVAL);
+            *due += clock.elapsed() - before;
+            continue;
+        }
+        let target = deadline.map_or(*due, |deadline| (*due).min(deadline));
+        let now = clock.elapsed();
+        if now >= target {
+            return true;
+        }
+        clock.sleep((target - now).min(CHECK_INTERVAL));
     }
-    log::info!("Playback complete.");
 }
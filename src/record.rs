@@ -1,147 +1,1412 @@
-use crate::event::SerializableEvent;
+use crate::backend::{self, InputBackend};
+use crate::event::{ScrollDetails, SerializableEvent, SerializableEventType};
+use crate::recording::{self, JsonlWriter, Recording, RecordingMetadata};
 use crate::config::{KeyMaps, Modifier};
+use crate::display::DisplayInfo;
+use crate::environment::Environment;
+use crate::history::{self, RunKind, RunTimer};
+use crate::key_repeat;
+use crate::modifiers::ModifierState;
+use crate::own_ui::{OwnUi, OwnUiFilter};
+use crate::platform;
+use crate::screenshot::{self, ScreenshotTrigger};
+use crate::play::Hook;
+use crate::worker::{self, WorkerCommand, WorkerReport};
 use anyhow::Result;
-use rdev::{listen, Event, EventType, Key};
-use std::fs::File;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
+use rdev::{Event, EventType, Key};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufReader, Write};
+use std::str::FromStr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::Span;
 
+/// Events captured so far, owned by the writer thread.
 struct RecorderState {
-    is_recording: bool,
-    cmd_pressed: bool,
-    alt_pressed: bool,
-    ctrl_pressed: bool,
-    shift_pressed: bool,
-    events: Vec<SerializableEvent>,
-    last_time: SystemTime,
-}
-
-pub fn run_record(output_path: PathBuf, keymaps: KeyMaps, immediate: bool) -> Result<()> {
-    log::info!("Running in background.");
-    log::info!("Start Recording: {:?} + {:?}", keymaps.start_recording.modifiers, keymaps.start_recording.trigger);
-    log::info!("Stop Recording: {:?} + {:?}", keymaps.stop_recording.modifiers, keymaps.stop_recording.trigger);
-
-    // Create file immediately to ensure it exists
-    save_events(&[], &output_path)?;
-
-    let state = Arc::new(Mutex::new(RecorderState {
-        is_recording: immediate,
-        cmd_pressed: false,
-        alt_pressed: false,
-        ctrl_pressed: false,
-        shift_pressed: false,
-        events: Vec::new(),
-        last_time: SystemTime::now(),
-    }));
-
-    let state_clone = state.clone();
-    let output_path_clone = output_path.clone();
-    let keymaps = keymaps.clone();
+    started: bool,
+    events: VecDeque<SerializableEvent>,
+    displays: Vec<DisplayInfo>,
+    environment: Option<Environment>,
+    /// Clipboard text from the last snapshot, so unchanged text is not repeated.
+    last_clipboard: Option<String>,
+    /// Longest time kept between the first and last event.
+    rolling_window: Option<Duration>,
+    /// Time between the first and last event.
+    span: Duration,
+    /// Shortest time between stored mouse moves.
+    move_interval: Option<Duration>,
+    /// Latest mouse move held back by `move_interval`, with the delays of
+    /// the moves it replaced folded in.
+    pending_move: Option<SerializableEvent>,
+    /// Time since the last stored mouse move.
+    since_move: Duration,
+    /// Whether keys that auto-repeated are stored as single holds.
+    collapse_key_repeat: bool,
+    /// Bytes the stored events take up as JSON lines.
+    size: u64,
+    /// Drops input meant for the recording app's own UI.
+    own_ui: Option<OwnUiFilter>,
+}
 
-    // Handle Ctrl+C / SIGTERM
-    let state_ctrlc = state.clone();
-    let output_path_ctrlc = output_path.clone();
-    ctrlc::set_handler(move || {
-        log::info!("Ctrl+C / SIGTERM handler triggered");
-        let state = state_ctrlc.lock().unwrap();
-        if state.is_recording {
-            log::info!("Received termination signal. Saving recording...");
-            if let Err(e) = save_events(&state.events, &output_path_ctrlc) {
-                log::error!("Failed to save events: {}", e);
+impl RecorderState {
+    fn begin(&mut self, displays: Vec<DisplayInfo>, environment: Environment) {
+        self.started = true;
+        self.events.clear();
+        self.span = Duration::ZERO;
+        self.size = 0;
+        self.displays = displays;
+        self.environment = Some(environment);
+        self.last_clipboard = None;
+        self.pending_move = None;
+        self.since_move = Duration::ZERO;
+    }
+
+    /// Stores `event`, holding back mouse moves that come sooner than
+    /// `move_interval` after the last one. The latest held back move is
+    /// stored before the next other event, so clicks still land where they did.
+    fn push(&mut self, mut event: SerializableEvent, hooks: &RecorderHooks) {
+        let Some(interval) = self.move_interval else {
+            return self.append(event, hooks);
+        };
+        let delay = event.delay();
+        self.since_move += delay;
+        if matches!(event.event_type, SerializableEventType::MouseMove { .. }) {
+            if let Some(pending) = self.pending_move.take() {
+                event.set_delay(pending.delay() + delay);
+            }
+            if self.since_move < interval {
+                self.pending_move = Some(event);
+                return;
             }
-        } else {
-            log::info!("Not recording, exiting without save.");
+            self.since_move = Duration::ZERO;
+        } else if let Some(pending) = self.pending_move.take() {
+            self.since_move = delay;
+            self.append(pending, hooks);
         }
-        std::process::exit(0);
-    })?;
+        self.append(event, hooks);
+    }
+
+    fn append(&mut self, mut event: SerializableEvent, hooks: &RecorderHooks) {
+        if self.events.is_empty() && self.rolling_window.is_some() {
+            event.set_delay(Duration::ZERO);
+        }
+        tracing::info!("Recorded event: {:?}", event);
+        if let Some(hook) = &hooks.on_event {
+            hook(&event);
+        }
+        self.span += event.delay();
+        self.size += event_size(&event);
+        self.events.push_back(event);
+
+        // Drop the oldest events once they fall out of the window, so the
+        // new first event starts the recording
+        if let Some(window) = self.rolling_window {
+            while self.span > window && self.events.len() > 1 {
+                if let Some(dropped) = self.events.pop_front() {
+                    self.size -= event_size(&dropped);
+                }
+                if let Some(first) = self.events.front_mut() {
+                    self.span -= first.delay();
+                    self.size -= event_size(first);
+                    first.set_delay(Duration::ZERO);
+                    self.size += event_size(first);
+                }
+            }
+        }
+    }
+
+    /// Drops the events `scope` takes back, `since_last` after the last one
+    /// was captured. Returns how many were dropped.
+    fn undo(&mut self, scope: UndoScope, since_last: Duration) -> usize {
+        // A held back move is newer than any stored event
+        self.pending_move = None;
+        self.since_move = Duration::ZERO;
+        let mut dropped = Vec::new();
+        let mut age = since_last;
+        while let Some(last) = self.events.back() {
+            let keep = match scope {
+                UndoScope::Last(window) => age >= window,
+                UndoScope::Checkpoint => matches!(last.event_type, SerializableEventType::Checkpoint { .. }),
+            };
+            if keep {
+                break;
+            }
+            age += last.delay();
+            self.span -= last.delay();
+            self.size -= event_size(last);
+            dropped.extend(self.events.pop_back());
+        }
+
+        // Keys and buttons let go of in what was dropped would otherwise
+        // stay held for the rest of playback
+        for release in releases_dropped(&self.events, &dropped) {
+            let release = SerializableEvent {
+                event_type: release,
+                delay_ms: 0,
+                delay_us: 0,
+            };
+            self.size += event_size(&release);
+            self.events.push_back(release);
+        }
+        self.last_clipboard = self.events.iter().rev().find_map(|event| match &event.event_type {
+            SerializableEventType::Clipboard { text } => Some(text.clone()),
+            _ => None,
+        });
+        dropped.len()
+    }
+
+    fn to_recording(&self) -> Option<Recording> {
+        self.started.then(|| {
+            let events: Vec<SerializableEvent> = self.events.iter().cloned().collect();
+            let events = if self.collapse_key_repeat { key_repeat::collapse(&events) } else { events };
+            let mut recording = Recording::new(events, &self.displays);
+            recording.metadata.environment = self.environment.clone();
+            recording
+        })
+    }
+}
+
+/// Bytes `event` takes up as a line of a JSON lines recording.
+fn event_size(event: &SerializableEvent) -> u64 {
+    serde_json::to_string(event).map_or(0, |line| line.len() as u64 + 1)
+}
+
+/// Releases among `dropped` of keys and buttons still pressed at the end of `kept`.
+fn releases_dropped(kept: &VecDeque<SerializableEvent>, dropped: &[SerializableEvent]) -> Vec<SerializableEventType> {
+    let mut held: Vec<SerializableEventType> = Vec::new();
+    for event in kept {
+        let release = match &event.event_type {
+            SerializableEventType::KeyPress(key) | SerializableEventType::KeyPressText { key, .. } => SerializableEventType::KeyRelease(*key),
+            SerializableEventType::ButtonPress(button) => SerializableEventType::ButtonRelease(*button),
+            release @ (SerializableEventType::KeyRelease(_) | SerializableEventType::ButtonRelease(_)) => {
+                held.retain(|held| held != release);
+                continue;
+            }
+            _ => continue,
+        };
+        // Held keys repeat their press
+        if !held.contains(&release) {
+            held.push(release);
+        }
+    }
+    held.retain(|release| dropped.iter().any(|event| &event.event_type == release));
+    held
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecordOptions {
+    /// Stop automatically once capture has been running this long.
+    pub duration: Option<Duration>,
+    /// Upper bound for any single recorded delay.
+    pub max_gap: Option<Duration>,
+    /// Keep the character typed by every key press, so playback can replay
+    /// characters on a different keyboard layout.
+    pub capture_text: bool,
+    /// Snapshot the clipboard text at each paste, so playback pastes the same text.
+    pub capture_clipboard: bool,
+    /// When to save screenshots while recording.
+    pub screenshots: Vec<ScreenshotTrigger>,
+    /// Where screenshots are saved. No screenshots are taken without one.
+    pub screenshot_dir: Option<PathBuf>,
+    /// Save a screenshot as capture begins, as `thumbnail.png` in
+    /// `screenshot_dir`.
+    pub thumbnail: bool,
+    /// Keep only the most recent stretch of input this long, dropping older
+    /// events as new ones arrive.
+    pub rolling_window: Option<Duration>,
+    /// Keep at most one mouse move per interval, to shrink recordings. The
+    /// last position before a click or key press is always kept.
+    pub move_interval: Option<Duration>,
+    /// How often [`run_record`] saves a JSON recording while it is still
+    /// being made. JSON lines recordings are written as events arrive anyway.
+    pub autosave: Option<AutosaveInterval>,
+    /// What the undo hotkey takes back. The hotkey does nothing without one.
+    pub undo: Option<UndoScope>,
+    /// Save each key that auto-repeated while held as a single hold, which
+    /// playback repeats at the playback machine's key repeat rate.
+    pub collapse_key_repeat: bool,
+    /// Stop automatically once this many events have been recorded.
+    pub max_events: Option<usize>,
+    /// Stop automatically once the recorded events take up this many bytes
+    /// as JSON lines, roughly the size of the saved file.
+    pub max_size: Option<u64>,
+}
+
+impl RecordOptions {
+    /// Command line flags for `macro record` that reproduce these options.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for (flag, value) in [("--duration", self.duration), ("--max-gap", self.max_gap), ("--move-interval", self.move_interval), ("--buffer", self.rolling_window)] {
+            if let Some(value) = value {
+                args.push(flag.to_string());
+                args.push(humantime::format_duration(value).to_string());
+            }
+        }
+        if self.capture_text {
+            args.push("--capture-text".to_string());
+        }
+        if self.capture_clipboard {
+            args.push("--capture-clipboard".to_string());
+        }
+        if self.thumbnail {
+            args.push("--thumbnail".to_string());
+        }
+        if self.collapse_key_repeat {
+            args.push("--collapse-key-repeat".to_string());
+        }
+        if let Some(max_events) = self.max_events {
+            args.extend(["--max-events".to_string(), max_events.to_string()]);
+        }
+        if let Some(max_size) = self.max_size {
+            args.extend(["--max-size".to_string(), format_size(max_size)]);
+        }
+        if !self.screenshots.is_empty() {
+            args.push("--screenshots".to_string());
+            args.push(self.screenshots.iter().map(ToString::to_string).collect::<Vec<_>>().join(","));
+        }
+        match self.autosave {
+            Some(AutosaveInterval::Events(events)) => args.extend(["--autosave-interval".to_string(), events.to_string()]),
+            Some(AutosaveInterval::Time(time)) => args.extend(["--autosave-interval".to_string(), humantime::format_duration(time).to_string()]),
+            None => {}
+        }
+        if let Some(undo) = self.undo.filter(|undo| *undo != UndoScope::default()) {
+            args.push("--undo".to_string());
+            args.push(undo.to_string());
+        }
+        args
+    }
+}
+
+/// How often a recording in progress is saved, trading crash safety against
+/// rewriting the whole file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutosaveInterval {
+    /// After every this many events.
+    Events(usize),
+    /// This long after the first unsaved event.
+    Time(Duration),
+}
+
+impl FromStr for AutosaveInterval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(0) => Err("expected at least 1 event".to_string()),
+            Ok(events) => Ok(AutosaveInterval::Events(events)),
+            Err(_) => humantime::parse_duration(s)
+                .map(AutosaveInterval::Time)
+                .map_err(|_| format!("expected a number of events like 500 or a time like 10s, got '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for AutosaveInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AutosaveInterval::Events(events) => write!(f, "{} events", events),
+            AutosaveInterval::Time(time) => write!(f, "{}", humantime::format_duration(*time)),
+        }
+    }
+}
+
+/// Parses a size like `500KB`, `10MB` or `1GB`, or a plain number of bytes.
+/// Units are powers of 1024.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let upper = s.trim().to_ascii_uppercase();
+    let split = upper.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(upper.len());
+    let (number, unit) = upper.split_at(split);
+    let multiplier: u64 = match unit.trim() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return Err(format!("expected a size like 500KB, 10MB or 1GB, got '{}'", s)),
+    };
+    match number.parse::<f64>() {
+        Ok(number) if number > 0.0 => Ok((number * multiplier as f64) as u64),
+        _ => Err(format!("expected a size like 500KB, 10MB or 1GB, got '{}'", s)),
+    }
+}
+
+/// Writes `bytes` in the largest unit it is a whole number of, the way
+/// [`parse_size`] reads it back.
+pub fn format_size(bytes: u64) -> String {
+    for (unit, multiplier) in [("GB", 1u64 << 30), ("MB", 1 << 20), ("KB", 1 << 10)] {
+        if bytes >= multiplier && bytes.is_multiple_of(multiplier) {
+            return format!("{}{}", bytes / multiplier, unit);
+        }
+    }
+    format!("{}B", bytes)
+}
+
+/// A limit that stopped a recording before it was stopped by hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordLimit {
+    Duration(Duration),
+    Events(usize),
+    Size(u64),
+}
+
+impl fmt::Display for RecordLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordLimit::Duration(duration) => write!(f, "time limit of {}", humantime::format_duration(*duration)),
+            RecordLimit::Events(events) => write!(f, "limit of {} events", events),
+            RecordLimit::Size(bytes) => write!(f, "size limit of {}", format_size(*bytes)),
+        }
+    }
+}
+
+/// How much of a recording in progress the undo hotkey takes back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoScope {
+    /// Everything captured this long before the hotkey.
+    Last(Duration),
+    /// Everything since the last checkpoint, which is kept. Everything if
+    /// there is none.
+    Checkpoint,
+}
 
-    let callback = move |event: Event| {
-        // log::trace!("Received event: {:?}", event.event_type); // Too noisy for info level, but good for debug
-        let mut state = state_clone.lock().unwrap();
-        
-        // Update modifier keys
-        match event.event_type {
-            EventType::KeyPress(Key::MetaLeft) | EventType::KeyPress(Key::MetaRight) => state.cmd_pressed = true,
-            EventType::KeyRelease(Key::MetaLeft) | EventType::KeyRelease(Key::MetaRight) => state.cmd_pressed = false,
-            EventType::KeyPress(Key::Alt) | EventType::KeyPress(Key::AltGr) => state.alt_pressed = true,
-            EventType::KeyRelease(Key::Alt) | EventType::KeyRelease(Key::AltGr) => state.alt_pressed = false,
-            EventType::KeyPress(Key::ControlLeft) | EventType::KeyPress(Key::ControlRight) => state.ctrl_pressed = true,
-            EventType::KeyRelease(Key::ControlLeft) | EventType::KeyRelease(Key::ControlRight) => state.ctrl_pressed = false,
-            EventType::KeyPress(Key::ShiftLeft) | EventType::KeyPress(Key::ShiftRight) => state.shift_pressed = true,
-            EventType::KeyRelease(Key::ShiftLeft) | EventType::KeyRelease(Key::ShiftRight) => state.shift_pressed = false,
-            _ => {}
-        }
-
-        // Check for Hotkeys
-        let check_modifiers = |modifiers: &[Modifier]| -> bool {
-            for m in modifiers {
-                match m {
-                    Modifier::Cmd => if !state.cmd_pressed { return false; },
-                    Modifier::Alt => if !state.alt_pressed { return false; },
-                    Modifier::Ctrl => if !state.ctrl_pressed { return false; },
-                    Modifier::Shift => if !state.shift_pressed { return false; },
-                }
-            }
-            true
+impl Default for UndoScope {
+    fn default() -> Self {
+        UndoScope::Last(Duration::from_secs(5))
+    }
+}
+
+impl FromStr for UndoScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "checkpoint" => Ok(UndoScope::Checkpoint),
+            _ => humantime::parse_duration(s)
+                .map(UndoScope::Last)
+                .map_err(|_| format!("expected a time like 5s or 'checkpoint', got '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for UndoScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UndoScope::Last(time) => write!(f, "{}", humantime::format_duration(*time)),
+            UndoScope::Checkpoint => write!(f, "checkpoint"),
+        }
+    }
+}
+
+/// Callbacks invoked on the recorder's writer thread. Slow callbacks delay
+/// later events being stored, but never the delivery of input to other apps.
+#[derive(Default)]
+struct RecorderHooks {
+    on_start: Hook<dyn Fn() + Send + Sync>,
+    on_event: Hook<dyn Fn(&SerializableEvent) + Send + Sync>,
+    on_finish: Hook<dyn Fn(&Recording) + Send + Sync>,
+    on_save_buffer: Hook<dyn Fn(&Recording) + Send + Sync>,
+    on_undo: Hook<dyn Fn(&Recording) + Send + Sync>,
+    on_error: Hook<dyn Fn(&anyhow::Error) + Send + Sync>,
+    on_limit: Hook<dyn Fn(RecordLimit) + Send + Sync>,
+}
+
+impl RecorderHooks {
+    fn start(&self) {
+        if let Some(hook) = &self.on_start {
+            hook();
+        }
+    }
+}
+
+/// Captures input events into a [`Recording`].
+pub struct Recorder {
+    backend: Arc<dyn InputBackend>,
+    keymaps: Option<KeyMaps>,
+    start_immediately: bool,
+    options: RecordOptions,
+    own_ui: Option<OwnUi>,
+    hooks: RecorderHooks,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self {
+            backend: backend::default_backend(),
+            keymaps: None,
+            start_immediately: true,
+            options: RecordOptions::default(),
+            own_ui: None,
+            hooks: RecorderHooks::default(),
+        }
+    }
+
+    pub fn backend(mut self, backend: Arc<dyn InputBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Watches for the start/stop recording hotkeys. The hotkey presses
+    /// themselves are never recorded.
+    pub fn hotkeys(mut self, keymaps: KeyMaps) -> Self {
+        self.keymaps = Some(keymaps);
+        self
+    }
+
+    /// Whether capture begins as soon as the session starts, or only once
+    /// the start hotkey is pressed.
+    pub fn start_immediately(mut self, start_immediately: bool) -> Self {
+        self.start_immediately = start_immediately;
+        self
+    }
+
+    pub fn options(mut self, options: RecordOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Leaves out input meant for the recording app's own windows and menu,
+    /// e.g. the clicks that stop recording from a tray menu.
+    pub fn own_ui(mut self, own_ui: OwnUi) -> Self {
+        self.own_ui = Some(own_ui);
+        self
+    }
+
+    /// Stops automatically once capture has been running for `duration`.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.options.duration = Some(duration);
+        self
+    }
+
+    /// Caps each recorded delay, so long idle pauses are collapsed.
+    pub fn max_gap(mut self, max_gap: Duration) -> Self {
+        self.options.max_gap = Some(max_gap);
+        self
+    }
+
+    /// Records the character typed by every key press alongside the key.
+    pub fn capture_text(mut self, capture_text: bool) -> Self {
+        self.options.capture_text = capture_text;
+        self
+    }
+
+    /// Records the clipboard text before each paste shortcut.
+    pub fn capture_clipboard(mut self, capture_clipboard: bool) -> Self {
+        self.options.capture_clipboard = capture_clipboard;
+        self
+    }
+
+    /// Saves screenshots into `dir` at each of the given moments.
+    pub fn screenshots(mut self, triggers: Vec<ScreenshotTrigger>, dir: PathBuf) -> Self {
+        self.options.screenshots = triggers;
+        self.options.screenshot_dir = Some(dir);
+        self
+    }
+
+    /// Saves a screenshot into `dir` as capture begins, to tell the
+    /// recording apart from others.
+    pub fn thumbnail(mut self, dir: PathBuf) -> Self {
+        self.options.thumbnail = true;
+        self.options.screenshot_dir = Some(dir);
+        self
+    }
+
+    /// Keeps at most one mouse move per `interval`.
+    pub fn move_interval(mut self, interval: Duration) -> Self {
+        self.options.move_interval = Some(interval);
+        self
+    }
+
+    /// Keeps only the last `window` of input, for saving retroactively with
+    /// the save buffer hotkey.
+    pub fn rolling_window(mut self, window: Duration) -> Self {
+        self.options.rolling_window = Some(window);
+        self
+    }
+
+    /// Lets the undo hotkey take back `scope` of the recording.
+    pub fn undo(mut self, scope: UndoScope) -> Self {
+        self.options.undo = Some(scope);
+        self
+    }
+
+    /// Saves keys that auto-repeated while held as single holds.
+    pub fn collapse_key_repeat(mut self, collapse_key_repeat: bool) -> Self {
+        self.options.collapse_key_repeat = collapse_key_repeat;
+        self
+    }
+
+    /// Stops automatically once `max_events` events have been recorded.
+    pub fn max_events(mut self, max_events: usize) -> Self {
+        self.options.max_events = Some(max_events);
+        self
+    }
+
+    /// Stops automatically once the recording takes up about `max_size` bytes.
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.options.max_size = Some(max_size);
+        self
+    }
+
+    /// Called when capture begins.
+    pub fn on_start(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        self.hooks.on_start = Some(Box::new(hook));
+        self
+    }
+
+    /// Called for each captured event.
+    pub fn on_event(mut self, hook: impl Fn(&SerializableEvent) + Send + Sync + 'static) -> Self {
+        self.hooks.on_event = Some(Box::new(hook));
+        self
+    }
+
+    /// Called with the finished recording when the session is waited on.
+    pub fn on_finish(mut self, hook: impl Fn(&Recording) + Send + Sync + 'static) -> Self {
+        self.hooks.on_finish = Some(Box::new(hook));
+        self
+    }
+
+    /// Called with the buffered events each time the save buffer hotkey is
+    /// pressed while recording with a rolling window.
+    pub fn on_save_buffer(mut self, hook: impl Fn(&Recording) + Send + Sync + 'static) -> Self {
+        self.hooks.on_save_buffer = Some(Box::new(hook));
+        self
+    }
+
+    /// Called with what is left of the recording each time the undo hotkey
+    /// takes events back, e.g. to rewrite a file `on_event` appends to.
+    pub fn on_undo(mut self, hook: impl Fn(&Recording) + Send + Sync + 'static) -> Self {
+        self.hooks.on_undo = Some(Box::new(hook));
+        self
+    }
+
+    /// Called if the backend fails to listen for events.
+    pub fn on_error(mut self, hook: impl Fn(&anyhow::Error) + Send + Sync + 'static) -> Self {
+        self.hooks.on_error = Some(Box::new(hook));
+        self
+    }
+
+    /// Called when the duration, event or size limit is reached, just
+    /// before capture stops. The recording is then finished as if it had
+    /// been stopped by hand.
+    pub fn on_limit(mut self, hook: impl Fn(RecordLimit) + Send + Sync + 'static) -> Self {
+        self.hooks.on_limit = Some(Box::new(hook));
+        self
+    }
+
+    /// Starts listening on a background thread.
+    ///
+    /// Most backends cannot stop listening once started, so the listener
+    /// thread outlives the session and simply ignores further events.
+    pub fn start(self) -> RecordingSession {
+        let displays = self.backend.displays();
+        let state = Arc::new(Mutex::new(RecorderState {
+            started: false,
+            events: VecDeque::new(),
+            displays: displays.clone(),
+            environment: None,
+            last_clipboard: None,
+            rolling_window: self.options.rolling_window,
+            span: Duration::ZERO,
+            move_interval: self.options.move_interval,
+            pending_move: None,
+            since_move: Duration::ZERO,
+            collapse_key_repeat: self.options.collapse_key_repeat,
+            size: 0,
+            own_ui: self.own_ui.as_ref().map(|own_ui| own_ui.filter(&displays)),
+        }));
+        let recording = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(false));
+        let hooks = Arc::new(self.hooks);
+        let (done_tx, done_rx) = mpsc::channel();
+        let (captured_tx, captured_rx) = mpsc::channel();
+        let handle = RecordingHandle {
+            state: state.clone(),
+            recording: recording.clone(),
+            paused: paused.clone(),
+            captured_tx: captured_tx.clone(),
         };
+        let RecordOptions { duration, max_gap, capture_text, capture_clipboard, screenshots, screenshot_dir, thumbnail, rolling_window, undo, max_events, max_size, .. } = self.options;
+
+        let thumbnail = screenshot_dir.as_deref().filter(|_| thumbnail).map(screenshot::thumbnail_in);
+        let span = tracing::info_span!("record", immediate = self.start_immediately, rolling = rolling_window.is_some());
+        if self.start_immediately {
+            let _entered = span.enter();
+            tracing::info!("Recording started...");
+            recording.store(true, Ordering::SeqCst);
+            let environment = Environment::capture(self.backend.as_ref());
+            state.lock().unwrap().begin(displays, environment);
+            if let Some(path) = &thumbnail {
+                screenshot::capture_in_background(self.backend.clone(), path.clone());
+            }
+            hooks.start();
+            if let Some(duration) = duration {
+                stop_after(handle.clone(), hooks.clone(), duration);
+            }
+        }
 
-        if let EventType::KeyPress(key) = event.event_type {
-            // Start Recording
-            if key == keymaps.start_recording.trigger && check_modifiers(&keymaps.start_recording.modifiers) {
-                if !state.is_recording {
-                    log::info!("Recording started...");
-                    state.is_recording = true;
-                    state.events.clear();
-                    state.last_time = SystemTime::now();
+        let writer = Writer {
+            backend: self.backend.clone(),
+            state: state.clone(),
+            hooks: hooks.clone(),
+            handle: handle.clone(),
+            duration,
+            capture_text,
+            capture_clipboard,
+            screenshots,
+            screenshot_dir,
+            thumbnail,
+            undo,
+            max_events,
+            max_size,
+            limit_reached: false,
+            primary: platform::primary_modifier(),
+            span,
+        };
+        thread::spawn(move || writer.run(captured_rx, done_tx));
+
+        // The listener callback runs on the thread that delivers input to the
+        // whole system, so it only tracks hotkeys and timing and hands
+        // everything else to the writer thread.
+        let keymaps = self.keymaps;
+        let scroll_backend = self.backend.clone();
+        let mut modifiers = ModifierState::from_os();
+        // Keys whose press is in the recording
+        let mut pressed = HashSet::new();
+        let mut started = self.start_immediately;
+        // Monotonic, so wall clock changes (NTP, DST) never skew delays
+        let mut last_time = Instant::now();
+        let callback = move |event: Event| {
+            modifiers.update(&event.event_type);
+            let is_recording = recording.load(Ordering::SeqCst);
+
+            // Check for Hotkeys
+            if let (Some(keymaps), EventType::KeyPress(key)) = (&keymaps, event.event_type) {
+                // Start Recording
+                if modifiers.matches(&keymaps.start_recording, key) && !is_recording && !started {
+                    started = true;
+                    last_time = Instant::now();
+                    recording.store(true, Ordering::SeqCst);
+                    let _ = captured_tx.send(Captured::Start);
                     return; // Don't record the hotkey itself
                 }
+                // Save Buffer
+                if is_recording && rolling_window.is_some() && modifiers.matches(&keymaps.save_buffer, key) {
+                    let _ = captured_tx.send(Captured::SaveBuffer);
+                    return;
+                }
+                // Take Screenshot
+                if is_recording && modifiers.matches(&keymaps.take_screenshot, key) {
+                    let _ = captured_tx.send(Captured::Screenshot);
+                    return;
+                }
+                // Pause / Resume Recording
+                if is_recording && modifiers.matches(&keymaps.pause, key) {
+                    let now_paused = !paused.load(Ordering::SeqCst);
+                    paused.store(now_paused, Ordering::SeqCst);
+                    tracing::info!("Recording {}.", if now_paused { "paused" } else { "resumed" });
+                    return;
+                }
+                // Mark Checkpoint
+                if is_recording && !paused.load(Ordering::SeqCst) && modifiers.matches(&keymaps.checkpoint, key) {
+                    let delay = elapsed(&mut last_time, max_gap);
+                    let _ = captured_tx.send(Captured::Checkpoint { delay });
+                    return;
+                }
+                // Undo
+                if is_recording && undo.is_some() && !paused.load(Ordering::SeqCst) && modifiers.matches(&keymaps.undo, key) {
+                    // The next event follows on from here, as after a pause
+                    let since_last = elapsed(&mut last_time, None);
+                    let _ = captured_tx.send(Captured::Undo { since_last });
+                    return;
+                }
+                // Stop Recording
+                if modifiers.matches(&keymaps.stop_recording, key) && is_recording {
+                    recording.store(false, Ordering::SeqCst);
+                    let _ = captured_tx.send(Captured::Stop);
+                    return;
+                }
             }
-            // Stop Recording
-            if key == keymaps.stop_recording.trigger && check_modifiers(&keymaps.stop_recording.modifiers) {
-                if state.is_recording {
-                    log::info!("Recording stopped.");
-                    state.is_recording = false;
-                    if let Err(e) = save_events(&state.events, &output_path_clone) {
-                        log::error!("Failed to save events: {}", e);
+
+            if is_recording {
+                if paused.load(Ordering::SeqCst) {
+                    // Dropped, and the next delay starts from here so the
+                    // pause itself does not end up in the recording
+                    last_time = Instant::now();
+                    return;
+                }
+                // A key held since before capture started, like a modifier
+                // of the start hotkey, has no press in the recording, so its
+                // release is left out too
+                match event.event_type {
+                    EventType::KeyPress(key) => {
+                        pressed.insert(key);
                     }
-                    std::process::exit(0);
+                    EventType::KeyRelease(key) if !pressed.remove(&key) => return,
+                    _ => {}
+                }
+                let delay = elapsed(&mut last_time, max_gap);
+                // Only known while the backend is still delivering the event
+                let scroll = if matches!(event.event_type, EventType::Wheel { .. }) { scroll_backend.scroll_details() } else { None };
+                let _ = captured_tx.send(Captured::Event { event, delay, modifiers, scroll });
+            }
+        };
+
+        let listen_backend = self.backend.clone();
+        let listen_hooks = hooks.clone();
+        let listen_tx = handle.captured_tx.clone();
+        thread::spawn(move || {
+            let result = listen_backend.listen(Box::new(callback));
+            if let Err(error) = &result {
+                tracing::error!("Error: {:?}", error);
+                if let Some(hook) = &listen_hooks.on_error {
+                    hook(error);
+                }
+            }
+            // The backend stopped delivering events, so the session is over either way
+            let _ = listen_tx.send(Captured::Ended(result));
+        });
+
+        RecordingSession { handle, done_rx, hooks }
+    }
+}
+
+/// Time since `last_time`, capped at `max_gap`, moving `last_time` to now.
+fn elapsed(last_time: &mut Instant, max_gap: Option<Duration>) -> Duration {
+    let now = Instant::now();
+    let mut delay = now.saturating_duration_since(*last_time);
+    if let Some(max_gap) = max_gap {
+        delay = delay.min(max_gap);
+    }
+    *last_time = now;
+    delay
+}
+
+/// Message from the listener callback to the writer thread.
+enum Captured {
+    Start,
+    Event {
+        event: Event,
+        delay: Duration,
+        /// Modifiers held when the event happened.
+        modifiers: ModifierState,
+        /// What the backend saw of a wheel event beyond its deltas.
+        scroll: Option<ScrollDetails>,
+    },
+    Screenshot,
+    SaveBuffer,
+    /// The checkpoint hotkey, `delay` after the previous event.
+    Checkpoint { delay: Duration },
+    /// The undo hotkey, `since_last` after the previous event.
+    Undo { since_last: Duration },
+    /// Sent after the last event, so everything before it is stored by the
+    /// time the session is told capture has stopped.
+    Stop,
+    /// The backend stopped listening, with its result.
+    Ended(Result<()>),
+}
+
+/// Converts captured events and stores them, off the listener thread.
+struct Writer {
+    backend: Arc<dyn InputBackend>,
+    state: Arc<Mutex<RecorderState>>,
+    hooks: Arc<RecorderHooks>,
+    handle: RecordingHandle,
+    duration: Option<Duration>,
+    capture_text: bool,
+    capture_clipboard: bool,
+    screenshots: Vec<ScreenshotTrigger>,
+    screenshot_dir: Option<PathBuf>,
+    /// Where to save a screenshot as capture begins, if anywhere.
+    thumbnail: Option<PathBuf>,
+    undo: Option<UndoScope>,
+    max_events: Option<usize>,
+    max_size: Option<u64>,
+    /// Set once a limit stopped capture, so events still on their way are dropped.
+    limit_reached: bool,
+    primary: Modifier,
+    /// Span of the session, closed as it ends.
+    span: Span,
+}
+
+impl Writer {
+    fn run(mut self, captured_rx: mpsc::Receiver<Captured>, done_tx: mpsc::Sender<Result<()>>) {
+        // The handle keeps a sender alive, so this only ends with the process
+        for captured in captured_rx {
+            let span = self.span.clone();
+            let _entered = span.enter();
+            let mut state = self.state.lock().unwrap();
+            match captured {
+                Captured::Start => {
+                    tracing::info!("Recording started...");
+                    state.begin(self.backend.displays(), Environment::capture(self.backend.as_ref()));
+                    if let Some(path) = &self.thumbnail {
+                        screenshot::capture_in_background(self.backend.clone(), path.clone());
+                    }
+                    self.hooks.start();
+                    if let Some(duration) = self.duration {
+                        stop_after(self.handle.clone(), self.hooks.clone(), duration);
+                    }
+                }
+                Captured::Event { .. } | Captured::Checkpoint { .. } if self.limit_reached => {}
+                Captured::Event { event, delay, modifiers, scroll } => {
+                    self.store(&mut state, event, delay, modifiers, scroll);
+                    self.limit_reached = self.check_limits(&state);
+                }
+                Captured::Screenshot => self.screenshot(&state, ScreenshotTrigger::Hotkey),
+                Captured::Checkpoint { delay } => {
+                    self.checkpoint(&mut state, delay);
+                    self.limit_reached = self.check_limits(&state);
+                }
+                Captured::Undo { since_last } => self.undo(&mut state, since_last),
+                Captured::SaveBuffer => {
+                    if let (Some(hook), Some(recording)) = (&self.hooks.on_save_buffer, state.to_recording()) {
+                        hook(&recording);
+                    }
+                }
+                Captured::Stop => {
+                    tracing::info!("Recording stopped.");
+                    let _ = done_tx.send(Ok(()));
+                    self.span = Span::none();
+                }
+                Captured::Ended(result) => {
+                    let _ = done_tx.send(result);
+                    self.span = Span::none();
+                }
+            }
+        }
+    }
+
+    fn store(&self, state: &mut RecorderState, event: Event, mut delay: Duration, modifiers: ModifierState, scroll: Option<ScrollDetails>) {
+        if let Some(own_ui) = &mut state.own_ui {
+            let Some(kept) = own_ui.keep(&event.event_type, delay) else {
+                return;
+            };
+            delay = kept;
+        }
+
+        if let EventType::ButtonPress(_) = event.event_type {
+            self.screenshot(state, ScreenshotTrigger::Click);
+        }
+
+        let is_paste = event.event_type == EventType::KeyPress(Key::KeyV) && modifiers.is_held(&self.primary);
+        if self.capture_clipboard && is_paste {
+            match self.backend.clipboard_text() {
+                Ok(Some(text)) if state.last_clipboard.as_ref() != Some(&text) => {
+                    state.last_clipboard = Some(text.clone());
+                    let mut snapshot = SerializableEvent {
+                        event_type: SerializableEventType::Clipboard { text },
+                        delay_ms: 0,
+                        delay_us: 0,
+                    };
+                    snapshot.set_delay(delay);
+                    state.push(snapshot, &self.hooks);
+                    // The paste itself follows the snapshot immediately
+                    delay = Duration::ZERO;
                 }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Could not read the clipboard: {:?}", e),
             }
         }
 
-        if state.is_recording {
-             let now = SystemTime::now();
-             let delay = now.duration_since(state.last_time).unwrap().as_millis() as u64;
-             state.last_time = now;
+        // Shortcuts stay physical keys, as their text is not what they do
+        let capture_text = self.capture_text && !modifiers.cmd && !modifiers.ctrl;
+        if let Some(serializable_event) = SerializableEvent::from_rdev(event, delay, &state.displays, capture_text, scroll) {
+            state.push(serializable_event, &self.hooks);
+        }
+    }
+
+    /// Stores a checkpoint named after how many came before it, e.g.
+    /// `checkpoint-1` for the first.
+    fn checkpoint(&self, state: &mut RecorderState, delay: Duration) {
+        let count = state
+            .events
+            .iter()
+            .filter(|event| matches!(event.event_type, SerializableEventType::Checkpoint { .. }))
+            .count();
+        let name = format!("checkpoint-{}", count + 1);
+        tracing::info!("Checkpoint '{}' marked.", name);
+        let mut event = SerializableEvent {
+            event_type: SerializableEventType::Checkpoint { name },
+            delay_ms: 0,
+            delay_us: 0,
+        };
+        event.set_delay(delay);
+        state.push(event, &self.hooks);
+    }
+
+    fn undo(&self, state: &mut RecorderState, since_last: Duration) {
+        let Some(scope) = self.undo else {
+            return;
+        };
+        let dropped = state.undo(scope, since_last);
+        tracing::info!("Undo: dropped {} events.", dropped);
+        if let (Some(hook), Some(recording)) = (&self.hooks.on_undo, state.to_recording()) {
+            hook(&recording);
+        }
+    }
+
+    /// Stops capture once the recording has grown to its event or size
+    /// limit. Returns whether it did.
+    fn check_limits(&self, state: &RecorderState) -> bool {
+        let limit = match (self.max_events, self.max_size) {
+            (Some(max_events), _) if state.events.len() >= max_events => RecordLimit::Events(max_events),
+            (_, Some(max_size)) if state.size >= max_size => RecordLimit::Size(max_size),
+            _ => return false,
+        };
+        reach_limit(&self.handle, &self.hooks, limit);
+        true
+    }
 
-             if let Some(serializable_event) = SerializableEvent::from_rdev(event.clone(), delay) {
-                 log::info!("Recorded event: {:?}", serializable_event);
-                 state.events.push(serializable_event);
-                 
-                 // serialization removed for performance
-             }
+    /// Saves a screenshot named after the next event's index, if screenshots
+    /// are enabled for `trigger`.
+    fn screenshot(&self, state: &RecorderState, trigger: ScreenshotTrigger) {
+        if let Some(dir) = &self.screenshot_dir
+            && self.screenshots.contains(&trigger)
+        {
+            let path = screenshot::recording_path(dir, trigger, state.events.len());
+            screenshot::capture_in_background(self.backend.clone(), path);
         }
+    }
+}
+
+/// Stops the recording from a timer thread once `duration` has passed.
+fn stop_after(handle: RecordingHandle, hooks: Arc<RecorderHooks>, duration: Duration) {
+    tracing::info!("Recording will stop automatically after {:?}.", duration);
+    thread::spawn(move || {
+        thread::sleep(duration);
+        reach_limit(&handle, &hooks, RecordLimit::Duration(duration));
+    });
+}
+
+/// Warns that `limit` was reached and stops capture, if it is still going.
+fn reach_limit(handle: &RecordingHandle, hooks: &RecorderHooks, limit: RecordLimit) {
+    if !handle.is_recording() {
+        return;
+    }
+    tracing::warn!("Recording reached its {}, stopping.", limit);
+    if let Some(hook) = &hooks.on_limit {
+        hook(limit);
+    }
+    handle.stop();
+}
+
+/// Cloneable handle for stopping or inspecting a running recording from
+/// another thread (e.g. a signal handler).
+#[derive(Clone)]
+pub struct RecordingHandle {
+    state: Arc<Mutex<RecorderState>>,
+    recording: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    captured_tx: mpsc::Sender<Captured>,
+}
+
+impl RecordingHandle {
+    pub fn stop(&self) {
+        self.recording.store(false, Ordering::SeqCst);
+        let _ = self.captured_tx.send(Captured::Stop);
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::SeqCst)
+    }
+
+    /// Drops input until [`resume`](Self::resume) is called. The time spent
+    /// paused is left out of the recording.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Events captured so far, or `None` if capture has not started yet.
+    pub fn snapshot(&self) -> Option<Recording> {
+        self.state.lock().unwrap().to_recording()
+    }
+}
+
+pub struct RecordingSession {
+    handle: RecordingHandle,
+    done_rx: mpsc::Receiver<Result<()>>,
+    hooks: Arc<RecorderHooks>,
+}
+
+impl RecordingSession {
+    pub fn handle(&self) -> RecordingHandle {
+        self.handle.clone()
+    }
+
+    /// Blocks until the recording is stopped by the stop hotkey, a
+    /// [`RecordingHandle`], or the backend ending. Returns `None` if capture
+    /// never started.
+    pub fn wait(self) -> Result<Option<Recording>> {
+        // The writer thread lives as long as the session's handle, so this cannot disconnect
+        self.done_rx.recv()??;
+        self.handle.recording.store(false, Ordering::SeqCst);
+        let recording = self.handle.state.lock().unwrap().to_recording();
+        if let (Some(hook), Some(recording)) = (&self.hooks.on_finish, &recording) {
+            hook(recording);
+        }
+        Ok(recording)
+    }
+
+    pub fn stop(self) -> Result<Option<Recording>> {
+        self.handle.stop();
+        self.wait()
+    }
+}
+
+pub fn run_record(backend: Arc<dyn InputBackend>, output_path: PathBuf, options: RecordOptions, keymaps: KeyMaps, immediate: bool) -> Result<()> {
+    platform::check_input_backend()?;
+    let timer = RunTimer::start(RunKind::Record, Some(&output_path), options.to_args());
+    let result = record_to_file(backend, &output_path, options, keymaps, immediate);
+    // Quitting before capture started is not a run
+    if !matches!(result, Ok(false)) {
+        history::log(&timer.finish(&result, false));
+    }
+    result.map(|_| ())
+}
+
+/// Records a new segment onto the end of the recording at `output_path`,
+/// called `segment` or `segment-<n>`, creating the file if it does not
+/// exist yet. Captures into a file next to it first, so the recording is
+/// left as it was if capture fails.
+pub fn run_record_segment(backend: Arc<dyn InputBackend>, output_path: PathBuf, segment: Option<String>, options: RecordOptions, keymaps: KeyMaps, immediate: bool) -> Result<()> {
+    platform::check_input_backend()?;
+    let mut recording = if output_path.exists() {
+        Recording::load(&output_path)?
+    } else {
+        Recording::new(Vec::new(), &backend.displays())
     };
+    if let Some(name) = &segment
+        && recording.segments().contains(&name.as_str())
+    {
+        anyhow::bail!("{} already has a segment named '{}'", output_path.display(), name);
+    }
+    let extension = output_path.extension().unwrap_or_default().to_string_lossy();
+    let part_path = output_path.with_extension(format!("segment.{}", extension));
 
-    if let Err(error) = listen(callback) {
-        log::error!("Error: {:?}", error);
-        return Err(anyhow::anyhow!("Listen error: {:?}", error));
+    let timer = RunTimer::start(RunKind::Record, Some(&output_path), options.to_args());
+    let result = record_to_file(backend, &part_path, options, keymaps, immediate).and_then(|started| {
+        if started {
+            let part = Recording::load(&part_path)?;
+            let name = recording.append_segment(segment, part.events)?;
+            recording.save(&output_path)?;
+            tracing::info!("Added segment '{}' to {:?}", name, output_path);
+        }
+        Ok(started)
+    });
+    let _ = fs::remove_file(&part_path);
+    if !matches!(result, Ok(false)) {
+        history::log(&timer.finish(&result, false));
+    }
+    result.map(|_| ())
+}
+
+/// Records into `output_path` until stopped, without checking that this
+/// session can capture input. Returns whether capture ever started; if it
+/// never did, `output_path` is left untouched.
+pub fn record_to_file(backend: Arc<dyn InputBackend>, output_path: &Path, options: RecordOptions, keymaps: KeyMaps, immediate: bool) -> Result<bool> {
+    tracing::info!("Running in background.");
+    tracing::info!("Start Recording: {:?} + {:?}", keymaps.start_recording.modifiers, keymaps.start_recording.trigger);
+    tracing::info!("Stop Recording: {:?} + {:?}", keymaps.stop_recording.modifiers, keymaps.stop_recording.trigger);
+    tracing::info!("Pause Recording: {:?} + {:?}", keymaps.pause.modifiers, keymaps.pause.trigger);
+    tracing::info!("Mark Checkpoint: {:?} + {:?}", keymaps.checkpoint.modifiers, keymaps.checkpoint.trigger);
+    if let Some(undo) = options.undo {
+        tracing::info!("Undo Last {}: {:?} + {:?}", undo, keymaps.undo.modifiers, keymaps.undo.trigger);
+    }
+
+    let autosave_interval = options.autosave;
+    let collapse_key_repeat = options.collapse_key_repeat;
+    let mut recorder = Recorder::new()
+        .backend(backend.clone())
+        .hotkeys(keymaps)
+        .start_immediately(immediate)
+        .options(options)
+        .on_limit(|limit| eprintln!("Warning: The recording reached its {}, stopping and saving it.", limit));
+    // JSON lines recordings are written as events arrive instead of all at
+    // the end, so a crash loses nothing. Nothing touches the file until
+    // capture starts, so quitting before then leaves an existing one as it was.
+    let jsonl = recording::is_jsonl(output_path);
+    let writer: Arc<Mutex<Option<JsonlWriter>>> = Arc::new(Mutex::new(None));
+    if jsonl {
+        let start_writer = writer.clone();
+        let event_writer = writer.clone();
+        let undo_writer = writer.clone();
+        let start_backend = backend.clone();
+        let start_path = output_path.to_path_buf();
+        let path = output_path.to_path_buf();
+        recorder = recorder
+            .on_start(move || {
+                let metadata = RecordingMetadata::capture(&start_backend.displays()).with_environment(Environment::capture(start_backend.as_ref()));
+                match JsonlWriter::create(&start_path, &metadata) {
+                    Ok(writer) => *start_writer.lock().unwrap() = Some(writer),
+                    Err(e) => tracing::error!("Failed to create the recording: {:?}", e),
+                }
+            })
+            .on_event(move |event| {
+                if let Some(writer) = event_writer.lock().unwrap().as_mut()
+                    && let Err(e) = writer.write_event(event)
+                {
+                    tracing::error!("Failed to append event to the recording: {:?}", e);
+                }
+            })
+            .on_undo(move |recording| {
+                if let Some(writer) = undo_writer.lock().unwrap().as_mut()
+                    && let Err(e) = writer.rewrite(&path, recording)
+                {
+                    tracing::error!("Failed to rewrite the recording after undo: {:?}", e);
+                }
+            });
+    } else {
+        // Create the file as soon as capture starts, so it shows up right away
+        let start_backend = backend.clone();
+        let path = output_path.to_path_buf();
+        recorder = recorder.on_start(move || {
+            if let Err(e) = Recording::new(Vec::new(), &start_backend.displays()).save(&path) {
+                tracing::error!("Failed to create the recording: {:?}", e);
+            }
+        });
+    }
+    let mut autosave = None;
+    if let Some(interval) = autosave_interval.filter(|_| !jsonl) {
+        tracing::info!("Autosave: every {}", interval);
+        let (tx, rx) = mpsc::channel();
+        let event_tx = tx.clone();
+        recorder = recorder.on_event(move |_| {
+            let _ = event_tx.send(AutosaveSignal::Event);
+        });
+        autosave = Some((tx, rx, interval));
+    }
+    let session = recorder.start();
+    let autosave = autosave.map(|(tx, rx, interval)| {
+        let handle = session.handle();
+        let path = output_path.to_path_buf();
+        (tx, thread::spawn(move || run_autosave(rx, &handle, &path, interval)))
+    });
+
+    // The tray app controls its workers over stdin
+    if immediate {
+        let handle = session.handle();
+        worker::listen_for_commands(BufReader::new(io::stdin()), move |command| match command {
+            WorkerCommand::Pause => handle.pause(),
+            WorkerCommand::Resume => handle.resume(),
+            WorkerCommand::Stop => handle.stop(),
+            WorkerCommand::Status => worker::report(&WorkerReport::Status {
+                paused: handle.is_paused(),
+                events: handle.snapshot().map_or(0, |recording| recording.events.len()),
+            }),
+        });
+    }
+
+    // Handle Ctrl+C / SIGTERM
+    let handle = session.handle();
+    ctrlc::set_handler(move || {
+        tracing::info!("Ctrl+C / SIGTERM handler triggered");
+        handle.stop();
+    })?;
+
+    let recording = session.wait()?;
+    // Stop autosaving first, so a late autosave never overwrites the final save
+    if let Some((tx, thread)) = autosave {
+        let _ = tx.send(AutosaveSignal::Stop);
+        let _ = thread.join();
+    }
+    match &recording {
+        Some(recording) if jsonl => {
+            match writer.lock().unwrap().as_mut() {
+                // Events went out as they were captured, before their repeats could be told apart
+                Some(writer) if collapse_key_repeat => writer.rewrite(output_path, recording)?,
+                Some(_) => {}
+                // Creating the file failed when capture started, so this is the last chance
+                None => recording.save(output_path)?,
+            }
+            tracing::info!("Saved {} events to {:?}", recording.events.len(), output_path);
+        }
+        Some(recording) => save_recording(recording, output_path)?,
+        None => tracing::info!("Not recording, exiting without save."),
     }
 
+    Ok(recording.is_some())
+}
+
+enum AutosaveSignal {
+    Event,
+    Stop,
+}
+
+/// Saves the recording behind `handle` to `path` whenever `interval` has
+/// passed since the last save, until told to stop.
+fn run_autosave(signals: mpsc::Receiver<AutosaveSignal>, handle: &RecordingHandle, path: &Path, interval: AutosaveInterval) {
+    let mut unsaved = 0;
+    let mut first_unsaved = Instant::now();
+    loop {
+        match signals.recv_timeout(Duration::from_millis(100)) {
+            Ok(AutosaveSignal::Event) => {
+                if unsaved == 0 {
+                    first_unsaved = Instant::now();
+                }
+                unsaved += 1;
+            }
+            Ok(AutosaveSignal::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+        let due = match interval {
+            AutosaveInterval::Events(events) => unsaved >= events,
+            AutosaveInterval::Time(time) => unsaved > 0 && first_unsaved.elapsed() >= time,
+        };
+        if !due {
+            continue;
+        }
+        unsaved = 0;
+        let Some(recording) = handle.snapshot() else {
+            continue;
+        };
+        // Written beside the recording and moved over it, so a crash mid-save
+        // leaves the previous save intact
+        let temp_path = path.with_extension("json.autosave");
+        match recording.save(&temp_path).and_then(|()| Ok(fs::rename(&temp_path, path)?)) {
+            Ok(()) => tracing::debug!("Autosaved {} events to {:?}", recording.events.len(), path),
+            Err(e) => tracing::error!("Autosave to {:?} failed: {:?}", path, e),
+        }
+    }
+}
+
+/// Keeps the last `window` of input in memory, saving it to `output_path`
+/// each time the save buffer hotkey is pressed. Later saves pick the next
+/// free numbered name unless `force` is set. Runs until the stop recording
+/// hotkey or Ctrl+C.
+pub fn run_record_buffer(
+    backend: Arc<dyn InputBackend>,
+    output_path: PathBuf,
+    options: RecordOptions,
+    keymaps: KeyMaps,
+    window: Duration,
+    force: bool,
+) -> Result<()> {
+    platform::check_input_backend()?;
+    tracing::info!("Keeping the last {} of input.", humantime::format_duration(window));
+    tracing::info!("Save Buffer: {:?} + {:?}", keymaps.save_buffer.modifiers, keymaps.save_buffer.trigger);
+    tracing::info!("Stop Recording: {:?} + {:?}", keymaps.stop_recording.modifiers, keymaps.stop_recording.trigger);
+
+    let session = Recorder::new()
+        .backend(backend)
+        .hotkeys(keymaps)
+        .options(options)
+        .rolling_window(window)
+        .on_save_buffer(move |recording| {
+            let path = if force || !output_path.exists() {
+                output_path.clone()
+            } else {
+                next_available_path(&output_path)
+            };
+            if let Err(e) = save_recording(recording, &path) {
+                tracing::error!("Failed to save buffer to {:?}: {:?}", path, e);
+            }
+        })
+        .start();
+
+    // Handle Ctrl+C / SIGTERM
+    let handle = session.handle();
+    ctrlc::set_handler(move || {
+        tracing::info!("Ctrl+C / SIGTERM handler triggered");
+        handle.stop();
+    })?;
+
+    session.wait()?;
     Ok(())
 }
 
-pub fn save_events(events: &[SerializableEvent], path: &PathBuf) -> Result<()> {
-    if events.is_empty() {
-        log::warn!("No events captured! This usually means the application does not have Accessibility Permissions.");
-        log::warn!("Please check System Settings -> Privacy & Security -> Accessibility.");
+/// Streams captured events to stdout as JSON lines instead of saving a file,
+/// so they can be piped into filters or straight into `macro play -`.
+pub fn run_record_stream(backend: Arc<dyn InputBackend>, options: RecordOptions, keymaps: KeyMaps, immediate: bool) -> Result<()> {
+    platform::check_input_backend()?;
+    tracing::info!("Streaming events to stdout.");
+    tracing::info!("Start Recording: {:?} + {:?}", keymaps.start_recording.modifiers, keymaps.start_recording.trigger);
+    tracing::info!("Stop Recording: {:?} + {:?}", keymaps.stop_recording.modifiers, keymaps.stop_recording.trigger);
+
+    let session = Recorder::new()
+        .backend(backend)
+        .hotkeys(keymaps)
+        .start_immediately(immediate)
+        .options(options)
+        .on_limit(|limit| eprintln!("Warning: The recording reached its {}, stopping.", limit))
+        .on_event(|event| {
+            if let Err(e) = write_event_line(&mut io::stdout().lock(), event) {
+                tracing::error!("Failed to write event to stdout: {:?}", e);
+            }
+        })
+        .start();
+
+    // stdout carries the events, so status requests are only logged
+    if immediate {
+        let handle = session.handle();
+        worker::listen_for_commands(BufReader::new(io::stdin()), move |command| match command {
+            WorkerCommand::Pause => handle.pause(),
+            WorkerCommand::Resume => handle.resume(),
+            WorkerCommand::Stop => handle.stop(),
+            WorkerCommand::Status => tracing::info!("Status: paused: {}", handle.is_paused()),
+        });
+    }
+
+    // Handle Ctrl+C / SIGTERM
+    let handle = session.handle();
+    ctrlc::set_handler(move || {
+        tracing::info!("Ctrl+C / SIGTERM handler triggered");
+        handle.stop();
+    })?;
+
+    session.wait()?;
+    Ok(())
+}
+
+/// Writes `event` as one JSON line, in the format [`play_stream`](crate::play::play_stream) reads.
+pub(crate) fn write_event_line(out: &mut impl Write, event: &SerializableEvent) -> Result<()> {
+    serde_json::to_writer(&mut *out, event)?;
+    writeln!(out)?;
+    // Flush every line so a reader on the other end of the pipe sees it immediately
+    out.flush()?;
+    Ok(())
+}
+
+/// First of `name-2.ext`, `name-3.ext`, ... that does not exist yet.
+pub fn next_available_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|ext| ext.to_string_lossy());
+    (2..)
+        .map(|n| {
+            let name = match &extension {
+                Some(ext) => format!("{}-{}.{}", stem, n, ext),
+                None => format!("{}-{}", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+pub fn save_recording(recording: &Recording, path: &Path) -> Result<()> {
+    if recording.events.is_empty() {
+        tracing::warn!("No events captured! This usually means the application does not have Accessibility Permissions.");
+        tracing::warn!("Please check System Settings -> Privacy & Security -> Accessibility.");
     }
-    log::info!("Saving {} events to {:?}", events.len(), path);
-    let file = File::create(path)?;
-    serde_json::to_writer(&file, events)?;
-    // Ensure data is flushed to disk before returning
-    file.sync_all()?;
-    log::info!("Saved to {:?}", path);
+    tracing::info!("Saving {} events to {:?}", recording.events.len(), path);
+    recording.save(path)?;
+    tracing::info!("Saved to {:?}", path);
     Ok(())
 }
@@ -0,0 +1,329 @@
+use crate::config::Preset;
+use crate::display::{self, DisplayInfo};
+use crate::environment::Environment;
+use crate::event::{SerializableEvent, SerializableEventType};
+use crate::pack::{self, Package};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Unit used for the mouse coordinates stored in a recording.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CoordinateSpace {
+    #[default]
+    Points,
+    Pixels,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordingMetadata {
+    pub version: u32,
+    /// Backing scale factor of the primary display at capture time.
+    pub scale_factor: f64,
+    #[serde(default)]
+    pub coordinate_space: CoordinateSpace,
+    #[serde(default)]
+    pub displays: Vec<DisplayInfo>,
+    /// Operating system, keyboard layout and frontmost app at capture time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<Environment>,
+    /// Playback settings this recording is played with unless others are given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub playback: Option<Preset>,
+}
+
+impl RecordingMetadata {
+    /// Describes the current machine, for a recording being captured now.
+    pub fn capture(displays: &[DisplayInfo]) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            scale_factor: display::primary_scale_factor(displays),
+            coordinate_space: CoordinateSpace::Points,
+            displays: displays.to_vec(),
+            environment: None,
+            playback: None,
+        }
+    }
+
+    pub fn with_environment(mut self, environment: Environment) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// Metadata assumed for files written before metadata existed. Those were
+    /// captured on macOS, where rdev already reports points.
+    pub fn legacy() -> Self {
+        Self {
+            version: 0,
+            scale_factor: 1.0,
+            coordinate_space: CoordinateSpace::Points,
+            displays: Vec::new(),
+            environment: None,
+            playback: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Recording {
+    pub metadata: RecordingMetadata,
+    pub events: Vec<SerializableEvent>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RecordingFile {
+    Current(Recording),
+    Legacy(Vec<SerializableEvent>),
+}
+
+/// First line of a JSON lines recording.
+#[derive(Serialize, Deserialize)]
+struct MetadataLine {
+    metadata: RecordingMetadata,
+}
+
+/// Whether `path` is saved as JSON lines: a metadata line followed by one
+/// line per event, rather than a single JSON document.
+pub fn is_jsonl(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "jsonl")
+}
+
+/// Appends events to a JSON lines recording as they happen. Each line is
+/// written whole, so a recording cut short by a crash stays readable.
+pub struct JsonlWriter {
+    file: File,
+}
+
+impl JsonlWriter {
+    /// Creates the file at `path`, replacing any existing one, and writes the metadata line.
+    pub fn create(path: &Path, metadata: &RecordingMetadata) -> Result<Self> {
+        let mut writer = Self { file: File::create(path)? };
+        writer.write_line(&MetadataLine { metadata: metadata.clone() })?;
+        Ok(writer)
+    }
+
+    pub fn write_event(&mut self, event: &SerializableEvent) -> Result<()> {
+        self.write_line(event)
+    }
+
+    fn write_line(&mut self, value: &impl Serialize) -> Result<()> {
+        let mut line = serde_json::to_vec(value)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Replaces the file at `path` with one holding all of `recording`, and
+    /// carries on appending to that. The new file is written beside the old
+    /// one and moved over it, so a crash part way leaves one of them whole.
+    pub fn rewrite(&mut self, path: &Path, recording: &Recording) -> Result<()> {
+        let temp_path = path.with_extension("jsonl.rewrite");
+        let writer = Self::create_with(&temp_path, recording)?;
+        std::fs::rename(&temp_path, path)?;
+        *self = writer;
+        Ok(())
+    }
+
+    /// Creates the file at `path` holding all of `recording`.
+    fn create_with(path: &Path, recording: &Recording) -> Result<Self> {
+        let mut writer = Self::create(path, &recording.metadata)?;
+        for event in &recording.events {
+            writer.write_event(event)?;
+        }
+        Ok(writer)
+    }
+
+    /// Flushes everything written to disk.
+    pub fn finish(self) -> Result<()> {
+        self.file.sync_all()?;
+        Ok(())
+    }
+}
+
+impl Recording {
+    pub fn new(events: Vec<SerializableEvent>, displays: &[DisplayInfo]) -> Self {
+        Self {
+            metadata: RecordingMetadata::capture(displays),
+            events,
+        }
+    }
+
+    /// Loads a recording, accepting the current format, the bare event
+    /// arrays written by older versions, JSON lines and packages.
+    pub fn load(path: &Path) -> Result<Self> {
+        if pack::is_package(path) {
+            return Ok(Package::open(path)?.recording);
+        }
+        let text = std::fs::read_to_string(path)?;
+        let recording = match serde_json::from_str(&text) {
+            Ok(RecordingFile::Current(recording)) => recording,
+            Ok(RecordingFile::Legacy(events)) => {
+                tracing::info!("Loaded legacy recording without metadata, assuming points.");
+                Self {
+                    metadata: RecordingMetadata::legacy(),
+                    events,
+                }
+            }
+            Err(_) if looks_like_jsonl(&text) => Self::from_jsonl(&text)?,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(recording)
+    }
+
+    /// Parses JSON lines, as written by [`JsonlWriter`] or `macro record -`.
+    /// A last line that stops short is skipped, as it is an event cut off
+    /// by a crash.
+    fn from_jsonl(text: &str) -> Result<Self> {
+        let lines: Vec<(usize, &str)> = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .collect();
+        let mut lines = lines.as_slice();
+        let metadata = match lines.first().and_then(|(_, line)| serde_json::from_str::<MetadataLine>(line).ok()) {
+            Some(line) => {
+                lines = &lines[1..];
+                line.metadata
+            }
+            // Streams are written by the current recorder, which stores points
+            None => RecordingMetadata::capture(&[]),
+        };
+        let mut events = Vec::with_capacity(lines.len());
+        for (position, (number, line)) in lines.iter().enumerate() {
+            match serde_json::from_str(line) {
+                Ok(event) => events.push(event),
+                Err(e) if position + 1 == lines.len() && e.is_eof() => tracing::warn!("Skipping incomplete last line {}: {}", number + 1, e),
+                Err(e) => anyhow::bail!("Line {}: {}", number + 1, e),
+            }
+        }
+        Ok(Self { metadata, events })
+    }
+
+    /// Names of the recording's segments, in order.
+    pub fn segments(&self) -> Vec<&str> {
+        self.events
+            .iter()
+            .filter_map(|event| match &event.event_type {
+                SerializableEventType::Segment { name } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Adds `events` to the end as a new segment called `name`, or
+    /// `segment-<n>` if not given, and returns its name. Events already
+    /// there that are not in a segment become the first one.
+    pub fn append_segment(&mut self, name: Option<String>, events: Vec<SerializableEvent>) -> Result<String> {
+        let mut segments = self.segments().len();
+        if segments == 0 && !self.events.is_empty() {
+            self.events.insert(0, segment_marker("segment-1".to_string()));
+            segments = 1;
+        }
+        let name = name.unwrap_or_else(|| format!("segment-{}", segments + 1));
+        if self.segments().contains(&name.as_str()) {
+            anyhow::bail!("The recording already has a segment named '{}'", name);
+        }
+        self.events.push(segment_marker(name.clone()));
+        self.events.extend(events);
+        Ok(name)
+    }
+
+    /// Merges `recordings` into one that plays each of their events when it
+    /// would have played on its own, so they can run at the same time. Of
+    /// events due at once, those of earlier recordings come first. The
+    /// recordings must store mouse positions the same way.
+    pub fn interleave(recordings: Vec<Recording>) -> Result<Self> {
+        let Some(metadata) = recordings.first().map(|recording| recording.metadata.clone()) else {
+            anyhow::bail!("No recordings to play");
+        };
+        let mut timeline = Vec::new();
+        for (index, recording) in recordings.into_iter().enumerate() {
+            if recording.metadata.coordinate_space != metadata.coordinate_space {
+                anyhow::bail!("Recording {} stores mouse positions in {:?}, not {:?} like the first", index + 1, recording.metadata.coordinate_space, metadata.coordinate_space);
+            }
+            let mut at = Duration::ZERO;
+            for event in recording.events {
+                at += event.delay();
+                timeline.push((at, event));
+            }
+        }
+        // Stable, so ties keep the order they were added in
+        timeline.sort_by_key(|(at, _)| *at);
+        let mut previous = Duration::ZERO;
+        let events = timeline
+            .into_iter()
+            .map(|(at, mut event)| {
+                event.set_delay(at - previous);
+                previous = at;
+                event
+            })
+            .collect();
+        Ok(Self { metadata, events })
+    }
+
+    /// Saves the recording, as JSON lines if `path` ends in `.jsonl`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if pack::is_package(path) {
+            anyhow::bail!("Cannot save over the package {:?}, save the recording to a .json file and pack it again", path);
+        }
+        if is_jsonl(path) {
+            return JsonlWriter::create_with(path, self)?.finish();
+        }
+        let file = File::create(path)?;
+        serde_json::to_writer(&file, self)?;
+        // Ensure data is flushed to disk before returning
+        file.sync_all()?;
+        Ok(())
+    }
+}
+
+/// Playback settings the recording at `path` is meant to be played with: a
+/// package's preset, or else the settings saved in the recording itself.
+pub fn saved_playback(path: &Path) -> Result<Option<Preset>> {
+    if pack::is_package(path) {
+        let package = Package::open(path)?;
+        return Ok(package.preset.or(package.recording.metadata.playback));
+    }
+    let recording = Recording::load(path).with_context(|| format!("Could not load {:?}", path))?;
+    Ok(recording.metadata.playback)
+}
+
+/// Saves `changes` over the playback settings kept in the recording at
+/// `path`, or with `clear` removes them, and prints what it is left with.
+pub fn run_defaults(path: &Path, changes: Preset, clear: bool) -> Result<()> {
+    let mut recording = Recording::load(path).with_context(|| format!("Could not load {:?}", path))?;
+    if clear || changes != Preset::default() {
+        recording.metadata.playback = match clear {
+            true => None,
+            false => Some(changes.or(recording.metadata.playback.unwrap_or_default())),
+        };
+        recording.save(path)?;
+    }
+    match &recording.metadata.playback {
+        Some(settings) => println!("Playback settings: {}", serde_json::to_string(settings)?),
+        None => println!("No playback settings saved, the config file's are used."),
+    }
+    Ok(())
+}
+
+fn segment_marker(name: String) -> SerializableEvent {
+    SerializableEvent {
+        event_type: SerializableEventType::Segment { name },
+        delay_ms: 0,
+        delay_us: 0,
+    }
+}
+
+/// Whether `text` starts with a complete JSON value on its own line, which a
+/// JSON document spanning several lines never does.
+fn looks_like_jsonl(text: &str) -> bool {
+    text.lines()
+        .find(|line| !line.trim().is_empty())
+        .is_some_and(|line| serde_json::from_str::<serde_json::Value>(line).is_ok())
+}
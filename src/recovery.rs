@@ -0,0 +1,61 @@
+use crate::record;
+use crate::recording::Recording;
+use crate::screenshot;
+use anyhow::Result;
+use chrono::Local;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Start of the names of the files the menu bar app records into before
+/// they are saved.
+const TEMP_PREFIX: &str = "macro_recording_";
+
+/// New temp file in `dir` for the menu bar app to record into. It is JSON
+/// lines, so everything captured up to a crash is already on disk.
+pub fn temp_recording_path(dir: &Path) -> PathBuf {
+    dir.join(format!("{}{}.jsonl", TEMP_PREFIX, Local::now().format("%Y%m%d_%H%M%S")))
+}
+
+/// Temp recordings left in `dir` by a recording that never got saved or
+/// discarded, e.g. because the app or its recorder crashed. Oldest first.
+pub fn orphaned_recordings(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let is_recording = matches!(path.extension().and_then(|e| e.to_str()), Some("json" | "jsonl"));
+            name.starts_with(TEMP_PREFIX) && is_recording && path.is_file()
+        })
+        .collect();
+    // Names end in the time recording started
+    paths.sort();
+    paths
+}
+
+/// Saves the temp recording at `temp_path` into `recordings_dir` as JSON,
+/// with its thumbnail if it has one, and removes it. Returns where it was saved.
+pub fn recover(temp_path: &Path, recordings_dir: &Path) -> Result<PathBuf> {
+    let recording = Recording::load(temp_path)?;
+    let stem = temp_path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = format!("recovered_{}.json", stem.trim_start_matches(TEMP_PREFIX));
+    fs::create_dir_all(recordings_dir)?;
+    let path = recordings_dir.join(name);
+    let path = if path.exists() { record::next_available_path(&path) } else { path };
+    recording.save(&path)?;
+    if let Err(e) = screenshot::copy_thumbnail(temp_path, &path) {
+        tracing::warn!("Could not keep the thumbnail of {:?}: {:?}", temp_path, e);
+    }
+    fs::remove_file(temp_path)?;
+    let _ = fs::remove_dir_all(screenshot::dir_for(temp_path));
+    Ok(path)
+}
+
+/// Removes the temp recording at `temp_path` along with its screenshots.
+pub fn discard(temp_path: &Path) {
+    let _ = fs::remove_file(temp_path);
+    let _ = fs::remove_dir_all(screenshot::dir_for(temp_path));
+}
@@ -0,0 +1,183 @@
+use crate::event::SerializableEventType;
+use crate::platform;
+use rdev::{Button, Key};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Neg;
+use std::str::FromStr;
+
+/// Keys, mouse buttons and scroll directions swapped for others on
+/// playback, so a recording can be adapted to different hardware or flipped
+/// mouse buttons without recording it again.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Remap {
+    pub keys: HashMap<Key, Key>,
+    pub buttons: HashMap<Button, Button>,
+    pub wheel: WheelRemap,
+}
+
+impl Remap {
+    pub fn key(&self, key: Key) -> Key {
+        self.keys.get(&key).copied().unwrap_or(key)
+    }
+
+    pub fn button(&self, button: Button) -> Button {
+        self.buttons.get(&button).copied().unwrap_or(button)
+    }
+
+    /// Scroll deltas as remapped by `wheel`.
+    pub fn wheel<T: Neg<Output = T>>(&self, delta_x: T, delta_y: T) -> (T, T) {
+        match self.wheel {
+            WheelRemap::Same => (delta_x, delta_y),
+            WheelRemap::Invert => (-delta_x, -delta_y),
+            WheelRemap::Swap => (delta_y, delta_x),
+        }
+    }
+
+    /// Whether `event` presses a key that is remapped, so typing the text it
+    /// recorded would not do what the remap asks.
+    pub fn remaps_key(&self, event: &SerializableEventType) -> bool {
+        match event {
+            SerializableEventType::KeyPress(key) | SerializableEventType::KeyPressText { key, .. } | SerializableEventType::KeyRelease(key) => {
+                self.keys.contains_key(key)
+            }
+            _ => false,
+        }
+    }
+
+    /// Command line flags for `macro play` that reproduce this remap.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for (&from, &to) in &self.keys {
+            args.push("--remap-key".to_string());
+            args.push(KeyMapping { from, to }.to_string());
+        }
+        for (&from, &to) in &self.buttons {
+            args.push("--remap-button".to_string());
+            args.push(ButtonMapping { from, to }.to_string());
+        }
+        if self.wheel != WheelRemap::Same {
+            args.push("--remap-wheel".to_string());
+            args.push(self.wheel.to_string());
+        }
+        args
+    }
+}
+
+/// How scrolling is changed on playback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WheelRemap {
+    #[default]
+    Same,
+    /// Scroll the other way, e.g. between natural and traditional scrolling.
+    Invert,
+    /// Scroll horizontally instead of vertically and the other way round.
+    Swap,
+}
+
+impl FromStr for WheelRemap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "same" => Ok(WheelRemap::Same),
+            "invert" => Ok(WheelRemap::Invert),
+            "swap" => Ok(WheelRemap::Swap),
+            other => Err(format!("expected 'same', 'invert' or 'swap', got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for WheelRemap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WheelRemap::Same => write!(f, "same"),
+            WheelRemap::Invert => write!(f, "invert"),
+            WheelRemap::Swap => write!(f, "swap"),
+        }
+    }
+}
+
+/// Parses a key by its name in recordings, e.g. "KeyA" or "ShiftLeft", or
+/// a single letter or digit like "A" or "1". Function keys past F12, e.g.
+/// "F13", are read as this platform's code for them.
+pub fn parse_key(s: &str) -> Result<Key, String> {
+    if let Some(code) = s.strip_prefix('F').and_then(|number| number.parse().ok()).and_then(platform::function_key_code) {
+        return Ok(Key::Unknown(code));
+    }
+    let name = match s.chars().collect::<Vec<_>>().as_slice() {
+        [c] if c.is_ascii_alphabetic() => format!("Key{}", c.to_ascii_uppercase()),
+        [c] if c.is_ascii_digit() => format!("Num{}", c),
+        _ => s.to_string(),
+    };
+    if let Some(code) = name.strip_prefix("Unknown(").and_then(|rest| rest.strip_suffix(')')) {
+        return code.parse().map(Key::Unknown).map_err(|_| format!("expected a key code, got '{}'", code));
+    }
+    serde_json::from_value(serde_json::Value::String(name)).map_err(|_| format!("expected a key name like 'KeyA' or 'ShiftLeft', got '{}'", s))
+}
+
+/// Parses a mouse button: "left", "right", "middle" or a button number.
+pub fn parse_button(s: &str) -> Result<Button, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "left" => Ok(Button::Left),
+        "right" => Ok(Button::Right),
+        "middle" => Ok(Button::Middle),
+        other => other
+            .trim_start_matches("unknown(")
+            .trim_end_matches(')')
+            .parse()
+            .map(Button::Unknown)
+            .map_err(|_| format!("expected 'left', 'right', 'middle' or a button number, got '{}'", s)),
+    }
+}
+
+/// Splits a mapping written `FROM=TO`.
+pub(crate) fn split_mapping(s: &str) -> Result<(&str, &str), String> {
+    s.split_once('=')
+        .map(|(from, to)| (from.trim(), to.trim()))
+        .ok_or_else(|| format!("expected FROM=TO, got '{}'", s))
+}
+
+/// One key played as another, written `FROM=TO`, e.g. "A=B".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyMapping {
+    pub from: Key,
+    pub to: Key,
+}
+
+impl FromStr for KeyMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = split_mapping(s)?;
+        Ok(KeyMapping { from: parse_key(from)?, to: parse_key(to)? })
+    }
+}
+
+impl fmt::Display for KeyMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}={:?}", self.from, self.to)
+    }
+}
+
+/// One mouse button played as another, written `FROM=TO`, e.g. "Right=Left".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ButtonMapping {
+    pub from: Button,
+    pub to: Button,
+}
+
+impl FromStr for ButtonMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = split_mapping(s)?;
+        Ok(ButtonMapping { from: parse_button(from)?, to: parse_button(to)? })
+    }
+}
+
+impl fmt::Display for ButtonMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}={:?}", self.from, self.to)
+    }
+}
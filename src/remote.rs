@@ -0,0 +1,96 @@
+use crate::backend::InputBackend;
+use crate::clock::{Clock, SystemClock};
+use crate::config::KeyMaps;
+use crate::play::{self, PlaybackControl, PlaybackOptions};
+use crate::platform;
+use crate::record::{self, RecordOptions, Recorder, RecordingHandle};
+use anyhow::Result;
+use std::io::{self, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+/// Captures input and streams it to a `macro receive` at `addr` as JSON
+/// lines, until the stop recording hotkey, Ctrl+C, or the receiver going away.
+pub fn run_send(backend: Arc<dyn InputBackend>, addr: &str, options: RecordOptions, keymaps: KeyMaps) -> Result<()> {
+    platform::check_input_backend()?;
+    let stream = TcpStream::connect(addr).map_err(|e| anyhow::anyhow!("Could not connect to {}: {}", addr, e))?;
+    // Events are tiny and sent one at a time, so don't hold them back to batch
+    stream.set_nodelay(true)?;
+    tracing::info!("Sending input to {}.", addr);
+    tracing::info!("Stop Sending: {:?} + {:?}", keymaps.stop_recording.modifiers, keymaps.stop_recording.trigger);
+
+    let stream = Mutex::new(stream);
+    let handle: Arc<OnceLock<RecordingHandle>> = Arc::new(OnceLock::new());
+    let hook_handle = handle.clone();
+    let session = Recorder::new()
+        .backend(backend)
+        .hotkeys(keymaps)
+        .options(options)
+        .on_event(move |event| {
+            if let Err(e) = record::write_event_line(&mut *stream.lock().unwrap(), event) {
+                tracing::error!("Lost the connection to the receiver: {:?}", e);
+                if let Some(handle) = hook_handle.get() {
+                    handle.stop();
+                }
+            }
+        })
+        .start();
+    let _ = handle.set(session.handle());
+
+    // Handle Ctrl+C / SIGTERM
+    let handle = session.handle();
+    ctrlc::set_handler(move || {
+        tracing::info!("Ctrl+C / SIGTERM handler triggered");
+        handle.stop();
+    })?;
+
+    session.wait()?;
+    Ok(())
+}
+
+/// Plays the events streamed by one sender until it disconnects or playback
+/// is stopped, then releases modifiers and mouse buttons in case the sender
+/// went away mid-shortcut or mid-drag.
+pub fn receive_from(stream: TcpStream, backend: &dyn InputBackend, clock: &dyn Clock, options: &PlaybackOptions, control: &PlaybackControl) -> Result<()> {
+    stream.set_nodelay(true)?;
+    let result = play::play_stream(backend, clock, BufReader::new(stream), options, control);
+    play::release_all(backend);
+    result
+}
+
+/// Simulates input streamed from `macro send`, one sender at a time, until
+/// the stop playback hotkey or Ctrl+C.
+pub fn run_receive(backend: Arc<dyn InputBackend>, addr: SocketAddr, options: PlaybackOptions, keymaps: KeyMaps) -> Result<()> {
+    platform::check_input_backend()?;
+    let listener = TcpListener::bind(addr).map_err(|e| anyhow::anyhow!("Could not listen on {}: {}", addr, e))?;
+    tracing::info!("Waiting for a sender on {}.", addr);
+    tracing::info!("Stop Receiving: {:?} + {:?}", keymaps.stop_playback.modifiers, keymaps.stop_playback.trigger);
+
+    let control = PlaybackControl::default();
+    play::spawn_stop_listener(backend.clone(), keymaps, control.clone());
+    let clock = SystemClock::new();
+    // Polled, so the stop hotkey also works while no sender is connected
+    listener.set_nonblocking(true)?;
+    while !control.is_stopped() {
+        let (stream, peer) = match listener.accept() {
+            Ok(accepted) => accepted,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to accept a sender: {}", e);
+                continue;
+            }
+        };
+        tracing::info!("Receiving input from {}.", peer);
+        stream.set_nonblocking(false)?;
+        if let Err(e) = receive_from(stream, backend.as_ref(), &clock, &options, &control) {
+            tracing::error!("Stream from {} ended with an error: {:?}", peer, e);
+        }
+        tracing::info!("{} disconnected.", peer);
+    }
+    Ok(())
+}
@@ -0,0 +1,320 @@
+use crate::backend::{InputBackend, SharedBackend};
+use crate::config::KeyMaps;
+use crate::environment;
+use crate::play::{self, PlaybackControl, PlaybackOptions, Player};
+use crate::own_ui::OwnUi;
+use crate::record::{self, Recorder, RecordingSession};
+use crate::recording::Recording;
+use crate::remap;
+use anyhow::{Context, Result};
+use rdev::{Button, EventType, Key};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Pause between the events of one command, e.g. the move, press and
+/// release of a click, so apps see them as separate.
+const STEP_DELAY: Duration = Duration::from_millis(20);
+
+const HELP: &str = "\
+Commands:
+  move X Y                    Move the mouse
+  click X Y [left|right|middle]
+                              Move the mouse and click
+  type \"TEXT\"                 Type text
+  key KEY                     Press and release a key, e.g. Return or A
+  scroll DX DY                Scroll by wheel notches
+  wait DURATION               Wait, e.g. 500ms or 2s
+  play FILE [FLAGS]           Play a recording, with the flags of `macro play`
+  record start [FILE]         Start recording to FILE, events.json by default
+  record stop                 Stop recording and save it
+  help                        Show this list
+  quit                        Leave, saving a recording in progress";
+
+/// One line typed at the `macro repl` prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplCommand {
+    Move { x: f64, y: f64 },
+    Click { x: f64, y: f64, button: Button },
+    Type(String),
+    Key(Key),
+    Scroll { delta_x: i64, delta_y: i64 },
+    Wait(Duration),
+    /// Arguments to `macro play`, starting with the recording.
+    Play(Vec<String>),
+    RecordStart(PathBuf),
+    RecordStop,
+    Help,
+    Quit,
+}
+
+impl FromStr for ReplCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = split_words(s)?;
+        let (name, args) = words.split_first().ok_or_else(|| "expected a command, try 'help'".to_string())?;
+        let arity = |expected: &[usize], usage: &str| {
+            if expected.contains(&args.len()) { Ok(()) } else { Err(format!("usage: {}", usage)) }
+        };
+        match name.as_str() {
+            "move" => {
+                arity(&[2], "move X Y")?;
+                Ok(ReplCommand::Move { x: number(&args[0])?, y: number(&args[1])? })
+            }
+            "click" => {
+                arity(&[2, 3], "click X Y [left|right|middle]")?;
+                let button = args.get(2).map_or(Ok(Button::Left), |button| remap::parse_button(button))?;
+                Ok(ReplCommand::Click { x: number(&args[0])?, y: number(&args[1])?, button })
+            }
+            "type" => {
+                arity(&[1], "type \"TEXT\"")?;
+                Ok(ReplCommand::Type(args[0].clone()))
+            }
+            "key" => {
+                arity(&[1], "key KEY")?;
+                Ok(ReplCommand::Key(remap::parse_key(&args[0])?))
+            }
+            "scroll" => {
+                arity(&[2], "scroll DX DY")?;
+                Ok(ReplCommand::Scroll { delta_x: number(&args[0])?, delta_y: number(&args[1])? })
+            }
+            "wait" => {
+                arity(&[1], "wait DURATION")?;
+                humantime::parse_duration(&args[0]).map(ReplCommand::Wait).map_err(|e| format!("expected a duration like '500ms', got '{}': {}", args[0], e))
+            }
+            "play" => {
+                if args.is_empty() {
+                    return Err("usage: play FILE [FLAGS]".to_string());
+                }
+                Ok(ReplCommand::Play(args.to_vec()))
+            }
+            "record" => match args.split_first() {
+                Some((action, rest)) if action == "start" && rest.len() <= 1 => {
+                    Ok(ReplCommand::RecordStart(PathBuf::from(rest.first().map_or("events.json", String::as_str))))
+                }
+                Some((action, [])) if action == "stop" => Ok(ReplCommand::RecordStop),
+                _ => Err("usage: record start [FILE] or record stop".to_string()),
+            },
+            "help" => Ok(ReplCommand::Help),
+            "quit" | "exit" => Ok(ReplCommand::Quit),
+            other => Err(format!("unknown command '{}', try 'help'", other)),
+        }
+    }
+}
+
+fn number<T: FromStr>(word: &str) -> Result<T, String> {
+    word.parse().map_err(|_| format!("expected a number, got '{}'", word))
+}
+
+/// Splits a line into words at whitespace, keeping text in double quotes
+/// together as one word. Inside quotes, `\"` and `\\` stand for `"` and `\`.
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut word = String::new();
+        if c == '"' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') if matches!(chars.peek(), Some('"' | '\\')) => word.extend(chars.next()),
+                    Some(c) => word.push(c),
+                    None => return Err("expected a closing '\"'".to_string()),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+        }
+        words.push(word);
+    }
+    Ok(words)
+}
+
+type PlayOptionsCallback = Box<dyn FnMut(&[String]) -> Result<(Vec<PathBuf>, PlaybackOptions)>>;
+
+/// Runs [`ReplCommand`]s as they are typed, with the same backend as
+/// playback and recording.
+pub struct Repl {
+    backend: Arc<dyn InputBackend>,
+    /// Listens for recordings and the stop hotkey at once. Input is still
+    /// simulated through `backend`, so a recording captures it.
+    listener: Arc<SharedBackend>,
+    /// Shared by every `play`, and stopped and paused by the hotkeys.
+    control: PlaybackControl,
+    /// Has the keyboard while the prompt waits for a line, so the typing
+    /// of commands is left out of recordings.
+    own_ui: OwnUi,
+    recording: Option<(PathBuf, RecordingSession)>,
+    play_options: Option<PlayOptionsCallback>,
+}
+
+impl Repl {
+    /// Starts listening for the stop and pause playback hotkeys, which work
+    /// until the process ends.
+    pub fn new(backend: Arc<dyn InputBackend>, keymaps: KeyMaps) -> Self {
+        let listener = Arc::new(SharedBackend::new(backend.clone()));
+        let control = PlaybackControl::default();
+        listener.watch(play::stop_hotkeys(keymaps, control.clone()));
+        Self {
+            backend,
+            listener,
+            control,
+            own_ui: OwnUi::default(),
+            recording: None,
+            play_options: None,
+        }
+    }
+
+    /// Turns the arguments of `play`, those of `macro play`, into the
+    /// recordings to play and how. Without it, `play` fails.
+    pub fn play_options(mut self, hook: impl FnMut(&[String]) -> Result<(Vec<PathBuf>, PlaybackOptions)> + 'static) -> Self {
+        self.play_options = Some(Box::new(hook));
+        self
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Runs `command`. `help` and `quit` are up to the caller.
+    pub fn execute(&mut self, command: &ReplCommand) -> Result<()> {
+        match command {
+            ReplCommand::Move { x, y } => self.backend.simulate(&EventType::MouseMove { x: *x, y: *y }),
+            ReplCommand::Click { x, y, button } => {
+                self.backend.simulate(&EventType::MouseMove { x: *x, y: *y })?;
+                thread::sleep(STEP_DELAY);
+                self.backend.simulate(&EventType::ButtonPress(*button))?;
+                thread::sleep(STEP_DELAY);
+                self.backend.simulate(&EventType::ButtonRelease(*button))
+            }
+            ReplCommand::Type(text) => self.backend.simulate_text(text),
+            ReplCommand::Key(key) => {
+                self.backend.simulate(&EventType::KeyPress(*key))?;
+                thread::sleep(STEP_DELAY);
+                self.backend.simulate(&EventType::KeyRelease(*key))
+            }
+            ReplCommand::Scroll { delta_x, delta_y } => self.backend.simulate(&EventType::Wheel { delta_x: *delta_x, delta_y: *delta_y }),
+            ReplCommand::Wait(duration) => {
+                thread::sleep(*duration);
+                Ok(())
+            }
+            ReplCommand::Play(args) => {
+                let Some(hook) = &mut self.play_options else {
+                    anyhow::bail!("Playing recordings is not available here");
+                };
+                let (paths, options) = hook(args)?;
+                self.play(&paths, options)
+            }
+            ReplCommand::RecordStart(path) => {
+                if let Some((recording, _)) = &self.recording {
+                    anyhow::bail!("Already recording to {:?}, use 'record stop' first", recording);
+                }
+                // Like `macro record --auto-number`, never replacing an earlier recording
+                let path = if path.exists() { record::next_available_path(path) } else { path.clone() };
+                let session = Recorder::new().backend(self.listener.clone()).own_ui(self.own_ui.clone()).start();
+                println!("Recording to {}, 'record stop' to save it.", path.display());
+                self.recording = Some((path, session));
+                Ok(())
+            }
+            ReplCommand::RecordStop => self.stop_recording(),
+            ReplCommand::Help | ReplCommand::Quit => Ok(()),
+        }
+    }
+
+    /// Plays the recordings at `paths` at the same time, on this thread,
+    /// until they complete or the stop hotkey is pressed.
+    fn play(&mut self, paths: &[PathBuf], options: PlaybackOptions) -> Result<()> {
+        if paths.iter().any(|path| path == Path::new("-")) {
+            anyhow::bail!("Cannot play from stdin here, it is where commands are typed");
+        }
+        let recordings = paths
+            .iter()
+            .map(|path| Recording::load(path).with_context(|| format!("Could not load {:?}", path)))
+            .collect::<Result<Vec<_>>>()?;
+        let recording = Recording::interleave(recordings)?;
+        environment::check(&recording, self.backend.as_ref(), options.strict)?;
+
+        self.control.reset();
+        let player = Player::new().backend(self.backend.clone()).options(options).with_control(self.control.clone());
+        player.play(&recording)?;
+        if self.control.is_stopped() {
+            println!("Playback stopped.");
+        }
+        Ok(())
+    }
+
+    /// Sets whether the prompt is waiting for a line. Keys pressed
+    /// meanwhile go to the terminal, so a recording leaves them out.
+    fn set_prompting(&self, prompting: bool) {
+        if !prompting && self.is_recording() {
+            // Lets the recorder see the Return that ended the line before
+            // keys count again
+            thread::sleep(STEP_DELAY);
+        }
+        self.own_ui.set_focused(prompting);
+    }
+
+    /// Stops the recording in progress and saves it.
+    pub fn stop_recording(&mut self) -> Result<()> {
+        let Some((path, session)) = self.recording.take() else {
+            anyhow::bail!("Not recording, use 'record start' first");
+        };
+        match session.stop()? {
+            Some(recording) => {
+                recording.save(&path)?;
+                println!("Saved {} events to {}.", recording.events.len(), path.display());
+            }
+            None => println!("Nothing was recorded."),
+        }
+        Ok(())
+    }
+}
+
+/// Reads commands from `input` one per line and runs them with `repl` until
+/// `quit` or the end of the input, printing errors without stopping. A
+/// recording still in progress at the end is saved.
+pub fn run_repl(mut repl: Repl, input: impl BufRead) -> Result<()> {
+    println!("Type 'help' for a list of commands.");
+    let prompt = |repl: &Repl| {
+        repl.set_prompting(true);
+        print!("macro> ");
+        io::stdout().flush()
+    };
+    prompt(&repl)?;
+    for line in input.lines() {
+        let line = line?;
+        repl.set_prompting(false);
+        if !line.trim().is_empty() {
+            match line.parse::<ReplCommand>() {
+                Ok(ReplCommand::Quit) => break,
+                Ok(ReplCommand::Help) => println!("{}", HELP),
+                Ok(command) => {
+                    if let Err(e) = repl.execute(&command) {
+                        eprintln!("Error: {:#}", e);
+                    }
+                }
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        }
+        prompt(&repl)?;
+    }
+    if repl.is_recording() {
+        repl.stop_recording()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use crate::event::SerializableEventType;
+use crate::recording::Recording;
+use crate::remap::{split_mapping, KeyMapping};
+use anyhow::Result;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Parses a point written `X,Y`, e.g. "1200,800".
+fn parse_point(s: &str) -> Result<(f64, f64), String> {
+    s.split_once(',')
+        .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+        .ok_or_else(|| format!("expected a point like '1200,800', got '{}'", s))
+}
+
+/// Clicks at one point moved to another, written `FROM=TO`, e.g.
+/// "1200,800=900,600".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointMapping {
+    pub from: (f64, f64),
+    pub to: (f64, f64),
+}
+
+impl FromStr for PointMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = split_mapping(s)?;
+        Ok(PointMapping { from: parse_point(from)?, to: parse_point(to)? })
+    }
+}
+
+impl fmt::Display for PointMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}={},{}", self.from.0, self.from.1, self.to.0, self.to.1)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplaceOptions {
+    pub keys: Vec<KeyMapping>,
+    pub points: Vec<PointMapping>,
+    /// How far a click can be from the `from` of a point mapping, in the
+    /// recording's coordinates, and still be moved.
+    pub tolerance: f64,
+}
+
+/// How many events each mapping of [`ReplaceOptions`] changed, in order.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Replacements {
+    pub keys: Vec<usize>,
+    pub points: Vec<usize>,
+}
+
+impl Replacements {
+    pub fn is_empty(&self) -> bool {
+        self.keys.iter().chain(&self.points).all(|count| *count == 0)
+    }
+}
+
+/// `event_type` with its key changed as the first of `keys` for it asks,
+/// and the index of that mapping. Key presses lose the text they typed, as
+/// the new key types something else.
+fn replace_key(event_type: &SerializableEventType, keys: &[KeyMapping]) -> Option<(usize, SerializableEventType)> {
+    let key = match event_type {
+        SerializableEventType::KeyPress(key)
+        | SerializableEventType::KeyPressText { key, .. }
+        | SerializableEventType::KeyRelease(key)
+        | SerializableEventType::Hold { key, .. } => *key,
+        _ => return None,
+    };
+    let index = keys.iter().position(|mapping| mapping.from == key)?;
+    let to = keys[index].to;
+    let replaced = match event_type {
+        SerializableEventType::KeyRelease(_) => SerializableEventType::KeyRelease(to),
+        SerializableEventType::Hold { duration_ms, .. } => SerializableEventType::Hold { key: to, duration_ms: *duration_ms, text: None },
+        _ => SerializableEventType::KeyPress(to),
+    };
+    Some((index, replaced))
+}
+
+/// Rewrites the keys and click positions of `recording` as `options` asks.
+/// A click's position is the mouse move before its button press or
+/// release, which is moved along with the display position it keeps.
+pub fn replace(recording: &Recording, options: &ReplaceOptions) -> (Recording, Replacements) {
+    let mut events = recording.events.clone();
+    let mut replaced = Replacements {
+        keys: vec![0; options.keys.len()],
+        points: vec![0; options.points.len()],
+    };
+
+    let mut clicked = vec![false; events.len()];
+    let mut last_move = None;
+    for (index, event) in events.iter_mut().enumerate() {
+        match &event.event_type {
+            SerializableEventType::MouseMove { .. } => last_move = Some(index),
+            SerializableEventType::ButtonPress(_) | SerializableEventType::ButtonRelease(_) => {
+                if let Some(last_move) = last_move {
+                    clicked[last_move] = true;
+                }
+            }
+            event_type => {
+                if let Some((mapping, event_type)) = replace_key(event_type, &options.keys) {
+                    replaced.keys[mapping] += 1;
+                    event.event_type = event_type;
+                }
+            }
+        }
+    }
+
+    for (event, _) in events.iter_mut().zip(clicked).filter(|(_, clicked)| *clicked) {
+        let SerializableEventType::MouseMove { x, y, display } = &mut event.event_type else {
+            continue;
+        };
+        let Some(mapping) = options
+            .points
+            .iter()
+            .position(|mapping| (mapping.from.0 - *x).hypot(mapping.from.1 - *y) <= options.tolerance)
+        else {
+            continue;
+        };
+        let to = options.points[mapping].to;
+        if let Some(display) = display {
+            display.x += to.0 - *x;
+            display.y += to.1 - *y;
+        }
+        (*x, *y) = to;
+        replaced.points[mapping] += 1;
+    }
+
+    let recording = Recording {
+        metadata: recording.metadata.clone(),
+        events,
+    };
+    (recording, replaced)
+}
+
+/// Rewrites the recording at `input` as `options` asks, saves it to
+/// `output` and prints what each mapping changed. Nothing is saved if no
+/// event matched.
+pub fn run_replace(input: &Path, output: &Path, options: &ReplaceOptions) -> Result<()> {
+    if options.keys.is_empty() && options.points.is_empty() {
+        anyhow::bail!("Nothing to replace, give at least one --key or --point");
+    }
+    let recording = Recording::load(input)?;
+    let (replaced, counts) = replace(&recording, options);
+    for (mapping, count) in options.keys.iter().zip(&counts.keys) {
+        println!("{}: {} key events", mapping, count);
+    }
+    for (mapping, count) in options.points.iter().zip(&counts.points) {
+        println!("{}: {} clicks", mapping, count);
+    }
+    if counts.is_empty() {
+        println!("Nothing matched, so nothing was saved.");
+        return Ok(());
+    }
+    replaced.save(output)?;
+    println!("Saved to {}", output.display());
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use crate::diff::points_per_unit;
+use crate::event::{SerializableEvent, SerializableEventType};
+use crate::recording::Recording;
+use anyhow::Result;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How the mouse moves of a recording are timed by `macro retime`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MoveTiming {
+    /// As recorded.
+    #[default]
+    Keep,
+    /// Each stretch of moves takes longer the further it goes, like a hand
+    /// would, with the time shared out by distance.
+    Natural,
+    /// This long between the moves of a stretch.
+    Fixed(Duration),
+}
+
+impl FromStr for MoveTiming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(MoveTiming::Keep),
+            "natural" => Ok(MoveTiming::Natural),
+            other => humantime::parse_duration(other)
+                .map(MoveTiming::Fixed)
+                .map_err(|_| format!("expected 'keep', 'natural' or a duration like '10ms', got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for MoveTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveTiming::Keep => write!(f, "keep"),
+            MoveTiming::Natural => write!(f, "natural"),
+            MoveTiming::Fixed(interval) => write!(f, "{}", humantime::format_duration(*interval)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetimeOptions {
+    /// Time between one key press or release and the next while typing.
+    /// `None` keeps the recorded delays.
+    pub typing: Option<Duration>,
+    /// Time before each mouse button press and release.
+    pub clicks: Option<Duration>,
+    pub moves: MoveTiming,
+    /// Delays longer than this are left as recorded, as they are usually
+    /// waits for an app rather than sloppiness. Zero retimes them all.
+    pub keep_pauses: Duration,
+}
+
+impl Default for RetimeOptions {
+    fn default() -> Self {
+        Self {
+            typing: None,
+            clicks: None,
+            moves: MoveTiming::Keep,
+            keep_pauses: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetimeOptions {
+    fn is_pause(&self, delay: Duration) -> bool {
+        !self.keep_pauses.is_zero() && delay > self.keep_pauses
+    }
+}
+
+fn is_key(event_type: &SerializableEventType) -> bool {
+    matches!(
+        event_type,
+        SerializableEventType::KeyPress(_) | SerializableEventType::KeyPressText { .. } | SerializableEventType::KeyRelease(_)
+    )
+}
+
+fn is_button(event_type: &SerializableEventType) -> bool {
+    matches!(event_type, SerializableEventType::ButtonPress(_) | SerializableEventType::ButtonRelease(_))
+}
+
+fn move_point(event: &SerializableEvent) -> Option<(f64, f64)> {
+    match event.event_type {
+        SerializableEventType::MouseMove { x, y, .. } => Some((x, y)),
+        _ => None,
+    }
+}
+
+/// Rewrites the delays of `recording` by the kind of event they lead up to,
+/// so it plays back at an even pace. The delay before the first event of a
+/// stretch of typing or moves, and pauses, are kept as recorded.
+pub fn retime(recording: &Recording, options: &RetimeOptions) -> Recording {
+    let mut events = recording.events.clone();
+    for index in 0..events.len() {
+        if options.is_pause(events[index].delay()) {
+            continue;
+        }
+        let previous = index.checked_sub(1).map(|previous| &events[previous]);
+        let delay = match &events[index].event_type {
+            event_type if is_key(event_type) && previous.is_some_and(|previous| is_key(&previous.event_type)) => options.typing,
+            event_type if is_button(event_type) => options.clicks,
+            SerializableEventType::MouseMove { .. } if previous.and_then(move_point).is_some() => match options.moves {
+                MoveTiming::Fixed(interval) => Some(interval),
+                MoveTiming::Keep | MoveTiming::Natural => None,
+            },
+            _ => None,
+        };
+        if let Some(delay) = delay {
+            events[index].set_delay(delay);
+        }
+    }
+    if options.moves == MoveTiming::Natural {
+        spread_moves(&mut events, points_per_unit(recording), options);
+    }
+    Recording {
+        metadata: recording.metadata.clone(),
+        events,
+    }
+}
+
+/// Time a hand takes to move the mouse `distance` points, growing with the
+/// log of the distance as in Fitts's law.
+fn natural_duration(distance: f64) -> Duration {
+    Duration::from_millis(50) + Duration::from_millis(100).mul_f64((1.0 + distance / 10.0).log2())
+}
+
+/// Gives each stretch of consecutive moves its natural duration, shared out
+/// between the moves by how far each goes.
+fn spread_moves(events: &mut [SerializableEvent], points_per_unit: f64, options: &RetimeOptions) {
+    let mut start = 0;
+    while start < events.len() {
+        let mut end = start;
+        while end < events.len() && move_point(&events[end]).is_some() && (end == start || !options.is_pause(events[end].delay())) {
+            end += 1;
+        }
+        if end > start + 1 {
+            let points: Vec<(f64, f64)> = events[start..end].iter().filter_map(move_point).collect();
+            let steps: Vec<f64> = points
+                .windows(2)
+                .map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1) * points_per_unit)
+                .collect();
+            let distance: f64 = steps.iter().sum();
+            let total = natural_duration(distance);
+            for (event, step) in events[start + 1..end].iter_mut().zip(steps) {
+                let share = if distance > 0.0 { step / distance } else { 0.0 };
+                event.set_delay(total.mul_f64(share));
+            }
+        }
+        start = end.max(start + 1);
+    }
+}
+
+/// Retimes the recording at `input`, saves it to `output` and prints how
+/// its length changed.
+pub fn run_retime(input: &Path, output: &Path, options: &RetimeOptions) -> Result<()> {
+    let recording = Recording::load(input)?;
+    let retimed = retime(&recording, options);
+    retimed.save(output)?;
+    let length = |recording: &Recording| recording.events.iter().map(SerializableEvent::delay).sum::<Duration>().as_secs_f64();
+    println!("Length: {:.2}s -> {:.2}s", length(&recording), length(&retimed));
+    println!("Saved to {}", output.display());
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use crate::backend::InputBackend;
+use chrono::Local;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+
+/// Moment at which a screenshot is saved to help debug a replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenshotTrigger {
+    /// Every mouse button press while recording.
+    Click,
+    /// The screenshot hotkey while recording.
+    Hotkey,
+    /// Playback aborted by a failed assertion.
+    Failure,
+}
+
+impl FromStr for ScreenshotTrigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "click" => Ok(ScreenshotTrigger::Click),
+            "hotkey" => Ok(ScreenshotTrigger::Hotkey),
+            "failure" => Ok(ScreenshotTrigger::Failure),
+            other => Err(format!("expected 'click', 'hotkey' or 'failure', got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for ScreenshotTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScreenshotTrigger::Click => write!(f, "click"),
+            ScreenshotTrigger::Hotkey => write!(f, "hotkey"),
+            ScreenshotTrigger::Failure => write!(f, "failure"),
+        }
+    }
+}
+
+/// Directory next to a recording where its screenshots are saved, e.g.
+/// `events-screenshots/` for `events.json`. Recordings streamed as `-` use
+/// `screenshots/` in the working directory.
+pub fn dir_for(recording_path: &Path) -> PathBuf {
+    if recording_path == Path::new("-") {
+        return PathBuf::from("screenshots");
+    }
+    let stem = recording_path.file_stem().unwrap_or_default().to_string_lossy();
+    recording_path.with_file_name(format!("{}-screenshots", stem))
+}
+
+/// Screenshot of the screen as `recording_path` started recording, to tell
+/// recordings apart when choosing one.
+pub fn thumbnail_path(recording_path: &Path) -> PathBuf {
+    thumbnail_in(&dir_for(recording_path))
+}
+
+/// Thumbnail in the screenshot directory `dir`.
+pub fn thumbnail_in(dir: &Path) -> PathBuf {
+    dir.join("thumbnail.png")
+}
+
+/// Copies the thumbnail of the recording at `from` to go with the one at
+/// `to`. Does nothing if `from` has none.
+pub fn copy_thumbnail(from: &Path, to: &Path) -> std::io::Result<()> {
+    let (from, to) = (thumbnail_path(from), thumbnail_path(to));
+    if !from.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(from, to)?;
+    Ok(())
+}
+
+/// File for a screenshot taken during recording, numbered by the index of
+/// the event it was taken at so the two can be matched up.
+pub fn recording_path(dir: &Path, trigger: ScreenshotTrigger, event_index: usize) -> PathBuf {
+    dir.join(format!("{}-{:04}.png", trigger, event_index))
+}
+
+/// File for a screenshot taken during playback, named by the time it was taken.
+pub fn playback_path(dir: &Path, trigger: ScreenshotTrigger) -> PathBuf {
+    dir.join(format!("{}-{}.png", trigger, Local::now().format("%Y%m%d_%H%M%S")))
+}
+
+/// Saves a screenshot to `path`, logging rather than returning failures so a
+/// missing screenshot never interrupts recording or playback.
+pub fn capture(backend: &dyn InputBackend, path: &Path) {
+    if let Some(parent) = path.parent()
+        && let Err(e) = std::fs::create_dir_all(parent)
+    {
+        tracing::error!("Could not create screenshot directory {:?}: {:?}", parent, e);
+        return;
+    }
+    match backend.capture_screen(path) {
+        Ok(()) => tracing::info!("Saved screenshot to {:?}", path),
+        Err(e) => tracing::error!("Could not save screenshot to {:?}: {:?}", path, e),
+    }
+}
+
+/// Like [`capture`], on a background thread so the caller is not held up.
+pub fn capture_in_background(backend: Arc<dyn InputBackend>, path: PathBuf) {
+    thread::spawn(move || capture(backend.as_ref(), &path));
+}
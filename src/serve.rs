@@ -0,0 +1,557 @@
+use crate::backend::InputBackend;
+use crate::config::Preset;
+use crate::event::SerializableEvent;
+use crate::history::{self, RunKind, RunTimer};
+use crate::play::{PlaybackControl, PlaybackOptions, PlaybackOutcome, PlaybackPosition, PlaybackProgress, Player};
+use crate::pack;
+use crate::platform;
+use crate::record;
+use crate::recording::{self, Recording};
+use crate::screenshot;
+use crate::worker;
+use anyhow::Result;
+use chrono::Local;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::io::{BufRead, BufReader};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::Duration;
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+/// Body of `POST /playback`. Options left out fall back to the server's defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PlayRequest {
+    /// File name of a recording in the recordings folder.
+    recording: String,
+    speed: Option<f64>,
+    repeat_count: Option<u32>,
+    /// Seconds between repeats.
+    repeat_interval: Option<f64>,
+    /// Seconds to wait before the first event.
+    countdown: Option<f64>,
+    humanize_ms: Option<u64>,
+    from: Option<String>,
+    to: Option<String>,
+    from_checkpoint: Option<String>,
+    to_checkpoint: Option<String>,
+}
+
+/// Body of `POST /recording`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RecordRequest {
+    /// File name to save to in the recordings folder. Defaults to a timestamped name.
+    name: Option<String>,
+    /// Save a screenshot as recording starts.
+    thumbnail: bool,
+}
+
+/// The last playback started, kept after it ends so clients can read how it went.
+struct PlaybackState {
+    recording: String,
+    control: PlaybackControl,
+    progress: Option<PlaybackProgress>,
+    /// `None` while still playing.
+    outcome: Option<PlaybackOutcome>,
+    error: Option<String>,
+}
+
+/// A `macro record -` worker and the events it has streamed back so far.
+struct RecordingState {
+    recording: String,
+    path: PathBuf,
+    child: Child,
+    events: Arc<Mutex<Vec<SerializableEvent>>>,
+}
+
+/// Channels of the clients watching `/events`, which get a JSON message for
+/// every captured event and playback step.
+#[derive(Clone, Default)]
+struct Subscribers(Arc<Mutex<Vec<mpsc::Sender<Value>>>>);
+
+impl Subscribers {
+    /// Sends `message` to every subscriber, forgetting those that went away.
+    fn publish(&self, message: Value) {
+        self.0.lock().unwrap().retain(|tx| tx.send(message.clone()).is_ok());
+    }
+}
+
+/// Error reply, with the HTTP status it is sent with.
+struct ApiError(u16, String);
+
+type Reply = std::result::Result<Value, ApiError>;
+
+fn bad_request(message: impl Into<String>) -> ApiError {
+    ApiError(400, message.into())
+}
+
+fn conflict(message: impl Into<String>) -> ApiError {
+    ApiError(409, message.into())
+}
+
+/// Routes API requests to playback and recording, independent of the HTTP
+/// transport so it can be driven directly.
+pub struct Server {
+    backend: Arc<dyn InputBackend>,
+    recordings_dir: PathBuf,
+    defaults: PlaybackOptions,
+    playback: Arc<Mutex<Option<PlaybackState>>>,
+    recording: Mutex<Option<RecordingState>>,
+    subscribers: Subscribers,
+    /// History file playback runs are added to, if any.
+    history: Option<PathBuf>,
+}
+
+impl Server {
+    /// Serves recordings from `recordings_dir`, playing them with `defaults`
+    /// unless a request overrides them.
+    pub fn new(backend: Arc<dyn InputBackend>, recordings_dir: PathBuf, defaults: PlaybackOptions) -> Self {
+        Self {
+            backend,
+            recordings_dir,
+            defaults,
+            playback: Arc::new(Mutex::new(None)),
+            recording: Mutex::new(None),
+            subscribers: Subscribers::default(),
+            history: None,
+        }
+    }
+
+    /// Adds each playback run to the history file at `path`.
+    pub fn history(mut self, path: PathBuf) -> Self {
+        self.history = Some(path);
+        self
+    }
+
+    /// Handles one request, returning the HTTP status and JSON body to reply with.
+    pub fn handle(&self, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let path = path.split('?').next().unwrap_or_default().trim_end_matches('/');
+        let reply = match (method, path) {
+            ("GET", "/recordings") => self.list_recordings(),
+            ("GET", "/status") => Ok(self.status()),
+            ("POST", "/playback") => self.start_playback(body),
+            ("POST", "/playback/stop") => self.control_playback(PlaybackControl::stop),
+            ("POST", "/playback/pause") => self.control_playback(PlaybackControl::pause),
+            ("POST", "/playback/resume") => self.control_playback(PlaybackControl::resume),
+            ("POST", "/recording") => self.start_recording(body),
+            ("POST", "/recording/stop") => self.stop_recording(),
+            _ => Err(ApiError(404, format!("No route for {} {}", method, path))),
+        };
+        match reply {
+            Ok(value) => (200, value),
+            Err(ApiError(status, message)) => (status, json!({ "error": message })),
+        }
+    }
+
+    /// Live updates, starting with the current status. Ends when the server
+    /// is dropped.
+    pub fn subscribe(&self) -> mpsc::Receiver<Value> {
+        let (tx, rx) = mpsc::channel();
+        let _ = tx.send(json!({ "type": "status", "status": self.status() }));
+        self.subscribers.0.lock().unwrap().push(tx);
+        rx
+    }
+
+    fn list_recordings(&self) -> Reply {
+        let mut names = Vec::new();
+        let mut thumbnails = serde_json::Map::new();
+        if let Ok(entries) = std::fs::read_dir(&self.recordings_dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_file() && path.extension().is_some_and(|ext| ext == "json" || ext == "jsonl" || ext == pack::EXTENSION) {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    // Relative to the recordings folder, like the names
+                    let thumbnail = screenshot::thumbnail_path(Path::new(&name));
+                    if self.recordings_dir.join(&thumbnail).is_file() {
+                        thumbnails.insert(name.clone(), json!(thumbnail));
+                    }
+                    names.push(name);
+                }
+            }
+        }
+        names.sort();
+        Ok(json!({ "recordings": names, "thumbnails": thumbnails }))
+    }
+
+    fn status(&self) -> Value {
+        let playback = self.playback.lock().unwrap().as_ref().map(|playback| {
+            let state = match playback.outcome {
+                None if playback.control.is_paused() => "paused",
+                None => "playing",
+                Some(outcome) => outcome_name(outcome),
+            };
+            json!({
+                "recording": playback.recording,
+                "state": state,
+                "repeat": playback.progress.map(|p| p.repeat),
+                "events_played": playback.progress.map(|p| p.events_played),
+                "total_events": playback.progress.map(|p| p.total_events),
+                "error": playback.error,
+            })
+        });
+        let mut recording = self.recording.lock().unwrap();
+        if let Some(state) = recording.as_mut()
+            && !matches!(state.child.try_wait(), Ok(None))
+        {
+            // The worker ended on its own, e.g. from the stop recording hotkey
+            *recording = None;
+        }
+        let recording = recording.as_ref().map(|state| {
+            json!({
+                "recording": state.recording,
+                "events": state.events.lock().unwrap().len(),
+            })
+        });
+        json!({ "playback": playback, "recording": recording })
+    }
+
+    fn start_playback(&self, body: &str) -> Reply {
+        let request: PlayRequest = parse_body(body)?;
+        let path = self.recording_path(&request.recording)?;
+        if !path.is_file() {
+            return Err(ApiError(404, format!("No recording named '{}'", request.recording)));
+        }
+        let saved = recording::saved_playback(&path).map_err(|e| bad_request(format!("{:#}", e)))?;
+        let options = self.playback_options(&request, saved.unwrap_or_default())?;
+        let recording = Recording::load(&path).map_err(|e| bad_request(format!("{:#}", e)))?;
+        crate::play::event_range(&recording, options.from.as_ref(), options.to.as_ref()).map_err(|e| bad_request(e.to_string()))?;
+
+        if self.recording.lock().unwrap().is_some() {
+            return Err(conflict("A recording is in progress"));
+        }
+        let mut playback = self.playback.lock().unwrap();
+        if playback.as_ref().is_some_and(|playback| playback.outcome.is_none()) {
+            return Err(conflict("Playback is already running"));
+        }
+
+        let name = request.recording.clone();
+        tracing::info!("Playing {:?} for an API request.", path);
+        let timer = RunTimer::start(RunKind::Play, Some(&path), options.to_args());
+        let history = self.history.clone();
+        let state = self.playback.clone();
+        let finish_state = self.playback.clone();
+        let subscribers = self.subscribers.clone();
+        let player = Player::new().backend(self.backend.clone()).options(options).on_finish(move |outcome| {
+            if let Some(playback) = finish_state.lock().unwrap().as_mut() {
+                playback.outcome = Some(outcome);
+            }
+        });
+        let control = player.control();
+        *playback = Some(PlaybackState {
+            recording: name,
+            control: control.clone(),
+            progress: None,
+            outcome: None,
+            error: None,
+        });
+        self.subscribers.publish(json!({ "type": "playback_started", "recording": request.recording }));
+        thread::spawn(move || {
+            let result = player.play_with_progress(&recording, &mut |progress| {
+                if let Some(playback) = state.lock().unwrap().as_mut() {
+                    playback.progress = Some(progress);
+                }
+                subscribers.publish(json!({
+                    "type": "playback_progress",
+                    "repeat": progress.repeat,
+                    "events_played": progress.events_played,
+                    "total_events": progress.total_events,
+                }));
+            });
+            if let Some(history) = &history
+                && let Err(e) = history::append(history, &timer.finish(&result, control.is_stopped()))
+            {
+                tracing::warn!("Could not add the run to the history in {:?}: {:?}", history, e);
+            }
+            let error = result.err().map(|e| {
+                tracing::error!("API playback failed: {:?}", e);
+                e.to_string()
+            });
+            let mut playback = state.lock().unwrap();
+            if let Some(playback) = playback.as_mut() {
+                let outcome = *playback.outcome.get_or_insert(PlaybackOutcome::Failed);
+                playback.error = error.clone();
+                subscribers.publish(json!({ "type": "playback_finished", "outcome": outcome_name(outcome), "error": error }));
+            }
+        });
+        drop(playback);
+        Ok(self.status())
+    }
+
+    /// The server's defaults, overridden by the `saved` settings of the
+    /// recording and then by those in the request.
+    fn playback_options(&self, request: &PlayRequest, saved: Preset) -> std::result::Result<PlaybackOptions, ApiError> {
+        let position = |value: &Option<String>, checkpoint: &Option<String>| match (value, checkpoint) {
+            (Some(_), Some(_)) => Err(bad_request("Give a position or a checkpoint, not both")),
+            (Some(value), None) => value.parse::<PlaybackPosition>().map(Some).map_err(bad_request),
+            (None, Some(name)) => Ok(Some(PlaybackPosition::Checkpoint(name.clone()))),
+            (None, None) => Ok(None),
+        };
+        let mut options = self.defaults.clone();
+        if let Some(speed) = request.speed.or(saved.speed) {
+            options.speed = speed;
+        }
+        if let Some(repeat_count) = request.repeat_count.or(saved.repeat_count) {
+            options.repeat_count = repeat_count;
+        }
+        if let Some(repeat_interval) = request.repeat_interval.or(saved.repeat_interval) {
+            options.repeat_interval = repeat_interval;
+        }
+        if let Some(countdown) = request.countdown.or(saved.countdown) {
+            options.countdown = Duration::try_from_secs_f64(countdown).map_err(|e| bad_request(format!("Invalid countdown: {}", e)))?;
+        }
+        if let Some(humanize_ms) = request.humanize_ms {
+            options.humanize = Duration::from_millis(humanize_ms);
+        }
+        options.from = position(&request.from, &request.from_checkpoint)?;
+        options.to = position(&request.to, &request.to_checkpoint)?;
+        Ok(options)
+    }
+
+    fn control_playback(&self, action: fn(&PlaybackControl)) -> Reply {
+        match self.playback.lock().unwrap().as_ref() {
+            Some(playback) if playback.outcome.is_none() => action(&playback.control),
+            _ => return Err(conflict("Nothing is playing")),
+        }
+        Ok(self.status())
+    }
+
+    fn start_recording(&self, body: &str) -> Reply {
+        let request: RecordRequest = parse_body(body)?;
+        let name = request
+            .name
+            .unwrap_or_else(|| format!("recording_{}.json", Local::now().format("%Y%m%d_%H%M%S")));
+        let path = self.recording_path(&name)?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        if path.exists() {
+            return Err(conflict(format!("A recording named '{}' already exists", name)));
+        }
+        if self.playback.lock().unwrap().as_ref().is_some_and(|playback| playback.outcome.is_none()) {
+            return Err(conflict("Playback is running"));
+        }
+        let mut recording = self.recording.lock().unwrap();
+        if recording.is_some() {
+            return Err(conflict("A recording is already in progress"));
+        }
+
+        // A worker process, like the tray app's, so each recording gets a
+        // fresh input listener that goes away with it
+        let macro_bin = std::env::current_exe().map_err(|e| ApiError(500, e.to_string()))?;
+        let mut child = Command::new(macro_bin)
+            .args(["record", "-", "--immediate"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| ApiError(500, format!("Failed to spawn macro record: {}", e)))?;
+        tracing::info!("Recording to {:?} for an API request.", path);
+        if request.thumbnail {
+            screenshot::capture_in_background(self.backend.clone(), screenshot::thumbnail_path(&path));
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let worker_events = events.clone();
+        let displays = self.backend.displays();
+        let save_path = path.clone();
+        let subscribers = self.subscribers.clone();
+        let saved_name = name.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                match serde_json::from_str::<SerializableEvent>(&line) {
+                    Ok(event) => {
+                        subscribers.publish(json!({ "type": "captured", "event": event }));
+                        worker_events.lock().unwrap().push(event);
+                    }
+                    Err(e) => tracing::warn!("Skipping unreadable event from the recorder: {}", e),
+                }
+            }
+            // The worker has exited, so everything it captured is here
+            let events = std::mem::take(&mut *worker_events.lock().unwrap());
+            let count = events.len();
+            match record::save_recording(&Recording::new(events, &displays), &save_path) {
+                Ok(()) => subscribers.publish(json!({ "type": "recording_saved", "recording": saved_name, "events": count })),
+                Err(e) => tracing::error!("Failed to save recording to {:?}: {:?}", save_path, e),
+            }
+        });
+
+        self.subscribers.publish(json!({ "type": "recording_started", "recording": name }));
+        *recording = Some(RecordingState { recording: name, path, child, events });
+        drop(recording);
+        Ok(self.status())
+    }
+
+    fn stop_recording(&self) -> Reply {
+        let Some(mut state) = self.recording.lock().unwrap().take() else {
+            return Err(conflict("Nothing is being recorded"));
+        };
+        if let Err(e) = worker::stop(&mut state.child, Duration::from_secs(5)) {
+            tracing::error!("Failed to stop the recording worker: {}", e);
+        }
+        Ok(json!({ "recording": state.recording, "path": state.path }))
+    }
+
+    /// Path of the recording called `name` in the recordings folder. Names
+    /// are plain file names, so requests cannot reach files elsewhere.
+    fn recording_path(&self, name: &str) -> std::result::Result<PathBuf, ApiError> {
+        let is_plain = !name.is_empty() && Path::new(name).file_name().is_some_and(|file_name| file_name == name);
+        if !is_plain {
+            return Err(bad_request(format!("'{}' is not a recording file name", name)));
+        }
+        let path = self.recordings_dir.join(name);
+        Ok(if path.extension().is_some() { path } else { path.with_extension("json") })
+    }
+}
+
+fn outcome_name(outcome: PlaybackOutcome) -> &'static str {
+    match outcome {
+        PlaybackOutcome::Completed => "completed",
+        PlaybackOutcome::Stopped => "stopped",
+        PlaybackOutcome::Failed => "failed",
+    }
+}
+
+fn parse_body<T: for<'de> Deserialize<'de> + Default>(body: &str) -> std::result::Result<T, ApiError> {
+    if body.trim().is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_str(body).map_err(|e| bad_request(format!("Invalid request body: {}", e)))
+}
+
+/// Headers of a request that tell whether a web page could have sent it.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RequestHeaders<'a> {
+    pub host: Option<&'a str>,
+    pub origin: Option<&'a str>,
+    pub content_type: Option<&'a str>,
+}
+
+impl<'a> RequestHeaders<'a> {
+    fn of(request: &'a tiny_http::Request) -> Self {
+        let header = |name: &'static str| request.headers().iter().find(|header| header.field.equiv(name)).map(|header| header.value.as_str());
+        Self {
+            host: header("Host"),
+            origin: header("Origin"),
+            content_type: header("Content-Type"),
+        }
+    }
+}
+
+/// The reply to refuse a request to the server on `addr` with, if a web
+/// page open in the user's browser could have sent it: one whose `Host` is
+/// not the server (DNS rebinding), whose `Origin` is another site, or a
+/// POST without a JSON body, which pages can only send after a CORS
+/// preflight the server never allows. Tools like curl send no `Origin`.
+pub fn check_request(addr: SocketAddr, method: &str, headers: &RequestHeaders) -> Option<(u16, Value)> {
+    let refuse = |status, message: &str| Some((status, json!({ "error": message })));
+    if let Some(host) = headers.host
+        && !is_own_host(host, addr)
+    {
+        return refuse(403, "Requests must be addressed to localhost or the address the server listens on");
+    }
+    if let Some(origin) = headers.origin
+        && headers.host.is_none_or(|host| origin != format!("http://{}", host))
+    {
+        return refuse(403, "Requests from web pages are not allowed");
+    }
+    let is_json = headers
+        .content_type
+        .is_some_and(|content_type| content_type.split(';').next().unwrap_or_default().trim().eq_ignore_ascii_case("application/json"));
+    if method == "POST" && !is_json {
+        return refuse(415, "Send POST requests with Content-Type: application/json");
+    }
+    None
+}
+
+/// Whether the `Host` header `host` names the server on `addr`, by the
+/// address it listens on or, when that is a loopback address, localhost.
+/// When it listens on every address it is open to other machines anyway,
+/// under whatever name they know it by.
+fn is_own_host(host: &str, addr: SocketAddr) -> bool {
+    let (name, port) = match host.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        Some((name, rest)) => (name, rest.strip_prefix(':')),
+        None => match host.rsplit_once(':') {
+            Some((name, port)) => (name, Some(port)),
+            None => (host, None),
+        },
+    };
+    if port.map_or(80, |port| port.parse().unwrap_or(0)) != addr.port() {
+        return false;
+    }
+    let ip = addr.ip();
+    ip.is_unspecified()
+        || (name.eq_ignore_ascii_case("localhost") && ip.is_loopback())
+        || name.parse::<IpAddr>().is_ok_and(|name| name == ip || (name.is_loopback() && ip.is_loopback()))
+}
+
+/// Serves the API on `addr` until the process is stopped.
+pub fn run_serve(server: Server, addr: SocketAddr) -> Result<()> {
+    platform::check_input_backend()?;
+    let http = tiny_http::Server::http(addr).map_err(|e| anyhow::anyhow!("Could not listen on {}: {}", addr, e))?;
+    tracing::info!("Serving the API on http://{}", addr);
+    let content_type: tiny_http::Header = "Content-Type: application/json".parse().unwrap();
+
+    for mut request in http.incoming_requests() {
+        // Checked for /events too, which would otherwise let any site read what is typed
+        let refusal = check_request(addr, request.method().as_str(), &RequestHeaders::of(&request));
+        if refusal.is_none() && request.url().split('?').next() == Some("/events") {
+            accept_websocket(request, server.subscribe());
+            continue;
+        }
+        let mut body = String::new();
+        let (status, value) = match refusal {
+            Some(refusal) => refusal,
+            None => match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => server.handle(request.method().as_str(), request.url(), &body),
+                Err(e) => (400, json!({ "error": format!("Could not read request body: {}", e) })),
+            },
+        };
+        tracing::info!("{} {} -> {}", request.method(), request.url(), status);
+        let response = tiny_http::Response::from_string(value.to_string())
+            .with_status_code(status)
+            .with_header(content_type.clone());
+        if let Err(e) = request.respond(response) {
+            tracing::warn!("Failed to send response: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Upgrades a `/events` request to a WebSocket and forwards `updates` to it
+/// as text messages until the client goes away.
+fn accept_websocket(request: tiny_http::Request, updates: mpsc::Receiver<Value>) {
+    let key = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Sec-WebSocket-Key"))
+        .map(|header| header.value.as_str().to_string());
+    let Some(key) = key else {
+        let response = tiny_http::Response::from_string(json!({ "error": "Connect to /events with a WebSocket" }).to_string())
+            .with_status_code(400);
+        if let Err(e) = request.respond(response) {
+            tracing::warn!("Failed to send response: {}", e);
+        }
+        return;
+    };
+    tracing::info!("WebSocket client connected to /events");
+    let accept = format!("Sec-WebSocket-Accept: {}", derive_accept_key(key.as_bytes()));
+    let response = tiny_http::Response::empty(101).with_header(accept.parse::<tiny_http::Header>().unwrap());
+    let stream = request.upgrade("websocket", response);
+    thread::spawn(move || {
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        for update in updates {
+            if socket.send(Message::Text(update.to_string())).is_err() {
+                break;
+            }
+        }
+        tracing::info!("WebSocket client disconnected from /events");
+    });
+}
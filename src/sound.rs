@@ -0,0 +1,47 @@
+use crate::platform;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::thread;
+
+/// Moments the menu bar app can play a sound at, so they can be told
+/// apart without looking at the tray icon, e.g. while working full screen.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Cue {
+    RecordingStarted,
+    RecordingStopped,
+    PlaybackStarted,
+    PlaybackFinished,
+    PlaybackFailed,
+}
+
+impl fmt::Display for Cue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cue::RecordingStarted => write!(f, "recording_started"),
+            Cue::RecordingStopped => write!(f, "recording_stopped"),
+            Cue::PlaybackStarted => write!(f, "playback_started"),
+            Cue::PlaybackFinished => write!(f, "playback_finished"),
+            Cue::PlaybackFailed => write!(f, "playback_failed"),
+        }
+    }
+}
+
+/// Plays the sound file `custom`, or else the system sound for `cue`, on a
+/// background thread. A sound that cannot be played is only logged.
+pub fn play(cue: Cue, custom: Option<&Path>) {
+    let Some(path) = custom.map(Path::to_path_buf).or_else(|| platform::system_sound(cue)) else {
+        tracing::debug!("No sound for {} on this platform.", cue);
+        return;
+    };
+    thread::spawn(move || play_file(&path));
+}
+
+fn play_file(path: &Path) {
+    match platform::sound_command(path).status() {
+        Ok(status) if !status.success() => tracing::warn!("Could not play {:?}: the player exited with {}", path, status),
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Could not play {:?}: {}", path, e),
+    }
+}
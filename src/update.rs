@@ -0,0 +1,57 @@
+use crate::config::UpdateChannel;
+use crate::platform;
+use anyhow::Result;
+use self_update::backends::github::{ReleaseList, Update};
+use self_update::update::Release;
+
+const REPO_OWNER: &str = "keval8solanki";
+const REPO_NAME: &str = "macro";
+
+/// Whether `version` is a pre-release such as `1.4.0-beta.2`.
+pub fn is_prerelease(version: &str) -> bool {
+    version.split('+').next().unwrap_or_default().contains('-')
+}
+
+/// Newest of `versions` on `channel` that is newer than `current`. The
+/// stable channel skips pre-releases, the beta channel takes either.
+pub fn newest_update<'a>(current: &str, versions: impl IntoIterator<Item = &'a str>, channel: UpdateChannel) -> Option<&'a str> {
+    let is_newer = |version: &str, than: &str| self_update::version::bump_is_greater(than, version).unwrap_or(false);
+    versions
+        .into_iter()
+        .filter(|version| channel == UpdateChannel::Beta || !is_prerelease(version))
+        .filter(|version| is_newer(version, current))
+        .fold(None, |newest, version| match newest {
+            Some(newest) if !is_newer(version, newest) => Some(newest),
+            _ => Some(version),
+        })
+}
+
+/// Newest release on `channel` with a build for this platform, if it is
+/// newer than the running version.
+pub fn available_update(channel: UpdateChannel) -> Result<Option<Release>> {
+    let releases = ReleaseList::configure()
+        .repo_owner(REPO_OWNER)
+        .repo_name(REPO_NAME)
+        .with_target(platform::release_target())
+        .build()?
+        .fetch()?;
+    let newest = newest_update(env!("CARGO_PKG_VERSION"), releases.iter().map(|release| release.version.as_str()), channel)
+        .map(str::to_string);
+    Ok(newest.and_then(|newest| releases.into_iter().find(|release| release.version == newest)))
+}
+
+/// Downloads `release` and replaces the running binary with it. Takes
+/// effect once the app is restarted.
+pub fn install(release: &Release) -> Result<()> {
+    Update::configure()
+        .repo_owner(REPO_OWNER)
+        .repo_name(REPO_NAME)
+        .bin_name("macro")
+        .target(platform::release_target())
+        .target_version_tag(&format!("v{}", release.version))
+        .show_download_progress(true)
+        .current_version(env!("CARGO_PKG_VERSION"))
+        .build()?
+        .update()?;
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use crate::play::PlaybackOutcome;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use std::thread;
+use std::process::{Child, ExitStatus};
+use std::time::{Duration, Instant};
+
+/// Command the tray app sends to a recording or playback worker process, one
+/// per line on the worker's stdin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerCommand {
+    Pause,
+    Resume,
+    /// Finish up and exit: a recording is saved, playback releases what it holds.
+    Stop,
+    /// Print a [`WorkerReport::Status`] line on stdout.
+    Status,
+}
+
+impl FromStr for WorkerCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pause" => Ok(WorkerCommand::Pause),
+            "resume" => Ok(WorkerCommand::Resume),
+            "stop" => Ok(WorkerCommand::Stop),
+            "status" => Ok(WorkerCommand::Status),
+            other => Err(format!("expected 'pause', 'resume', 'stop' or 'status', got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for WorkerCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkerCommand::Pause => write!(f, "pause"),
+            WorkerCommand::Resume => write!(f, "resume"),
+            WorkerCommand::Stop => write!(f, "stop"),
+            WorkerCommand::Status => write!(f, "status"),
+        }
+    }
+}
+
+/// Writes `command` to a worker's stdin.
+pub fn send_command(child: &mut Child, command: WorkerCommand) -> io::Result<()> {
+    let stdin = child
+        .stdin
+        .as_mut()
+        .ok_or_else(|| io::Error::other("the worker's stdin is not piped"))?;
+    writeln!(stdin, "{}", command)?;
+    stdin.flush()
+}
+
+/// Sends `stop` to a worker and waits for it to exit, killing it if it has
+/// not within `timeout`.
+pub fn stop(child: &mut Child, timeout: Duration) -> io::Result<ExitStatus> {
+    // Fails if the worker already exited on its own, e.g. from the stop hotkey
+    if let Err(e) = send_command(child, WorkerCommand::Stop) {
+        tracing::debug!("Could not send stop to worker {}: {}", child.id(), e);
+    }
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    tracing::warn!("Worker {} did not stop within {:?}, killing it.", child.id(), timeout);
+    child.kill()?;
+    child.wait()
+}
+
+/// Reads commands from `reader` on a background thread until it ends,
+/// skipping lines that are not a command.
+pub fn listen_for_commands(reader: impl BufRead + Send + 'static, on_command: impl Fn(WorkerCommand) + Send + 'static) {
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.trim().parse() {
+                Ok(command) => on_command(command),
+                Err(e) => tracing::warn!("Ignoring worker command: {}", e),
+            }
+        }
+    });
+}
+
+/// Status line a playback worker started with `--json-progress` prints on
+/// stdout, one JSON object per line. Recording workers only print `Status`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerReport {
+    /// Sent after each simulated event.
+    Progress { repeat: u32, events_played: usize, total_events: usize },
+    /// Sent every second, so a long wait between events is not mistaken for a hang.
+    Heartbeat,
+    /// An event could not be sent, or an assertion failed and aborted playback.
+    Error { message: String },
+    /// Playback ended. Always the last report.
+    Finished { outcome: PlaybackOutcome },
+    /// Answer to [`WorkerCommand::Status`]. `events` counts the events
+    /// recorded so far, or played so far in the current repeat.
+    Status { paused: bool, events: usize },
+}
+
+/// Prints `report` as one line on stdout.
+pub fn report(report: &WorkerReport) {
+    let mut stdout = io::stdout().lock();
+    let written = serde_json::to_writer(&mut stdout, report)
+        .map_err(io::Error::from)
+        .and_then(|()| writeln!(stdout))
+        .and_then(|()| stdout.flush());
+    if let Err(e) = written {
+        tracing::warn!("Failed to write worker report: {}", e);
+    }
+}
+
+/// Reports a heartbeat every `interval` on a background thread, for as long
+/// as the process runs.
+pub fn report_heartbeats(interval: Duration) {
+    thread::spawn(move || {
+        loop {
+            thread::sleep(interval);
+            report(&WorkerReport::Heartbeat);
+        }
+    });
+}
+
+/// Reads reports from a worker's stdout on a background thread until it
+/// ends, skipping lines that are not a report.
+pub fn listen_for_reports(reader: impl BufRead + Send + 'static, on_report: impl Fn(WorkerReport) + Send + 'static) {
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            match serde_json::from_str(&line) {
+                Ok(report) => on_report(report),
+                Err(e) => tracing::debug!("Ignoring worker output {:?}: {}", line, e),
+            }
+        }
+    });
+}
@@ -0,0 +1,25 @@
+use macro_lib::agent::{self, CronSchedule};
+use std::path::Path;
+
+#[test]
+fn cron_lines_become_launchd_calendar_intervals() {
+    let schedule: CronSchedule = "30 9,17 * * 1-5/2".parse().unwrap();
+    let intervals = schedule.calendar_intervals();
+    assert_eq!(intervals.len(), 6);
+    assert_eq!(intervals[0], [("Minute", 30), ("Hour", 9), ("Weekday", 1)]);
+    assert_eq!(intervals[5], [("Minute", 30), ("Hour", 17), ("Weekday", 5)]);
+    // Sunday is 0 to launchd, whichever way it is written
+    let sundays: CronSchedule = "0 12 * * 0,7".parse().unwrap();
+    assert_eq!(sundays.calendar_intervals(), [[("Minute", 0), ("Hour", 12), ("Weekday", 0)]]);
+    assert_eq!("* * * * *".parse::<CronSchedule>().unwrap().calendar_intervals(), [Vec::new()]);
+    assert_eq!("61 * * * *".parse::<CronSchedule>().unwrap_err(), "expected a minute from 0 to 59, got '61'");
+    assert!("0 9 * *".parse::<CronSchedule>().is_err());
+
+    let label = agent::label_for(Path::new("/Users/me/Macros/daily report.json"));
+    assert_eq!(label, "com.keval8solanki.macro.daily-report");
+    let program = ["/Applications/Macro.app/Contents/MacOS/macro".to_string(), "play".to_string(), "a&b.json".to_string()];
+    let plist = agent::plist(&label, &program, &schedule);
+    assert!(plist.contains("<string>a&amp;b.json</string>"));
+    assert!(plist.contains("      <key>Weekday</key>\n      <integer>3</integer>\n"));
+    assert_eq!(agent::plist_schedule(&plist), Some("30 9,17 * * 1-5/2"));
+}
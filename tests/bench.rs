@@ -0,0 +1,24 @@
+use macro_lib::backend::MockBackend;
+use macro_lib::bench::{self, Timings};
+use macro_lib::clock::VirtualClock;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn bench_reports_overshoot_and_events_capture_never_saw() {
+    let samples: Vec<Duration> = (1..=20).rev().map(Duration::from_millis).collect();
+    let timings = Timings::from_samples(&samples).unwrap();
+    assert_eq!((timings.samples, timings.min_us, timings.max_us), (20, 1_000, 20_000));
+    assert_eq!((timings.median_us, timings.p95_us), (11_000, 19_000));
+    assert_eq!(Timings::from_samples(&[]), None);
+
+    // The mock neither overshoots its sleeps nor feeds simulated input back
+    let clock = Arc::new(VirtualClock::new());
+    let backend = Arc::new(MockBackend::new(clock.clone()));
+    let report = bench::bench(backend.clone(), clock.as_ref(), 3).unwrap();
+    assert_eq!(report.sleep_overshoot.iter().map(|sleep| sleep.requested_ms).collect::<Vec<_>>(), [1, 5, 16]);
+    assert!(report.sleep_overshoot.iter().all(|sleep| sleep.overshoot.max_us == 0));
+    assert_eq!(report.simulate.unwrap().samples, 3);
+    assert_eq!((report.round_trip, report.lost), (None, 3));
+    assert_eq!(backend.simulated().len(), 3);
+}
@@ -0,0 +1,63 @@
+use macro_lib::backend::MockBackend;
+use macro_lib::chain::{self, Chain, OnFailure};
+use macro_lib::clock::{Clock, VirtualClock};
+use macro_lib::event::{SerializableEvent, SerializableEventType};
+use macro_lib::play::Player;
+use macro_lib::recording::Recording;
+use rdev::{EventType, Key};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn save_key(path: PathBuf, key: Key) {
+    let event = SerializableEvent { event_type: SerializableEventType::KeyPress(key), delay_ms: 10, delay_us: 0 };
+    Recording::new(vec![event], &[]).save(&path).unwrap();
+}
+
+#[test]
+fn failed_steps_continue_or_run_their_cleanup_and_stop() {
+    let dir = std::env::temp_dir().join("macro-test-chain");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    save_key(dir.join("a.json"), Key::KeyA);
+    save_key(dir.join("b.json"), Key::KeyB);
+    save_key(dir.join("cleanup.json"), Key::KeyC);
+    let path = dir.join("chain.json");
+    std::fs::write(
+        &path,
+        r#"{"steps": [
+            {"recording": "missing.json", "on_failure": "continue"},
+            {"recording": "a.json"},
+            {"recording": "broken.json", "on_failure": "run cleanup.json"},
+            {"recording": "b.json"}
+        ]}"#,
+    )
+    .unwrap();
+
+    let chain = Chain::load(&path).unwrap();
+    assert_eq!(chain.steps[1].on_failure, OnFailure::Abort);
+    assert_eq!(chain.steps[2].on_failure, OnFailure::Run(dir.join("cleanup.json")));
+    assert_eq!("retry".parse::<OnFailure>().unwrap_err(), "expected 'abort', 'continue' or 'run <recording>', got 'retry'");
+
+    let clock = Arc::new(VirtualClock::new());
+    let backend = Arc::new(MockBackend::new(clock.clone()));
+    let player = Player::new().backend(backend.clone()).clock(clock);
+    let error = chain::play_chain(&player, &chain).unwrap_err();
+    assert!(format!("{:#}", error).starts_with("Step 3"));
+    // The cleanup ran in place of the rest of the chain
+    let keys: Vec<EventType> = backend.simulated().into_iter().map(|(_, event)| event).collect();
+    assert_eq!(keys, [EventType::KeyPress(Key::KeyA), EventType::KeyPress(Key::KeyC)]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn countdown_ends_early_when_stopped() {
+    let clock = Arc::new(VirtualClock::new());
+    let player = Player::new().backend(Arc::new(MockBackend::new(clock.clone()))).clock(clock.clone());
+    assert!(player.count_down(Duration::from_secs(3)));
+    assert_eq!(clock.elapsed(), Duration::from_secs(3));
+
+    player.control().stop();
+    assert!(!player.count_down(Duration::from_secs(3)));
+    assert_eq!(clock.elapsed(), Duration::from_secs(3));
+}
@@ -0,0 +1,23 @@
+//! Fixtures shared by the integration tests. Not every test uses each of
+//! them, so each one is allowed to go unused.
+
+use macro_lib::event::{SerializableEvent, SerializableEventType};
+use rdev::Key;
+
+/// Event `delay_ms` after the one before it.
+#[allow(dead_code)]
+pub fn event(event_type: SerializableEventType, delay_ms: u64) -> SerializableEvent {
+    SerializableEvent { event_type, delay_ms, delay_us: 0 }
+}
+
+/// Press of `key`, `delay_ms` after the event before it.
+#[allow(dead_code)]
+pub fn key_event(key: Key, delay_ms: u64) -> SerializableEvent {
+    event(SerializableEventType::KeyPress(key), delay_ms)
+}
+
+/// Move to `x`, `y`, recorded without display information.
+#[allow(dead_code)]
+pub fn mouse_move(x: f64, y: f64) -> SerializableEventType {
+    SerializableEventType::MouseMove { x, y, display: None }
+}
@@ -0,0 +1,67 @@
+use macro_lib::config::{self, Config, MidiTrigger, Preset};
+use macro_lib::sound::Cue;
+use std::path::PathBuf;
+
+#[test]
+fn presets_come_from_the_config_file_or_the_defaults() {
+    let defaults: Config = serde_json::from_str("{}").unwrap();
+    assert_eq!(defaults.preset("fast-debug").unwrap().speed, Some(5.0));
+    assert_eq!(defaults.preset("overnight").unwrap().repeat_count, Some(0));
+
+    let config: Config = serde_json::from_str(r#"{"presets": {"demo": {"speed": 0.5}}}"#).unwrap();
+    assert_eq!(config.preset("demo").unwrap(), &Preset { speed: Some(0.5), repeat_count: None, repeat_interval: None, countdown: None });
+    assert_eq!(config.preset("overnight").unwrap_err().to_string(), "No preset named 'overnight', expected one of: demo");
+    assert_eq!(serde_json::to_string(config.preset("demo").unwrap()).unwrap(), r#"{"speed":0.5}"#);
+}
+
+#[test]
+fn midi_triggers_match_note_on_messages() {
+    let config: Config = serde_json::from_str(r#"{"midi_triggers": [{"note": 36, "recording": "a.json"}, {"note": 37, "channel": 10, "recording": "b.json"}]}"#).unwrap();
+    let [any_channel, drums] = config.midi_triggers.as_slice() else { panic!("expected two triggers") };
+    assert_eq!(any_channel, &MidiTrigger { note: 36, channel: None, recording: PathBuf::from("a.json") });
+
+    assert!(any_channel.matches(&[0x90, 36, 100]));
+    assert!(any_channel.matches(&[0x95, 36, 1]));
+    assert!(!any_channel.matches(&[0x90, 37, 100]));
+    // Note off, and note on with velocity 0
+    assert!(!any_channel.matches(&[0x80, 36, 100]));
+    assert!(!any_channel.matches(&[0x90, 36, 0]));
+    assert!(!any_channel.matches(&[0xB0, 36, 100]));
+    assert!(!any_channel.matches(&[0x90]));
+
+    assert!(drums.matches(&[0x99, 37, 64]));
+    assert!(!drums.matches(&[0x90, 37, 64]));
+    assert!(serde_json::from_str::<Config>("{}").unwrap().midi_triggers.is_empty());
+}
+
+#[test]
+fn checks_config_files_for_mistakes() {
+    assert!(config::check(r#"{"playback_speed": 1.5}"#).is_empty());
+
+    let problems = config::check("{\n  \"playback_speed\": 1.5\n  \"countdown\": 3\n}");
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("expected `,` or `}` at line 3 column 3\n"), "{}", problems[0]);
+    assert!(problems[0].ends_with("\n3 |   \"countdown\": 3\n  |   ^"), "{}", problems[0]);
+
+    let problems = config::check(r#"{"playbak_speed": 2, "playback_speed": 0, "midi_triggers": [{"note": 200, "channel": 0, "recording": "a.json"}]}"#);
+    assert_eq!(
+        problems,
+        vec![
+            "Unknown setting 'playbak_speed', it is ignored",
+            "playback_speed must be above 0, got 0",
+            "MIDI trigger for \"a.json\": note must be 0 to 127, got 200",
+            "MIDI trigger for \"a.json\": channel must be 1 to 16, got 0",
+        ]
+    );
+}
+
+#[test]
+fn sounds_are_set_by_cue_name() {
+    let config: Config = serde_json::from_str(r#"{"sound_cues": true, "sounds": {"playback_failed": "/no/such/alarm.wav"}}"#).unwrap();
+    assert!(config.sound_cues);
+    assert_eq!(config.sounds.get(&Cue::PlaybackFailed), Some(&PathBuf::from("/no/such/alarm.wav")));
+
+    let problems = config::check(r#"{"sounds": {"playback_failed": "/no/such/alarm.wav"}}"#);
+    assert_eq!(problems, vec!["Sound for playback_failed: /no/such/alarm.wav does not exist"]);
+    assert!(!config::check(r#"{"sounds": {"recording_paused": "a.wav"}}"#).is_empty());
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::{event, key_event, mouse_move};
+use macro_lib::diff::{diff, DiffEntry, DiffOptions};
+use macro_lib::recording::Recording;
+use rdev::Key;
+
+#[test]
+fn aligns_changed_events_and_timing() {
+    let left = Recording::new(vec![key_event(Key::KeyA, 0), key_event(Key::KeyB, 100), key_event(Key::KeyC, 100)], &[]);
+    let right = Recording::new(vec![key_event(Key::KeyA, 0), key_event(Key::KeyC, 500), key_event(Key::KeyD, 100)], &[]);
+
+    assert_eq!(
+        diff(&left, &right, &DiffOptions::default()),
+        vec![
+            DiffEntry::Same { left: 0, right: 0, offset_ms: 0 },
+            DiffEntry::Removed { left: 1 },
+            DiffEntry::Same { left: 2, right: 1, offset_ms: 300 },
+            DiffEntry::Added { right: 2 },
+        ]
+    );
+}
+
+#[test]
+fn nearby_mouse_moves_match_when_included() {
+    let left = Recording::new(vec![event(mouse_move(100.0, 100.0), 10), event(mouse_move(400.0, 300.0), 10)], &[]);
+    let right = Recording::new(vec![event(mouse_move(102.0, 99.0), 10), event(mouse_move(40.0, 30.0), 10)], &[]);
+    let options = DiffOptions {
+        include_moves: true,
+        ..Default::default()
+    };
+
+    assert!(diff(&left, &right, &DiffOptions::default()).is_empty());
+    assert_eq!(
+        diff(&left, &right, &options),
+        vec![
+            DiffEntry::Same { left: 0, right: 0, offset_ms: 0 },
+            DiffEntry::Removed { left: 1 },
+            DiffEntry::Added { right: 1 },
+        ]
+    );
+}
@@ -0,0 +1,27 @@
+use macro_lib::exit::{ExitStatus, RunResult};
+use macro_lib::platform::MissingPermissions;
+use macro_lib::play::PlaybackOutcome;
+use std::io;
+use std::time::Duration;
+
+#[test]
+fn runs_map_to_documented_exit_codes() {
+    let permissions: anyhow::Result<PlaybackOutcome> = Err(anyhow::Error::from(MissingPermissions(vec!["Accessibility"])));
+    let missing_file = Err(anyhow::Error::from(io::Error::from(io::ErrorKind::NotFound)).context("Could not load recording"));
+    let assertion = Err(anyhow::anyhow!("Assertion failed"));
+
+    assert_eq!(ExitStatus::of(&Ok(PlaybackOutcome::Completed), ExitStatus::PlaybackError), ExitStatus::Success);
+    assert_eq!(ExitStatus::of(&Ok(PlaybackOutcome::Stopped), ExitStatus::PlaybackError).code(), 3);
+    assert_eq!(ExitStatus::of(&permissions, ExitStatus::PlaybackError).code(), 4);
+    assert_eq!(ExitStatus::of(&missing_file, ExitStatus::PlaybackError).code(), 5);
+    assert_eq!(ExitStatus::of(&assertion, ExitStatus::PlaybackError).code(), 6);
+    assert_eq!(ExitStatus::of(&assertion, ExitStatus::Error).code(), 1);
+
+    let stopped = RunResult::new(&Ok(PlaybackOutcome::Stopped), ExitStatus::PlaybackError, Duration::from_millis(4200));
+    assert_eq!(serde_json::to_string(&stopped).unwrap(), r#"{"status":"stopped","exit_code":3,"duration":4.2}"#);
+    let failed = RunResult::new(&assertion, ExitStatus::PlaybackError, Duration::from_secs(1));
+    assert_eq!(
+        serde_json::to_string(&failed).unwrap(),
+        r#"{"status":"playback_error","exit_code":6,"duration":1.0,"error":"Assertion failed"}"#
+    );
+}
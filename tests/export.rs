@@ -0,0 +1,34 @@
+use macro_lib::event::{SerializableEvent, SerializableEventType};
+use macro_lib::export::write_csv;
+use macro_lib::recording::Recording;
+use rdev::{Button, Key};
+
+#[test]
+fn writes_one_csv_row_per_event() {
+    let event = |event_type, delay_ms| SerializableEvent {
+        event_type,
+        delay_ms,
+        delay_us: 0,
+    };
+    let recording = Recording::new(
+        vec![
+            event(SerializableEventType::MouseMove { x: 10.5, y: 20.0, display: None }, 0),
+            event(SerializableEventType::ButtonPress(Button::Left), 50),
+            event(SerializableEventType::KeyPressText { key: Key::KeyC, text: "ç".to_string() }, 100),
+            event(SerializableEventType::Clipboard { text: "a, \"b\"".to_string() }, 25),
+        ],
+        &[],
+    );
+
+    let mut out = Vec::new();
+    write_csv(&recording, &mut out).unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "timestamp_ms,event,key_button,x,y,delta_x,delta_y,text\n\
+         0,MouseMove,,10.5,20,,,\n\
+         50,ButtonPress,Left,,,,,\n\
+         150,KeyPress,KeyC,,,,,ç\n\
+         175,Clipboard,,,,,,\"a, \"\"b\"\"\"\n"
+    );
+}
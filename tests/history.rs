@@ -0,0 +1,30 @@
+use macro_lib::history::{self, RunKind, RunTimer};
+use macro_lib::play::PlaybackOutcome;
+use std::path::Path;
+
+#[test]
+fn runs_are_appended_and_loaded_back_in_order() {
+    let dir = std::env::temp_dir().join("macro-test-history");
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("history.jsonl");
+    assert!(history::load(&path).unwrap().is_empty());
+
+    let played = RunTimer::start(RunKind::Play, Some(Path::new("demo.json")), vec!["--speed".into(), "2".into()])
+        .finish(&Ok(()), false);
+    let stopped = RunTimer::start(RunKind::Record, None, Vec::new()).finish(&Ok(()), true);
+    let failed = RunTimer::start(RunKind::Play, None, Vec::new()).finish(&Err::<(), _>(anyhow::anyhow!("no access")), true);
+    assert_eq!(played.outcome, PlaybackOutcome::Completed);
+    assert!(played.path.as_ref().unwrap().is_absolute());
+    assert_eq!(stopped.outcome, PlaybackOutcome::Stopped);
+    assert_eq!(failed.outcome, PlaybackOutcome::Failed);
+    assert_eq!(failed.error.as_deref(), Some("no access"));
+
+    history::append(&path, &played).unwrap();
+    history::append(&path, &stopped).unwrap();
+    // A line cut short by a crash does not lose the rest of the history
+    std::fs::write(&path, std::fs::read_to_string(&path).unwrap() + "{\"kind\":\"pl\n").unwrap();
+    history::append(&path, &failed).unwrap();
+
+    assert_eq!(history::load(&path).unwrap(), vec![played, stopped, failed]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,18 @@
+use macro_lib::instance;
+use std::net::TcpListener;
+
+#[test]
+fn only_the_first_instance_gets_the_lock() {
+    let port = 47_863;
+    assert!(!instance::is_running_on(port));
+
+    let lock = instance::acquire_on(port).unwrap();
+    assert!(lock.is_some());
+    assert!(instance::is_running_on(port));
+    assert!(instance::acquire_on(port).unwrap().is_none());
+
+    // A port held by some other program is not mistaken for another instance
+    let other = TcpListener::bind(("127.0.0.1", 47_864)).unwrap();
+    assert!(instance::acquire_on(47_864).is_err());
+    drop(other);
+}
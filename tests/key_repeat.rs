@@ -0,0 +1,57 @@
+mod common;
+
+use common::event;
+use macro_lib::event::SerializableEventType;
+use macro_lib::key_repeat::{self, KeyRepeat};
+use macro_lib::recording::Recording;
+use rdev::{Button, Key};
+use std::time::Duration;
+
+#[test]
+fn collapses_auto_repeat_into_holds_and_plays_them_back_with_local_repeats() {
+    let events = vec![
+        event(SerializableEventType::KeyPress(Key::KeyA), 10),
+        event(SerializableEventType::KeyPress(Key::KeyA), 500),
+        event(SerializableEventType::ButtonPress(Button::Left), 10),
+        event(SerializableEventType::KeyPress(Key::KeyA), 20),
+        event(SerializableEventType::ButtonRelease(Button::Left), 10),
+        event(SerializableEventType::KeyRelease(Key::KeyA), 60),
+        // Pressed once, so left alone
+        event(SerializableEventType::KeyPress(Key::KeyB), 100),
+        event(SerializableEventType::KeyRelease(Key::KeyB), 50),
+    ];
+
+    let collapsed = key_repeat::collapse(&events);
+
+    let timeline: Vec<_> = collapsed.iter().map(|e| (e.event_type.clone(), e.delay())).collect();
+    let hold = SerializableEventType::Hold { key: Key::KeyA, duration_ms: 600, text: None };
+    assert_eq!(
+        timeline,
+        vec![
+            (hold, Duration::from_millis(10)),
+            (SerializableEventType::ButtonPress(Button::Left), Duration::from_millis(510)),
+            (SerializableEventType::ButtonRelease(Button::Left), Duration::from_millis(30)),
+            (SerializableEventType::KeyPress(Key::KeyB), Duration::from_millis(160)),
+            (SerializableEventType::KeyRelease(Key::KeyB), Duration::from_millis(50)),
+        ]
+    );
+
+    let repeat = KeyRepeat { delay: Duration::from_millis(200), interval: Duration::from_millis(150) };
+    let expanded = key_repeat::expand(&Recording::new(collapsed, &[]), repeat);
+
+    let timeline: Vec<_> = expanded.events.iter().map(|e| (e.event_type.clone(), e.delay())).collect();
+    assert_eq!(
+        timeline,
+        vec![
+            (SerializableEventType::KeyPress(Key::KeyA), Duration::from_millis(10)),
+            (SerializableEventType::KeyPress(Key::KeyA), Duration::from_millis(200)),
+            (SerializableEventType::KeyPress(Key::KeyA), Duration::from_millis(150)),
+            (SerializableEventType::KeyPress(Key::KeyA), Duration::from_millis(150)),
+            (SerializableEventType::ButtonPress(Button::Left), Duration::from_millis(10)),
+            (SerializableEventType::ButtonRelease(Button::Left), Duration::from_millis(30)),
+            (SerializableEventType::KeyRelease(Key::KeyA), Duration::from_millis(60)),
+            (SerializableEventType::KeyPress(Key::KeyB), Duration::from_millis(100)),
+            (SerializableEventType::KeyRelease(Key::KeyB), Duration::from_millis(50)),
+        ]
+    );
+}
@@ -0,0 +1,24 @@
+use macro_lib::logging::RotatingFile;
+use std::io::Write;
+
+#[test]
+fn log_files_rotate_and_keep_only_the_newest() {
+    let dir = std::env::temp_dir().join("macro-test-logging");
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("play.log");
+
+    let mut log = RotatingFile::open(&path, 10, 2).unwrap();
+    for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+        log.write_all(line.as_bytes()).unwrap();
+    }
+    drop(log);
+    // Appending again picks up the size already on disk
+    RotatingFile::open(&path, 10, 2).unwrap().write_all(b"fifth\n").unwrap();
+
+    let read = |path| std::fs::read_to_string(path).unwrap();
+    assert_eq!(read(path.clone()), "fifth\n");
+    assert_eq!(read(RotatingFile::rotated_path(&path, 1)), "fourth\n");
+    assert_eq!(read(RotatingFile::rotated_path(&path, 2)), "third\n");
+    assert!(!RotatingFile::rotated_path(&path, 3).exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::{event, mouse_move};
+use macro_lib::event::{SerializableEvent, SerializableEventType};
+use macro_lib::optimize::{optimize, OptimizeOptions};
+use macro_lib::recording::Recording;
+use rdev::{Button, Key};
+use std::time::Duration;
+
+#[test]
+fn simplifies_paths_and_drops_redundant_events_keeping_timing() {
+    let recording = Recording::new(
+        vec![
+            event(mouse_move(0.0, 0.0), 10),
+            event(mouse_move(10.0, 0.4), 10),
+            event(mouse_move(20.0, 0.0), 10),
+            SerializableEvent { delay_us: 400, ..event(mouse_move(30.0, 0.0), 10) },
+            // Already there
+            event(mouse_move(30.0, 0.0), 10),
+            // Released at the same instant
+            event(SerializableEventType::KeyPress(Key::KeyA), 5),
+            event(SerializableEventType::KeyRelease(Key::KeyA), 0),
+            event(SerializableEventType::ButtonPress(Button::Left), 20),
+        ],
+        &[],
+    );
+
+    let optimized = optimize(&recording, &OptimizeOptions::default());
+
+    let events: Vec<_> = optimized.events.iter().map(|e| (e.event_type.clone(), e.delay())).collect();
+    assert_eq!(
+        events,
+        vec![
+            (mouse_move(0.0, 0.0), Duration::from_millis(10)),
+            (mouse_move(30.0, 0.0), Duration::from_millis(30)),
+            (SerializableEventType::ButtonPress(Button::Left), Duration::from_millis(35)),
+        ]
+    );
+}
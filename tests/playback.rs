@@ -0,0 +1,754 @@
+mod common;
+
+use common::key_event;
+use macro_lib::backend::{EventCallback, InputBackend, MockBackend};
+use macro_lib::clock::{Clock, VirtualClock};
+use macro_lib::display::{DisplayInfo, DisplayMapping, DisplayPoint, Transform};
+use macro_lib::event::{KeyReplay, ScrollPhase, SerializableEvent, SerializableEventType};
+use macro_lib::play::{do_playback, event_range, play_stream, release_all, scaled_delay, HoldTiming, PlaybackControl, PlaybackOptions, PlaybackOutcome, PlaybackPosition, PlaybackProgress, Player, Watchdog};
+use macro_lib::recording::Recording;
+use rdev::{Button, EventType, Key};
+use std::io::Cursor;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn recording(events: Vec<SerializableEvent>) -> Recording {
+    Recording::new(events, &[])
+}
+
+fn setup() -> (Arc<VirtualClock>, MockBackend) {
+    let clock = Arc::new(VirtualClock::new());
+    let backend = MockBackend::new(clock.clone());
+    (clock, backend)
+}
+
+fn play(backend: &dyn InputBackend, clock: &dyn Clock, recording: &Recording, options: PlaybackOptions) {
+    do_playback(backend, clock, recording, &options, Arc::new(AtomicBool::new(false))).unwrap();
+}
+
+/// Stops playback once a given number of events has been simulated.
+struct StopAfter {
+    inner: MockBackend,
+    remaining: AtomicUsize,
+    stop_flag: Arc<AtomicBool>,
+}
+
+impl InputBackend for StopAfter {
+    fn listen(&self, callback: EventCallback) -> anyhow::Result<()> {
+        self.inner.listen(callback)
+    }
+
+    fn simulate(&self, event_type: &EventType) -> anyhow::Result<()> {
+        self.inner.simulate(event_type)?;
+        if self.remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.stop_flag.store(true, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+}
+
+/// Takes a fixed time to send each event, like a slow system would.
+struct Slow {
+    inner: MockBackend,
+    clock: Arc<VirtualClock>,
+    cost: Duration,
+}
+
+impl InputBackend for Slow {
+    fn listen(&self, callback: EventCallback) -> anyhow::Result<()> {
+        self.inner.listen(callback)
+    }
+
+    fn simulate(&self, event_type: &EventType) -> anyhow::Result<()> {
+        self.inner.simulate(event_type)?;
+        self.clock.advance(self.cost);
+        Ok(())
+    }
+}
+
+/// Cannot send any event, like a system that revoked input access.
+struct Failing;
+
+impl InputBackend for Failing {
+    fn listen(&self, _callback: EventCallback) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn simulate(&self, _event_type: &EventType) -> anyhow::Result<()> {
+        anyhow::bail!("not allowed")
+    }
+}
+
+#[test]
+fn replays_events_at_recorded_times() {
+    let (clock, backend) = setup();
+    let recording = recording(vec![key_event(Key::KeyA, 100), key_event(Key::KeyB, 250)]);
+
+    play(&backend, clock.as_ref(), &recording, PlaybackOptions::default());
+
+    let simulated = backend.simulated();
+    assert_eq!(
+        simulated,
+        vec![
+            (Duration::from_millis(100), EventType::KeyPress(Key::KeyA)),
+            (Duration::from_millis(350), EventType::KeyPress(Key::KeyB)),
+        ]
+    );
+}
+
+#[test]
+fn time_spent_sending_events_does_not_delay_later_ones() {
+    let (clock, inner) = setup();
+    let backend = Slow {
+        inner,
+        clock: clock.clone(),
+        cost: Duration::from_millis(5),
+    };
+    let recording = recording(vec![key_event(Key::KeyA, 100), key_event(Key::KeyB, 100), key_event(Key::KeyC, 100)]);
+
+    play(&backend, clock.as_ref(), &recording, PlaybackOptions::default());
+
+    let times: Vec<_> = backend.inner.simulated().into_iter().map(|(t, _)| t).collect();
+    assert_eq!(times, vec![Duration::from_millis(100), Duration::from_millis(200), Duration::from_millis(300)]);
+}
+
+#[test]
+fn replays_sub_millisecond_delays() {
+    let (clock, backend) = setup();
+    let mut first = key_event(Key::KeyA, 0);
+    first.set_delay(Duration::from_micros(1_250));
+    let mut second = key_event(Key::KeyB, 0);
+    second.set_delay(Duration::from_micros(500));
+    let recording = recording(vec![first, second]);
+
+    play(&backend, clock.as_ref(), &recording, PlaybackOptions::default());
+
+    let times: Vec<_> = backend.simulated().into_iter().map(|(t, _)| t).collect();
+    assert_eq!(times, vec![Duration::from_micros(1_250), Duration::from_micros(1_750)]);
+}
+
+#[test]
+fn countdown_delays_the_first_event() {
+    let (clock, backend) = setup();
+    let recording = recording(vec![key_event(Key::KeyA, 100)]);
+    let options = PlaybackOptions {
+        countdown: Duration::from_secs(3),
+        ..Default::default()
+    };
+
+    play(&backend, clock.as_ref(), &recording, options);
+
+    assert_eq!(backend.simulated(), vec![(Duration::from_millis(3100), EventType::KeyPress(Key::KeyA))]);
+}
+
+#[test]
+fn humanize_keeps_events_near_their_recorded_times() {
+    let (clock, backend) = setup();
+    let recording = recording((0..50).map(|_| key_event(Key::KeyA, 100)).collect());
+    let options = PlaybackOptions {
+        humanize: Duration::from_millis(20),
+        ..Default::default()
+    };
+
+    play(&backend, clock.as_ref(), &recording, options);
+
+    for (index, (time, _)) in backend.simulated().into_iter().enumerate() {
+        let recorded = Duration::from_millis(100 * (index as u64 + 1));
+        assert!(time + Duration::from_millis(20) >= recorded, "event {} at {:?}", index, time);
+        assert!(time <= recorded + Duration::from_millis(20), "event {} at {:?}", index, time);
+    }
+}
+
+#[test]
+fn plays_only_the_requested_range() {
+    let keys = [Key::KeyA, Key::KeyB, Key::KeyC, Key::KeyD, Key::KeyE];
+    let recording = recording(keys.iter().map(|key| key_event(*key, 100)).collect());
+    let played = |from, to| {
+        let (clock, backend) = setup();
+        let options = PlaybackOptions { from, to, ..Default::default() };
+        play(&backend, clock.as_ref(), &recording, options);
+        backend.simulated()
+    };
+
+    // A slice starts right away, then keeps the recorded gaps
+    assert_eq!(
+        played(Some(PlaybackPosition::Index(1)), Some(PlaybackPosition::Index(2))),
+        vec![(Duration::ZERO, EventType::KeyPress(Key::KeyB)), (Duration::from_millis(100), EventType::KeyPress(Key::KeyC))]
+    );
+    let keys_played = |simulated: Vec<(Duration, EventType)>| simulated.into_iter().map(|(_, e)| e).collect::<Vec<_>>();
+    assert_eq!(
+        keys_played(played(Some(PlaybackPosition::Time(Duration::from_millis(350))), None)),
+        vec![EventType::KeyPress(Key::KeyD), EventType::KeyPress(Key::KeyE)]
+    );
+    assert_eq!(
+        keys_played(played(None, Some(PlaybackPosition::Time(Duration::from_millis(200))))),
+        vec![EventType::KeyPress(Key::KeyA), EventType::KeyPress(Key::KeyB)]
+    );
+    assert!(event_range(&recording, Some(&PlaybackPosition::Index(5)), None).is_err());
+    assert!(event_range(&recording, Some(&PlaybackPosition::Index(3)), Some(&PlaybackPosition::Index(2))).is_err());
+}
+
+#[test]
+fn plays_from_a_named_checkpoint() {
+    let (clock, backend) = setup();
+    let checkpoint = SerializableEvent {
+        event_type: SerializableEventType::Checkpoint { name: "login".to_string() },
+        delay_ms: 100,
+        delay_us: 0,
+    };
+    let recording = recording(vec![key_event(Key::KeyA, 100), checkpoint, key_event(Key::KeyB, 100)]);
+    let options = PlaybackOptions {
+        from: Some(PlaybackPosition::Checkpoint("login".to_string())),
+        ..Default::default()
+    };
+
+    play(&backend, clock.as_ref(), &recording, options);
+
+    assert_eq!(backend.simulated(), vec![(Duration::from_millis(100), EventType::KeyPress(Key::KeyB))]);
+    assert!(event_range(&recording, Some(&PlaybackPosition::Checkpoint("logout".to_string())), None).is_err());
+}
+
+#[test]
+fn speed_scales_delays() {
+    let (clock, backend) = setup();
+    let recording = recording(vec![key_event(Key::KeyA, 100), key_event(Key::KeyB, 200)]);
+    let options = PlaybackOptions {
+        speed: 2.0,
+        ..Default::default()
+    };
+
+    play(&backend, clock.as_ref(), &recording, options);
+
+    let times: Vec<_> = backend.simulated().into_iter().map(|(t, _)| t).collect();
+    assert_eq!(times, vec![Duration::from_millis(50), Duration::from_millis(150)]);
+}
+
+#[test]
+fn hold_timing_keeps_keys_down_at_high_speed() {
+    let (clock, backend) = setup();
+    let release = SerializableEvent {
+        event_type: SerializableEventType::KeyRelease(Key::KeyA),
+        delay_ms: 50,
+        delay_us: 0,
+    };
+    let recording = recording(vec![key_event(Key::KeyA, 100), release, key_event(Key::KeyB, 100)]);
+    let options = PlaybackOptions {
+        speed: 10.0,
+        hold: HoldTiming::Recorded,
+        ..Default::default()
+    };
+
+    play(&backend, clock.as_ref(), &recording, options);
+
+    // The release waits out the full 50ms hold, and the gap after it is still scaled
+    let times: Vec<_> = backend.simulated().into_iter().map(|(t, _)| t).collect();
+    assert_eq!(times, vec![Duration::from_millis(10), Duration::from_millis(60), Duration::from_millis(70)]);
+    assert_eq!("30ms".parse(), Ok(HoldTiming::AtLeast(Duration::from_millis(30))));
+}
+
+#[test]
+fn mouse_and_keyboard_speeds_scale_their_own_gaps() {
+    let (clock, backend) = setup();
+    let click = SerializableEvent {
+        event_type: SerializableEventType::ButtonPress(Button::Left),
+        delay_ms: 400,
+        delay_us: 0,
+    };
+    let recording = recording(vec![click, key_event(Key::KeyA, 100)]);
+    let options = PlaybackOptions {
+        speed: 2.0,
+        mouse_speed: 2.0,
+        keyboard_speed: 0.5,
+        ..Default::default()
+    };
+
+    play(&backend, clock.as_ref(), &recording, options);
+
+    let times: Vec<_> = backend.simulated().into_iter().map(|(t, _)| t).collect();
+    assert_eq!(times, vec![Duration::from_millis(100), Duration::from_millis(200)]);
+}
+
+#[test]
+fn invalid_speed_falls_back_to_real_time() {
+    assert_eq!(scaled_delay(Duration::from_millis(100), 0.0), Duration::from_millis(100));
+    assert_eq!(scaled_delay(Duration::from_millis(100), -2.0), Duration::from_millis(100));
+    assert_eq!(scaled_delay(Duration::from_millis(100), f64::NAN), Duration::from_millis(100));
+    assert_eq!(scaled_delay(Duration::from_millis(100), f64::INFINITY), Duration::from_millis(100));
+    assert_eq!(scaled_delay(Duration::from_millis(100), 0.5), Duration::from_millis(200));
+}
+
+#[test]
+fn repeats_the_requested_number_of_times() {
+    let (clock, backend) = setup();
+    let recording = recording(vec![key_event(Key::KeyA, 10), key_event(Key::KeyB, 10)]);
+    let options = PlaybackOptions {
+        repeat_count: 3,
+        ..Default::default()
+    };
+
+    play(&backend, clock.as_ref(), &recording, options);
+
+    assert_eq!(backend.simulated().len(), 6);
+    assert_eq!(clock.elapsed(), Duration::from_millis(60));
+}
+
+#[test]
+fn waits_repeat_interval_between_runs() {
+    let (clock, backend) = setup();
+    let recording = recording(vec![key_event(Key::KeyA, 0)]);
+    let options = PlaybackOptions {
+        repeat_count: 2,
+        repeat_interval: 1.0,
+        ..Default::default()
+    };
+
+    play(&backend, clock.as_ref(), &recording, options);
+
+    let times: Vec<_> = backend.simulated().into_iter().map(|(t, _)| t).collect();
+    assert_eq!(times.len(), 2);
+    assert_eq!(times[0], Duration::ZERO);
+    assert!(times[1] >= Duration::from_secs(1));
+}
+
+#[test]
+fn stop_flag_set_before_start_plays_nothing() {
+    let (clock, backend) = setup();
+    let recording = recording(vec![key_event(Key::KeyA, 10)]);
+
+    do_playback(
+        &backend,
+        clock.as_ref(),
+        &recording,
+        &PlaybackOptions::default(),
+        Arc::new(AtomicBool::new(true)),
+    )
+    .unwrap();
+
+    assert!(backend.simulated().is_empty());
+}
+
+#[test]
+fn stop_flag_ends_infinite_playback() {
+    let clock = Arc::new(VirtualClock::new());
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let backend = StopAfter {
+        inner: MockBackend::new(clock.clone()),
+        remaining: AtomicUsize::new(5),
+        stop_flag: stop_flag.clone(),
+    };
+    let recording = recording(vec![key_event(Key::KeyA, 10), key_event(Key::KeyB, 10)]);
+    let options = PlaybackOptions {
+        repeat_count: 0,
+        ..Default::default()
+    };
+
+    do_playback(&backend, clock.as_ref(), &recording, &options, stop_flag).unwrap();
+
+    // Five presses, then the keys still held down are released
+    let simulated: Vec<_> = backend.inner.simulated().into_iter().map(|(_, e)| e).collect();
+    assert_eq!(simulated.len(), 7);
+    assert!(simulated[..5].iter().all(|e| matches!(e, EventType::KeyPress(_))));
+    assert_eq!(simulated[5..], [EventType::KeyRelease(Key::KeyA), EventType::KeyRelease(Key::KeyB)]);
+}
+
+#[test]
+fn repeat_for_stops_at_deadline_and_releases_held_keys() {
+    let (clock, backend) = setup();
+    let recording = recording(vec![
+        key_event(Key::KeyA, 0),
+        SerializableEvent {
+            event_type: SerializableEventType::KeyRelease(Key::KeyA),
+            delay_ms: 1000,
+            delay_us: 0,
+        },
+    ]);
+    let options = PlaybackOptions {
+        repeat_count: 0,
+        repeat_for: Some(Duration::from_millis(1500)),
+        ..Default::default()
+    };
+
+    play(&backend, clock.as_ref(), &recording, options);
+
+    assert_eq!(
+        backend.simulated(),
+        vec![
+            (Duration::ZERO, EventType::KeyPress(Key::KeyA)),
+            (Duration::from_millis(1000), EventType::KeyRelease(Key::KeyA)),
+            (Duration::from_millis(1000), EventType::KeyPress(Key::KeyA)),
+            (Duration::from_millis(1500), EventType::KeyRelease(Key::KeyA)),
+        ]
+    );
+}
+
+#[test]
+fn reports_progress_after_each_event() {
+    let (clock, backend) = setup();
+    let recording = recording(vec![key_event(Key::KeyA, 10), key_event(Key::KeyB, 10)]);
+    let player = Player::new()
+        .backend(Arc::new(backend))
+        .clock(clock)
+        .repeat_count(2);
+
+    let mut updates = Vec::new();
+    player.play_with_progress(&recording, &mut |progress| updates.push(progress)).unwrap();
+
+    let progress = |repeat, events_played| PlaybackProgress { repeat, events_played, total_events: 2 };
+    assert_eq!(updates, vec![progress(0, 1), progress(0, 2), progress(1, 1), progress(1, 2)]);
+}
+
+#[test]
+fn calls_lifecycle_hooks_in_order() {
+    let (clock, backend) = setup();
+    let recording = recording(vec![key_event(Key::KeyA, 10)]);
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let (on_start, on_event, on_repeat, on_finish) = (calls.clone(), calls.clone(), calls.clone(), calls.clone());
+    let player = Player::new()
+        .backend(Arc::new(backend))
+        .clock(clock)
+        .repeat_count(2)
+        .on_start(move || on_start.lock().unwrap().push("start".to_string()))
+        .on_event(move |_| on_event.lock().unwrap().push("event".to_string()))
+        .on_repeat_complete(move |count| on_repeat.lock().unwrap().push(format!("repeat {}", count)))
+        .on_finish(move |outcome| on_finish.lock().unwrap().push(format!("finish {:?}", outcome)));
+
+    player.play(&recording).unwrap();
+
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec!["start", "event", "repeat 1", "event", "repeat 2", &format!("finish {:?}", PlaybackOutcome::Completed)]
+    );
+}
+
+#[test]
+fn plays_json_lines_stream() {
+    let (clock, backend) = setup();
+    let input = [key_event(Key::KeyA, 100), key_event(Key::KeyB, 250)]
+        .iter()
+        .map(|event| serde_json::to_string(event).unwrap() + "\n")
+        .collect::<String>();
+
+    play_stream(&backend, clock.as_ref(), Cursor::new(input), &PlaybackOptions::default(), &PlaybackControl::default()).unwrap();
+
+    assert_eq!(
+        backend.simulated(),
+        vec![
+            (Duration::from_millis(100), EventType::KeyPress(Key::KeyA)),
+            (Duration::from_millis(350), EventType::KeyPress(Key::KeyB)),
+        ]
+    );
+}
+
+#[test]
+fn smooth_scroll_falls_back_to_rounded_wheel() {
+    let (clock, backend) = setup();
+    let recording = recording(vec![SerializableEvent {
+        event_type: SerializableEventType::SmoothWheel {
+            delta_x: 0.4,
+            delta_y: -2.6,
+            phase: ScrollPhase::Changed,
+        },
+        delay_ms: 0,
+        delay_us: 0,
+    }]);
+
+    play(&backend, clock.as_ref(), &recording, PlaybackOptions::default());
+
+    assert_eq!(
+        backend.simulated(),
+        vec![(Duration::ZERO, EventType::Wheel { delta_x: 0, delta_y: -3 })]
+    );
+}
+
+#[test]
+fn text_key_press_falls_back_to_raw_key() {
+    let (clock, backend) = setup();
+    let recording = recording(vec![SerializableEvent {
+        event_type: SerializableEventType::KeyPressText {
+            key: Key::KeyE,
+            text: "é".to_string(),
+        },
+        delay_ms: 0,
+        delay_us: 0,
+    }]);
+
+    play(&backend, clock.as_ref(), &recording, PlaybackOptions::default());
+
+    assert_eq!(backend.simulated(), vec![(Duration::ZERO, EventType::KeyPress(Key::KeyE))]);
+}
+
+#[test]
+fn key_replay_mode_decides_when_text_is_typed() {
+    let text_event = |text: &str| SerializableEvent {
+        event_type: SerializableEventType::KeyPressText {
+            key: Key::KeyQ,
+            text: text.to_string(),
+        },
+        delay_ms: 0,
+        delay_us: 0,
+    };
+    let ascii = text_event("a");
+    let accented = text_event("é");
+
+    assert_eq!(ascii.replay_text(KeyReplay::Auto), None);
+    assert_eq!(accented.replay_text(KeyReplay::Auto), Some("é"));
+    assert_eq!(accented.replay_text(KeyReplay::Physical), None);
+    assert_eq!(ascii.replay_text(KeyReplay::Characters), Some("a"));
+    assert_eq!(key_event(Key::KeyQ, 0).replay_text(KeyReplay::Characters), None);
+}
+
+#[test]
+fn restores_clipboard_before_paste() {
+    let (clock, backend) = setup();
+    let backend = backend.with_clipboard("stale");
+    let recording = recording(vec![
+        SerializableEvent {
+            event_type: SerializableEventType::Clipboard {
+                text: "recorded".to_string(),
+            },
+            delay_ms: 0,
+            delay_us: 0,
+        },
+        key_event(Key::KeyV, 0),
+    ]);
+
+    play(&backend, clock.as_ref(), &recording, PlaybackOptions::default());
+
+    assert_eq!(backend.clipboard().as_deref(), Some("recorded"));
+    assert_eq!(backend.simulated(), vec![(Duration::ZERO, EventType::KeyPress(Key::KeyV))]);
+}
+
+#[test]
+fn failed_assertion_aborts_playback() {
+    let (clock, backend) = setup();
+    let backend = Arc::new(backend.with_clipboard("hello world"));
+    let outcomes = Arc::new(Mutex::new(Vec::new()));
+    let hook_outcomes = outcomes.clone();
+    let assert_clipboard = |text: &str| SerializableEvent {
+        event_type: SerializableEventType::AssertClipboardContains { text: text.to_string() },
+        delay_ms: 0,
+        delay_us: 0,
+    };
+    let recording = recording(vec![
+        assert_clipboard("world"),
+        key_event(Key::KeyA, 10),
+        assert_clipboard("goodbye"),
+        key_event(Key::KeyB, 10),
+    ]);
+
+    let result = Player::new()
+        .backend(backend.clone())
+        .clock(clock)
+        .on_finish(move |outcome| hook_outcomes.lock().unwrap().push(outcome))
+        .play(&recording);
+
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("goodbye"), "unexpected error: {}", error);
+    assert_eq!(*outcomes.lock().unwrap(), vec![PlaybackOutcome::Failed]);
+    // The held key is released when playback is aborted
+    assert_eq!(
+        backend.simulated(),
+        vec![
+            (Duration::from_millis(10), EventType::KeyPress(Key::KeyA)),
+            (Duration::from_millis(10), EventType::KeyRelease(Key::KeyA)),
+        ]
+    );
+}
+
+#[test]
+fn failed_assertion_saves_screenshot() {
+    let (clock, backend) = setup();
+    let dir = std::env::temp_dir().join("macro-test-screenshots");
+    let recording = recording(vec![SerializableEvent {
+        event_type: SerializableEventType::AssertFrontmostApp { name: "Finder".to_string() },
+        delay_ms: 0,
+        delay_us: 0,
+    }]);
+    let options = PlaybackOptions {
+        screenshot_on_failure: Some(dir.clone()),
+        ..Default::default()
+    };
+
+    let result = do_playback(&backend, clock.as_ref(), &recording, &options, Arc::new(AtomicBool::new(false)));
+
+    assert!(result.is_err());
+    let screenshots = backend.screenshots();
+    assert_eq!(screenshots.len(), 1);
+    assert!(screenshots[0].starts_with(&dir));
+}
+
+#[test]
+fn maps_mouse_moves_onto_target_display() {
+    let clock = Arc::new(VirtualClock::new());
+    let secondary = DisplayInfo {
+        id: 2,
+        x: 1920.0,
+        y: 0.0,
+        width: 1280.0,
+        height: 800.0,
+        is_primary: false,
+        scale_factor: 2.0,
+    };
+    let primary = DisplayInfo {
+        id: 1,
+        x: 0.0,
+        y: 0.0,
+        width: 1920.0,
+        height: 1080.0,
+        is_primary: true,
+        scale_factor: 1.0,
+    };
+    let backend = MockBackend::new(clock.clone()).with_displays(vec![primary, secondary]);
+    let recording = recording(vec![SerializableEvent {
+        event_type: SerializableEventType::MouseMove {
+            x: 2020.0,
+            y: 50.0,
+            display: Some(DisplayPoint {
+                display_id: 2,
+                x: 100.0,
+                y: 50.0,
+            }),
+        },
+        delay_ms: 0,
+        delay_us: 0,
+    }]);
+
+    play(&backend, clock.as_ref(), &recording, PlaybackOptions::default());
+    let options = PlaybackOptions {
+        display_mapping: DisplayMapping::Primary,
+        ..Default::default()
+    };
+    play(&backend, clock.as_ref(), &recording, options);
+
+    let moves: Vec<_> = backend.simulated().into_iter().map(|(_, e)| e).collect();
+    assert_eq!(
+        moves,
+        vec![
+            EventType::MouseMove { x: 2020.0, y: 50.0 },
+            EventType::MouseMove { x: 100.0, y: 50.0 },
+        ]
+    );
+}
+
+#[test]
+fn transforms_and_offsets_mouse_positions() {
+    let clock = Arc::new(VirtualClock::new());
+    let display = DisplayInfo {
+        id: 1,
+        x: 0.0,
+        y: 0.0,
+        width: 1920.0,
+        height: 1080.0,
+        is_primary: true,
+        scale_factor: 1.0,
+    };
+    let backend = MockBackend::new(clock.clone()).with_displays(vec![display]);
+    let recording = recording(vec![SerializableEvent {
+        event_type: SerializableEventType::MouseMove { x: 100.0, y: 50.0, display: None },
+        delay_ms: 0,
+        delay_us: 0,
+    }]);
+    let options = PlaybackOptions {
+        transform: Transform::MirrorX,
+        offset: (-20.0, 30.0),
+        ..Default::default()
+    };
+
+    play(&backend, clock.as_ref(), &recording, options);
+
+    let moves: Vec<_> = backend.simulated().into_iter().map(|(_, e)| e).collect();
+    assert_eq!(moves, vec![EventType::MouseMove { x: 1800.0, y: 80.0 }]);
+}
+
+#[test]
+fn release_all_lets_go_of_modifiers_and_buttons() {
+    let (_, backend) = setup();
+
+    release_all(&backend);
+
+    let simulated: Vec<_> = backend.simulated().into_iter().map(|(_, e)| e).collect();
+    assert!(simulated.contains(&EventType::KeyRelease(Key::MetaLeft)));
+    assert!(simulated.contains(&EventType::KeyRelease(Key::ShiftRight)));
+    assert!(simulated.contains(&EventType::ButtonRelease(Button::Left)));
+    assert!(simulated.iter().all(|e| matches!(e, EventType::KeyRelease(_) | EventType::ButtonRelease(_))));
+}
+
+#[test]
+fn remaps_keys_buttons_and_scrolling() {
+    use macro_lib::remap::{ButtonMapping, KeyMapping, Remap, WheelRemap};
+
+    let keys: KeyMapping = "a=ShiftLeft".parse().unwrap();
+    let buttons: ButtonMapping = "Right=Left".parse().unwrap();
+    assert_eq!(keys, KeyMapping { from: Key::KeyA, to: Key::ShiftLeft });
+    assert_eq!(keys.to_string().parse::<KeyMapping>().unwrap(), keys);
+    assert!("Right".parse::<ButtonMapping>().is_err());
+    let (clock, backend) = setup();
+    let events = vec![
+        key_event(Key::KeyA, 0),
+        SerializableEvent { event_type: SerializableEventType::ButtonPress(Button::Right), delay_ms: 0, delay_us: 0 },
+        SerializableEvent { event_type: SerializableEventType::Wheel { delta_x: 0, delta_y: 3 }, delay_ms: 0, delay_us: 0 },
+    ];
+    let remap = Remap {
+        keys: [(keys.from, keys.to)].into(),
+        buttons: [(buttons.from, buttons.to)].into(),
+        wheel: WheelRemap::Invert,
+    };
+
+    play(&backend, clock.as_ref(), &recording(events), PlaybackOptions { remap, ..Default::default() });
+
+    let simulated: Vec<EventType> = backend.simulated().into_iter().map(|(_, event)| event).collect();
+    assert_eq!(
+        simulated[..3],
+        [EventType::KeyPress(Key::ShiftLeft), EventType::ButtonPress(Button::Left), EventType::Wheel { delta_x: 0, delta_y: -3 }]
+    );
+}
+
+#[test]
+fn waits_for_app_to_be_frontmost() {
+    let clock = Arc::new(VirtualClock::new());
+    let backend = MockBackend::new(clock.clone()).with_frontmost_app("Finder");
+    let launched = recording(vec![
+        SerializableEvent {
+            event_type: SerializableEventType::WaitForApp { app: "Safari".to_string(), timeout_ms: 5000, launch: true },
+            delay_ms: 0,
+            delay_us: 0,
+        },
+        key_event(Key::KeyA, 10),
+    ]);
+
+    play(&backend, clock.as_ref(), &launched, PlaybackOptions::default());
+    assert_eq!(backend.frontmost_app().unwrap(), "Safari");
+    assert_eq!(backend.simulated(), vec![(Duration::from_millis(10), EventType::KeyPress(Key::KeyA))]);
+
+    // Without launching, the app never comes to the front
+    let never = recording(vec![SerializableEvent {
+        event_type: SerializableEventType::WaitForApp { app: "Mail".to_string(), timeout_ms: 5000, launch: false },
+        delay_ms: 0,
+        delay_us: 0,
+    }]);
+    let error = do_playback(&backend, clock.as_ref(), &never, &PlaybackOptions::default(), Arc::new(AtomicBool::new(false))).unwrap_err();
+    assert!(error.to_string().contains("Timed out after 5.0s waiting for \"Mail\""), "unexpected error: {}", error);
+}
+
+#[test]
+fn watchdog_fails_endless_runs_that_go_too_far() {
+    let recording = recording(vec![key_event(Key::KeyA, 100)]);
+    let run = |backend: &dyn InputBackend, watchdog: Watchdog| {
+        let clock = VirtualClock::new();
+        let options = PlaybackOptions { repeat_count: 0, watchdog, ..Default::default() };
+        do_playback(backend, &clock, &recording, &options, Arc::new(AtomicBool::new(false))).unwrap_err().to_string()
+    };
+    let (_, backend) = setup();
+
+    let error = run(&backend, Watchdog { max_total_repeats: Some(3), ..Default::default() });
+    assert!(error.contains("limit of 3 repeats"), "unexpected error: {}", error);
+    let presses = backend.simulated().into_iter().filter(|(_, event)| *event == EventType::KeyPress(Key::KeyA)).count();
+    assert_eq!(presses, 3);
+
+    let error = run(&backend, Watchdog { max_total_duration: Some(Duration::from_millis(450)), ..Default::default() });
+    assert!(error.contains("limit of 450ms"), "unexpected error: {}", error);
+
+    let error = run(&Failing, Watchdog { max_consecutive_failures: Some(5), ..Default::default() });
+    assert!(error.contains("5 events in a row"), "unexpected error: {}", error);
+}
@@ -0,0 +1,368 @@
+use macro_lib::backend::{EventCallback, InputBackend, MockBackend, SharedBackend};
+use macro_lib::clock::VirtualClock;
+use macro_lib::config::{KeyCombo, KeyMaps, Modifier};
+use macro_lib::display::{self, DisplayInfo};
+use macro_lib::event::{ScrollDetails, ScrollPhase, SerializableEventType};
+use macro_lib::own_ui::{Area, OwnUi};
+use macro_lib::record::{self, AutosaveInterval, RecordLimit, RecordOptions, Recorder, RecordingSession, UndoScope};
+use rdev::{Button, Event, EventType, Key};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+fn input(event_type: EventType) -> Event {
+    Event {
+        time: SystemTime::now(),
+        name: None,
+        event_type,
+    }
+}
+
+#[test]
+fn stores_every_event_before_the_session_ends() {
+    let events: Vec<Event> = (0..500)
+        .flat_map(|_| [input(EventType::KeyPress(Key::KeyA)), input(EventType::KeyRelease(Key::KeyA))])
+        .collect();
+    let backend = MockBackend::new(Arc::new(VirtualClock::new())).with_input(events);
+
+    let recording = Recorder::new().backend(Arc::new(backend)).start().wait().unwrap().unwrap();
+
+    assert_eq!(recording.events.len(), 1000);
+    assert_eq!(recording.events[0].event_type, SerializableEventType::KeyPress(Key::KeyA));
+    assert_eq!(recording.events[999].event_type, SerializableEventType::KeyRelease(Key::KeyA));
+}
+
+#[test]
+fn pause_hotkey_leaves_input_out_until_resumed() {
+    let keymaps = KeyMaps {
+        pause: KeyCombo {
+            modifiers: vec![],
+            trigger: Key::F9,
+        },
+        ..Default::default()
+    };
+    let events = [Key::KeyA, Key::F9, Key::KeyB, Key::F9, Key::KeyC]
+        .map(|key| input(EventType::KeyPress(key)))
+        .to_vec();
+    let backend = MockBackend::new(Arc::new(VirtualClock::new())).with_input(events);
+
+    let recording = Recorder::new()
+        .backend(Arc::new(backend))
+        .hotkeys(keymaps)
+        .start_immediately(true)
+        .start()
+        .wait()
+        .unwrap()
+        .unwrap();
+
+    let recorded: Vec<_> = recording.events.into_iter().map(|e| e.event_type).collect();
+    assert_eq!(
+        recorded,
+        vec![SerializableEventType::KeyPress(Key::KeyA), SerializableEventType::KeyPress(Key::KeyC)]
+    );
+}
+
+#[test]
+fn leaves_out_releasing_modifiers_held_before_capture_started() {
+    let keymaps = KeyMaps {
+        start_recording: KeyCombo {
+            modifiers: vec![Modifier::Shift],
+            trigger: Key::F1,
+        },
+        ..Default::default()
+    };
+    let events = vec![
+        input(EventType::KeyPress(Key::ShiftLeft)),
+        input(EventType::KeyPress(Key::F1)),
+        input(EventType::KeyRelease(Key::F1)),
+        input(EventType::KeyPress(Key::KeyA)),
+        input(EventType::KeyRelease(Key::ShiftLeft)),
+        // Held since before the recorder started listening
+        input(EventType::KeyRelease(Key::ControlLeft)),
+        input(EventType::KeyRelease(Key::KeyA)),
+    ];
+    let backend = MockBackend::new(Arc::new(VirtualClock::new())).with_input(events);
+
+    let recording = Recorder::new()
+        .backend(Arc::new(backend))
+        .hotkeys(keymaps)
+        .start_immediately(false)
+        .start()
+        .wait()
+        .unwrap()
+        .unwrap();
+
+    let recorded: Vec<_> = recording.events.into_iter().map(|e| e.event_type).collect();
+    assert_eq!(
+        recorded,
+        vec![SerializableEventType::KeyPress(Key::KeyA), SerializableEventType::KeyRelease(Key::KeyA)]
+    );
+}
+
+#[test]
+fn move_interval_keeps_the_last_move_before_a_click() {
+    let events = vec![
+        input(EventType::MouseMove { x: 1.0, y: 1.0 }),
+        input(EventType::MouseMove { x: 2.0, y: 2.0 }),
+        input(EventType::MouseMove { x: 3.0, y: 3.0 }),
+        input(EventType::ButtonPress(Button::Left)),
+        input(EventType::ButtonRelease(Button::Left)),
+    ];
+    let backend = MockBackend::new(Arc::new(VirtualClock::new())).with_input(events);
+
+    let recording = Recorder::new()
+        .backend(Arc::new(backend))
+        .move_interval(Duration::from_secs(3600))
+        .start()
+        .wait()
+        .unwrap()
+        .unwrap();
+
+    let recorded: Vec<_> = recording.events.into_iter().map(|e| e.event_type).collect();
+    assert_eq!(recorded.len(), 3);
+    assert!(matches!(recorded[0], SerializableEventType::MouseMove { x, y, .. } if x == 3.0 && y == 3.0));
+    assert_eq!(recorded[1], SerializableEventType::ButtonPress(Button::Left));
+    assert_eq!(recorded[2], SerializableEventType::ButtonRelease(Button::Left));
+}
+
+#[test]
+fn stops_at_the_event_limit_and_keeps_what_came_before() {
+    let events: Vec<Event> = (0..50)
+        .flat_map(|_| [input(EventType::KeyPress(Key::KeyA)), input(EventType::KeyRelease(Key::KeyA))])
+        .collect();
+    let backend = MockBackend::new(Arc::new(VirtualClock::new())).with_input(events);
+    let reached = Arc::new(Mutex::new(Vec::new()));
+    let on_limit = reached.clone();
+
+    let recording = Recorder::new()
+        .backend(Arc::new(backend))
+        .max_events(10)
+        .on_limit(move |limit| on_limit.lock().unwrap().push(limit))
+        .start()
+        .wait()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(recording.events.len(), 10);
+    assert_eq!(*reached.lock().unwrap(), vec![RecordLimit::Events(10)]);
+    assert_eq!(record::parse_size("10MB"), Ok(10 << 20));
+    assert_eq!(record::format_size(512 << 10), "512KB");
+    assert!(record::parse_size("lots").is_err());
+}
+
+#[test]
+fn leaves_out_clicks_on_the_apps_own_window_and_menu() {
+    let click_at = |x, y| {
+        [
+            input(EventType::MouseMove { x, y }),
+            input(EventType::ButtonPress(Button::Left)),
+            input(EventType::ButtonRelease(Button::Left)),
+        ]
+    };
+    // Settings window, somewhere else, tray icon, its menu below it, somewhere else
+    let events = [click_at(50.0, 50.0), click_at(300.0, 300.0), click_at(510.0, 10.0), click_at(510.0, 100.0), click_at(300.0, 400.0)].concat();
+    let backend = MockBackend::new(Arc::new(VirtualClock::new())).with_input(events);
+    let own_ui = OwnUi::default();
+    own_ui.set_window("settings", Some(Area { x: 0.0, y: 0.0, width: 100.0, height: 100.0 }));
+    own_ui.set_menu_icon(Some(Area { x: 500.0, y: 0.0, width: 20.0, height: 20.0 }));
+
+    let recording = Recorder::new().backend(Arc::new(backend)).own_ui(own_ui).start().wait().unwrap().unwrap();
+
+    let recorded: Vec<_> = recording.events.into_iter().map(|e| e.event_type).collect();
+    assert_eq!(recorded.len(), 6);
+    assert!(matches!(recorded[0], SerializableEventType::MouseMove { x, y, .. } if x == 300.0 && y == 300.0));
+    assert_eq!(recorded[1], SerializableEventType::ButtonPress(Button::Left));
+    assert_eq!(recorded[2], SerializableEventType::ButtonRelease(Button::Left));
+    assert!(matches!(recorded[3], SerializableEventType::MouseMove { x, y, .. } if x == 300.0 && y == 400.0));
+}
+
+#[test]
+fn finds_the_apps_own_window_on_scaled_displays() {
+    let display = DisplayInfo { id: 1, x: 0.0, y: 0.0, width: 800.0, height: 600.0, is_primary: true, scale_factor: 2.0 };
+    // Captured positions are in pixels where the platform does not use points
+    let scale = display::native_units_per_point(std::slice::from_ref(&display));
+    let events = [
+        input(EventType::MouseMove { x: 75.0 * scale, y: 75.0 * scale }),
+        input(EventType::ButtonPress(Button::Left)),
+        input(EventType::ButtonRelease(Button::Left)),
+    ];
+    let backend = MockBackend::new(Arc::new(VirtualClock::new())).with_displays(vec![display]).with_input(events.to_vec());
+    let own_ui = OwnUi::default();
+    own_ui.set_window("settings", Some(Area { x: 0.0, y: 0.0, width: 100.0, height: 100.0 }));
+
+    let recording = Recorder::new().backend(Arc::new(backend)).own_ui(own_ui).start().wait().unwrap().unwrap();
+
+    assert!(recording.events.is_empty());
+}
+
+#[test]
+fn keeps_trackpad_scrolls_smooth_and_wheel_notches_in_lines() {
+    let events = vec![
+        input(EventType::Wheel { delta_x: 0, delta_y: -3 }),
+        input(EventType::Wheel { delta_x: 0, delta_y: -5 }),
+        input(EventType::Wheel { delta_x: 0, delta_y: 1 }),
+    ];
+    let trackpad = |delta_y, phase| ScrollDetails { continuous: true, delta_x: 0.0, delta_y, phase };
+    let backend = MockBackend::new(Arc::new(VirtualClock::new()))
+        .with_input(events)
+        .with_scroll_details(vec![
+            trackpad(-3.0, ScrollPhase::Began),
+            trackpad(-5.0, ScrollPhase::Momentum),
+            ScrollDetails { continuous: false, delta_x: 0.0, delta_y: 1.0, phase: ScrollPhase::None },
+        ]);
+
+    let recording = Recorder::new().backend(Arc::new(backend)).start().wait().unwrap().unwrap();
+
+    let scrolls: Vec<_> = recording.events.iter().map(|event| event.event_type.clone()).collect();
+    assert_eq!(
+        scrolls,
+        [
+            SerializableEventType::SmoothWheel { delta_x: 0.0, delta_y: -3.0, phase: ScrollPhase::Began },
+            SerializableEventType::SmoothWheel { delta_x: 0.0, delta_y: -5.0, phase: ScrollPhase::Momentum },
+            SerializableEventType::Wheel { delta_x: 0, delta_y: 1 },
+        ]
+    );
+}
+
+#[test]
+fn parses_autosave_intervals() {
+    assert_eq!("500".parse(), Ok(AutosaveInterval::Events(500)));
+    assert_eq!("10s".parse(), Ok(AutosaveInterval::Time(Duration::from_secs(10))));
+    assert!("0".parse::<AutosaveInterval>().is_err());
+    assert!("often".parse::<AutosaveInterval>().is_err());
+}
+
+// The only test here that sets the Ctrl+C handler, which works once per process
+#[test]
+fn quitting_before_capture_starts_leaves_the_recording_alone() {
+    let dir = std::env::temp_dir().join("macro-test-record");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("events.json");
+    std::fs::write(&path, "existing").unwrap();
+    // Input runs out without the start hotkey ever being pressed
+    let backend = MockBackend::new(Arc::new(VirtualClock::new())).with_input(vec![input(EventType::KeyPress(Key::KeyA)), input(EventType::KeyRelease(Key::KeyA))]);
+
+    let started = record::record_to_file(Arc::new(backend), &path, RecordOptions::default(), KeyMaps::default(), false).unwrap();
+
+    assert!(!started);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "existing");
+}
+
+#[test]
+fn undo_hotkey_takes_back_everything_since_the_checkpoint() {
+    let keymaps = KeyMaps {
+        checkpoint: KeyCombo {
+            modifiers: vec![],
+            trigger: Key::F6,
+        },
+        undo: KeyCombo {
+            modifiers: vec![],
+            trigger: Key::F9,
+        },
+        ..Default::default()
+    };
+    let events = vec![
+        input(EventType::ButtonPress(Button::Left)),
+        input(EventType::KeyPress(Key::F6)),
+        input(EventType::ButtonRelease(Button::Left)),
+        input(EventType::KeyPress(Key::KeyA)),
+        input(EventType::KeyPress(Key::F9)),
+        input(EventType::KeyPress(Key::KeyB)),
+    ];
+    let backend = MockBackend::new(Arc::new(VirtualClock::new())).with_input(events);
+
+    let recording = Recorder::new()
+        .backend(Arc::new(backend))
+        .hotkeys(keymaps)
+        .undo(UndoScope::Checkpoint)
+        .start()
+        .wait()
+        .unwrap()
+        .unwrap();
+
+    let recorded: Vec<_> = recording.events.into_iter().map(|e| e.event_type).collect();
+    assert_eq!(
+        recorded,
+        vec![
+            SerializableEventType::ButtonPress(Button::Left),
+            SerializableEventType::Checkpoint { name: "checkpoint-1".to_string() },
+            // Let go of in the part taken back
+            SerializableEventType::ButtonRelease(Button::Left),
+            SerializableEventType::KeyPress(Key::KeyB),
+        ]
+    );
+    assert_eq!("10s".parse(), Ok(UndoScope::Last(Duration::from_secs(10))));
+    assert_eq!("checkpoint".parse(), Ok(UndoScope::Checkpoint));
+    assert!("oops".parse::<UndoScope>().is_err());
+}
+
+/// Backend that delivers whatever the test sends, and like the real ones
+/// keeps listening until there is nothing left to send.
+struct ChannelBackend {
+    events: Mutex<mpsc::Receiver<Event>>,
+    listens: AtomicUsize,
+}
+
+impl InputBackend for ChannelBackend {
+    fn listen(&self, mut callback: EventCallback) -> anyhow::Result<()> {
+        self.listens.fetch_add(1, Ordering::SeqCst);
+        for event in self.events.lock().unwrap().iter() {
+            callback(event);
+        }
+        Ok(())
+    }
+
+    fn simulate(&self, _event_type: &EventType) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Presses `key` until `session` has recorded something, as events sent
+/// before it starts listening are not delivered to it.
+fn press_until_recorded(session: &RecordingSession, events: &mpsc::Sender<Event>, key: Key) {
+    while session.handle().snapshot().is_none_or(|recording| recording.events.is_empty()) {
+        events.send(input(EventType::KeyPress(key))).unwrap();
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn shared_backend_listens_once_for_one_session_after_another() {
+    let (events, receiver) = mpsc::channel();
+    let inner = Arc::new(ChannelBackend {
+        events: Mutex::new(receiver),
+        listens: AtomicUsize::new(0),
+    });
+    let shared = Arc::new(SharedBackend::new(inner.clone()));
+
+    let first = Recorder::new().backend(shared.clone()).start();
+    press_until_recorded(&first, &events, Key::KeyA);
+    let first = first.stop().unwrap().unwrap();
+
+    let second = Recorder::new().backend(shared.clone()).start();
+    press_until_recorded(&second, &events, Key::KeyB);
+    let second = second.stop().unwrap().unwrap();
+
+    assert_eq!(inner.listens.load(Ordering::SeqCst), 1);
+    assert!(first.events.iter().all(|event| event.event_type == SerializableEventType::KeyPress(Key::KeyA)));
+    assert!(second.events.iter().all(|event| event.event_type == SerializableEventType::KeyPress(Key::KeyB)));
+}
+
+#[test]
+fn shared_backend_does_not_record_what_it_simulated() {
+    let backend = MockBackend::new(Arc::new(VirtualClock::new())).with_input(vec![
+        input(EventType::KeyPress(Key::KeyA)),
+        input(EventType::MouseMove { x: 10.4, y: 20.0 }),
+        input(EventType::KeyPress(Key::KeyB)),
+    ]);
+    let shared = Arc::new(SharedBackend::new(Arc::new(backend)));
+    shared.simulate(&EventType::KeyPress(Key::KeyA)).unwrap();
+    shared.simulate(&EventType::MouseMove { x: 10.0, y: 20.0 }).unwrap();
+
+    let recording = Recorder::new().backend(shared).start().wait().unwrap().unwrap();
+
+    let recorded: Vec<_> = recording.events.iter().map(|event| event.event_type.clone()).collect();
+    assert_eq!(recorded, vec![SerializableEventType::KeyPress(Key::KeyB)]);
+}
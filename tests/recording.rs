@@ -0,0 +1,213 @@
+use macro_lib::config::Preset;
+use macro_lib::display::DisplayInfo;
+use macro_lib::environment::{self, Environment};
+use macro_lib::event::{SerializableEvent, SerializableEventType};
+use macro_lib::pack::{self, Package};
+use macro_lib::recording::{self, Recording};
+use macro_lib::recovery;
+use macro_lib::screenshot;
+use rdev::Key;
+use std::io::Write;
+
+#[test]
+fn loads_json_lines_cut_short_by_a_crash() {
+    let dir = std::env::temp_dir().join("macro-test-recording");
+    std::fs::create_dir_all(&dir).unwrap();
+    let events: Vec<_> = [Key::KeyA, Key::KeyB]
+        .into_iter()
+        .map(|key| SerializableEvent {
+            event_type: SerializableEventType::KeyPress(key),
+            delay_ms: 10,
+            delay_us: 0,
+        })
+        .collect();
+    let recording = Recording::new(events, &[]);
+    let types = |recording: &Recording| recording.events.iter().map(|e| e.event_type.clone()).collect::<Vec<_>>();
+    let expected = types(&recording);
+
+    let document = dir.join("keys.json");
+    recording.save(&document).unwrap();
+    assert_eq!(types(&Recording::load(&document).unwrap()), expected);
+
+    let lines = dir.join("keys.jsonl");
+    recording.save(&lines).unwrap();
+    assert_eq!(std::fs::read_to_string(&lines).unwrap().lines().count(), 3);
+    let mut file = std::fs::OpenOptions::new().append(true).open(&lines).unwrap();
+    write!(file, r#"{{"event_type":{{"KeyPress":"Ke"#).unwrap();
+    assert_eq!(types(&Recording::load(&lines).unwrap()), expected);
+}
+
+#[test]
+fn recovers_temp_recordings_left_behind() {
+    let temp_dir = std::env::temp_dir().join("macro-test-recovery");
+    let recordings_dir = temp_dir.join("Macros");
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    std::fs::create_dir_all(&temp_dir).unwrap();
+    let event = SerializableEvent {
+        event_type: SerializableEventType::KeyPress(Key::KeyA),
+        delay_ms: 10,
+        delay_us: 0,
+    };
+    let orphan = temp_dir.join("macro_recording_20240101_120000.jsonl");
+    Recording::new(vec![event], &[]).save(&orphan).unwrap();
+    std::fs::write(temp_dir.join("unrelated.json"), "{}").unwrap();
+
+    assert_eq!(recovery::orphaned_recordings(&temp_dir), vec![orphan.clone()]);
+    let saved = recovery::recover(&orphan, &recordings_dir).unwrap();
+
+    assert_eq!(saved, recordings_dir.join("recovered_20240101_120000.json"));
+    assert_eq!(Recording::load(&saved).unwrap().events.len(), 1);
+    assert!(!orphan.exists());
+    assert!(recovery::orphaned_recordings(&temp_dir).is_empty());
+}
+
+#[test]
+fn interleaves_recordings_by_when_each_event_is_due() {
+    let keys = |keys: &[(Key, u64)]| {
+        let events = keys
+            .iter()
+            .map(|&(key, delay_ms)| SerializableEvent { event_type: SerializableEventType::KeyPress(key), delay_ms, delay_us: 0 })
+            .collect();
+        Recording::new(events, &[])
+    };
+    let first = keys(&[(Key::KeyA, 10), (Key::KeyB, 20)]);
+    let second = keys(&[(Key::KeyC, 5), (Key::KeyD, 25)]);
+
+    let merged = Recording::interleave(vec![first, second]).unwrap();
+
+    let timeline: Vec<_> = merged.events.iter().map(|e| (e.event_type.clone(), e.delay_ms)).collect();
+    assert_eq!(
+        timeline,
+        vec![
+            (SerializableEventType::KeyPress(Key::KeyC), 5),
+            (SerializableEventType::KeyPress(Key::KeyA), 5),
+            (SerializableEventType::KeyPress(Key::KeyB), 20),
+            (SerializableEventType::KeyPress(Key::KeyD), 0),
+        ]
+    );
+}
+
+#[test]
+fn appended_segments_can_be_played_alone() {
+    use macro_lib::play::{event_range, PlaybackPosition};
+
+    let press = |key| SerializableEvent { event_type: SerializableEventType::KeyPress(key), delay_ms: 10, delay_us: 0 };
+    let mut recording = Recording::new(vec![press(Key::KeyA)], &[]);
+    assert_eq!(recording.append_segment(Some("setup".to_string()), vec![press(Key::KeyB), press(Key::KeyC)]).unwrap(), "setup");
+    assert_eq!(recording.append_segment(None, vec![press(Key::KeyD)]).unwrap(), "segment-3");
+    assert!(recording.append_segment(Some("setup".to_string()), Vec::new()).is_err());
+    assert_eq!(recording.segments(), vec!["segment-1", "setup", "segment-3"]);
+
+    let setup = PlaybackPosition::Segment("setup".to_string());
+    let range = event_range(&recording, Some(&setup), Some(&setup)).unwrap();
+    let played: Vec<_> = recording.events[range].iter().map(|e| e.event_type.clone()).collect();
+    assert_eq!(
+        played,
+        vec![
+            SerializableEventType::Segment { name: "setup".to_string() },
+            SerializableEventType::KeyPress(Key::KeyB),
+            SerializableEventType::KeyPress(Key::KeyC),
+        ]
+    );
+    assert!(event_range(&recording, Some(&PlaybackPosition::Segment("teardown".to_string())), None).is_err());
+}
+
+#[test]
+fn packs_a_recording_with_its_screenshots_and_preset() {
+    let dir = std::env::temp_dir().join("macro-test-pack");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("login.json");
+    let event = SerializableEvent {
+        event_type: SerializableEventType::KeyPress(Key::KeyA),
+        delay_ms: 10,
+        delay_us: 0,
+    };
+    Recording::new(vec![event], &[]).save(&input).unwrap();
+    let thumbnail = screenshot::thumbnail_path(&input);
+    std::fs::create_dir_all(thumbnail.parent().unwrap()).unwrap();
+    std::fs::write(&thumbnail, b"png").unwrap();
+    let preset = Preset { speed: Some(2.0), ..Default::default() };
+
+    let output = pack::default_output(&input);
+    pack::pack(&input, &output, Some(&preset)).unwrap();
+
+    assert_eq!(output, dir.join("login.macro"));
+    let package = Package::open(&output).unwrap();
+    assert_eq!(package.recording.events.len(), 1);
+    assert_eq!(package.preset, Some(preset));
+    assert_eq!(package.screenshots, vec!["thumbnail.png".to_string()]);
+    // Everything that loads recordings opens packages too
+    assert_eq!(Recording::load(&output).unwrap().events.len(), 1);
+    assert!(Recording::load(&output).unwrap().save(&output).is_err());
+}
+
+#[test]
+fn keeps_playback_settings_in_the_recording() {
+    let dir = std::env::temp_dir().join("macro-test-saved-playback");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("report.json");
+    Recording::new(Vec::new(), &[]).save(&input).unwrap();
+    assert_eq!(recording::saved_playback(&input).unwrap(), None);
+
+    recording::run_defaults(&input, Preset { speed: Some(2.0), countdown: Some(3.0), ..Default::default() }, false).unwrap();
+    recording::run_defaults(&input, Preset { repeat_count: Some(5), ..Default::default() }, false).unwrap();
+    let saved = Preset { speed: Some(2.0), repeat_count: Some(5), repeat_interval: None, countdown: Some(3.0) };
+    assert_eq!(recording::saved_playback(&input).unwrap(), Some(saved.clone()));
+
+    // A package's preset wins over the settings saved in its recording
+    let output = pack::default_output(&input);
+    pack::pack(&input, &output, None).unwrap();
+    assert_eq!(recording::saved_playback(&output).unwrap(), Some(saved));
+    let preset = Preset { speed: Some(0.5), ..Default::default() };
+    pack::pack(&input, &output, Some(&preset)).unwrap();
+    assert_eq!(recording::saved_playback(&output).unwrap(), Some(preset));
+
+    recording::run_defaults(&input, Preset::default(), true).unwrap();
+    assert_eq!(recording::saved_playback(&input).unwrap(), None);
+}
+
+#[test]
+fn reports_environment_changes_likely_to_break_playback() {
+    let display = |width: f64| DisplayInfo {
+        id: 1,
+        x: 0.0,
+        y: 0.0,
+        width,
+        height: 1080.0,
+        is_primary: true,
+        scale_factor: 1.0,
+    };
+    let recorded = Environment {
+        os: "macos".to_string(),
+        os_version: Some("14.5".to_string()),
+        keyboard_layout: Some("com.apple.keylayout.US".to_string()),
+        frontmost_app: Some("Safari".to_string()),
+    };
+    let mut recording = Recording::new(Vec::new(), &[display(1920.0)]);
+    recording.metadata.environment = Some(recorded.clone());
+
+    // A minor update and another frontmost app are fine
+    let current = Environment {
+        os_version: Some("14.6".to_string()),
+        frontmost_app: Some("Terminal".to_string()),
+        ..recorded.clone()
+    };
+    assert!(environment::differences(&recording, &current, &[display(1920.0)]).is_empty());
+
+    let current = Environment {
+        os_version: Some("15.0".to_string()),
+        keyboard_layout: Some("com.apple.keylayout.German".to_string()),
+        ..recorded
+    };
+    let differences = environment::differences(&recording, &current, &[display(2560.0)]);
+    assert_eq!(
+        differences,
+        vec![
+            "recorded on macos 14.5, playing on macos 15.0",
+            "keyboard layout was com.apple.keylayout.US, is now com.apple.keylayout.German",
+            "displays were 1920x1080 at 0,0 @1x, are now 2560x1080 at 0,0 @1x",
+        ]
+    );
+}
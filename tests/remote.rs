@@ -0,0 +1,45 @@
+use macro_lib::backend::MockBackend;
+use macro_lib::clock::VirtualClock;
+use macro_lib::event::{SerializableEvent, SerializableEventType};
+use macro_lib::play::{PlaybackControl, PlaybackOptions};
+use macro_lib::remote::receive_from;
+use rdev::{EventType, Key};
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+#[test]
+fn releases_modifiers_a_disconnected_sender_left_held() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let sender = std::thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let typed = [
+            SerializableEventType::KeyPress(Key::ShiftLeft),
+            SerializableEventType::KeyPress(Key::KeyA),
+            SerializableEventType::KeyRelease(Key::KeyA),
+        ];
+        for event_type in typed {
+            let event = SerializableEvent {
+                event_type,
+                delay_ms: 0,
+                delay_us: 0,
+            };
+            writeln!(stream, "{}", serde_json::to_string(&event).unwrap()).unwrap();
+        }
+        // Dropped mid-shortcut
+    });
+    let (stream, _) = listener.accept().unwrap();
+    let clock = Arc::new(VirtualClock::new());
+    let backend = MockBackend::new(clock.clone());
+
+    receive_from(stream, &backend, clock.as_ref(), &PlaybackOptions::default(), &PlaybackControl::default()).unwrap();
+    sender.join().unwrap();
+
+    let simulated: Vec<_> = backend.simulated().into_iter().map(|(_, e)| e).collect();
+    assert_eq!(
+        &simulated[..3],
+        &[EventType::KeyPress(Key::ShiftLeft), EventType::KeyPress(Key::KeyA), EventType::KeyRelease(Key::KeyA)]
+    );
+    assert!(simulated[3..].contains(&EventType::KeyRelease(Key::ShiftLeft)));
+}
@@ -0,0 +1,83 @@
+use macro_lib::backend::MockBackend;
+use macro_lib::clock::VirtualClock;
+use macro_lib::config::KeyMaps;
+use macro_lib::event::{SerializableEvent, SerializableEventType};
+use macro_lib::play::PlaybackOptions;
+use macro_lib::recording::Recording;
+use macro_lib::repl::{Repl, ReplCommand};
+use rdev::{Button, EventType, Key};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[test]
+fn parses_commands_with_quoted_text() {
+    assert_eq!("click 100 200".parse(), Ok(ReplCommand::Click { x: 100.0, y: 200.0, button: Button::Left }));
+    assert_eq!("click 5 6 right".parse(), Ok(ReplCommand::Click { x: 5.0, y: 6.0, button: Button::Right }));
+    assert_eq!(r#"type "say \"hi\"""#.parse(), Ok(ReplCommand::Type("say \"hi\"".to_string())));
+    assert_eq!("wait 500ms".parse(), Ok(ReplCommand::Wait(Duration::from_millis(500))));
+    assert_eq!(
+        "play file.json --speed 2".parse(),
+        Ok(ReplCommand::Play(vec!["file.json".to_string(), "--speed".to_string(), "2".to_string()]))
+    );
+    assert_eq!("record start".parse(), Ok(ReplCommand::RecordStart("events.json".into())));
+    assert!("click 100".parse::<ReplCommand>().unwrap_err().contains("usage: click"));
+    assert!("type \"unfinished".parse::<ReplCommand>().is_err());
+}
+
+#[test]
+fn runs_commands_and_plays_recordings_on_the_backend() {
+    let dir = std::env::temp_dir().join("macro-test-repl");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let recording = dir.join("file.json");
+    let event = SerializableEvent { event_type: SerializableEventType::KeyPress(Key::KeyA), delay_ms: 10, delay_us: 0 };
+    Recording::new(vec![event], &[]).save(&recording).unwrap();
+
+    let backend = Arc::new(MockBackend::new(Arc::new(VirtualClock::new())));
+    let played = Arc::new(Mutex::new(Vec::new()));
+    let hook_played = played.clone();
+    let mut repl = Repl::new(backend.clone(), KeyMaps::default()).play_options(move |args| {
+        hook_played.lock().unwrap().push(args.to_vec());
+        let path = if args[0] == "-" { PathBuf::from("-") } else { dir.join(&args[0]) };
+        Ok((vec![path], PlaybackOptions { speed: 2.0, ..Default::default() }))
+    });
+
+    for line in ["click 100 200", "key Return", "play file.json --speed 2"] {
+        repl.execute(&line.parse().unwrap()).unwrap();
+    }
+
+    let simulated: Vec<_> = backend.simulated().into_iter().map(|(_, event)| event).collect();
+    assert_eq!(
+        simulated,
+        vec![
+            EventType::MouseMove { x: 100.0, y: 200.0 },
+            EventType::ButtonPress(Button::Left),
+            EventType::ButtonRelease(Button::Left),
+            EventType::KeyPress(Key::Return),
+            EventType::KeyRelease(Key::Return),
+            EventType::KeyPress(Key::KeyA),
+        ]
+    );
+    assert_eq!(*played.lock().unwrap(), vec![vec!["file.json".to_string(), "--speed".to_string(), "2".to_string()]]);
+    // Stdin is where commands are typed, so it cannot be played from
+    assert!(repl.execute(&"play -".parse().unwrap()).unwrap_err().to_string().contains("stdin"));
+    assert!(repl.execute(&ReplCommand::RecordStop).is_err());
+}
+
+#[test]
+fn record_start_keeps_an_existing_recording() {
+    let dir = std::env::temp_dir().join("macro-test-repl-record");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let existing = dir.join("events.json");
+    std::fs::write(&existing, "earlier").unwrap();
+
+    let backend = Arc::new(MockBackend::new(Arc::new(VirtualClock::new())));
+    let mut repl = Repl::new(backend, KeyMaps::default());
+    repl.execute(&ReplCommand::RecordStart(existing.clone())).unwrap();
+    repl.execute(&ReplCommand::RecordStop).unwrap();
+
+    assert_eq!(std::fs::read_to_string(&existing).unwrap(), "earlier");
+    assert!(dir.join("events-2.json").exists());
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::event;
+use macro_lib::display::DisplayPoint;
+use macro_lib::event::SerializableEventType;
+use macro_lib::recording::Recording;
+use macro_lib::remap;
+use macro_lib::replace::{self, ReplaceOptions};
+use rdev::{Button, Key};
+
+/// Move to `x`, `y` on a display whose left edge is at 100.
+fn move_on_display(x: f64, y: f64) -> SerializableEventType {
+    SerializableEventType::MouseMove { x, y, display: Some(DisplayPoint { display_id: 1, x: x - 100.0, y }) }
+}
+
+#[test]
+fn swaps_keys_and_moves_clicks_at_a_point() {
+    let f13 = remap::parse_key("F13").unwrap();
+    let recording = Recording::new(
+        vec![
+            event(SerializableEventType::KeyPressText { key: f13, text: "x".to_string() }, 10),
+            event(SerializableEventType::KeyRelease(f13), 10),
+            event(SerializableEventType::KeyPress(Key::KeyA), 10),
+            // Passes over the point without clicking there
+            event(move_on_display(1200.0, 800.0), 10),
+            event(move_on_display(1201.0, 799.0), 10),
+            event(SerializableEventType::ButtonPress(Button::Left), 10),
+            event(SerializableEventType::ButtonRelease(Button::Left), 10),
+            event(move_on_display(50.0, 50.0), 10),
+            event(SerializableEventType::ButtonPress(Button::Left), 10),
+        ],
+        &[],
+    );
+    let options = ReplaceOptions {
+        keys: vec!["F13=F5".parse().unwrap()],
+        points: vec!["1200,800=900,600".parse().unwrap()],
+        tolerance: 2.0,
+    };
+
+    let (replaced, counts) = replace::replace(&recording, &options);
+
+    let timeline: Vec<_> = replaced.events.iter().map(|e| e.event_type.clone()).collect();
+    assert_eq!(
+        timeline,
+        vec![
+            SerializableEventType::KeyPress(Key::F5),
+            SerializableEventType::KeyRelease(Key::F5),
+            SerializableEventType::KeyPress(Key::KeyA),
+            move_on_display(1200.0, 800.0),
+            move_on_display(900.0, 600.0),
+            SerializableEventType::ButtonPress(Button::Left),
+            SerializableEventType::ButtonRelease(Button::Left),
+            move_on_display(50.0, 50.0),
+            SerializableEventType::ButtonPress(Button::Left),
+        ]
+    );
+    assert_eq!(counts.keys, vec![2]);
+    assert_eq!(counts.points, vec![1]);
+    assert!("1200,800".parse::<replace::PointMapping>().is_err());
+}
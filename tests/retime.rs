@@ -0,0 +1,41 @@
+mod common;
+
+use common::{event, mouse_move};
+use macro_lib::event::SerializableEventType;
+use macro_lib::recording::Recording;
+use macro_lib::retime::{retime, MoveTiming, RetimeOptions};
+use rdev::{Button, Key};
+use std::time::Duration;
+
+#[test]
+fn evens_out_delays_by_kind_of_event_keeping_pauses() {
+    let recording = Recording::new(
+        vec![
+            event(mouse_move(0.0, 0.0), 500),
+            event(mouse_move(30.0, 40.0), 7),
+            event(mouse_move(60.0, 80.0), 90),
+            event(SerializableEventType::ButtonPress(Button::Left), 40),
+            event(SerializableEventType::ButtonRelease(Button::Left), 95),
+            event(SerializableEventType::KeyPress(Key::KeyH), 300),
+            event(SerializableEventType::KeyRelease(Key::KeyH), 23),
+            event(SerializableEventType::KeyPress(Key::KeyI), 180),
+            // Waiting for the app, left alone
+            event(SerializableEventType::KeyPress(Key::Return), 4000),
+        ],
+        &[],
+    );
+    let options = RetimeOptions {
+        typing: Some(Duration::from_millis(60)),
+        clicks: Some(Duration::from_millis(150)),
+        moves: MoveTiming::Natural,
+        ..Default::default()
+    };
+
+    let retimed = retime(&recording, &options);
+
+    let delays: Vec<_> = retimed.events.iter().map(|e| e.delay().as_millis()).collect();
+    // 100 points in two equal steps: 50ms + 100ms * log2(11), about 396ms
+    assert_eq!(delays, vec![500, 197, 197, 150, 150, 300, 60, 60, 4000]);
+    assert_eq!("natural".parse(), Ok(MoveTiming::Natural));
+    assert_eq!("10ms".parse(), Ok(MoveTiming::Fixed(Duration::from_millis(10))));
+}
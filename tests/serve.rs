@@ -0,0 +1,116 @@
+use macro_lib::backend::MockBackend;
+use macro_lib::clock::VirtualClock;
+use macro_lib::event::{SerializableEvent, SerializableEventType};
+use macro_lib::play::PlaybackOptions;
+use macro_lib::recording::Recording;
+use macro_lib::serve::{check_request, RequestHeaders, Server};
+use rdev::{EventType, Key};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[test]
+fn plays_a_recording_from_the_recordings_folder() {
+    let dir = std::env::temp_dir().join("macro-test-serve");
+    std::fs::create_dir_all(&dir).unwrap();
+    let events = [Key::KeyA, Key::KeyB]
+        .into_iter()
+        .map(|key| SerializableEvent {
+            event_type: SerializableEventType::KeyPress(key),
+            delay_ms: 0,
+            delay_us: 0,
+        })
+        .collect();
+    Recording::new(events, &[]).save(&dir.join("keys.json")).unwrap();
+    std::fs::create_dir_all(dir.join("keys-screenshots")).unwrap();
+    std::fs::write(dir.join("keys-screenshots").join("thumbnail.png"), b"png").unwrap();
+    let backend = Arc::new(MockBackend::new(Arc::new(VirtualClock::new())));
+    let server = Server::new(backend.clone(), dir, PlaybackOptions::default());
+
+    let (status, body) = server.handle("GET", "/recordings", "");
+    assert_eq!(status, 200);
+    assert!(body["recordings"].as_array().unwrap().contains(&"keys.json".into()));
+    assert_eq!(body["thumbnails"]["keys.json"], "keys-screenshots/thumbnail.png");
+
+    let (status, _) = server.handle("POST", "/playback", r#"{"recording": "keys"}"#);
+    assert_eq!(status, 200);
+    let started = Instant::now();
+    while server.handle("GET", "/status", "").1["playback"]["state"] == "playing" {
+        assert!(started.elapsed() < Duration::from_secs(5), "playback never finished");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let (_, body) = server.handle("GET", "/status", "");
+    assert_eq!(body["playback"]["state"], "completed");
+    assert_eq!(body["playback"]["events_played"], 2);
+    let played: Vec<_> = backend.simulated().into_iter().map(|(_, e)| e).collect();
+    assert_eq!(played, vec![EventType::KeyPress(Key::KeyA), EventType::KeyPress(Key::KeyB)]);
+
+    // Names are plain file names inside the recordings folder
+    assert_eq!(server.handle("POST", "/playback", r#"{"recording": "../keys.json"}"#).0, 400);
+    assert_eq!(server.handle("POST", "/playback", r#"{"recording": "missing.json"}"#).0, 404);
+}
+
+#[test]
+fn subscribers_receive_playback_progress() {
+    let dir = std::env::temp_dir().join("macro-test-serve-events");
+    std::fs::create_dir_all(&dir).unwrap();
+    let event = SerializableEvent {
+        event_type: SerializableEventType::KeyPress(Key::KeyA),
+        delay_ms: 0,
+        delay_us: 0,
+    };
+    Recording::new(vec![event], &[]).save(&dir.join("key.json")).unwrap();
+    let backend = Arc::new(MockBackend::new(Arc::new(VirtualClock::new())));
+    let server = Server::new(backend, dir, PlaybackOptions::default());
+
+    let updates = server.subscribe();
+    server.handle("POST", "/playback", r#"{"recording": "key.json"}"#);
+
+    let next = || updates.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(next()["type"], "status");
+    assert_eq!(next()["type"], "playback_started");
+    let progress = next();
+    assert_eq!(progress["type"], "playback_progress");
+    assert_eq!(progress["events_played"], 1);
+    let finished = next();
+    assert_eq!(finished["type"], "playback_finished");
+    assert_eq!(finished["outcome"], "completed");
+}
+
+#[test]
+fn refuses_requests_web_pages_could_send() {
+    let addr: SocketAddr = "127.0.0.1:8787".parse().unwrap();
+    let tool = RequestHeaders {
+        host: Some("localhost:8787"),
+        content_type: Some("application/json"),
+        ..Default::default()
+    };
+    assert_eq!(check_request(addr, "POST", &tool), None);
+    assert_eq!(check_request(addr, "GET", &RequestHeaders { host: Some("127.0.0.1:8787"), ..Default::default() }), None);
+
+    // fetch(..., {mode: "no-cors", body: '{"recording":"x"}'}) from another site
+    let page = RequestHeaders {
+        host: Some("localhost:8787"),
+        origin: Some("https://example.com"),
+        content_type: Some("text/plain;charset=UTF-8"),
+    };
+    assert_eq!(check_request(addr, "POST", &page).unwrap().0, 403);
+    // A site whose name was rebound to 127.0.0.1
+    let rebound = RequestHeaders { host: Some("example.com:8787"), ..tool };
+    assert_eq!(check_request(addr, "POST", &rebound).unwrap().0, 403);
+    assert_eq!(check_request(addr, "POST", &RequestHeaders { content_type: None, ..tool }).unwrap().0, 415);
+}
+
+#[test]
+fn refuses_event_streams_to_other_sites() {
+    let addr: SocketAddr = "127.0.0.1:8787".parse().unwrap();
+    // new WebSocket("ws://127.0.0.1:8787/events") from another site
+    let page = RequestHeaders {
+        host: Some("127.0.0.1:8787"),
+        origin: Some("https://example.com"),
+        ..Default::default()
+    };
+    assert_eq!(check_request(addr, "GET", &page).unwrap().0, 403);
+    let own = RequestHeaders { origin: Some("http://127.0.0.1:8787"), ..page };
+    assert_eq!(check_request(addr, "GET", &own), None);
+}
@@ -0,0 +1,12 @@
+use macro_lib::config::UpdateChannel;
+use macro_lib::update;
+
+#[test]
+fn channels_pick_the_newest_release_they_allow() {
+    let versions = ["1.2.0", "1.4.0-beta.1", "1.3.0", "1.1.0", "1.3.1+build.5"];
+    assert_eq!(update::newest_update("1.2.0", versions, UpdateChannel::Stable), Some("1.3.1+build.5"));
+    assert_eq!(update::newest_update("1.2.0", versions, UpdateChannel::Beta), Some("1.4.0-beta.1"));
+    assert_eq!(update::newest_update("1.4.0", versions, UpdateChannel::Beta), None);
+    assert!(update::is_prerelease("2.0.0-rc.1+build.5"));
+    assert!(!update::is_prerelease("2.0.0+build-5"));
+}
@@ -0,0 +1,41 @@
+use macro_lib::play::PlaybackOutcome;
+use macro_lib::worker::{listen_for_commands, listen_for_reports, WorkerCommand, WorkerReport};
+use std::io::Cursor;
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[test]
+fn reads_reports_and_skips_other_output() {
+    let output = concat!(
+        r#"{"type":"progress","repeat":0,"events_played":1,"total_events":2}"#,
+        "\nnot a report\n",
+        r#"{"type":"heartbeat"}"#,
+        "\n",
+        r#"{"type":"finished","outcome":"failed"}"#,
+        "\n",
+    );
+    let (tx, rx) = mpsc::channel();
+
+    listen_for_reports(Cursor::new(output), move |report| tx.send(report).unwrap());
+
+    let reports: Vec<_> = std::iter::from_fn(|| rx.recv_timeout(Duration::from_secs(5)).ok()).collect();
+    assert_eq!(
+        reports,
+        vec![
+            WorkerReport::Progress { repeat: 0, events_played: 1, total_events: 2 },
+            WorkerReport::Heartbeat,
+            WorkerReport::Finished { outcome: PlaybackOutcome::Failed },
+        ]
+    );
+}
+
+#[test]
+fn reads_commands_and_skips_unknown_ones() {
+    let input = "pause\nresume\nquit\n\nstatus\nstop\n";
+    let (tx, rx) = mpsc::channel();
+
+    listen_for_commands(Cursor::new(input), move |command| tx.send(command).unwrap());
+
+    let commands: Vec<_> = std::iter::from_fn(|| rx.recv_timeout(Duration::from_secs(5)).ok()).collect();
+    assert_eq!(commands, vec![WorkerCommand::Pause, WorkerCommand::Resume, WorkerCommand::Status, WorkerCommand::Stop]);
+}